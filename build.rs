@@ -0,0 +1,22 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The published tree-sitter-toml crate requires an older tree-sitter, so its parser is
+    // vendored and compiled here instead.
+    #[cfg(feature = "lang-toml")]
+    {
+        let src_dir = std::path::Path::new("grammars/tree-sitter-toml/src");
+        let parser_path = src_dir.join("parser.c");
+        let scanner_path = src_dir.join("scanner.c");
+        println!("cargo:rerun-if-changed={}", parser_path.display());
+        println!("cargo:rerun-if-changed={}", scanner_path.display());
+        cc::Build::new()
+            .include(src_dir)
+            .flag_if_supported("-Wno-unused-parameter")
+            .flag_if_supported("-Wno-unused-but-set-variable")
+            .flag_if_supported("-Wno-trigraphs")
+            .file(parser_path)
+            .file(scanner_path)
+            .compile("tree-sitter-toml");
+    }
+}
@@ -1,3 +1,9 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+};
+
+use camino::Utf8Path;
 use dupe::Dupe;
 
 use crate::{
@@ -5,43 +11,139 @@ use crate::{
     result::Result,
     source_path::SourcePath,
     supported_language::SupportedLanguage,
-    trigger::{FilePattern, RawFilePattern},
+    trigger::{CaseSensitivity, FilePattern, RawFilePattern},
 };
 
 #[derive(Debug)]
-pub struct Associations(Vec<Association>);
+pub struct Associations {
+    associations: Vec<Association>,
+
+    /// Interpreters named in the shebang lines of extensionless scripts.
+    interpreters: Vec<(String, SupportedLanguage)>,
+}
 
 impl Associations {
-    pub fn base() -> Self {
-        Self(
-            [
-                ("*.go", SupportedLanguage::Go),
-                ("*.py", SupportedLanguage::Python),
-                ("*.rs", SupportedLanguage::Rust),
-            ]
-            .into_iter()
-            .map(|(pattern, language)| {
-                let file_patterns = vec![RawFilePattern::new(pattern).compile().unwrap()];
-                Association {
-                    file_patterns,
-                    in_base: true,
-                    language,
-                }
-            })
-            .collect(),
-        )
+    /// How much of an extensionless file is read to look for a shebang line.
+    const SHEBANG_SNIFF_LEN: u64 = 256;
+
+    pub fn base(case_sensitivity: CaseSensitivity) -> Self {
+        let associations = [
+            ("*.go", SupportedLanguage::Go),
+            ("*.java", SupportedLanguage::Java),
+            ("*.json", SupportedLanguage::Json),
+            ("*.kt", SupportedLanguage::Kotlin),
+            ("*.kts", SupportedLanguage::Kotlin),
+            ("*.py", SupportedLanguage::Python),
+            ("*.rb", SupportedLanguage::Ruby),
+            ("*.rake", SupportedLanguage::Ruby),
+            ("Gemfile", SupportedLanguage::Ruby),
+            ("Rakefile", SupportedLanguage::Ruby),
+            ("*.rs", SupportedLanguage::Rust),
+            ("*.toml", SupportedLanguage::Toml),
+            ("*.yaml", SupportedLanguage::Yaml),
+            ("*.yml", SupportedLanguage::Yaml),
+        ]
+        .into_iter()
+        .map(|(pattern, language)| {
+            let file_patterns = vec![RawFilePattern::new(pattern)
+                .compile(case_sensitivity)
+                .unwrap()];
+            Association {
+                file_patterns,
+                in_base: true,
+                language,
+            }
+        })
+        .collect();
+        let interpreters = [
+            ("python", SupportedLanguage::Python),
+            ("python2", SupportedLanguage::Python),
+            ("python3", SupportedLanguage::Python),
+            ("ruby", SupportedLanguage::Ruby),
+        ]
+        .into_iter()
+        .map(|(name, language)| (name.to_owned(), language))
+        .collect();
+        Self {
+            associations,
+            interpreters,
+        }
     }
 
     pub fn insert(&mut self, file_patterns: Vec<FilePattern>, language: SupportedLanguage) {
-        self.0.push(Association {
+        self.associations.push(Association {
             file_patterns,
             in_base: false,
             language,
         })
     }
 
+    /// Run scripts with the given interpreter as the given language.
+    pub fn insert_interpreter(&mut self, name: String, language: SupportedLanguage) {
+        self.interpreters.push((name, language));
+    }
+
+    /// The language of the given file, or if its path has no extension and matches no pattern,
+    /// the language of the interpreter named in its shebang line.
+    pub fn detect_language(&self, source_path: &SourcePath) -> Result<Option<SupportedLanguage>> {
+        self.detect_language_with(source_path, || Self::read_shebang(&source_path.abs_path))
+    }
+
+    /// As [`Self::detect_language`], but takes any shebang from the given content rather than
+    /// from the file on disk.
+    pub fn detect_language_of_content(
+        &self,
+        source_path: &SourcePath,
+        content: &str,
+    ) -> Result<Option<SupportedLanguage>> {
+        self.detect_language_with(source_path, || {
+            content
+                .lines()
+                .next()
+                .filter(|first_line| first_line.starts_with("#!"))
+                .map(Into::into)
+        })
+    }
+
+    fn detect_language_with(
+        &self,
+        source_path: &SourcePath,
+        shebang: impl FnOnce() -> Option<String>,
+    ) -> Result<Option<SupportedLanguage>> {
+        if let Some(language) = self.get_language(source_path)? {
+            return Ok(Some(language));
+        }
+        if source_path.abs_path.extension().is_some() {
+            return Ok(None);
+        }
+        Ok(shebang().and_then(|shebang| self.get_interpreted_language(&shebang)))
+    }
+
+    fn read_shebang(path: &Utf8Path) -> Option<String> {
+        let file = File::open(path).ok()?;
+        let mut first_line = String::new();
+        BufReader::new(file.take(Self::SHEBANG_SNIFF_LEN))
+            .read_line(&mut first_line)
+            .ok()?;
+        first_line.starts_with("#!").then_some(first_line)
+    }
+
+    /// The language run by the interpreter named in the given shebang line.
+    pub fn get_interpreted_language(&self, shebang: &str) -> Option<SupportedLanguage> {
+        let mut words = shebang.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        }
+        self.interpreters
+            .iter()
+            .rev()
+            .find(|(name, _)| name == interpreter)
+            .map(|(_, language)| *language)
+    }
+
     pub fn get_language(&self, source_path: &SourcePath) -> Result<Option<SupportedLanguage>> {
-        let mut language_matches = self.0.iter().rev().filter_map(|association| {
+        let mut language_matches = self.associations.iter().rev().filter_map(|association| {
             let Association {
                 file_patterns,
                 in_base,
@@ -82,7 +184,10 @@ struct Association {
 
 #[cfg(test)]
 mod test {
+    use std::fs;
+
     use camino::Utf8PathBuf;
+    use indoc::indoc;
 
     use crate::context::Context;
 
@@ -91,8 +196,20 @@ mod test {
     #[test]
     fn base() {
         Test::file("foo/bar.go").has_association(SupportedLanguage::Go);
+        Test::file("foo/Bar.java").has_association(SupportedLanguage::Java);
+        Test::file("foo/bar.kt").has_association(SupportedLanguage::Kotlin);
+        Test::file("foo/build.gradle.kts").has_association(SupportedLanguage::Kotlin);
         Test::file("foo/bar.py").has_association(SupportedLanguage::Python);
+        Test::file("foo/bar.rb").has_association(SupportedLanguage::Ruby);
+        Test::file("lib/tasks/db.rake").has_association(SupportedLanguage::Ruby);
+        Test::file("Gemfile").has_association(SupportedLanguage::Ruby);
+        Test::file("foo/Rakefile").has_association(SupportedLanguage::Ruby);
+        Test::file("foo/Gemfile.lock").has_no_association();
         Test::file("foo/bar.rs").has_association(SupportedLanguage::Rust);
+        Test::file("foo/package.json").has_association(SupportedLanguage::Json);
+        Test::file("foo/Cargo.toml").has_association(SupportedLanguage::Toml);
+        Test::file("foo/bar.yaml").has_association(SupportedLanguage::Yaml);
+        Test::file(".github/workflows/ci.yml").has_association(SupportedLanguage::Yaml);
         // *.star=python is an extra association, not a base one.
         Test::file("foo/bar.star").has_no_association();
 
@@ -110,7 +227,7 @@ mod test {
                 self.setup();
                 assert_eq!(
                     expected_language,
-                    Associations::base()
+                    Associations::base(CaseSensitivity::Sensitive)
                         .get_language(&SourcePath::new_in(self.file.into(), "".into()))
                         .unwrap()
                         .unwrap()
@@ -121,7 +238,7 @@ mod test {
                 self.setup();
                 assert_eq!(
                     None,
-                    Associations::base()
+                    Associations::base(CaseSensitivity::Sensitive)
                         .get_language(&SourcePath::new_in(self.file.into(), "".into()))
                         .unwrap()
                 )
@@ -133,11 +250,53 @@ mod test {
         }
     }
 
+    #[test]
+    fn shebangs() {
+        let mut associations = Associations::base(CaseSensitivity::Sensitive);
+        associations.insert_interpreter("pypy3".into(), SupportedLanguage::Python);
+        let language = |shebang| associations.get_interpreted_language(shebang);
+        assert_eq!(
+            language("#!/usr/bin/python3\n"),
+            Some(SupportedLanguage::Python)
+        );
+        assert_eq!(
+            language("#!/usr/bin/env ruby -w"),
+            Some(SupportedLanguage::Ruby)
+        );
+        assert_eq!(
+            language("#! /usr/bin/env -S PYTHONPATH=lib pypy3"),
+            Some(SupportedLanguage::Python)
+        );
+        assert_eq!(language("#!/bin/sh"), None);
+        assert_eq!(language("python3"), None);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::write(
+            tempdir_path.join("script"),
+            "#!/usr/bin/env python3\nprint(1)\n",
+        )
+        .unwrap();
+        fs::write(tempdir_path.join("script.sh"), "#!/usr/bin/env python3\n").unwrap();
+        fs::write(tempdir_path.join("notes"), "python3\n").unwrap();
+        let detected = |path: &str| {
+            associations
+                .detect_language(&SourcePath::new(&tempdir_path.join(path), &tempdir_path))
+                .unwrap()
+        };
+        assert_eq!(detected("script"), Some(SupportedLanguage::Python));
+        assert_eq!(detected("script.sh"), None);
+        assert_eq!(detected("notes"), None);
+        assert_eq!(detected("missing"), None);
+    }
+
     #[test]
     fn ambiguous() {
         let associations = {
-            let mut associations = Associations::base();
-            let pattern = RawFilePattern::new("*.shrödinger").compile().unwrap();
+            let mut associations = Associations::base(CaseSensitivity::Sensitive);
+            let pattern = RawFilePattern::new("*.shrödinger")
+                .compile(CaseSensitivity::Sensitive)
+                .unwrap();
             associations.insert(vec![pattern.clone()], SupportedLanguage::Rust);
             associations.insert(vec![pattern], SupportedLanguage::Go);
             associations
@@ -150,8 +309,10 @@ mod test {
     #[test]
     fn override_base() {
         let associations = {
-            let mut associations = Associations::base();
-            let pattern = RawFilePattern::new("*.c").compile().unwrap();
+            let mut associations = Associations::base(CaseSensitivity::Sensitive);
+            let pattern = RawFilePattern::new("*.c")
+                .compile(CaseSensitivity::Sensitive)
+                .unwrap();
             associations.insert(vec![pattern], SupportedLanguage::Python);
             associations
         };
@@ -167,13 +328,17 @@ mod test {
     #[test]
     fn nonambiguous_overlap() {
         let associations = {
-            let mut associations = Associations::base();
+            let mut associations = Associations::base(CaseSensitivity::Sensitive);
             associations.insert(
-                vec![RawFilePattern::new("*.rust-file").compile().unwrap()],
+                vec![RawFilePattern::new("*.rust-file")
+                    .compile(CaseSensitivity::Sensitive)
+                    .unwrap()],
                 SupportedLanguage::Rust,
             );
             associations.insert(
-                vec![RawFilePattern::new("rust-files/*").compile().unwrap()],
+                vec![RawFilePattern::new("rust-files/*")
+                    .compile(CaseSensitivity::Sensitive)
+                    .unwrap()],
                 SupportedLanguage::Rust,
             );
             associations
@@ -196,7 +361,7 @@ mod test {
         let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
 
         Context::init(&tempdir_path, false).unwrap();
-        let associations = Context::acquire_in(&tempdir_path)
+        let associations = Context::acquire(&tempdir_path)
             .unwrap()
             .associations()
             .unwrap();
@@ -206,5 +371,77 @@ mod test {
             .unwrap();
         // Default manifest must add a *.star=python association.
         assert_eq!(SupportedLanguage::Python, language);
+        let language = associations
+            .get_language(&SourcePath::new_in("foo/BUILD.bazel".into(), "".into()))
+            .unwrap();
+        assert_eq!(Some(SupportedLanguage::Python), language);
+    }
+
+    #[test]
+    fn custom_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::write(
+            tempdir_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [languages.rust]
+                use-for = ["*.rs.in"]
+
+                [languages.python]
+                use-for = ["generated/*.rs"]
+            "#},
+        )
+        .unwrap();
+
+        let associations = Context::acquire(&tempdir_path)
+            .unwrap()
+            .associations()
+            .unwrap();
+        let language = |path: &str| {
+            associations
+                .get_language(&SourcePath::new_in(path.into(), "".into()))
+                .unwrap()
+        };
+        assert_eq!(language("src/lib.rs.in"), Some(SupportedLanguage::Rust));
+        assert_eq!(
+            language("generated/lib.rs"),
+            Some(SupportedLanguage::Python)
+        );
+        assert_eq!(language("src/lib.rs"), Some(SupportedLanguage::Rust));
+        assert_eq!(language("vexes/lib.star"), None);
+    }
+
+    #[test]
+    fn manifest_extends_defaults() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::write(
+            tempdir_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [languages.rust]
+                use-for = ["*.rs.in"]
+            "#},
+        )
+        .unwrap();
+
+        let associations = Context::acquire(&tempdir_path)
+            .unwrap()
+            .associations()
+            .unwrap();
+        let language = |path: &str| {
+            associations
+                .get_language(&SourcePath::new_in(path.into(), "".into()))
+                .unwrap()
+        };
+        assert_eq!(language("src/lib.rs.in"), Some(SupportedLanguage::Rust));
+        assert_eq!(language("src/main.py"), Some(SupportedLanguage::Python));
+        assert_eq!(language("vexes/lib.star"), Some(SupportedLanguage::Python));
+        assert_eq!(language("BUILD.bazel"), Some(SupportedLanguage::Python));
     }
 }
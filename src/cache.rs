@@ -0,0 +1,442 @@
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    io::{ErrorKind, Write},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{info, log_enabled};
+use sha2::{digest::Output, Digest, Sha256};
+
+use crate::{
+    context::Context,
+    error::{Error, IOAction},
+    result::Result,
+    scan::FileRunData,
+    scriptlets::source::ScriptSource,
+    severity::SeverityOverrides,
+    source_path::{PrettyPath, SourcePath},
+    theme,
+};
+
+/// An on-disk store of the results of scanning individual files.
+///
+/// Results are keyed on the version of vex, the content of all scriptlets, the manifest, the
+/// content of the grammar libraries it loads, the severity overrides, whether the run is lenient
+/// and the path and content of the scanned file. Results computed by a
+/// different version of vex or with different scriptlets or configuration are discarded when the
+/// cache is opened.
+///
+/// Entries are stored as zstd-compressed JSON with a content checksum. Entries which fail to
+/// decompress or whose checksum does not match are removed and treated as missing.
+#[derive(Debug)]
+pub struct Cache {
+    dir: Utf8PathBuf,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CacheKey(Output<Sha256>);
+
+impl Cache {
+    pub const DIR: &'static str = ".vex/cache";
+
+    /// The version of the on-disk entry format. Changing this invalidates all existing caches.
+    const FORMAT_VERSION: u32 = 4;
+
+    const COMPRESSION_LEVEL: i32 = 3;
+
+    pub fn open(
+        ctx: &Context,
+        script_sources: &[impl ScriptSource],
+        severity_overrides: &SeverityOverrides,
+        lenient: bool,
+    ) -> Result<Self> {
+        let run_key = {
+            let mut hasher = DigestHasher::default();
+            env!("CARGO_PKG_VERSION").hash(&mut hasher);
+            Self::FORMAT_VERSION.hash(&mut hasher);
+            // Cached problems are stored already drawn.
+            theme::current().hash(&mut hasher);
+            // Converting to a `Value` first sorts the keys of any hash maps.
+            serde_json::to_value(&ctx.manifest)
+                .expect("internal error: cannot serialise manifest")
+                .to_string()
+                .hash(&mut hasher);
+            // Rebuilt grammars may parse files differently from the same path.
+            for grammar in &ctx.grammars {
+                let library_path = ctx.project_root.join(&grammar.library);
+                fs::read(&library_path)
+                    .map_err(|cause| Error::IO {
+                        path: PrettyPath::new(&library_path),
+                        action: IOAction::Read,
+                        cause,
+                    })?
+                    .hash(&mut hasher);
+            }
+            severity_overrides.hash(&mut hasher);
+            lenient.hash(&mut hasher);
+            for source in script_sources {
+                source.path().hash(&mut hasher);
+                source.content()?.hash(&mut hasher);
+            }
+            format!("{:x}", hasher.digest())
+        };
+
+        let cache_root = ctx.project_root.join(Self::DIR);
+        let dir = cache_root.join(&run_key);
+        fs::create_dir_all(&dir).map_err(|cause| Error::IO {
+            path: PrettyPath::new(&dir),
+            action: IOAction::Create,
+            cause,
+        })?;
+
+        // Invalidate results computed with other scriptlets, configuration or vex versions.
+        let entries = fs::read_dir(&cache_root).map_err(|cause| Error::IO {
+            path: PrettyPath::new(&cache_root),
+            action: IOAction::Read,
+            cause,
+        })?;
+        for entry in entries.flatten() {
+            if entry.file_name().to_str() == Some(&run_key) {
+                continue;
+            }
+            if log_enabled!(log::Level::Info) {
+                info!("removing stale cache {}", entry.path().display());
+            }
+            fs::remove_dir_all(entry.path()).ok();
+        }
+
+        Ok(Self { dir })
+    }
+
+    pub fn clear(project_root: &Utf8Path) -> Result<()> {
+        let cache_root = project_root.join(Self::DIR);
+        match fs::remove_dir_all(&cache_root) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(cause) => Err(Error::IO {
+                path: PrettyPath::new(&cache_root),
+                action: IOAction::Write,
+                cause,
+            }),
+        }
+    }
+
+    pub fn key(&self, path: &SourcePath, content: &str) -> CacheKey {
+        let mut hasher = DigestHasher::default();
+        path.pretty_path.hash(&mut hasher);
+        content.hash(&mut hasher);
+        CacheKey(hasher.digest())
+    }
+
+    pub fn get(&self, key: CacheKey) -> Option<FileRunData> {
+        let path = self.entry_path(key);
+        let compressed = fs::read(&path).ok()?;
+        let run_data = zstd::decode_all(compressed.as_slice())
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok());
+        if run_data.is_none() {
+            if log_enabled!(log::Level::Info) {
+                info!("removing corrupt cache entry {path}");
+            }
+            fs::remove_file(&path).ok();
+        }
+        run_data
+    }
+
+    pub fn put(&self, key: CacheKey, run_data: &FileRunData) -> Result<()> {
+        let path = self.entry_path(key);
+        let content = serde_json::to_vec(run_data).expect("internal error: cannot serialise");
+        let io_error = |cause| Error::IO {
+            path: PrettyPath::new(&path),
+            action: IOAction::Write,
+            cause,
+        };
+        let compressed = {
+            let mut encoder =
+                zstd::Encoder::new(Vec::new(), Self::COMPRESSION_LEVEL).map_err(io_error)?;
+            encoder.include_checksum(true).map_err(io_error)?;
+            encoder.write_all(&content).map_err(io_error)?;
+            encoder.finish().map_err(io_error)?
+        };
+        fs::write(&path, compressed).map_err(io_error)
+    }
+
+    fn entry_path(&self, key: CacheKey) -> Utf8PathBuf {
+        self.dir.join(format!("{:x}.json.zst", key.0))
+    }
+}
+
+/// Feeds values to SHA-256 through their `Hash` implementations. Unlike `DefaultHasher`, the
+/// algorithm is fixed and the digest is wide enough that keys for different content never meet.
+#[derive(Default)]
+struct DigestHasher(Sha256);
+
+impl DigestHasher {
+    fn digest(self) -> Output<Sha256> {
+        self.0.finalize()
+    }
+}
+
+impl Hasher for DigestHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        u64::from_le_bytes(digest[..8].try_into().expect("digest too short"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::{
+        cli::{MaxConcurrentFileLimit, MaxProblems},
+        context::Manifest,
+        scan::{self, ScanOptions},
+        scriptlets::{source::TestSource, InitOptions, PreinitOptions, PreinitingStore},
+        severity::Severity,
+    };
+
+    use super::*;
+
+    fn context(root_path: &Utf8Path, manifest: &str) -> Context {
+        let manifest: Manifest = toml_edit::de::from_str(manifest).unwrap();
+        Context::new_with_manifest(root_path, manifest)
+    }
+
+    #[test]
+    fn invalidation() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let source = |content| TestSource {
+            vex_dir: "vexes".into(),
+            path: Utf8PathBuf::from("vexes/test.star"),
+            content,
+        };
+        let file_path = SourcePath::new_in(Utf8Path::new("main.rs"), &root_path);
+        let run_data = || FileRunData {
+            irritations: vec![],
+            num_bytes_scanned: 10,
+        };
+        let base_ctx = context(&root_path, "[vex]\nversion = '1'");
+        let no_overrides = SeverityOverrides::default();
+
+        let cache = Cache::open(&base_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+        let key = cache.key(&file_path, "fn main() {}");
+        assert_eq!(cache.get(key), None);
+        cache.put(key, &run_data()).unwrap();
+        assert_eq!(cache.get(key), Some(run_data()));
+        assert_ne!(key, cache.key(&file_path, "fn main() { 1 }"));
+
+        let cache = Cache::open(&base_ctx, &[source("a = 2")], &no_overrides, false).unwrap();
+        assert_eq!(cache.get(key), None);
+
+        let no_ids: [String; 0] = [];
+        let reconfigurations = [
+            (
+                context(&root_path, "[vex]\nversion = '1'\n[config.test]\nx = 1"),
+                SeverityOverrides::default(),
+            ),
+            (
+                context(&root_path, "[vex]\nversion = '1'\n[checks]\ntest = 'deny'"),
+                SeverityOverrides::default(),
+            ),
+            (
+                context(
+                    &root_path,
+                    "[vex]\nversion = '1'\n[check.test]\nexclude = ['*.rs']",
+                ),
+                SeverityOverrides::default(),
+            ),
+            (
+                context(&root_path, "[vex]\nversion = '1'"),
+                SeverityOverrides::new(&no_ids, &no_ids, &["test".to_owned()]),
+            ),
+        ];
+        for (ctx, overrides) in &reconfigurations {
+            let cache = Cache::open(&base_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+            cache.put(key, &run_data()).unwrap();
+            let cache = Cache::open(&base_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+            assert_eq!(cache.get(key), Some(run_data()));
+
+            let cache = Cache::open(ctx, &[source("a = 1")], overrides, false).unwrap();
+            assert_eq!(cache.get(key), None);
+        }
+
+        // Scriptlets may report differently when lenient.
+        let cache = Cache::open(&base_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+        cache.put(key, &run_data()).unwrap();
+        let cache = Cache::open(&base_ctx, &[source("a = 1")], &no_overrides, true).unwrap();
+        assert_eq!(cache.get(key), None);
+
+        // A grammar library rebuilt in place may parse files differently.
+        let grammar_ctx = context(
+            &root_path,
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[grammars]]
+                name = "custom"
+                library = "custom.so"
+            "#},
+        );
+        fs::write(root_path.join("custom.so"), "old").unwrap();
+        let cache = Cache::open(&grammar_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+        cache.put(key, &run_data()).unwrap();
+        let cache = Cache::open(&grammar_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+        assert_eq!(cache.get(key), Some(run_data()));
+        fs::write(root_path.join("custom.so"), "new").unwrap();
+        let cache = Cache::open(&grammar_ctx, &[source("a = 1")], &no_overrides, false).unwrap();
+        assert_eq!(cache.get(key), None);
+
+        Cache::clear(&root_path).unwrap();
+        assert!(!root_path.join(Cache::DIR).exists());
+        Cache::clear(&root_path).unwrap();
+    }
+
+    #[test]
+    fn corruption() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let source = TestSource {
+            vex_dir: "vexes".into(),
+            path: Utf8PathBuf::from("vexes/test.star"),
+            content: "a = 1",
+        };
+        let file_path = SourcePath::new_in(Utf8Path::new("main.rs"), &root_path);
+        let run_data = FileRunData {
+            irritations: vec![],
+            num_bytes_scanned: 10,
+        };
+
+        let ctx = context(&root_path, "[vex]\nversion = '1'");
+        let cache = Cache::open(&ctx, &[source], &SeverityOverrides::default(), false).unwrap();
+        let key = cache.key(&file_path, "fn main() {}");
+        cache.put(key, &run_data).unwrap();
+        let entry_path = cache.entry_path(key);
+        let mut compressed = fs::read(&entry_path).unwrap();
+        assert_ne!(compressed, serde_json::to_vec(&run_data).unwrap());
+
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0xff;
+        fs::write(&entry_path, compressed).unwrap();
+        assert_eq!(cache.get(key), None);
+        assert!(!entry_path.exists());
+    }
+
+    #[test]
+    fn cached_results_reused() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        fs::write(root_path.join("main.rs"), "fn main() { let x = 1; }").unwrap();
+
+        let sources = [TestSource {
+            vex_dir: "vexes".into(),
+            path: Utf8PathBuf::from("vexes/test.star"),
+            content: indoc! {r#"
+                def init():
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search('rust', '(integer_literal) @lit', on_match)
+
+                def on_match(event):
+                    vex.warn('test', 'found literal', at=event.captures['lit'])
+            "#},
+        }];
+        let ctx = Context::acquire(&root_path).unwrap();
+        let run = |cache, severity_overrides| {
+            let store = PreinitingStore::new(&sources)
+                .unwrap()
+                .preinit(PreinitOptions {
+                    severity_overrides,
+                    ..PreinitOptions::default()
+                })
+                .unwrap()
+                .init(InitOptions::default())
+                .unwrap();
+            scan::scan_project(
+                &ctx,
+                &store,
+                ScanOptions {
+                    cache: Some(cache),
+                    max_problems: MaxProblems::Unlimited,
+                    max_concurrent_files: MaxConcurrentFileLimit::new(1),
+                    ..ScanOptions::default()
+                },
+            )
+            .unwrap()
+            .irritations
+        };
+
+        let no_overrides = SeverityOverrides::default();
+        let cache = Cache::open(&ctx, &sources, &no_overrides, false).unwrap();
+        let uncached_irritations = run(&cache, no_overrides.clone());
+        assert_eq!(uncached_irritations.len(), 1);
+        assert_eq!(fs::read_dir(&cache.dir).unwrap().count(), 1);
+        assert_eq!(run(&cache, no_overrides), uncached_irritations);
+
+        // Problems are stored with their severity, so must not be reused at a different level.
+        let no_ids: [String; 0] = [];
+        let denied = SeverityOverrides::new(&no_ids, &no_ids, &["test".to_owned()]);
+        let cache = Cache::open(&ctx, &sources, &denied, false).unwrap();
+        let irritations = run(&cache, denied);
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].severity(), Severity::Error);
+    }
+
+    #[test]
+    fn shared_state_not_cached() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        fs::write(root_path.join("main.rs"), "fn main() { let x = 1; }").unwrap();
+        fs::write(root_path.join("lib.rs"), "const X: u32 = 2;").unwrap();
+
+        let sources = [TestSource {
+            vex_dir: "vexes".into(),
+            path: Utf8PathBuf::from("vexes/test.star"),
+            content: indoc! {r#"
+                def init():
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search('rust', '(integer_literal) @lit', on_match)
+
+                def on_match(event):
+                    store = vex.store()
+                    store['seen'] = store.get('seen', 0) + 1
+                    vex.warn('test', 'seen %d' % store['seen'], at=event.captures['lit'])
+            "#},
+        }];
+        let ctx = Context::acquire(&root_path).unwrap();
+        let store = PreinitingStore::new(&sources)
+            .unwrap()
+            .preinit(PreinitOptions::default())
+            .unwrap()
+            .init(InitOptions::default())
+            .unwrap();
+        let no_overrides = SeverityOverrides::default();
+        let cache = Cache::open(&ctx, &sources, &no_overrides, false).unwrap();
+        let irritations = scan::scan_project(
+            &ctx,
+            &store,
+            ScanOptions {
+                cache: Some(&cache),
+                max_problems: MaxProblems::Unlimited,
+                max_concurrent_files: MaxConcurrentFileLimit::new(1),
+                ..ScanOptions::default()
+            },
+        )
+        .unwrap()
+        .irritations;
+        assert_eq!(irritations.len(), 2);
+        assert_eq!(fs::read_dir(&cache.dir).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use allocative::Allocative;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize as Serialise;
+use starlark_derive::Trace;
+
+use crate::{
+    context::Context, error::Error, result::Result, scriptlets::VexingStore,
+    source_path::PrettyPath, supported_language::SupportedLanguage, tags::CheckTags, vex_id::VexId,
+};
+
+/// What is known about each declared check without running it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckMetadata(BTreeMap<VexId, CheckInfo>);
+
+impl CheckMetadata {
+    /// Collect the metadata declared by scriptlets and by external checks in the manifest.
+    pub fn new(ctx: &Context, store: &VexingStore) -> Result<Self> {
+        let mut metadata = store.check_metadata().clone();
+        for check in &ctx.external {
+            metadata.declare(VexId::try_from(check.id.clone())?, CheckInfo::default());
+        }
+        Ok(metadata)
+    }
+
+    /// Record information about a check. Information from repeated declarations is merged.
+    pub fn declare(&mut self, vex_id: VexId, info: CheckInfo) {
+        let existing = self.0.entry(vex_id).or_default();
+        let CheckInfo {
+            path,
+            description,
+            code,
+            events,
+            languages,
+            examples,
+            allow_duplicates,
+        } = info;
+        existing.path = existing.path.take().or(path);
+        existing.description = description.or(existing.description.take());
+        existing.code = code.or(existing.code.take());
+        existing.events.extend(events);
+        existing.languages.extend(languages);
+        existing.examples.bad.extend(examples.bad);
+        existing.examples.good.extend(examples.good);
+        existing.allow_duplicates |= allow_duplicates;
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        other
+            .0
+            .into_iter()
+            .for_each(|(vex_id, info)| self.declare(vex_id, info))
+    }
+
+    pub fn get(&self, vex_id: &VexId) -> Option<&CheckInfo> {
+        self.0.get(vex_id)
+    }
+
+    /// Find the check with the given code, or failing that, the given id.
+    pub fn find(&self, code_or_id: &str) -> Option<(&VexId, &CheckInfo)> {
+        self.0
+            .iter()
+            .find(|(_, info)| info.code.as_deref() == Some(code_or_id))
+            .or_else(|| {
+                self.0
+                    .iter()
+                    .find(|(vex_id, _)| vex_id.as_ref() == code_or_id)
+            })
+    }
+
+    /// The code of the check with the given id, if it declared one.
+    pub fn code_of(&self, vex_id: &VexId) -> Option<&str> {
+        self.get(vex_id)?.code.as_deref()
+    }
+
+    /// Check that no two checks share a code.
+    pub fn verify_codes(&self) -> Result<()> {
+        let mut owners = BTreeMap::new();
+        for (vex_id, info) in &self.0 {
+            let Some(code) = &info.code else {
+                continue;
+            };
+            if let Some(first) = owners.insert(code, vex_id) {
+                return Err(Error::DuplicateCode {
+                    code: code.clone(),
+                    first: first.clone(),
+                    second: vex_id.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&VexId, &CheckInfo)> {
+        self.0.iter()
+    }
+
+    /// Describe the check with the given code or id, with its examples.
+    pub fn explain(&self, code_or_id: &str, check_tags: &CheckTags) -> Result<String> {
+        let Some((vex_id, info)) = self.find(code_or_id) else {
+            return Err(Error::UnknownCode(code_or_id.to_owned()));
+        };
+        let mut explanation = String::new();
+        match &info.code {
+            Some(code) => writeln!(explanation, "{code}: {vex_id}")?,
+            None => writeln!(explanation, "{vex_id}")?,
+        }
+        let tags = check_tags.of(vex_id).collect::<Vec<_>>();
+        if !tags.is_empty() {
+            writeln!(explanation, "tags: {}", tags.join(", "))?;
+        }
+        writeln!(
+            explanation,
+            "\n{}",
+            info.description
+                .as_deref()
+                .unwrap_or("(no description declared)")
+        )?;
+        for (heading, examples) in [("bad", &info.examples.bad), ("good", &info.examples.good)] {
+            for example in examples {
+                write!(
+                    explanation,
+                    "\n{heading}:\n{}",
+                    textwrap::indent(example.trim_end(), "    ")
+                )?;
+                explanation.push('\n');
+            }
+        }
+        Ok(explanation)
+    }
+
+    pub fn to_json(&self, check_tags: &CheckTags) -> String {
+        #[derive(Serialise)]
+        struct Check<'a> {
+            id: &'a str,
+            tags: Vec<&'a str>,
+            #[serde(flatten)]
+            info: &'a CheckInfo,
+        }
+
+        let checks: Vec<_> = self
+            .0
+            .iter()
+            .map(|(vex_id, info)| Check {
+                id: vex_id.as_ref(),
+                tags: check_tags.of(vex_id).collect(),
+                info,
+            })
+            .collect();
+        serde_json::to_string_pretty(&checks).expect("internal error: cannot serialise checks")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialise)]
+pub struct CheckInfo {
+    /// The scriptlet which declared this check relative to the vexes directory, if any.
+    pub path: Option<PrettyPath>,
+
+    pub description: Option<String>,
+
+    /// The stable code by which this check's problems are known, such as `VX001`.
+    pub code: Option<String>,
+
+    /// The events observed by the scriptlet which declared this check.
+    pub events: BTreeSet<&'static str>,
+
+    /// The languages this check declares that it searches.
+    pub languages: BTreeSet<SupportedLanguage>,
+
+    pub examples: Examples,
+
+    /// Whether identical problems found by this check are all reported, rather than only once.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allow_duplicates: bool,
+}
+
+pub fn validate_code(code: &str) -> Result<()> {
+    lazy_static! {
+        static ref VALID_CODE: Regex = Regex::new("^[A-Z][A-Z0-9]*$").unwrap();
+    }
+    if !VALID_CODE.is_match(code) {
+        return Err(Error::InvalidCode(code.to_string()));
+    }
+    Ok(())
+}
+
+/// Snippets in which a check should and should not find problems.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialise, Allocative, Trace)]
+pub struct Examples {
+    pub bad: Vec<String>,
+    pub good: Vec<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+    use serde_json::{json, Value};
+
+    use crate::{
+        scriptlets::{source::TestSource, InitOptions, PreinitOptions, PreinitingStore},
+        vextest::VexTest,
+    };
+
+    use super::*;
+
+    #[test]
+    fn declare() {
+        let id = |id: &str| VexId::try_from(id.to_string()).unwrap();
+        let mut metadata = CheckMetadata::default();
+        metadata.declare(
+            id("check"),
+            CheckInfo {
+                path: Some(PrettyPath::from("vexes/check.star")),
+                description: Some("first".into()),
+                code: None,
+                events: BTreeSet::from(["open_project"]),
+                languages: BTreeSet::from([SupportedLanguage::Rust]),
+                examples: Examples::default(),
+                allow_duplicates: false,
+            },
+        );
+        metadata.declare(
+            id("check"),
+            CheckInfo {
+                path: Some(PrettyPath::from("vexes/other.star")),
+                description: Some("second".into()),
+                code: Some("VX001".into()),
+                events: BTreeSet::from(["open_file"]),
+                languages: BTreeSet::from([SupportedLanguage::Go]),
+                examples: Examples::default(),
+                allow_duplicates: true,
+            },
+        );
+        let info = metadata.get(&id("check")).unwrap();
+        assert_eq!(info.path.as_ref().unwrap().as_str(), "vexes/check.star");
+        assert_eq!(info.description.as_deref(), Some("second"));
+        assert_eq!(metadata.code_of(&id("check")), Some("VX001"));
+        assert_eq!(metadata.find("VX001").unwrap().0, &id("check"));
+        assert_eq!(metadata.find("check").unwrap().0, &id("check"));
+        assert!(metadata.find("VX002").is_none());
+        assert_eq!(info.events, BTreeSet::from(["open_file", "open_project"]));
+        assert_eq!(
+            info.languages,
+            BTreeSet::from([SupportedLanguage::Go, SupportedLanguage::Rust])
+        );
+        assert!(info.allow_duplicates);
+    }
+
+    #[test]
+    fn codes() {
+        VexTest::new("invalid-code")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('check', code='vx-1')
+                        vex.observe('open_project', lambda event: None)
+                "#},
+            )
+            .returns_error("invalid code 'vx-1'");
+        VexTest::new("duplicate-code")
+            .with_scriptlet(
+                "vexes/first.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('first', code='VX1')
+                        vex.observe('open_project', lambda event: None)
+                "#},
+            )
+            .with_scriptlet(
+                "vexes/second.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('second', code='VX1')
+                        vex.observe('open_project', lambda event: None)
+                "#},
+            )
+            .returns_error("checks 'first' and 'second' both have code 'VX1'");
+
+        let irritations = VexTest::new("labelled")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('coded', code='VX1')
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('coded', 'has a code')
+                        vex.warn('uncoded', 'has no code')
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 2);
+        assert_eq!(irritations[0].code(), Some("VX1"));
+        assert!(
+            irritations[0]
+                .to_string()
+                .starts_with("warning[coded/VX1]: has a code"),
+            "{}",
+            irritations[0]
+        );
+        assert_eq!(irritations[1].code(), None);
+        assert!(irritations[1].to_string().starts_with("warning[uncoded]"));
+    }
+
+    #[test]
+    fn explain() {
+        let id = |id: &str| VexId::try_from(id.to_string()).unwrap();
+        let mut metadata = CheckMetadata::default();
+        metadata.declare(
+            id("big-numbers"),
+            CheckInfo {
+                description: Some("finds numbers which are too big".into()),
+                code: Some("VX001".into()),
+                examples: Examples {
+                    bad: vec!["let x = 1000;\n".into()],
+                    good: vec!["let x = 1;".into()],
+                },
+                ..CheckInfo::default()
+            },
+        );
+        metadata.declare(id("undocumented"), CheckInfo::default());
+        let mut check_tags = CheckTags::default();
+        check_tags.declare(id("big-numbers"), ["style".to_string()]);
+
+        assert_eq!(
+            metadata.explain("VX001", &check_tags).unwrap(),
+            indoc! {"
+                VX001: big-numbers
+                tags: style
+
+                finds numbers which are too big
+
+                bad:
+                    let x = 1000;
+
+                good:
+                    let x = 1;
+            "}
+        );
+        assert_eq!(
+            metadata.explain("undocumented", &check_tags).unwrap(),
+            "undocumented\n\n(no description declared)\n"
+        );
+        assert_eq!(
+            metadata
+                .explain("VX002", &check_tags)
+                .unwrap_err()
+                .to_string(),
+            "no check has code or id 'VX002', to list every check, run `vex list checks`"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(
+            root_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[external]]
+                id = "legacy-tool"
+                command = "lint-tool"
+                parser.regex = '^(?<message>.*)$'
+            "#},
+        )
+        .unwrap();
+        let ctx = Context::acquire(&root_path).unwrap();
+        let store = PreinitingStore::new(&[TestSource {
+            vex_dir: "vexes".into(),
+            path: Utf8PathBuf::from("vexes/test.star"),
+            content: indoc! {r#"
+                def init():
+                    vex.declare(
+                        'big-numbers',
+                        tags=['style'],
+                        description='finds numbers which are too big',
+                        code='VX001',
+                        languages=['rust', 'python'],
+                    )
+                    vex.observe('open_project', on_open_project)
+                    vex.observe('open_file', on_open_file)
+
+                def on_open_project(event):
+                    pass
+
+                def on_open_file(event):
+                    pass
+            "#},
+        }])
+        .unwrap()
+        .preinit(PreinitOptions::default())
+        .unwrap()
+        .init(InitOptions::default())
+        .unwrap();
+
+        let check_tags = CheckTags::new(&ctx, &store).unwrap();
+        let json: Value = serde_json::from_str(
+            &CheckMetadata::new(&ctx, &store)
+                .unwrap()
+                .to_json(&check_tags),
+        )
+        .unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {
+                    "id": "big-numbers",
+                    "tags": ["style"],
+                    "path": "test.star",
+                    "description": "finds numbers which are too big",
+                    "code": "VX001",
+                    "events": ["open_file", "open_project"],
+                    "languages": ["python", "rust"],
+                    "examples": { "bad": [], "good": [] },
+                },
+                {
+                    "id": "legacy-tool",
+                    "tags": [],
+                    "path": null,
+                    "description": null,
+                    "code": null,
+                    "events": [],
+                    "languages": [],
+                    "examples": { "bad": [], "good": [] },
+                },
+            ])
+        );
+    }
+}
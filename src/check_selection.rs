@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use glob::Pattern;
+
+use crate::{error::Error, result::Result};
+
+/// The checks to run, as passed to `--filter` and `--skip`.
+///
+/// Unlike severity overrides, a selection does not change what any check reports, only which
+/// checks run, so it is kept out of the key of cached results.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckSelection {
+    /// If non-empty, the only checks which run.
+    filter: Vec<Pattern>,
+
+    skip: HashSet<String>,
+}
+
+impl CheckSelection {
+    /// Run only the checks matching the given ids or globs, except those skipped.
+    pub fn new<'a>(
+        filter: impl IntoIterator<Item = &'a String>,
+        skip: impl IntoIterator<Item = &'a String>,
+    ) -> Result<Self> {
+        let filter = filter
+            .into_iter()
+            .map(|pattern| {
+                Pattern::new(pattern).map_err(|cause| Error::Pattern {
+                    pattern: pattern.clone(),
+                    cause_pos_offset: 0,
+                    cause,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let skip = skip.into_iter().cloned().collect();
+        Ok(Self { filter, skip })
+    }
+
+    /// Whether every check runs.
+    pub fn is_empty(&self) -> bool {
+        self.filter.is_empty() && self.skip.is_empty()
+    }
+
+    /// Whether the check or scriptlet with the given id runs.
+    pub fn selects(&self, id: &str) -> bool {
+        !self.skip.contains(id)
+            && (self.filter.is_empty() || self.filter.iter().any(|pattern| pattern.matches(id)))
+    }
+
+    /// Check that each filter matches at least one of the given ids, so that a mistyped filter
+    /// does not pass by checking nothing.
+    pub fn verify(&self, ids: &[&str]) -> Result<()> {
+        match self
+            .filter
+            .iter()
+            .find(|pattern| !ids.iter().any(|id| pattern.matches(id)))
+        {
+            Some(pattern) => Err(Error::NoChecksSelected(pattern.as_str().to_owned())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn selection() {
+        let ids = |ids: &[&str]| ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let selection =
+            |filter: &[&str], skip: &[&str]| CheckSelection::new(&ids(filter), &ids(skip)).unwrap();
+
+        let all = CheckSelection::default();
+        assert!(all.is_empty());
+        assert!(all.selects("anything"));
+
+        let style = selection(&["style-*", "no-unwrap"], &["style-long-lines"]);
+        assert!(!style.is_empty());
+        assert!(style.selects("style-naming"));
+        assert!(style.selects("no-unwrap"));
+        assert!(!style.selects("style-long-lines"));
+        assert!(!style.selects("no-panic"));
+
+        style
+            .verify(&["style-naming", "no-unwrap", "no-panic"])
+            .unwrap();
+        assert_eq!(
+            style.verify(&["style-naming"]).unwrap_err().to_string(),
+            "no check matches --filter 'no-unwrap'"
+        );
+        selection(&[], &["missing"]).verify(&[]).unwrap();
+
+        assert_eq!(
+            CheckSelection::new(&ids(&["[style"]), &ids(&[]))
+                .unwrap_err()
+                .to_string(),
+            "cannot compile \"[style\": invalid range pattern at position 0"
+        );
+    }
+}
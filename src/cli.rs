@@ -9,7 +9,7 @@ use clap::{
     ArgAction, Parser, Subcommand, ValueEnum,
 };
 
-use crate::{supported_language::SupportedLanguage, Result};
+use crate::{import::ImportFormat, severity::Severity, theme::Palette, Result};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -32,6 +32,22 @@ pub struct Args {
     #[arg(short, action=ArgAction::Count, value_name="level", global=true)]
     pub verbosity_level: u8,
 
+    /// Log how long each phase of the run takes
+    #[arg(long, global = true)]
+    pub timings: bool,
+
+    /// How to write log messages to stderr
+    #[arg(long, value_name = "format", global = true, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Colours to draw output with
+    #[arg(long, value_name = "palette", global = true)]
+    pub theme: Option<Palette>,
+
+    /// Draw output using only ASCII characters
+    #[arg(long, global = true)]
+    pub ascii: bool,
+
     /// Print help information, use `--help` for more detail
     #[arg(short, long, action=ArgAction::Help, global=true)]
     help: Option<bool>,
@@ -69,27 +85,67 @@ impl Args {
 
 #[derive(Debug, PartialEq, Eq, Subcommand)]
 pub enum Command {
+    /// Manage cached results
+    Cache(CacheCmd),
+
     /// Check this project for lint
-    Check(CheckCmd),
+    Check(Box<CheckCmd>),
+
+    /// Check that the examples declared by each check behave as declared
+    Doctest,
 
     /// Print the syntax tree of the given file
     Dump(DumpCmd),
 
+    /// Print the description and examples of the check with the given code
+    Explain(ExplainCmd),
+
+    /// Print the stable fingerprint of a problem
+    Fingerprint(FingerprintCmd),
+
+    /// Report problems found by another linter
+    Import(ImportCmd),
+
     /// Create new vex project with this directory as the root
     Init(InitCmd),
 
+    /// Install a git pre-commit hook which checks staged changes
+    InstallHook(InstallHookCmd),
+
     /// Print lists of things vex knows about
     List(ListCmd),
 
+    /// Run a language server which reports problems in open files
+    Lsp,
+
+    /// Create a starter check and a test for it
+    New(NewCmd),
+
+    /// Print a JSON Schema describing vex’s machine-readable formats
+    Schema(SchemaCmd),
+
+    /// Record the grammar plugins this project depends on in vex.lock
+    Sync(SyncCmd),
+
     /// Test available lints
     Test,
+
+    /// Browse problems in the terminal, opening or suppressing each
+    Tui(TuiCmd),
 }
 
 #[cfg(test)]
 impl Command {
+    pub fn into_cache_cmd(self) -> Option<CacheCmd> {
+        match self {
+            Self::Cache(c) => Some(c),
+            _ => None,
+        }
+    }
+
     pub fn into_check_cmd(self) -> Option<CheckCmd> {
         match self {
-            Self::Check(c) => Some(c),
+            Self::Check(c) => Some(*c),
             _ => None,
         }
     }
@@ -101,12 +157,80 @@ impl Command {
         }
     }
 
+    pub fn into_explain_cmd(self) -> Option<ExplainCmd> {
+        match self {
+            Self::Explain(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn into_fingerprint_cmd(self) -> Option<FingerprintCmd> {
+        match self {
+            Self::Fingerprint(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn into_import_cmd(self) -> Option<ImportCmd> {
+        match self {
+            Self::Import(i) => Some(i),
+            _ => None,
+        }
+    }
+
     pub fn into_init_cmd(self) -> Option<InitCmd> {
         match self {
             Self::Init(i) => Some(i),
             _ => None,
         }
     }
+
+    pub fn into_install_hook_cmd(self) -> Option<InstallHookCmd> {
+        match self {
+            Self::InstallHook(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn into_new_cmd(self) -> Option<NewCmd> {
+        match self {
+            Self::New(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn into_sync_cmd(self) -> Option<SyncCmd> {
+        match self {
+            Self::Sync(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn into_schema_cmd(self) -> Option<SchemaCmd> {
+        match self {
+            Self::Schema(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn into_tui_cmd(self) -> Option<TuiCmd> {
+        match self {
+            Self::Tui(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Parser)]
+pub struct CacheCmd {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Subcommand)]
+pub enum CacheCommand {
+    /// Delete all cached results
+    Clear,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Parser)]
@@ -114,13 +238,32 @@ pub struct ListCmd {
     /// What to print
     #[arg(value_name = "what")]
     pub what: ToList,
+
+    /// How to print the list
+    #[arg(long, value_name = "format", default_value = "text")]
+    pub format: OutputFormat,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, ValueEnum)]
 pub enum ToList {
+    Checks,
+    Events,
     Languages,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Parser)]
+pub struct SchemaCmd {
+    /// Which schema to print
+    #[arg(value_name = "what")]
+    pub what: SchemaKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SchemaKind {
+    Manifest,
+    Results,
+}
+
 #[derive(Debug, Default, PartialEq, Eq, Parser)]
 pub struct CheckCmd {
     /// Set concurrency limit
@@ -131,9 +274,278 @@ pub struct CheckCmd {
     #[arg(long)]
     pub lenient: bool,
 
+    /// Ignore and do not update cached results
+    #[arg(long)]
+    pub no_cache: bool,
+
     /// Exit early after this many problems (pass `unlimited` for no max)
     #[arg(long, default_value_t = MaxProblems::default(), value_parser = MaxProblems::parser(), value_name = "max")]
     pub max_problems: MaxProblems,
+
+    /// Report at most this many problems from each check (pass `unlimited` for no max)
+    #[arg(long, default_value_t = MaxProblems::Unlimited, value_parser = MaxProblems::parser(), value_name = "max")]
+    pub max_problems_per_check: MaxProblems,
+
+    /// Exit with an error if any problem is found, not only error-level problems
+    #[arg(long, conflicts_with_all = ["no_fail", "max_allowed"])]
+    pub fail_on_warn: bool,
+
+    /// Exit with an error if any problem of at least this severity is found
+    #[arg(long, value_name = "severity", conflicts_with_all = ["fail_on_warn", "no_fail", "max_allowed"])]
+    pub fail_level: Option<Severity>,
+
+    /// Only print problems of at least this severity
+    #[arg(long, value_name = "severity", default_value = "advice")]
+    pub report_level: Severity,
+
+    /// Never exit with an error because of problems found
+    #[arg(long, conflicts_with = "max_allowed")]
+    pub no_fail: bool,
+
+    /// Exit with an error only if more than this many problems are found
+    #[arg(long, value_name = "max")]
+    pub max_allowed: Option<usize>,
+
+    /// Exit with an error only if a check finds more problems than recorded in this file
+    #[arg(long, value_name = "path", conflicts_with_all = ["fail_on_warn", "fail_level", "no_fail", "max_allowed"])]
+    pub compare_baseline: Option<Utf8PathBuf>,
+
+    /// Lower the counts recorded in the baseline file to those found, creating it if needed
+    #[arg(long, requires = "compare_baseline", conflicts_with_all = ["changed", "staged", "filter", "skip", "only_tags", "report_level"])]
+    pub update_ratchet: bool,
+
+    /// Report errors raised by scriptlets as problems and carry on checking (default)
+    #[arg(long, overrides_with = "no_keep_going")]
+    pub keep_going: bool,
+
+    /// Stop at the first error raised by a scriptlet
+    #[arg(long, overrides_with = "keep_going")]
+    pub no_keep_going: bool,
+
+    /// Print how many files were skipped as their language is unknown, by extension
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Check files in symlinked directories
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Print problems in sections, one per file or per check
+    #[arg(long, value_name = "grouping", conflicts_with = "summary")]
+    pub group_by: Option<GroupBy>,
+
+    /// Print only how many problems each check found
+    #[arg(long)]
+    pub summary: bool,
+
+    /// The order in which to print problems
+    #[arg(long, value_name = "order", default_value = "file")]
+    pub sort: SortBy,
+
+    /// Write every intent declared by an observer to a JSON lines file, for debugging
+    #[arg(long, value_name = "path")]
+    pub trace_intents: Option<Utf8PathBuf>,
+
+    /// Only check files changed since the given git ref (default HEAD)
+    #[arg(long, value_name = "base_ref", num_args = 0..=1, require_equals = true, default_missing_value = "HEAD")]
+    pub changed: Option<String>,
+
+    /// Check the content of changed files as staged in git, rather than in the working tree
+    #[arg(long, requires = "changed", conflicts_with_all = ["fix", "emit_patch"])]
+    pub staged: bool,
+
+    /// Check content read from stdin instead of the project's files, without running external
+    /// checks or close_project handlers
+    #[arg(long, requires = "stdin_path", conflicts_with_all = ["changed", "fix", "emit_patch", "open", "compare_baseline"])]
+    pub stdin: bool,
+
+    /// Where the content read from stdin would be saved, relative to the project root
+    #[arg(long, value_name = "path", requires = "stdin")]
+    pub stdin_path: Option<Utf8PathBuf>,
+
+    /// Report problems from this vex as advice
+    #[arg(long, value_name = "vex_id")]
+    pub allow: Vec<String>,
+
+    /// Report problems from this vex as warnings
+    #[arg(long, value_name = "vex_id")]
+    pub warn: Vec<String>,
+
+    /// Report problems from this vex as errors
+    #[arg(long, value_name = "vex_id")]
+    pub deny: Vec<String>,
+
+    /// Only run checks whose ids match this id or glob
+    #[arg(long, value_name = "id_or_glob")]
+    pub filter: Vec<String>,
+
+    /// Do not run this check
+    #[arg(long, value_name = "vex_id")]
+    pub skip: Vec<String>,
+
+    /// Only report problems from checks with any of these tags
+    #[arg(long, value_name = "tags", value_delimiter = ',')]
+    pub only_tags: Vec<String>,
+
+    /// Apply suggested fixes
+    #[arg(long)]
+    pub fix: bool,
+
+    /// Ask before applying each fix
+    #[arg(long, requires = "fix")]
+    pub interactive: bool,
+
+    /// Keep the original of each fixed file in a `.orig` file beside it
+    #[arg(long, requires = "fix", conflicts_with = "dry_run")]
+    pub backup: bool,
+
+    /// Work out which fixes would be applied without changing any file
+    #[arg(long, requires = "fix")]
+    pub dry_run: bool,
+
+    /// Also write the fixes applied to a patch file
+    #[arg(long, value_name = "path", requires = "fix")]
+    pub diff_out: Option<Utf8PathBuf>,
+
+    /// Write suggested fixes to a patch file instead of applying them
+    #[arg(long, value_name = "path", conflicts_with = "fix")]
+    pub emit_patch: Option<Utf8PathBuf>,
+
+    /// Show the change each suggested fix would make under its problem
+    #[arg(long, conflicts_with = "stdin")]
+    pub show_fixes: bool,
+
+    /// Open the first problem in an editor
+    #[arg(long)]
+    pub open: bool,
+
+    /// How to print problems
+    #[arg(long, value_name = "format", default_value = "text")]
+    pub format: OutputFormat,
+
+    /// Only check that vex.toml is valid and every scriptlet loads, without scanning
+    #[arg(long, conflicts_with_all = ["fix", "emit_patch", "stdin", "changed", "open", "compare_baseline"])]
+    pub validate_config: bool,
+
+    /// Also write problems to a file, given as `format=path` (formats: json, junit)
+    #[arg(long, value_name = "format=path", value_parser = Report::parser())]
+    pub report: Vec<Report>,
+}
+
+impl CheckCmd {
+    pub fn fail_policy(&self) -> FailPolicy {
+        if self.no_fail || self.compare_baseline.is_some() {
+            FailPolicy::Never
+        } else if self.fail_on_warn {
+            FailPolicy::AnyProblem
+        } else if let Some(max_allowed) = self.max_allowed {
+            FailPolicy::MoreThan(max_allowed)
+        } else if let Some(fail_level) = self.fail_level {
+            FailPolicy::AtLeast(fail_level)
+        } else {
+            FailPolicy::default()
+        }
+    }
+}
+
+/// When problems found by `check` cause a non-zero exit code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FailPolicy {
+    Never,
+    AnyProblem,
+    AtLeast(Severity),
+    MoreThan(usize),
+}
+
+impl FailPolicy {
+    /// Whether a run fails, given how many problems were found and the most severe of them.
+    pub fn fails(&self, num_problems: usize, worst: Option<Severity>) -> bool {
+        match self {
+            Self::Never => false,
+            Self::AnyProblem => num_problems > 0,
+            Self::AtLeast(level) => worst.is_some_and(|worst| worst >= *level),
+            Self::MoreThan(max_allowed) => num_problems > *max_allowed,
+        }
+    }
+}
+
+impl Default for FailPolicy {
+    fn default() -> Self {
+        Self::AtLeast(Severity::Error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// A file to which problems are written in addition to the console output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub format: ReportFormat,
+    pub path: Utf8PathBuf,
+}
+
+impl Report {
+    fn parser() -> impl TypedValueParser {
+        StringValueParser::new().try_map(|s| {
+            let Some((format, path)) = s.split_once('=') else {
+                return Err(format!("expected format=path, got '{s}'"));
+            };
+            if path.is_empty() {
+                return Err(format!("no path given for {format} report"));
+            }
+            Ok(Self {
+                format: ReportFormat::from_str(format, false)?,
+                path: path.into(),
+            })
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+    Junit,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Parser)]
+pub struct TuiCmd {
+    /// How to group problems
+    #[arg(long, value_name = "grouping", default_value = "file")]
+    pub group_by: GroupBy,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    #[default]
+    File,
+    Check,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// By path then position in the file
+    #[default]
+    File,
+
+    /// By check id, then as for `file`
+    Check,
+
+    /// Most severe first, then as for `file`
+    Severity,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -295,13 +707,81 @@ pub struct DumpCmd {
     #[arg(value_name = "file")]
     pub path: Utf8PathBuf,
 
+    /// Output format
+    #[arg(long, value_enum, default_value_t, value_name = "format")]
+    pub format: DumpFormat,
+
     /// Remove location info, line-breaks and indentation
     #[arg(long)]
     pub compact: bool,
 
-    /// Override language detection
-    #[arg(long = "as", value_name = "language")]
-    pub language: Option<SupportedLanguage>,
+    /// Print the captures of each match of this query instead of the syntax tree
+    #[arg(long, value_name = "query")]
+    pub query: Option<String>,
+
+    /// Parse as the given language rather than detecting it
+    #[arg(long, visible_alias = "as", value_name = "language")]
+    pub language: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    /// An s-expression with locations
+    #[default]
+    Sexp,
+
+    /// A JSON tree with each node's kind, field, location and byte range
+    Json,
+
+    /// An s-expression with locations and source text
+    Pretty,
+}
+
+#[derive(Debug, PartialEq, Eq, Parser)]
+pub struct ExplainCmd {
+    /// The code of the check to explain, or its id
+    #[arg(value_name = "code")]
+    pub code: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Parser)]
+pub struct FingerprintCmd {
+    /// The problem to fingerprint, as path:line:column:check-id
+    #[arg(value_name = "finding")]
+    pub finding: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Parser)]
+pub struct SyncCmd {
+    /// Fail if vex.lock is missing or out of date instead of writing it
+    #[arg(long)]
+    pub locked: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Parser)]
+pub struct ImportCmd {
+    /// Format of the results to import
+    #[arg(long, value_name = "format")]
+    pub format: ImportFormat,
+
+    /// File containing the results to import
+    #[arg(value_name = "file")]
+    pub path: Utf8PathBuf,
+}
+
+#[derive(Debug, PartialEq, Eq, Parser)]
+pub struct NewCmd {
+    /// The id of the new check
+    #[arg(value_name = "check-id")]
+    pub name: String,
+
+    /// The language the new check searches
+    #[arg(long, value_name = "language", default_value = "rust")]
+    pub language: String,
+
+    /// Overwrite existing files
+    #[arg(long)]
+    pub force: bool,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Parser)]
@@ -309,6 +789,30 @@ pub struct InitCmd {
     /// Force init
     #[arg(long)]
     pub force: bool,
+
+    /// Also add starter checks for the given languages
+    #[arg(long, value_name = "template")]
+    pub template: Option<InitTemplate>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum InitTemplate {
+    Rust,
+    Python,
+
+    /// Both Rust and Python
+    Mixed,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Parser)]
+pub struct InstallHookCmd {
+    /// Overwrite an existing pre-commit hook
+    #[arg(long)]
+    pub force: bool,
+
+    /// Print configuration for the pre-commit framework instead of installing a hook
+    #[arg(long, conflicts_with = "force")]
+    pub pre_commit_config: bool,
 }
 
 fn parse_overrides() {
@@ -405,6 +909,44 @@ mod test {
         );
     }
 
+    #[test]
+    fn timings() {
+        assert!(!Args::try_parse_from(["vex", "check"]).unwrap().timings);
+        assert!(
+            Args::try_parse_from(["vex", "--timings", "check"])
+                .unwrap()
+                .timings
+        );
+        assert!(
+            Args::try_parse_from(["vex", "test", "--timings"])
+                .unwrap()
+                .timings
+        );
+    }
+
+    #[test]
+    fn log_format() {
+        let args = Args::try_parse_from(["vex", "check"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Text);
+        let args = Args::try_parse_from(["vex", "check", "--log-format", "json"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+        Args::try_parse_from(["vex", "check", "--log-format", "xml"]).unwrap_err();
+    }
+
+    #[test]
+    fn theme() {
+        let args = Args::try_parse_from(["vex", "check"]).unwrap();
+        assert_eq!(args.theme, None);
+        assert!(!args.ascii);
+
+        let args =
+            Args::try_parse_from(["vex", "check", "--theme", "colorblind", "--ascii"]).unwrap();
+        assert_eq!(args.theme, Some(Palette::Colorblind));
+        assert!(args.ascii);
+
+        Args::try_parse_from(["vex", "check", "--theme", "sepia"]).unwrap_err();
+    }
+
     #[test]
     fn verbosity_conflict() {
         const CMD: &str = "check";
@@ -421,12 +963,63 @@ mod test {
                     .unwrap()
                     .into_command(),
                 Command::List(ListCmd {
-                    what: ToList::Languages
+                    what: ToList::Languages,
+                    format: OutputFormat::Text,
+                }),
+            );
+        }
+
+        #[test]
+        fn events() {
+            assert_eq!(
+                Args::try_parse_from(["vex", "list", "events", "--format", "json"])
+                    .unwrap()
+                    .into_command(),
+                Command::List(ListCmd {
+                    what: ToList::Events,
+                    format: OutputFormat::Json,
+                }),
+            );
+        }
+
+        #[test]
+        fn checks() {
+            assert_eq!(
+                Args::try_parse_from(["vex", "list", "checks"])
+                    .unwrap()
+                    .into_command(),
+                Command::List(ListCmd {
+                    what: ToList::Checks,
+                    format: OutputFormat::Text,
+                }),
+            );
+            assert_eq!(
+                Args::try_parse_from(["vex", "list", "checks", "--format", "json"])
+                    .unwrap()
+                    .into_command(),
+                Command::List(ListCmd {
+                    what: ToList::Checks,
+                    format: OutputFormat::Json,
                 }),
             );
         }
     }
 
+    #[test]
+    fn cache() {
+        Args::try_parse_from(["vex", "cache"]).unwrap_err();
+        assert_eq!(
+            Args::try_parse_from(["vex", "cache", "clear"])
+                .unwrap()
+                .into_command()
+                .into_cache_cmd()
+                .unwrap(),
+            CacheCmd {
+                command: CacheCommand::Clear
+            },
+        );
+    }
+
     mod check {
         use super::*;
 
@@ -460,6 +1053,463 @@ mod test {
             let check_cmd = cmd.into_check_cmd().unwrap();
             assert_eq!(check_cmd.max_problems, MaxProblems::Unlimited);
         }
+
+        #[test]
+        fn max_problems_per_check() {
+            let max_problems_per_check = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+                    .max_problems_per_check
+            };
+            assert_eq!(
+                max_problems_per_check(&["vex", "check"]),
+                MaxProblems::Unlimited
+            );
+            assert_eq!(
+                max_problems_per_check(&["vex", "check", "--max-problems-per-check", "5"]),
+                MaxProblems::Limited(5)
+            );
+        }
+
+        #[test]
+        fn fail_policy() {
+            let fail_policy = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+                    .fail_policy()
+            };
+            assert_eq!(
+                fail_policy(&["vex", "check"]),
+                FailPolicy::AtLeast(Severity::Error)
+            );
+            assert_eq!(
+                fail_policy(&["vex", "check", "--fail-level", "warning"]),
+                FailPolicy::AtLeast(Severity::Warning)
+            );
+            assert_eq!(
+                fail_policy(&["vex", "check", "--fail-level", "info"]),
+                FailPolicy::AtLeast(Severity::Advice)
+            );
+            assert_eq!(
+                fail_policy(&["vex", "check", "--fail-on-warn"]),
+                FailPolicy::AnyProblem
+            );
+            assert_eq!(
+                fail_policy(&["vex", "check", "--no-fail"]),
+                FailPolicy::Never
+            );
+            assert_eq!(
+                fail_policy(&["vex", "check", "--max-allowed", "10"]),
+                FailPolicy::MoreThan(10)
+            );
+            for conflicting in [
+                ["--no-fail", "--fail-on-warn"],
+                ["--no-fail", "--max-allowed=1"],
+                ["--fail-on-warn", "--max-allowed=1"],
+                ["--fail-level=error", "--fail-on-warn"],
+                ["--fail-level=error", "--no-fail"],
+            ] {
+                Args::try_parse_from(["vex", "check"].into_iter().chain(conflicting)).unwrap_err();
+            }
+
+            assert!(!FailPolicy::Never.fails(10, Some(Severity::Error)));
+            assert!(!FailPolicy::AnyProblem.fails(0, None));
+            assert!(FailPolicy::AnyProblem.fails(1, Some(Severity::Advice)));
+            let errors = FailPolicy::AtLeast(Severity::Error);
+            assert!(!errors.fails(1, Some(Severity::Warning)));
+            assert!(errors.fails(1, Some(Severity::Error)));
+            assert!(!FailPolicy::AtLeast(Severity::Warning).fails(0, None));
+            assert!(FailPolicy::AtLeast(Severity::Warning).fails(1, Some(Severity::Error)));
+            assert!(!FailPolicy::MoreThan(2).fails(2, Some(Severity::Error)));
+            assert!(FailPolicy::MoreThan(2).fails(3, None));
+        }
+
+        #[test]
+        fn report_level() {
+            let report_level = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+                    .report_level
+            };
+            assert_eq!(report_level(&["vex", "check"]), Severity::Advice);
+            assert_eq!(
+                report_level(&["vex", "check", "--report-level", "warning"]),
+                Severity::Warning
+            );
+            Args::try_parse_from(["vex", "check", "--report-level", "loud"]).unwrap_err();
+        }
+
+        #[test]
+        fn keep_going() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert!(!check_cmd(&["vex", "check"]).no_keep_going);
+            assert!(check_cmd(&["vex", "check", "--no-keep-going"]).no_keep_going);
+            assert!(!check_cmd(&["vex", "check", "--no-keep-going", "--keep-going"]).no_keep_going);
+            assert!(check_cmd(&["vex", "check", "--keep-going", "--no-keep-going"]).no_keep_going);
+        }
+
+        #[test]
+        fn stats() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert!(!check_cmd(&["vex", "check"]).stats);
+            assert!(check_cmd(&["vex", "check", "--stats"]).stats);
+        }
+
+        #[test]
+        fn compare_baseline() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            let cmd = check_cmd(&["vex", "check", "--compare-baseline", "ratchet.toml"]);
+            assert_eq!(cmd.compare_baseline, Some("ratchet.toml".into()));
+            assert!(!cmd.update_ratchet);
+            assert_eq!(cmd.fail_policy(), FailPolicy::Never);
+            assert!(
+                check_cmd(&[
+                    "vex",
+                    "check",
+                    "--compare-baseline",
+                    "ratchet.toml",
+                    "--update-ratchet"
+                ])
+                .update_ratchet
+            );
+            Args::try_parse_from(["vex", "check", "--update-ratchet"]).unwrap_err();
+            for partial_scan_args in [
+                &["--changed"][..],
+                &["--changed", "--staged"],
+                &["--filter", "some-check"],
+                &["--skip", "some-check"],
+                &["--only-tags", "some-tag"],
+                &["--report-level", "error"],
+            ] {
+                let args = ["vex", "check", "--compare-baseline", "ratchet.toml"]
+                    .into_iter()
+                    .chain(partial_scan_args.iter().copied());
+                Args::try_parse_from(args.clone()).unwrap();
+                Args::try_parse_from(args.chain(["--update-ratchet"])).unwrap_err();
+            }
+            Args::try_parse_from([
+                "vex",
+                "check",
+                "--compare-baseline",
+                "ratchet.toml",
+                "--fail-on-warn",
+            ])
+            .unwrap_err();
+        }
+
+        #[test]
+        fn follow_symlinks() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert!(!check_cmd(&["vex", "check"]).follow_symlinks);
+            assert!(check_cmd(&["vex", "check", "--follow-symlinks"]).follow_symlinks);
+        }
+
+        #[test]
+        fn sort() {
+            let sort = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+                    .sort
+            };
+            assert_eq!(sort(&["vex", "check"]), SortBy::File);
+            assert_eq!(sort(&["vex", "check", "--sort", "check"]), SortBy::Check);
+            assert_eq!(
+                sort(&["vex", "check", "--sort", "severity"]),
+                SortBy::Severity
+            );
+            Args::try_parse_from(["vex", "check", "--sort", "message"]).unwrap_err();
+        }
+
+        #[test]
+        fn show_fixes() {
+            let show_fixes = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+                    .show_fixes
+            };
+            assert!(!show_fixes(&["vex", "check"]));
+            assert!(show_fixes(&["vex", "check", "--show-fixes"]));
+            Args::try_parse_from([
+                "vex",
+                "check",
+                "--show-fixes",
+                "--stdin",
+                "--stdin-path",
+                "src/main.rs",
+            ])
+            .unwrap_err();
+        }
+
+        #[test]
+        fn validate_config() {
+            let validate_config = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+                    .validate_config
+            };
+            assert!(!validate_config(&["vex", "check"]));
+            assert!(validate_config(&["vex", "check", "--validate-config"]));
+            Args::try_parse_from(["vex", "check", "--validate-config", "--fix"]).unwrap_err();
+        }
+
+        #[test]
+        fn grouping() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert_eq!(check_cmd(&["vex", "check"]).group_by, None);
+            assert_eq!(
+                check_cmd(&["vex", "check", "--group-by", "check"]).group_by,
+                Some(GroupBy::Check)
+            );
+            assert!(!check_cmd(&["vex", "check"]).summary);
+            assert!(check_cmd(&["vex", "check", "--summary"]).summary);
+            Args::try_parse_from(["vex", "check", "--summary", "--group-by", "file"]).unwrap_err();
+        }
+
+        #[test]
+        fn trace_intents() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert_eq!(check_cmd(&["vex", "check"]).trace_intents, None);
+            assert_eq!(
+                check_cmd(&["vex", "check", "--trace-intents", "intents.jsonl"]).trace_intents,
+                Some("intents.jsonl".into())
+            );
+        }
+
+        #[test]
+        fn changed() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert_eq!(check_cmd(&["vex", "check"]).changed, None);
+            assert_eq!(
+                check_cmd(&["vex", "check", "--changed"]).changed.as_deref(),
+                Some("HEAD")
+            );
+            assert_eq!(
+                check_cmd(&["vex", "check", "--changed=main"])
+                    .changed
+                    .as_deref(),
+                Some("main")
+            );
+            assert!(check_cmd(&["vex", "check", "--changed", "--staged"]).staged);
+            Args::try_parse_from(["vex", "check", "--staged"]).unwrap_err();
+        }
+
+        #[test]
+        fn stdin() {
+            let check_cmd =
+                Args::try_parse_from(["vex", "check", "--stdin", "--stdin-path", "src/main.rs"])
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap();
+            assert!(check_cmd.stdin);
+            assert_eq!(check_cmd.stdin_path, Some("src/main.rs".into()));
+
+            Args::try_parse_from(["vex", "check", "--stdin"]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--stdin-path", "src/main.rs"]).unwrap_err();
+            Args::try_parse_from([
+                "vex",
+                "check",
+                "--stdin",
+                "--stdin-path",
+                "src/main.rs",
+                "--fix",
+            ])
+            .unwrap_err();
+        }
+
+        #[test]
+        fn severity_overrides() {
+            let args = Args::try_parse_from([
+                "vex", "check", "--allow", "vex-1", "--warn", "vex-2", "--deny", "vex-3", "--deny",
+                "vex-4",
+            ])
+            .unwrap();
+            let check_cmd = args.into_command().into_check_cmd().unwrap();
+            assert_eq!(check_cmd.allow, ["vex-1"]);
+            assert_eq!(check_cmd.warn, ["vex-2"]);
+            assert_eq!(check_cmd.deny, ["vex-3", "vex-4"]);
+        }
+
+        #[test]
+        fn selection() {
+            let args = Args::try_parse_from([
+                "vex", "check", "--filter", "style-*", "--filter", "vex-1", "--skip", "vex-2",
+            ])
+            .unwrap();
+            let check_cmd = args.into_command().into_check_cmd().unwrap();
+            assert_eq!(check_cmd.filter, ["style-*", "vex-1"]);
+            assert_eq!(check_cmd.skip, ["vex-2"]);
+        }
+
+        #[test]
+        fn only_tags() {
+            let args = Args::try_parse_from([
+                "vex",
+                "check",
+                "--only-tags",
+                "security,style",
+                "--only-tags",
+                "perf",
+            ])
+            .unwrap();
+            let check_cmd = args.into_command().into_check_cmd().unwrap();
+            assert_eq!(check_cmd.only_tags, ["security", "style", "perf"]);
+        }
+
+        #[test]
+        fn fixes() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert!(!check_cmd(&["vex", "check"]).fix);
+            assert!(check_cmd(&["vex", "check", "--fix"]).fix);
+            assert_eq!(
+                check_cmd(&["vex", "check", "--emit-patch", "fixes.patch"]).emit_patch,
+                Some("fixes.patch".into())
+            );
+            Args::try_parse_from(["vex", "check", "--fix", "--emit-patch", "fixes.patch"])
+                .unwrap_err();
+            assert!(check_cmd(&["vex", "check", "--fix", "--interactive"]).interactive);
+            Args::try_parse_from(["vex", "check", "--interactive"]).unwrap_err();
+
+            let dry_run =
+                check_cmd(&["vex", "check", "--fix", "--dry-run", "--diff-out", "p.diff"]);
+            assert!(dry_run.dry_run);
+            assert_eq!(dry_run.diff_out, Some("p.diff".into()));
+            Args::try_parse_from(["vex", "check", "--dry-run"]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--diff-out", "p.diff"]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--fix", "--dry-run", "--backup"]).unwrap_err();
+        }
+
+        #[test]
+        fn format() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert_eq!(check_cmd(&["vex", "check"]).format, OutputFormat::Text);
+            assert_eq!(
+                check_cmd(&["vex", "check", "--format", "json"]).format,
+                OutputFormat::Json
+            );
+            Args::try_parse_from(["vex", "check", "--format", "yaml"]).unwrap_err();
+        }
+
+        #[test]
+        fn report() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert_eq!(check_cmd(&["vex", "check"]).report, []);
+            assert_eq!(
+                check_cmd(&[
+                    "vex",
+                    "check",
+                    "--report",
+                    "junit=out/vex.xml",
+                    "--report",
+                    "json=results.json",
+                ])
+                .report,
+                [
+                    Report {
+                        format: ReportFormat::Junit,
+                        path: "out/vex.xml".into(),
+                    },
+                    Report {
+                        format: ReportFormat::Json,
+                        path: "results.json".into(),
+                    },
+                ]
+            );
+            Args::try_parse_from(["vex", "check", "--report", "junit"]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--report", "junit="]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--report", "sarif=out.sarif"]).unwrap_err();
+        }
+
+        #[test]
+        fn open() {
+            let check_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap()
+            };
+            assert!(!check_cmd(&["vex", "check"]).open);
+            assert!(check_cmd(&["vex", "check", "--open"]).open);
+        }
     }
 
     mod dump {
@@ -487,10 +1537,68 @@ mod test {
         }
 
         #[test]
-        fn language() {
-            let args = Args::try_parse_from(["vex", "dump", "asdf.foo", "--as", "rust"]).unwrap();
+        fn format() {
+            let dump_cmd = |args: &[&str]| {
+                Args::try_parse_from(args)
+                    .unwrap()
+                    .into_command()
+                    .into_dump_cmd()
+                    .unwrap()
+            };
+            assert_eq!(dump_cmd(&["vex", "dump", "a.rs"]).format, DumpFormat::Sexp);
+            for (name, format) in [
+                ("sexp", DumpFormat::Sexp),
+                ("json", DumpFormat::Json),
+                ("pretty", DumpFormat::Pretty),
+            ] {
+                assert_eq!(
+                    dump_cmd(&["vex", "dump", "a.rs", "--format", name]).format,
+                    format
+                );
+            }
+            Args::try_parse_from(["vex", "dump", "a.rs", "--format", "xml"]).unwrap_err();
+        }
+
+        #[test]
+        fn query() {
+            let args = Args::try_parse_from(["vex", "dump", "a.rs", "--query", "(identifier) @id"])
+                .unwrap();
             let dump_cmd = args.into_command().into_dump_cmd().unwrap();
-            assert_eq!(SupportedLanguage::Rust, dump_cmd.language.unwrap());
+            assert_eq!(dump_cmd.query.as_deref(), Some("(identifier) @id"));
+        }
+
+        #[test]
+        fn language() {
+            for flag in ["--language", "--as"] {
+                let args = Args::try_parse_from(["vex", "dump", "asdf.foo", flag, "rust"]).unwrap();
+                let dump_cmd = args.into_command().into_dump_cmd().unwrap();
+                assert_eq!(dump_cmd.language.as_deref(), Some("rust"));
+            }
+        }
+    }
+
+    mod import {
+        use super::*;
+
+        #[test]
+        fn requires_format() {
+            Args::try_parse_from(["vex", "import", "results.json"]).unwrap_err();
+        }
+
+        #[test]
+        fn formats() {
+            for (name, format) in [
+                ("eslint-json", ImportFormat::EslintJson),
+                ("clippy-json", ImportFormat::ClippyJson),
+                ("sarif", ImportFormat::Sarif),
+            ] {
+                let args =
+                    Args::try_parse_from(["vex", "import", "--format", name, "results.json"])
+                        .unwrap();
+                let import_cmd = args.into_command().into_import_cmd().unwrap();
+                assert_eq!(import_cmd.format, format);
+                assert_eq!(import_cmd.path, "results.json");
+            }
         }
     }
 
@@ -502,7 +1610,10 @@ mod test {
                 .into_command()
                 .into_init_cmd()
                 .unwrap(),
-            InitCmd { force: false },
+            InitCmd {
+                force: false,
+                template: None
+            },
         );
         assert_eq!(
             Args::try_parse_from(["vex", "init", "--force"])
@@ -510,7 +1621,125 @@ mod test {
                 .into_command()
                 .into_init_cmd()
                 .unwrap(),
-            InitCmd { force: true },
+            InitCmd {
+                force: true,
+                template: None
+            },
+        );
+        assert_eq!(
+            Args::try_parse_from(["vex", "init", "--template", "mixed"])
+                .unwrap()
+                .into_command()
+                .into_init_cmd()
+                .unwrap(),
+            InitCmd {
+                force: false,
+                template: Some(InitTemplate::Mixed)
+            },
+        );
+        Args::try_parse_from(["vex", "init", "--template", "cobol"]).unwrap_err();
+    }
+
+    #[test]
+    fn install_hook() {
+        let install_hook_cmd = |args: &[&str]| {
+            Args::try_parse_from(args)
+                .unwrap()
+                .into_command()
+                .into_install_hook_cmd()
+                .unwrap()
+        };
+        assert_eq!(
+            install_hook_cmd(&["vex", "install-hook"]),
+            InstallHookCmd::default(),
+        );
+        assert!(install_hook_cmd(&["vex", "install-hook", "--force"]).force);
+        assert!(
+            install_hook_cmd(&["vex", "install-hook", "--pre-commit-config"]).pre_commit_config
+        );
+        Args::try_parse_from(["vex", "install-hook", "--force", "--pre-commit-config"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn doctest() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "doctest"])
+                .unwrap()
+                .into_command(),
+            Command::Doctest,
+        )
+    }
+
+    #[test]
+    fn explain() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "explain", "VX001"])
+                .unwrap()
+                .into_command()
+                .into_explain_cmd()
+                .unwrap(),
+            ExplainCmd {
+                code: "VX001".into(),
+            },
+        );
+    }
+
+    #[test]
+    fn fingerprint() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "fingerprint", "src/main.rs:2:13:some-check"])
+                .unwrap()
+                .into_command()
+                .into_fingerprint_cmd()
+                .unwrap(),
+            FingerprintCmd {
+                finding: "src/main.rs:2:13:some-check".into(),
+            },
+        );
+    }
+
+    #[test]
+    fn new() {
+        let new_cmd = |args: &[&str]| {
+            Args::try_parse_from(args)
+                .unwrap()
+                .into_command()
+                .into_new_cmd()
+                .unwrap()
+        };
+        assert_eq!(
+            new_cmd(&["vex", "new", "no-todo"]),
+            NewCmd {
+                name: "no-todo".into(),
+                language: "rust".into(),
+                force: false,
+            },
+        );
+        assert_eq!(
+            new_cmd(&["vex", "new", "no-todo", "--language", "go", "--force"]),
+            NewCmd {
+                name: "no-todo".into(),
+                language: "go".into(),
+                force: true,
+            },
+        );
+        Args::try_parse_from(["vex", "new"]).unwrap_err();
+    }
+
+    #[test]
+    fn sync() {
+        let sync_cmd = |args: &[&str]| {
+            Args::try_parse_from(args)
+                .unwrap()
+                .into_command()
+                .into_sync_cmd()
+                .unwrap()
+        };
+        assert_eq!(sync_cmd(&["vex", "sync"]), SyncCmd { locked: false });
+        assert_eq!(
+            sync_cmd(&["vex", "sync", "--locked"]),
+            SyncCmd { locked: true }
         );
     }
 
@@ -523,4 +1752,52 @@ mod test {
             Command::Test,
         )
     }
+
+    #[test]
+    fn tui() {
+        let tui_cmd = |args: &[&str]| {
+            Args::try_parse_from(args)
+                .unwrap()
+                .into_command()
+                .into_tui_cmd()
+                .unwrap()
+        };
+        assert_eq!(tui_cmd(&["vex", "tui"]).group_by, GroupBy::File);
+        assert_eq!(
+            tui_cmd(&["vex", "tui", "--group-by", "check"]).group_by,
+            GroupBy::Check
+        );
+        assert!(Args::try_parse_from(["vex", "tui", "--group-by", "severity"]).is_err());
+    }
+
+    #[test]
+    fn schema() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "schema", "results"])
+                .unwrap()
+                .into_command()
+                .into_schema_cmd()
+                .unwrap()
+                .what,
+            SchemaKind::Results
+        );
+        assert_eq!(
+            Args::try_parse_from(["vex", "schema", "manifest"])
+                .unwrap()
+                .into_command()
+                .into_schema_cmd()
+                .unwrap()
+                .what,
+            SchemaKind::Manifest
+        );
+        Args::try_parse_from(["vex", "schema"]).unwrap_err();
+    }
+
+    #[test]
+    fn lsp() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "lsp"]).unwrap().into_command(),
+            Command::Lsp,
+        )
+    }
 }
@@ -0,0 +1,875 @@
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+    process::ExitCode,
+};
+
+use camino::{Utf8Component, Utf8Path};
+use indoc::{formatdoc, printdoc};
+use joinery::JoinableIterator;
+use log::{debug, info, log_enabled};
+use rayon::ThreadPoolBuilder;
+use strum::IntoEnumIterator;
+
+use crate::{
+    cache::Cache,
+    check_metadata::CheckMetadata,
+    check_selection::CheckSelection,
+    cli::{
+        Args, CacheCmd, CacheCommand, CheckCmd, Command, ExplainCmd, FailPolicy, GroupBy,
+        ImportCmd, InitCmd, ListCmd, MaxProblems, OutputFormat, ReportFormat, SchemaCmd,
+        SchemaKind, ToList, TuiCmd,
+    },
+    context::{self, Context, Manifest, EXAMPLE_VEX_FILE},
+    doctest, dump,
+    editor::Editor,
+    error::{Error, IOAction},
+    fingerprint, fix, git, hook, import,
+    injection::Injection,
+    irritation::Irritation,
+    junit::JUnitReport,
+    lock, logger, lsp,
+    plural::Plural,
+    prompt::Prompt,
+    ratchet::Ratchet,
+    result::Result,
+    results::{self, Results},
+    scaffold, scan,
+    scan::{ProjectRunData, ScanOptions},
+    scriptlets::{
+        artifact_cache::ArtifactCache,
+        event::EventKind,
+        intent_log::IntentLog,
+        source::{self, ScriptSource},
+        InitOptions, Location, PreinitOptions, PreinitingStore, VexingStore,
+    },
+    severity::{Severity, SeverityOverrides},
+    source_path::{PrettyPath, SourcePath},
+    success, summary,
+    supported_language::SupportedLanguage,
+    tags::CheckTags,
+    test,
+    theme::{self, Glyphs},
+    timings::{self, Timer},
+    tui,
+    verbosity::Verbosity,
+};
+
+// TODO(kcza): move the subcommands to separate files
+pub fn main() -> ExitCode {
+    match run() {
+        Ok(c) => c,
+        Err(e) => {
+            crate::error!("{e}");
+            ExitCode::from(u8::MAX)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode> {
+    let args = Args::parse();
+
+    let verbosity = if args.quiet {
+        Verbosity::Quiet
+    } else {
+        args.verbosity_level.try_into()?
+    };
+    logger::init(verbosity, args.timings, args.log_format)?;
+    theme::init(args.theme, args.ascii.then_some(Glyphs::Ascii));
+
+    // The language server speaks over stdout, so cannot be interrupted by the banner.
+    if log_enabled!(log::Level::Info) && args.command != Command::Lsp {
+        print_banner();
+    }
+
+    match args.command {
+        Command::Cache(cache_args) => cache(cache_args),
+        Command::Check(cmd_args) => return check(*cmd_args),
+        Command::Doctest => doctest::doctest(),
+        Command::Dump(dump_args) => dump::dump(dump_args),
+        Command::Explain(explain_args) => explain(explain_args),
+        Command::Fingerprint(fingerprint_args) => fingerprint::fingerprint(fingerprint_args),
+        Command::Import(import_args) => return import(import_args),
+        Command::List(list_args) => list(list_args),
+        Command::Lsp => lsp(),
+        Command::New(new_args) => scaffold::scaffold(new_args),
+        Command::Schema(schema_args) => schema(schema_args),
+        Command::Sync(sync_args) => lock::sync(sync_args),
+        Command::Init(init_args) => init(init_args),
+        Command::InstallHook(install_hook_args) => hook::install_hook(install_hook_args),
+        Command::Test => test::test(),
+        Command::Tui(tui_args) => tui(tui_args),
+    }?;
+
+    Ok(logger::exit_code())
+}
+
+fn print_banner() {
+    printdoc! {
+        r#"
+
+            oooooo      oooo
+             `888.      .8'
+              `888.    .8'   .ooooo.   ooooo ooo
+               `888.  .8'   d88' `88b   `888.8"
+                `888..8'    888ooo888    `888.
+                 `8888'     888    .o   .8"888.
+                  `88'      `Y8bod8P'  o8o o888o
+
+            Let the pedantry begin.
+
+        "#
+    };
+}
+
+fn explain(explain_args: ExplainCmd) -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let verbosity = logger::verbosity();
+    let store = load_store(
+        &ctx,
+        &source::sources_in_project(&ctx)?,
+        verbosity,
+        SeverityOverrides::default().with_configured(ctx.checks.levels()),
+        CheckSelection::default(),
+        false,
+    )?;
+    let check_tags = CheckTags::new(&ctx, &store)?;
+    print!(
+        "{}",
+        CheckMetadata::new(&ctx, &store)?.explain(&explain_args.code, &check_tags)?
+    );
+    Ok(())
+}
+
+fn list(list_args: ListCmd) -> Result<()> {
+    let ListCmd { what, format } = list_args;
+    match what {
+        ToList::Checks => list_checks(format)?,
+        ToList::Events => list_events(format),
+        ToList::Languages => list_languages(format)?,
+    }
+    Ok(())
+}
+
+fn list_checks(format: OutputFormat) -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let verbosity = logger::verbosity();
+    let store = load_store(
+        &ctx,
+        &source::sources_in_project(&ctx)?,
+        verbosity,
+        SeverityOverrides::default().with_configured(ctx.checks.levels()),
+        CheckSelection::default(),
+        false,
+    )?;
+    let check_tags = CheckTags::new(&ctx, &store)?;
+    if format == OutputFormat::Json {
+        println!("{}", CheckMetadata::new(&ctx, &store)?.to_json(&check_tags));
+        return Ok(());
+    }
+    let id_width = check_tags
+        .iter()
+        .map(|(vex_id, _)| vex_id.as_ref().len())
+        .max()
+        .unwrap_or_default();
+    for (vex_id, tags) in check_tags.iter() {
+        println!(
+            "{:id_width$}  {}",
+            vex_id.as_ref(),
+            tags.iter().join_with(", ")
+        );
+    }
+    Ok(())
+}
+
+fn list_events(format: OutputFormat) {
+    // Match events are not observed directly, but are passed to `vex.search` callbacks.
+    match format {
+        OutputFormat::Text => {
+            let name_width = EventKind::iter()
+                .map(|kind| kind.name().len())
+                .max()
+                .unwrap_or_default();
+            EventKind::iter().for_each(|kind| {
+                let fields = kind.attr_names().iter().join_with(", ");
+                if kind.parseable() {
+                    println!("{:name_width$}  {fields}", kind.name())
+                } else {
+                    println!("{:name_width$}  {fields} (vex.search only)", kind.name())
+                }
+            })
+        }
+        OutputFormat::Json => {
+            let events: Vec<_> = EventKind::iter()
+                .map(|kind| {
+                    serde_json::json!({
+                        "name": kind.name(),
+                        "fields": kind.attr_names(),
+                        "observable": kind.parseable(),
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&events)
+                    .expect("internal error: cannot serialise events")
+            );
+        }
+    }
+}
+
+fn list_languages(format: OutputFormat) -> Result<()> {
+    // Acquiring the context loads any grammars declared in the manifest.
+    match Context::acquire(&context::current_dir()?) {
+        Ok(_) | Err(Error::ManifestNotFound) => {}
+        Err(err) => return Err(err),
+    }
+    match format {
+        OutputFormat::Text => SupportedLanguage::iter().for_each(|lang| {
+            if lang.is_available() {
+                println!("{lang}")
+            } else {
+                println!("{lang} (not compiled in)")
+            }
+        }),
+        OutputFormat::Json => {
+            let languages: Vec<_> = SupportedLanguage::iter()
+                .map(|lang| serde_json::json!({ "name": lang, "available": lang.is_available() }))
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&languages)
+                    .expect("internal error: cannot serialise languages")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn lsp() -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let verbosity = logger::verbosity();
+    let store = load_store(
+        &ctx,
+        &source::sources_in_project(&ctx)?,
+        verbosity,
+        SeverityOverrides::default().with_configured(ctx.checks.levels()),
+        CheckSelection::default(),
+        false,
+    )?;
+    lsp::serve(&ctx, &store, verbosity, io::stdin().lock(), io::stdout())
+}
+
+fn tui(tui_args: TuiCmd) -> Result<()> {
+    let mut terminal = tui::Terminal::stdout().ok_or(Error::NotATerminal)?;
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let verbosity = logger::verbosity();
+    let store = load_store(
+        &ctx,
+        &source::sources_in_project(&ctx)?,
+        verbosity,
+        SeverityOverrides::default().with_configured(ctx.checks.levels()),
+        CheckSelection::default(),
+        false,
+    )?;
+    let irritations = scan::scan_project(
+        &ctx,
+        &store,
+        ScanOptions {
+            max_problems: MaxProblems::Unlimited,
+            verbosity,
+            ..ScanOptions::default()
+        },
+    )?
+    .irritations;
+    if irritations.is_empty() {
+        success!("no problems found");
+        return Ok(());
+    }
+
+    let suppressions = tui::browse(
+        &ctx.project_root,
+        &ctx.associations()?,
+        &irritations,
+        tui_args.group_by,
+        &mut terminal,
+        |path, location| open(&ctx, path, location),
+    )?;
+    let fixed_files = fix::fix_files(&ctx.project_root, &suppressions)?;
+    fix::write_files(&ctx.project_root, &fixed_files, false)?;
+    if !suppressions.is_empty() {
+        success!(
+            "suppressed {}",
+            Plural::new(suppressions.len(), "problem", "problems")
+        );
+    }
+    Ok(())
+}
+
+fn schema(schema_args: SchemaCmd) -> Result<()> {
+    match schema_args.what {
+        SchemaKind::Manifest => print!("{}", Manifest::SCHEMA),
+        SchemaKind::Results => print!("{}", results::SCHEMA),
+    }
+    Ok(())
+}
+
+fn cache(cache_args: CacheCmd) -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    match cache_args.command {
+        CacheCommand::Clear => {
+            Cache::clear(&ctx.project_root)?;
+            success!("cache cleared");
+        }
+    }
+    Ok(())
+}
+
+fn check(cmd_args: CheckCmd) -> Result<ExitCode> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let verbosity = logger::verbosity();
+
+    let changed_files = cmd_args
+        .changed
+        .as_ref()
+        .map(|base_ref| git::changed_files(&ctx.project_root, base_ref, cmd_args.staged))
+        .transpose()?;
+    let staged_contents = changed_files
+        .as_ref()
+        .filter(|_| cmd_args.staged)
+        .map(|changed_files| git::staged_contents(&ctx.project_root, changed_files))
+        .transpose()?;
+    let stdin_path = cmd_args
+        .stdin_path
+        .as_deref()
+        .map(|path| stdin_source_path(&ctx, path))
+        .transpose()?;
+
+    let intent_log = cmd_args
+        .trace_intents
+        .as_deref()
+        .map(IntentLog::create)
+        .transpose()?;
+
+    let script_sources = source::sources_in_project(&ctx)?;
+    let severity_overrides =
+        SeverityOverrides::new(&cmd_args.allow, &cmd_args.warn, &cmd_args.deny)
+            .with_configured(ctx.checks.levels());
+    let selection = CheckSelection::new(&cmd_args.filter, &cmd_args.skip)?;
+    // Cached results declare no intents, so are not used while tracing, and unsaved content is
+    // not worth caching. Results from only some checks would be incomplete for a full run, so
+    // runs of a subset of checks neither read nor replace the cache.
+    let cache = if !cmd_args.no_cache
+        && !cmd_args.validate_config
+        && intent_log.is_none()
+        && stdin_path.is_none()
+        && selection.is_empty()
+    {
+        Some(Cache::open(
+            &ctx,
+            &script_sources,
+            &severity_overrides,
+            cmd_args.lenient,
+        )?)
+    } else {
+        None
+    };
+
+    let store = load_store(
+        &ctx,
+        &script_sources,
+        verbosity,
+        severity_overrides,
+        selection.clone(),
+        cmd_args.lenient,
+    )?;
+    {
+        let scriptlet_names = script_sources
+            .iter()
+            .map(|source| ctx.scriptlet_names.name_of(source.path()))
+            .collect::<Vec<_>>();
+        let ids = scriptlet_names
+            .iter()
+            .map(String::as_str)
+            .chain(store.check_metadata().iter().map(|(id, _)| id.as_ref()))
+            .chain(ctx.external.iter().map(|check| check.id.as_str()))
+            .collect::<Vec<_>>();
+        selection.verify(&ids)?;
+    }
+
+    if cmd_args.validate_config {
+        validate_config(&ctx, script_sources.len())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Configure global `rayon` thread pool.
+    ThreadPoolBuilder::new()
+        .num_threads(cmd_args.max_concurrent_files.into())
+        .build_global()
+        .expect("internal error: failed to configure global thread pool");
+
+    let scan_opts = ScanOptions {
+        changed_files: changed_files.as_ref(),
+        staged_contents: staged_contents.as_ref(),
+        cache: cache.as_ref(),
+        intent_log: intent_log.as_ref(),
+        ..scan_options(&cmd_args, verbosity)
+    };
+    let run_data = match &stdin_path {
+        Some(stdin_path) => {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|cause| Error::IO {
+                    path: PrettyPath::from("stdin"),
+                    action: IOAction::Read,
+                    cause,
+                })?;
+            scan::scan_buffer(&ctx, &store, stdin_path, content, scan_opts)?
+        }
+        None => scan::scan_project(&ctx, &store, scan_opts)?,
+    };
+    if let Some(intent_log) = intent_log {
+        intent_log.finish()?;
+    }
+    let overflow_hint = run_data.overflow_hint();
+    let truncation_notes = run_data.truncation_notes();
+    let skipped_table = run_data.skipped_table();
+    let ProjectRunData {
+        irritations,
+        num_files_scanned,
+        num_bytes_scanned,
+        mut num_hidden,
+        num_truncated,
+        ..
+    } = run_data;
+    // Problems beyond a check's limit still count towards failing the run.
+    for (vex_id, count) in num_truncated {
+        *num_hidden.entry(vex_id).or_default() += count;
+    }
+    let reported: Vec<_> = irritations
+        .iter()
+        .filter(|irr| irr.severity() >= cmd_args.report_level)
+        .cloned()
+        .collect();
+    output(&ctx, &store, &cmd_args, &reported, &truncation_notes)?;
+    fix_and_open(&ctx, &cmd_args, &reported)?;
+    log_scan_stats(
+        &cmd_args,
+        num_files_scanned,
+        num_bytes_scanned,
+        skipped_table,
+    );
+
+    let exit_code = summarise(
+        &irritations,
+        num_hidden.values().sum(),
+        &CheckTags::new(&ctx, &store)?,
+        cmd_args.fail_policy(),
+        cmd_args.report_level,
+    );
+    if let Some(overflow_hint) = overflow_hint {
+        log::warn!("{overflow_hint}");
+    }
+
+    if let Some(baseline_path) = &cmd_args.compare_baseline {
+        compare_baseline(
+            baseline_path,
+            &Ratchet::count(&irritations, &num_hidden),
+            cmd_args.update_ratchet,
+        )?;
+    }
+
+    Ok(exit_code)
+}
+
+/// Resolve the path given for content read from stdin, which must be inside the project.
+fn stdin_source_path(ctx: &Context, path: &Utf8Path) -> Result<SourcePath> {
+    let abs_path = ctx.project_root.join(path);
+    let in_project = abs_path.starts_with(ctx.project_root.as_str())
+        && !path
+            .components()
+            .any(|component| component == Utf8Component::ParentDir);
+    if !in_project {
+        return Err(Error::OutsideProject(PrettyPath::new(path)));
+    }
+    Ok(SourcePath::new(&abs_path, &ctx.project_root))
+}
+
+fn validate_config(ctx: &Context, num_scriptlets: usize) -> Result<()> {
+    // Parts of the manifest which are otherwise only checked once scanning starts.
+    ctx.associations()?;
+    ctx.per_check.exemptions(ctx.files.case_sensitivity())?;
+    Injection::compile_all(&ctx.injections, &ArtifactCache::new())?;
+    success!(
+        "{} and {} are valid",
+        Manifest::FILE_NAME,
+        Plural::new(num_scriptlets, "scriptlet", "scriptlets")
+    );
+    Ok(())
+}
+
+/// The options for scanning which follow directly from the command-line.
+fn scan_options(cmd_args: &CheckCmd, verbosity: Verbosity) -> ScanOptions<'_> {
+    ScanOptions {
+        only_tags: &cmd_args.only_tags,
+        sort_by: cmd_args.sort,
+        // A ratchet compares complete counts, so the scan never stops early.
+        max_problems: if cmd_args.compare_baseline.is_some() {
+            MaxProblems::Unlimited
+        } else {
+            cmd_args.max_problems
+        },
+        report_level: Some(cmd_args.report_level),
+        max_problems_per_check: match cmd_args.max_problems_per_check {
+            MaxProblems::Limited(max) if cmd_args.compare_baseline.is_none() => Some(max),
+            _ => None,
+        },
+        max_concurrent_files: cmd_args.max_concurrent_files,
+        verbosity,
+        fail_fast: cmd_args.no_keep_going,
+        follow_symlinks: cmd_args.follow_symlinks,
+        // Progress is only drawn for people watching, not for machines or logs.
+        progress: verbosity.is_terse()
+            && cmd_args.format == OutputFormat::Text
+            && io::stderr().is_terminal(),
+        ..ScanOptions::default()
+    }
+}
+
+/// Print the reported problems and write any reports requested.
+fn output(
+    ctx: &Context,
+    store: &VexingStore,
+    cmd_args: &CheckCmd,
+    reported: &[Irritation],
+    truncation_notes: &[String],
+) -> Result<()> {
+    let render_timer = Timer::start("render");
+    match cmd_args.format {
+        OutputFormat::Text if cmd_args.summary => {
+            log::warn!(custom = true; "{}", summary::count_table(reported))
+        }
+        OutputFormat::Text => {
+            let show_fixes_in = cmd_args.show_fixes.then_some(ctx.project_root.as_ref());
+            report(reported, cmd_args.group_by, show_fixes_in)?;
+            for note in truncation_notes {
+                log::warn!(custom = true; "{note}");
+            }
+        }
+        OutputFormat::Json => println!("{}", Results::new(&ctx.project, reported).to_json()),
+    }
+    render_timer.stop();
+    timings::log_breakdown();
+    for report in &cmd_args.report {
+        let content = match report.format {
+            ReportFormat::Json => Results::new(&ctx.project, reported).to_json(),
+            ReportFormat::Junit => {
+                let check_metadata = CheckMetadata::new(ctx, store)?;
+                let check_ids = check_metadata.iter().map(|(vex_id, _)| vex_id);
+                JUnitReport::new(&ctx.project, check_ids, reported).to_xml()
+            }
+        };
+        fs::write(&report.path, content).map_err(|cause| Error::IO {
+            path: PrettyPath::new(&report.path),
+            action: IOAction::Write,
+            cause,
+        })?;
+    }
+    Ok(())
+}
+
+/// Apply the fixes and open the editor as requested. Problems which were not reported are not
+/// fixed, as they were never shown.
+fn fix_and_open(ctx: &Context, cmd_args: &CheckCmd, reported: &[Irritation]) -> Result<()> {
+    if cmd_args.fix || cmd_args.emit_patch.is_some() {
+        let fix_opts = FixOptions {
+            interactive: cmd_args.interactive,
+            backup: cmd_args.backup,
+            write: cmd_args.fix && !cmd_args.dry_run,
+            patch_path: cmd_args
+                .emit_patch
+                .as_deref()
+                .or(cmd_args.diff_out.as_deref()),
+        };
+        fix(ctx, reported, fix_opts)?;
+    }
+
+    if cmd_args.open {
+        let first_location = reported.iter().find_map(|irritation| {
+            Some((
+                ctx.project_root.join(irritation.path()?.as_str()),
+                irritation.location()?,
+            ))
+        });
+        match first_location {
+            Some((path, location)) => open(ctx, &path, location)?,
+            None => {
+                if log_enabled!(log::Level::Info) {
+                    info!("no problem to open");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn log_scan_stats(
+    cmd_args: &CheckCmd,
+    num_files_scanned: u64,
+    num_bytes_scanned: u64,
+    skipped_table: Option<String>,
+) {
+    if log_enabled!(log::Level::Info) {
+        info!(
+            "scanned {}",
+            Plural::new(num_files_scanned, "file", "files"),
+        );
+    }
+    if log_enabled!(log::Level::Debug) {
+        let pretty_approx = |num| {
+            let num = num as f64;
+            if num < 1_000.0 {
+                format!("{num}")
+            } else if num < 1_000_000.0 {
+                format!("{:.1}K", num / 1_000.0)
+            } else if num < 1_000_000_000.0 {
+                format!("{:.1}M", num / 1_000_000.0)
+            } else {
+                format!("{:.1}G", num / 1_000_000_000.0)
+            }
+        };
+        debug!("scanned {} bytes", pretty_approx(num_bytes_scanned),);
+    }
+    if cmd_args.stats || log_enabled!(log::Level::Info) {
+        if let Some(skipped_table) = skipped_table {
+            log::warn!(custom = true; "{skipped_table}");
+        }
+    }
+}
+
+/// Compare the problems found with those recorded in the baseline, first lowering the recorded
+/// counts if requested.
+fn compare_baseline(baseline_path: &Utf8Path, current: &Ratchet, update: bool) -> Result<()> {
+    let baseline = Ratchet::read(baseline_path)?;
+    if update {
+        let tightened = match &baseline {
+            Some(baseline) => baseline.tighten(current),
+            None => current.tighten(current),
+        };
+        if baseline.as_ref() != Some(&tightened) {
+            tightened.write(baseline_path)?;
+            success!("updated {baseline_path}");
+        }
+    }
+    match baseline {
+        Some(baseline) => baseline.verify(current),
+        None if update => Ok(()),
+        None => Err(Error::RatchetNotFound(PrettyPath::new(baseline_path))),
+    }
+}
+
+/// Preinit and init the given scriptlets, as every command which runs checks does.
+fn load_store(
+    ctx: &Context,
+    script_sources: &[impl ScriptSource],
+    verbosity: Verbosity,
+    severity_overrides: SeverityOverrides,
+    selection: CheckSelection,
+    lenient: bool,
+) -> Result<VexingStore> {
+    let preinit_opts = PreinitOptions {
+        lenient,
+        project: ctx.project.clone(),
+        severity_overrides,
+        selection,
+        verbosity,
+    };
+    let init_opts = InitOptions {
+        verbosity,
+        config: ctx.config.clone(),
+    };
+    let preinit_timer = Timer::start("preinit");
+    let initing_store = PreinitingStore::new_with_names(script_sources, &ctx.scriptlet_names)?
+        .preinit(preinit_opts)?;
+    preinit_timer.stop();
+    let init_timer = Timer::start("init");
+    let store = initing_store.init(init_opts)?;
+    init_timer.stop();
+    Ok(store)
+}
+
+struct FixOptions<'a> {
+    interactive: bool,
+    backup: bool,
+
+    /// Whether to change the fixed files.
+    write: bool,
+
+    /// Where to write the fixes as a patch, if anywhere.
+    patch_path: Option<&'a Utf8Path>,
+}
+
+fn fix(ctx: &Context, irritations: &[Irritation], opts: FixOptions<'_>) -> Result<()> {
+    let FixOptions {
+        interactive,
+        backup,
+        write,
+        patch_path,
+    } = opts;
+    let fixes = if interactive {
+        let mut prompt = Prompt::new(io::stdin().lock(), io::stdout());
+        fix::select_interactively(&ctx.project_root, irritations, &mut prompt)?
+    } else {
+        irritations.iter().flat_map(Irritation::fix).collect()
+    };
+    let fixed_files = fix::fix_files(&ctx.project_root, fixes)?;
+    if let Some(patch_path) = patch_path {
+        fs::write(patch_path, fix::patch(&fixed_files)).map_err(|cause| Error::IO {
+            path: PrettyPath::new(patch_path),
+            action: IOAction::Write,
+            cause,
+        })?;
+        if log_enabled!(log::Level::Info) {
+            info!(
+                "wrote fixes for {} to {patch_path}",
+                Plural::new(fixed_files.len(), "file", "files")
+            );
+        }
+    }
+    if write {
+        fix::write_files(&ctx.project_root, &fixed_files, backup)?;
+        success!("fixed {}", Plural::new(fixed_files.len(), "file", "files"));
+    } else if patch_path.is_none() {
+        for fixed_file in &fixed_files {
+            log::warn!(custom = true; "would fix {}", fixed_file.path);
+        }
+        success!(
+            "would fix {}",
+            Plural::new(fixed_files.len(), "file", "files")
+        );
+    }
+    Ok(())
+}
+
+fn open(ctx: &Context, path: &Utf8Path, location: &Location) -> Result<()> {
+    Editor::new(ctx.run.editor_cmd.as_deref())?.open(
+        path,
+        location.start_row,
+        location.start_column + 1,
+    )
+}
+
+fn import(import_args: ImportCmd) -> Result<ExitCode> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let irritations = import::import(&ctx, &import_args)?;
+    report(&irritations, None, None)?;
+    Ok(summarise(
+        &irritations,
+        0,
+        &CheckTags::default(),
+        FailPolicy::default(),
+        Severity::Advice,
+    ))
+}
+
+/// Print each problem. If a project root is given, the change each fix would make to the files
+/// in it is shown under its problem.
+fn report(
+    irritations: &[Irritation],
+    group_by: Option<GroupBy>,
+    show_fixes_in: Option<&Utf8Path>,
+) -> Result<()> {
+    let report_one = |irr: &Irritation| {
+        let preview = match (show_fixes_in, irr.fix()) {
+            (Some(project_root), Some(fix)) => fix::preview(project_root, fix)?,
+            _ => None,
+        };
+        let rendered = match preview {
+            Some(preview) => format!("{irr}\n{}", preview.trim_end()),
+            None => irr.to_string(),
+        };
+        match irr.severity() {
+            Severity::Error => log::error!(custom=true; "{rendered}"),
+            Severity::Warning | Severity::Advice => log::warn!(custom=true; "{rendered}"),
+        }
+        Ok(())
+    };
+    let Some(group_by) = group_by else {
+        return irritations.iter().try_for_each(report_one);
+    };
+    for (group, members) in summary::groups(irritations, group_by) {
+        log::warn!(custom = true; "{}", summary::group_header(group, members.len()));
+        members.into_iter().try_for_each(report_one)?;
+    }
+    Ok(())
+}
+
+/// Log how many problems were found and return the exit code which the fail policy demands.
+fn summarise(
+    irritations: &[Irritation],
+    num_hidden: usize,
+    check_tags: &CheckTags,
+    fail_policy: FailPolicy,
+    report_level: Severity,
+) -> ExitCode {
+    // Problems below the report level are not printed, but may still fail the run.
+    let reported: Vec<_> = irritations
+        .iter()
+        .filter(|irr| irr.severity() >= report_level)
+        .cloned()
+        .collect();
+    let num_problems = reported.len();
+    let worst = irritations.iter().map(Irritation::severity).max();
+    let by_tag = {
+        let counts = check_tags.count(&reported);
+        if counts.is_empty() {
+            String::new()
+        } else {
+            let counts = counts
+                .into_iter()
+                .map(|(tag, count)| format!("{tag}: {count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(" ({counts})")
+        }
+    };
+    if num_problems != 0 {
+        log::warn!(
+            "found {}{by_tag}",
+            Plural::new(num_problems, "problem", "problems")
+        );
+    } else {
+        success!("no problems found");
+    }
+    if fail_policy.fails(irritations.len() + num_hidden, worst) {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn init(init_args: InitCmd) -> Result<()> {
+    let cwd = context::current_dir()?;
+    Context::init(&cwd, init_args.force)?;
+    let ctx = Context::acquire(&cwd)?;
+    let starter_checks = init_args
+        .template
+        .map(|template| scaffold::add_starter_checks(&ctx, template, init_args.force))
+        .transpose()?
+        .map(|paths| format!("\nadded starter checks {}", paths.iter().join_with(", ")))
+        .unwrap_or_default();
+    let vexes_dir = &ctx.manifest.run.vexes_dir;
+    success!(
+        "{}",
+        formatdoc!(
+            "
+                vex initialised
+                now add style rules in ./{}/
+                for an example, open ./{}/{EXAMPLE_VEX_FILE}{starter_checks}",
+            vexes_dir.as_str(),
+            vexes_dir.as_str(),
+        )
+    );
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use camino::{Utf8Path, Utf8PathBuf};
 use indoc::indoc;
 use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use toml_edit::DocumentMut;
 
 use std::collections::{BTreeMap, HashMap};
 use std::io::{BufWriter, ErrorKind, Read, Write};
 use std::ops::Deref;
+use std::time::Duration;
 use std::{
     env,
     fs::{self, File},
@@ -12,11 +14,22 @@ use std::{
 
 use crate::associations::Associations;
 use crate::error::{Error, IOAction};
+use crate::external::ExternalCheck;
+use crate::grammar::{self, GrammarConfig};
+use crate::injection::InjectionConfig;
+use crate::manifest_keys;
 use crate::result::Result;
+use crate::scriptlets::limits::HandlerLimits;
+use crate::scriptlets::source;
+use crate::severity::CheckLevel;
 use crate::source_path::PrettyPath;
 use crate::supported_language::SupportedLanguage;
-use crate::trigger::RawFilePattern;
+use crate::theme::{self, Theme};
+use crate::trigger::{CaseSensitivity, FilePattern, RawFilePattern};
 
+/// The location and manifest of a vex project. Contexts hold no process-wide state, so several
+/// may be acquired at once, except that grammars are registered by name and the theme is set
+/// for the whole process.
 #[derive(Debug)]
 pub struct Context {
     pub project_root: PrettyPath,
@@ -25,15 +38,21 @@ pub struct Context {
 
 pub const EXAMPLE_VEX_FILE: &str = "example.star";
 
+/// The directory vex was run from, where commands start searching for a project.
+pub fn current_dir() -> Result<Utf8PathBuf> {
+    let cwd = env::current_dir().map_err(|cause| Error::IO {
+        path: PrettyPath::from("."),
+        action: IOAction::Read,
+        cause,
+    })?;
+    Ok(Utf8PathBuf::try_from(cwd)?)
+}
+
 impl Context {
-    pub fn acquire() -> Result<Self> {
-        let (project_root, raw_data) = Manifest::acquire_content()?;
-        let project_root = PrettyPath::new(&project_root);
-        let data = toml_edit::de::from_str(&raw_data)?;
-        Ok(Context {
-            project_root,
-            manifest: data,
-        })
+    /// Find the project which contains the given directory, searching upwards for its manifest.
+    pub fn acquire(start_dir: &Utf8Path) -> Result<Self> {
+        let (project_root, raw_data) = Manifest::acquire_content(start_dir)?;
+        Self::new_from_content(&project_root, &raw_data)
     }
 
     pub fn new_with_manifest(project_root: &Utf8Path, manifest: Manifest) -> Self {
@@ -43,14 +62,24 @@ impl Context {
         }
     }
 
-    #[cfg(test)]
-    pub fn acquire_in(project_root: &Utf8Path) -> Result<Self> {
-        let (project_root, raw_data) = Manifest::acquire_content_in(project_root)?;
-        let project_root = PrettyPath::new(&project_root);
-        let data = toml_edit::de::from_str(&raw_data)?;
+    fn new_from_content(project_root: &Utf8Path, raw_data: &str) -> Result<Self> {
+        // Grammars are loaded first so that the languages they define may be configured.
+        grammar::load_all(project_root, &GrammarConfig::all_in(raw_data)?)?;
+
+        let manifest: Manifest = toml_edit::de::from_str(raw_data)?;
+        let unknown_keys = manifest_keys::unknown_keys(raw_data)?;
+        if !unknown_keys.is_empty() {
+            match manifest.run.version {
+                Version::V1 => unknown_keys
+                    .iter()
+                    .for_each(|unknown_key| log::warn!("{unknown_key}")),
+                Version::V2 => return Err(Error::UnknownManifestKeys(unknown_keys)),
+            }
+        }
+        theme::configure(manifest.theme);
         Ok(Context {
-            project_root,
-            manifest: data,
+            project_root: PrettyPath::new(project_root),
+            manifest,
         })
     }
 
@@ -117,7 +146,13 @@ impl Context {
     }
 
     pub fn associations(&self) -> Result<Associations> {
-        let mut ret = Associations::base();
+        let case_sensitivity = self.manifest.files.case_sensitivity();
+        let mut ret = Associations::base(case_sensitivity);
+        for (language, options) in self.manifest.languages.iter() {
+            for interpreter in &options.interpreters {
+                ret.insert_interpreter(interpreter.clone(), *language);
+            }
+        }
         self.manifest
             .languages
             .iter()
@@ -126,7 +161,7 @@ impl Context {
                     .file_associations
                     .iter()
                     .cloned()
-                    .map(|pattern| pattern.compile())
+                    .map(|pattern| pattern.compile(case_sensitivity))
                     .collect::<Result<Vec<_>>>();
                 (patterns, *language)
             })
@@ -134,6 +169,16 @@ impl Context {
                 ret.insert(patterns?, language);
                 Ok::<_, Error>(())
             })?;
+        for config in &self.manifest.grammars {
+            let language: SupportedLanguage = config.name.parse()?;
+            let patterns = config
+                .use_for
+                .iter()
+                .cloned()
+                .map(|pattern| pattern.compile(case_sensitivity))
+                .collect::<Result<Vec<_>>>()?;
+            ret.insert(patterns, language);
+        }
         Ok(ret)
     }
 
@@ -156,33 +201,68 @@ pub struct Manifest {
     #[serde(rename = "vex")]
     pub run: RunConfig,
 
+    #[serde(default)]
+    pub project: ProjectInfo,
+
     #[serde(default)]
     pub files: FilesConfig,
 
     #[serde(default)]
     pub lints: LintsConfig,
 
+    #[serde(default)]
+    pub checks: ChecksConfig,
+
+    #[serde(default, rename = "check")]
+    pub per_check: PerCheckConfig,
+
+    #[serde(default)]
+    pub config: ScriptletConfig,
+
+    #[serde(default)]
+    pub scriptlet_names: ScriptletNames,
+
+    /// Rule packs whose scriptlets run alongside those in the vexes directory, keyed by name.
+    #[serde(default)]
+    pub packs: BTreeMap<String, PackConfig>,
+
     #[serde(default)]
     pub languages: LanguagesConfig,
+
+    #[serde(default)]
+    pub theme: Theme,
+
+    #[serde(default)]
+    pub external: Vec<ExternalCheck>,
+
+    #[serde(default)]
+    pub grammars: Vec<GrammarConfig>,
+
+    /// Content embedded in files of one language which is checked as another.
+    #[serde(default)]
+    pub injections: Vec<InjectionConfig>,
 }
 
 impl Manifest {
     pub const FILE_NAME: &'static str = "vex.toml";
+
+    /// A JSON Schema which describes the manifest.
+    pub const SCHEMA: &'static str = include_str!("schemas/manifest.json");
     const DEFAULT_CONTENT: &'static str = indoc! {r#"
         [vex]
-        version = "1"
+        version = "2"
 
         [files]
         ignore = [ "vex.toml", "vexes/", ".git/", ".gitignore", "/target/" ]
 
-        [python]
-        use-for = [ "*.star" ]
+        [languages.python]
+        use-for = [ "*.star", "*.bzl", "BUILD.bazel" ]
     "#};
 
     fn init(project_root: impl AsRef<Utf8Path>, force: bool) -> Result<()> {
         let project_root = project_root.as_ref();
         if !force {
-            match Manifest::acquire_content_in(project_root) {
+            match Manifest::acquire_content(project_root) {
                 Ok((found_root, _)) => {
                     return Err(Error::AlreadyInited {
                         found_root: PrettyPath::new(&found_root),
@@ -206,7 +286,7 @@ impl Manifest {
             })?;
         let mut writer = BufWriter::new(file);
         writer
-            .write_all(Self::DEFAULT_CONTENT.as_bytes())
+            .write_all(Self::initial_content(project_root)?.as_bytes())
             .map_err(|cause| Error::IO {
                 path: PrettyPath::new(&file_path),
                 action: IOAction::Write,
@@ -215,17 +295,50 @@ impl Manifest {
         Ok(())
     }
 
-    fn acquire_content() -> Result<(Utf8PathBuf, String)> {
-        Self::acquire_content_in(&Utf8PathBuf::try_from(env::current_dir().map_err(
-            |cause| Error::IO {
-                path: PrettyPath::from("."),
-                action: IOAction::Read,
-                cause,
-            },
-        )?)?)
+    /// The content of a new manifest, which also ignores the files ignored by the project's
+    /// `.gitignore`, if it has one.
+    fn initial_content(project_root: &Utf8Path) -> Result<String> {
+        let gitignore_path = project_root.join(".gitignore");
+        let gitignore = match fs::read_to_string(&gitignore_path) {
+            Ok(gitignore) => gitignore,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::DEFAULT_CONTENT.into()),
+            Err(cause) => {
+                return Err(Error::IO {
+                    path: PrettyPath::new(&gitignore_path),
+                    action: IOAction::Read,
+                    cause,
+                })
+            }
+        };
+
+        let mut content: DocumentMut = Self::DEFAULT_CONTENT
+            .parse()
+            .expect("internal error: default manifest invalid");
+        let ignores = content["files"]["ignore"]
+            .as_array_mut()
+            .expect("internal error: default manifest has no ignores");
+        gitignore
+            .lines()
+            .map(str::trim)
+            // Vex has no way to un-ignore a file, so negated patterns are skipped.
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+            .filter(|line| {
+                RawFilePattern::new(*line)
+                    .compile(CaseSensitivity::default())
+                    .is_ok()
+            })
+            .for_each(|line| {
+                if !ignores.iter().any(|ignore| ignore.as_str() == Some(line)) {
+                    ignores.push(line);
+                }
+            });
+        ignores.fmt();
+        Ok(content.to_string())
     }
 
-    fn acquire_content_in(dir: &Utf8Path) -> Result<(Utf8PathBuf, String)> {
+    /// Find the manifest of the project which contains the given directory, returning the
+    /// project root and the manifest's content.
+    pub fn acquire_content(dir: &Utf8Path) -> Result<(Utf8PathBuf, String)> {
         let mut project_root = dir.to_path_buf();
         let mut manifest_file = loop {
             match File::open(project_root.join(Self::FILE_NAME)) {
@@ -233,7 +346,7 @@ impl Manifest {
                 Err(e) if e.kind() == ErrorKind::NotFound => {}
                 Err(e) => {
                     return Err(Error::IO {
-                        path: PrettyPath::from(Self::FILE_NAME),
+                        path: PrettyPath::new(&project_root.join(Self::FILE_NAME)),
                         action: IOAction::Read,
                         cause: e,
                     })
@@ -251,7 +364,7 @@ impl Manifest {
             manifest_file
                 .read_to_string(&mut manifest_raw)
                 .map_err(|cause| Error::IO {
-                    path: PrettyPath::from(Self::FILE_NAME),
+                    path: PrettyPath::new(&project_root.join(Self::FILE_NAME)),
                     action: IOAction::Read,
                     cause,
                 })?;
@@ -262,6 +375,30 @@ impl Manifest {
     }
 }
 
+/// Information which describes the project in reports and to scriptlets.
+#[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectInfo {
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// The team which owns the project.
+    #[serde(default)]
+    pub team: Option<String>,
+
+    /// Links to resources about the project, keyed by label.
+    #[serde(default)]
+    pub links: BTreeMap<String, String>,
+}
+
+/// A directory of scriptlets shared between projects, such as an organisation's rule pack.
+#[derive(Clone, Debug, Deserialise, Serialise, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PackConfig {
+    /// The directory which holds the pack's scriptlets, relative to the project root.
+    pub path: Utf8PathBuf,
+}
+
 #[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
 pub struct RunConfig {
     pub version: Version,
@@ -269,19 +406,60 @@ pub struct RunConfig {
     #[serde(default)]
     #[serde(rename = "directory")]
     pub vexes_dir: VexesDir,
+
+    /// Command used to open files, in which `{path}`, `{line}` and `{column}` are substituted.
+    #[serde(default, rename = "editor-cmd")]
+    pub editor_cmd: Option<String>,
+
+    /// Languages which at least one active check must search, so that a run fails rather than
+    /// silently checking nothing.
+    #[serde(default, rename = "require-checks-for")]
+    pub require_checks_for: Vec<String>,
+
+    /// The number of seconds a single call to an event handler may take, or zero for no limit.
+    #[serde(default, rename = "handler-timeout")]
+    pub handler_timeout: Option<u64>,
+
+    /// The number of statements a single call to an event handler may run.
+    #[serde(default, rename = "handler-step-limit")]
+    pub handler_step_limit: Option<u64>,
+
+    /// Whether to combine every query for a language into one, so each file is walked only once.
+    /// Experimental.
+    #[serde(default, rename = "single-pass-queries")]
+    pub single_pass_queries: bool,
+}
+
+impl RunConfig {
+    pub fn handler_limits(&self) -> HandlerLimits {
+        let timeout = match self.handler_timeout {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(HandlerLimits::DEFAULT_TIMEOUT),
+        };
+        HandlerLimits {
+            timeout,
+            max_steps: self.handler_step_limit,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
 pub enum Version {
-    #[default]
+    /// Unknown keys are only warned about.
     #[serde(rename = "1")]
     V1,
+
+    /// Unknown keys are rejected.
+    #[default]
+    #[serde(rename = "2")]
+    V2,
 }
 
 impl Version {
     #[allow(dead_code)]
     pub fn current() -> Self {
-        Self::V1
+        Self::V2
     }
 }
 
@@ -307,6 +485,85 @@ pub struct FilesConfig {
 
     #[serde(default, rename = "allow")]
     pub allows: Vec<RawFilePattern<String>>,
+
+    #[serde(default, rename = "case-sensitive")]
+    pub case_sensitive: Option<bool>,
+
+    #[serde(default)]
+    pub scan: ScanMode,
+
+    #[serde(default, rename = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    #[serde(default, rename = "non-utf8")]
+    pub non_utf8: NonUtf8Policy,
+
+    #[serde(default, rename = "max-file-size")]
+    pub max_file_size: Option<u64>,
+
+    #[serde(default)]
+    pub hidden: HiddenPolicy,
+}
+
+impl FilesConfig {
+    /// Whether ignore, allow and use-for patterns distinguish between upper- and lower-case
+    /// letters, by default following the platform.
+    pub fn case_sensitivity(&self) -> CaseSensitivity {
+        match self.case_sensitive {
+            Some(true) => CaseSensitivity::Sensitive,
+            Some(false) => CaseSensitivity::Insensitive,
+            None => CaseSensitivity::default(),
+        }
+    }
+
+    /// Whether some allow pattern names a hidden path, so hidden directories must be walked to
+    /// find the files it allows.
+    pub fn allows_hidden(&self) -> bool {
+        self.allows.iter().any(|allow| {
+            allow.split('/').any(|component| {
+                component.starts_with('.') && component != "." && component != ".."
+            })
+        })
+    }
+}
+
+/// Which files are scanned by default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScanMode {
+    /// Scan every file unless it is ignored.
+    #[default]
+    All,
+
+    /// Scan only files which match an allow pattern.
+    Allowlist,
+}
+
+/// What to do with files and directories whose names start with a dot.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+#[serde(rename_all = "kebab-case")]
+pub enum HiddenPolicy {
+    /// Skip them, unless they match an allow pattern which names a hidden path.
+    #[default]
+    Skip,
+
+    /// Scan them like any other path.
+    Scan,
+}
+
+/// What to do with files which are binary or not valid UTF-8.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+#[serde(rename_all = "kebab-case")]
+pub enum NonUtf8Policy {
+    /// Skip the file, logging why.
+    #[default]
+    Skip,
+
+    /// Replace invalid UTF-8 with the replacement character. Binary files are still skipped.
+    Lossy,
+
+    /// Stop with an error.
+    Error,
 }
 
 #[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
@@ -314,12 +571,139 @@ pub struct LintsConfig {
     active: BTreeMap<String, bool>,
 }
 
+/// Per-check levels, keyed by check id.
 #[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
-pub struct GroupsConfig {
-    active: BTreeMap<String, bool>,
+pub struct ChecksConfig(BTreeMap<String, CheckLevel>);
+
+impl ChecksConfig {
+    pub fn get(&self, id: &str) -> Option<CheckLevel> {
+        self.0.get(id).copied()
+    }
+
+    pub fn levels(&self) -> impl Iterator<Item = (&str, CheckLevel)> {
+        self.0.iter().map(|(id, level)| (id.as_str(), *level))
+    }
 }
 
-#[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
+/// Settings for individual checks, keyed by check id.
+#[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
+pub struct PerCheckConfig(BTreeMap<String, CheckConfig>);
+
+impl PerCheckConfig {
+    /// The compiled path rules of each check which has any.
+    pub fn exemptions(
+        &self,
+        case_sensitivity: CaseSensitivity,
+    ) -> Result<BTreeMap<&str, CheckPaths>> {
+        let compile = |patterns: &[RawFilePattern<String>]| {
+            patterns
+                .iter()
+                .cloned()
+                .map(|pattern| pattern.compile(case_sensitivity))
+                .collect::<Result<_>>()
+        };
+        self.0
+            .iter()
+            .filter(|(_, config)| !config.exclude.is_empty() || !config.allow.is_empty())
+            .map(|(id, config)| {
+                let paths = CheckPaths {
+                    exclude: compile(&config.exclude)?,
+                    allow: compile(&config.allow)?,
+                };
+                Ok((id.as_str(), paths))
+            })
+            .collect()
+    }
+
+    /// The most problems to report from each check which sets a limit.
+    pub fn max_problems(&self) -> BTreeMap<&str, u32> {
+        self.0
+            .iter()
+            .filter_map(|(id, config)| Some((id.as_str(), config.max_problems?)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CheckConfig {
+    /// Paths where problems found by this check are dropped.
+    #[serde(default)]
+    pub exclude: Vec<RawFilePattern<String>>,
+
+    /// If non-empty, the only paths where problems found by this check are kept.
+    #[serde(default)]
+    pub allow: Vec<RawFilePattern<String>>,
+
+    /// The most problems from this check to report.
+    #[serde(default, rename = "max-problems")]
+    pub max_problems: Option<u32>,
+}
+
+/// The compiled path rules of a single check.
+#[derive(Debug)]
+pub struct CheckPaths {
+    exclude: Vec<FilePattern>,
+    allow: Vec<FilePattern>,
+}
+
+impl CheckPaths {
+    /// Whether problems found at the given absolute path are dropped.
+    pub fn exempts(&self, path: &Utf8Path) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(path))
+            || (!self.allow.is_empty() && !self.allow.iter().any(|pattern| pattern.matches(path)))
+    }
+}
+
+/// Options read by scriptlets with `vex.config`, keyed by scriptlet name then option name.
+#[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
+pub struct ScriptletConfig(BTreeMap<String, BTreeMap<String, serde_json::Value>>);
+
+impl ScriptletConfig {
+    pub fn scope<'a>(&'a self, name: &'a str) -> ConfigScope<'a> {
+        ConfigScope { name, config: self }
+    }
+}
+
+/// Names given to scriptlets in place of their file stems, keyed by path within the vexes directory.
+#[derive(Clone, Debug, Default, Deserialise, Serialise, PartialEq)]
+pub struct ScriptletNames(BTreeMap<Utf8PathBuf, String>);
+
+impl ScriptletNames {
+    /// The name by which `[checks]` and `[config]` refer to the scriptlet at the given path.
+    /// Scriptlets from a rule pack are named within the pack's namespace.
+    pub fn name_of(&self, path: &Utf8Path) -> String {
+        let name = self
+            .0
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_owned());
+        match source::pack_of(path) {
+            Some(pack) => format!("{pack}:{name}"),
+            None => name,
+        }
+    }
+}
+
+/// The options visible to a single scriptlet.
+#[derive(Copy, Clone, Debug)]
+pub struct ConfigScope<'a> {
+    name: &'a str,
+    config: &'a ScriptletConfig,
+}
+
+impl ConfigScope<'_> {
+    pub fn get(&self, key: &str) -> Option<&serde_json::Value> {
+        self.config.0.get(self.name)?.get(key)
+    }
+
+    /// The path of the given option within the manifest.
+    pub fn key_path(&self, key: &str) -> String {
+        format!("config.{}.{key}", self.name)
+    }
+}
+
+#[derive(Clone, Debug, Serialise, PartialEq)]
 pub struct LanguagesConfig(HashMap<SupportedLanguage, LanguageOptions>);
 
 impl Default for LanguagesConfig {
@@ -328,7 +712,11 @@ impl Default for LanguagesConfig {
             [(
                 SupportedLanguage::Python,
                 LanguageOptions {
-                    file_associations: vec![RawFilePattern::new("*.star".into())],
+                    file_associations: ["*.star", "*.bzl", "BUILD.bazel"]
+                        .into_iter()
+                        .map(|pattern| RawFilePattern::new(pattern.into()))
+                        .collect(),
+                    interpreters: vec![],
                 },
             )]
             .into_iter()
@@ -337,6 +725,32 @@ impl Default for LanguagesConfig {
     }
 }
 
+impl LanguagesConfig {
+    /// Associate files matching the given pattern with the given language.
+    pub fn associate(&mut self, language: SupportedLanguage, pattern: RawFilePattern<String>) {
+        self.0
+            .entry(language)
+            .or_insert_with(|| LanguageOptions {
+                file_associations: vec![],
+                interpreters: vec![],
+            })
+            .file_associations
+            .push(pattern);
+    }
+}
+
+impl<'de> Deserialise<'de> for LanguagesConfig {
+    /// Each section in the manifest replaces the defaults for its own language only.
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let sections: HashMap<_, _> = HashMap::deserialize(deserializer)?;
+        let mut ret = Self::default();
+        ret.0.extend(sections);
+        Ok(ret)
+    }
+}
+
 impl Deref for LanguagesConfig {
     type Target = HashMap<SupportedLanguage, LanguageOptions>;
 
@@ -349,6 +763,10 @@ impl Deref for LanguagesConfig {
 pub struct LanguageOptions {
     #[serde(rename = "use-for", default)]
     file_associations: Vec<RawFilePattern<String>>,
+
+    /// Interpreters which run extensionless scripts in this language, as named by their shebangs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    interpreters: Vec<String>,
 }
 
 #[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
@@ -382,15 +800,18 @@ impl Deref for IgnoreData {
 
 #[cfg(test)]
 mod test {
+    use indoc::formatdoc;
     use insta::assert_yaml_snapshot;
     use regex::Regex;
-    use toml_edit::Document;
+    use serde_json::Value;
+    use toml_edit::DocumentMut;
 
     use crate::{
         cli::{MaxConcurrentFileLimit, MaxProblems},
-        scan::{self, ProjectRunData},
+        scan::{self, ProjectRunData, ScanOptions},
         scriptlets::{source, InitOptions, PreinitOptions, PreinitingStore},
-        verbosity::Verbosity,
+        severity::Severity,
+        theme::{Glyphs, Palette},
     };
 
     use super::*;
@@ -409,8 +830,9 @@ mod test {
                 .collect::<Vec<_>>(),
             &["vex.toml", "vexes/", ".git/", ".gitignore", "/target/"]
         );
+        assert_eq!(init_manifest.languages, LanguagesConfig::default());
 
-        let raw_manifest: Document = Manifest::DEFAULT_CONTENT.parse().unwrap();
+        let raw_manifest: DocumentMut = Manifest::DEFAULT_CONTENT.parse().unwrap();
         let formatted = {
             let mut formatted = raw_manifest.clone();
             formatted.fmt();
@@ -419,21 +841,45 @@ mod test {
         assert_eq!(raw_manifest.to_string(), formatted.to_string());
     }
 
+    #[test]
+    fn several_roots() {
+        let root = |name: &str| {
+            let tempdir = tempfile::tempdir().unwrap();
+            let root_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+            fs::write(
+                root_path.join(Manifest::FILE_NAME),
+                format!("[vex]\nversion = '1'\n\n[project]\nname = '{name}'\n"),
+            )
+            .unwrap();
+            fs::create_dir_all(root_path.join("src/nested")).unwrap();
+            (tempdir, root_path)
+        };
+        let (_first_dir, first_path) = root("first");
+        let (_second_dir, second_path) = root("second");
+
+        let first = Context::acquire(&first_path.join("src/nested")).unwrap();
+        let second = Context::acquire(&second_path).unwrap();
+        assert_eq!(first.project_root.as_str(), first_path.as_str());
+        assert_eq!(first.manifest.project.name.as_deref(), Some("first"));
+        assert_eq!(second.project_root.as_str(), second_path.as_str());
+        assert_eq!(second.manifest.project.name.as_deref(), Some("second"));
+    }
+
     #[test]
     fn init() -> Result<()> {
         let tempdir = tempfile::tempdir().unwrap();
         let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned())?;
 
         // Manifest not found
-        let err = Context::acquire_in(&tempdir_path).unwrap_err();
+        let err = Context::acquire(&tempdir_path).unwrap_err();
         assert_eq!(
             err.to_string(),
             "cannot find manifest, try running `vex init` in the project’s root"
         );
 
         Context::init(tempdir_path.clone(), false).unwrap();
-        let ctx = Context::acquire_in(&tempdir_path).unwrap();
-        PreinitingStore::new(&source::sources_in_dir(&ctx.vex_dir())?)
+        let ctx = Context::acquire(&tempdir_path).unwrap();
+        PreinitingStore::new(&source::sources_in_project(&ctx)?)
             .unwrap()
             .preinit(PreinitOptions::default())
             .unwrap()
@@ -451,8 +897,8 @@ mod test {
 
         // Already inited, force
         Context::init(&tempdir_path, true).unwrap();
-        let ctx = Context::acquire_in(&tempdir_path).unwrap();
-        PreinitingStore::new(&source::sources_in_dir(&ctx.vex_dir())?)
+        let ctx = Context::acquire(&tempdir_path).unwrap();
+        PreinitingStore::new(&source::sources_in_project(&ctx)?)
             .unwrap()
             .preinit(PreinitOptions::default())
             .unwrap()
@@ -483,16 +929,18 @@ mod test {
             .unwrap();
 
         Context::init(&tempdir_path, false)?;
-        let ctx = Context::acquire_in(&tempdir_path)?;
-        let store = PreinitingStore::new(&source::sources_in_dir(&ctx.vex_dir())?)?
+        let ctx = Context::acquire(&tempdir_path)?;
+        let store = PreinitingStore::new(&source::sources_in_project(&ctx)?)?
             .preinit(PreinitOptions::default())?
             .init(InitOptions::default())?;
         let ProjectRunData { irritations, .. } = scan::scan_project(
             &ctx,
             &store,
-            MaxProblems::Unlimited,
-            MaxConcurrentFileLimit::new(1),
-            Verbosity::default(),
+            ScanOptions {
+                max_problems: MaxProblems::Unlimited,
+                max_concurrent_files: MaxConcurrentFileLimit::new(1),
+                ..ScanOptions::default()
+            },
         )?;
         assert_yaml_snapshot!(irritations
             .into_iter()
@@ -502,13 +950,52 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn init_gitignore() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(
+            root_path.join(".gitignore"),
+            indoc! {"
+                # Build outputs
+                /target/
+                node_modules/
+
+                *.log
+                !keep.log
+                [
+            "},
+        )
+        .unwrap();
+
+        Context::init(&root_path, false).unwrap();
+        let manifest = Context::acquire(&root_path).unwrap().manifest;
+        assert_eq!(
+            manifest
+                .files
+                .ignores
+                .iter()
+                .map(RawFilePattern::to_string)
+                .collect::<Vec<_>>(),
+            &[
+                "vex.toml",
+                "vexes/",
+                ".git/",
+                ".gitignore",
+                "/target/",
+                "node_modules/",
+                "*.log"
+            ]
+        );
+    }
+
     #[test]
     fn defaults() {
         let root_dir = tempfile::tempdir().unwrap();
         let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
 
         Context::init(&root_path, false).unwrap();
-        let manifest = Context::acquire_in(&root_path).unwrap().manifest;
+        let manifest = Context::acquire(&root_path).unwrap().manifest;
 
         assert_eq!(manifest, Manifest::default());
     }
@@ -524,7 +1011,7 @@ mod test {
         let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
 
         Manifest::init(&root_path, false).unwrap();
-        let ctx = Context::acquire_in(&root_path).unwrap();
+        let ctx = Context::acquire(&root_path).unwrap();
         assert_eq!(ctx.manifest.run.version, Version::current());
     }
 
@@ -534,41 +1021,259 @@ mod test {
         toml_edit::de::from_str::<Manifest>("[vex]").unwrap_err();
 
         toml_edit::de::from_str::<Manifest>("[vex]\nversion = '1'").unwrap();
+        toml_edit::de::from_str::<Manifest>("[vex]\nversion = '2'").unwrap();
     }
 
     #[test]
-    fn maximal_manifest() {
-        let manifest_content = indoc! {r#"
-            [vex]
-            version = "1"
-            directory = "some-dir/"
+    fn unknown_keys() {
+        assert_eq!(
+            manifest_keys::unknown_keys(Manifest::DEFAULT_CONTENT).unwrap(),
+            []
+        );
+        assert_eq!(manifest_keys::unknown_keys(MAXIMAL_MANIFEST).unwrap(), []);
 
-            [files]
-            ignore = ["vexes/", "target/"]
-            allow = ["vexes/check-me.star", "target/check-me.rs"]
+        let manifest = |version| {
+            formatdoc! {r#"
+                [vex]
+                version = "{version}"
 
-            [lints.active]
-            lint-id-1 = false
-            lint-id-2 = true
+                [files]
+                ignroe = ["target/"]
+            "#}
+        };
+        Context::new_from_content(Utf8Path::new("/project"), &manifest("1")).unwrap();
+        let err = Context::new_from_content(Utf8Path::new("/project"), &manifest("2"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "unknown key 'files.ignroe' in vex.toml at line 5, column 1, did you mean 'ignore'?"
+        );
+    }
 
-            [languages.python]
-            use-for = ["*.star", "*.py2"]
-        "#};
-        let parsed_manifest: Manifest = toml_edit::de::from_str(manifest_content).unwrap();
+    const MAXIMAL_MANIFEST: &str = indoc! {r##"
+        [vex]
+        version = "1"
+        directory = "some-dir/"
+        editor-cmd = "code --goto {path}:{line}:{column}"
+        require-checks-for = ["rust", "python"]
+
+        [project]
+        name = "example"
+        team = "linting"
+        links = { repository = "https://example.com/example.git" }
+
+        [files]
+        ignore = ["vexes/", "target/"]
+        allow = ["vexes/check-me.star", "target/check-me.rs"]
+        case-sensitive = false
+        scan = "allowlist"
+        follow-symlinks = true
+        non-utf8 = "lossy"
+        max-file-size = 1_000_000
+
+        [lints.active]
+        lint-id-1 = false
+        lint-id-2 = true
+
+        [checks]
+        no-unwrap = "deny"
+        todo-comments = "off"
+
+        [check.no-unwrap]
+        exclude = ["legacy/**"]
+        allow = ["src/**"]
+
+        [config.long-functions]
+        max_length = 80
+
+        [scriptlet-names]
+        "style/long-functions.star" = "style-long-functions"
+
+        [packs.acme]
+        path = "../acme-vexes"
+
+        [languages.python]
+        use-for = ["*.star", "*.py2"]
+        interpreters = ["pypy3"]
+
+        [theme]
+        palette = "colorblind"
+        glyphs = "ascii"
+
+        [[external]]
+        id = "legacy-script"
+        command = "./scripts/lint.sh"
+        args = ["--all"]
+        severity = "advice"
+        parser.regex = '^(?<message>.*)$'
+
+        [[external]]
+        id = "legacy-tool"
+        command = "lint-tool"
+        exit-codes = [0, 1]
+        parser.json = { items = "/problems", message = "/msg", path = "/file", line = "/line" }
+
+        [[grammars]]
+        name = "hcl"
+        library = "grammars/libtree-sitter-hcl.so"
+        symbol = "tree_sitter_hcl"
+        use-for = ["*.hcl", "*.tf"]
+        line-comment = "#"
+
+        [[injections]]
+        language = "rust"
+        query = '(macro_invocation (token_tree (string_literal (string_content) @injection.content)))'
+        injected-language = "toml"
+    "##};
+
+    #[test]
+    fn maximal_manifest() {
+        let parsed_manifest: Manifest = toml_edit::de::from_str(MAXIMAL_MANIFEST).unwrap();
 
         assert_eq!(parsed_manifest.run.version, Version::V1);
         assert_eq!(parsed_manifest.run.vexes_dir.as_str(), "some-dir/");
+        assert_eq!(
+            parsed_manifest.run.editor_cmd.as_deref(),
+            Some("code --goto {path}:{line}:{column}")
+        );
+        assert_eq!(parsed_manifest.run.require_checks_for, ["rust", "python"]);
+        assert_eq!(parsed_manifest.project.name.as_deref(), Some("example"));
+        assert_eq!(parsed_manifest.project.team.as_deref(), Some("linting"));
+        assert_eq!(parsed_manifest.project.links.len(), 1);
         assert_eq!(parsed_manifest.files.ignores.into_inner().len(), 2);
         assert_eq!(parsed_manifest.files.allows.len(), 2);
+        assert_eq!(parsed_manifest.files.case_sensitive, Some(false));
+        assert_eq!(parsed_manifest.files.scan, ScanMode::Allowlist);
+        assert!(parsed_manifest.files.follow_symlinks);
+        assert_eq!(parsed_manifest.files.non_utf8, NonUtf8Policy::Lossy);
+        assert_eq!(parsed_manifest.files.max_file_size, Some(1_000_000));
         assert_eq!(
             parsed_manifest.lints.active,
             BTreeMap::from_iter([("lint-id-1".into(), false), ("lint-id-2".into(), true)])
         );
+        assert_eq!(
+            parsed_manifest.checks.levels().collect::<Vec<_>>(),
+            [
+                ("no-unwrap", CheckLevel::Deny),
+                ("todo-comments", CheckLevel::Off)
+            ]
+        );
+        let exemptions = parsed_manifest
+            .per_check
+            .exemptions(CaseSensitivity::Sensitive)
+            .unwrap();
+        assert_eq!(exemptions.keys().collect::<Vec<_>>(), [&"no-unwrap"]);
+        assert!(exemptions["no-unwrap"].exempts("/project/src/legacy/old.rs".into()));
+        assert!(exemptions["no-unwrap"].exempts("/project/tests/main.rs".into()));
+        assert!(!exemptions["no-unwrap"].exempts("/project/src/main.rs".into()));
+        assert_eq!(
+            parsed_manifest
+                .config
+                .scope("long-functions")
+                .get("max_length"),
+            Some(&serde_json::json!(80))
+        );
+        assert_eq!(
+            parsed_manifest
+                .scriptlet_names
+                .name_of("style/long-functions.star".into()),
+            "style-long-functions"
+        );
+        assert_eq!(
+            parsed_manifest
+                .scriptlet_names
+                .name_of("perf/long-functions.star".into()),
+            "long-functions"
+        );
+        assert_eq!(
+            parsed_manifest
+                .scriptlet_names
+                .name_of("@acme/style/long-functions.star".into()),
+            "acme:long-functions"
+        );
+        assert_eq!(parsed_manifest.packs["acme"].path, "../acme-vexes");
         assert_eq!(
             parsed_manifest.languages.deref()[&SupportedLanguage::Python]
                 .file_associations
                 .len(),
             2
         );
+        assert_eq!(
+            parsed_manifest.languages.deref()[&SupportedLanguage::Python].interpreters,
+            ["pypy3"]
+        );
+        assert_eq!(
+            parsed_manifest.theme,
+            Theme {
+                palette: Palette::Colorblind,
+                glyphs: Glyphs::Ascii,
+            }
+        );
+        assert_eq!(parsed_manifest.external.len(), 2);
+        assert_eq!(parsed_manifest.external[0].severity, Severity::Advice);
+        assert_eq!(parsed_manifest.external[1].severity, Severity::Warning);
+        assert_eq!(parsed_manifest.grammars.len(), 1);
+        assert_eq!(
+            parsed_manifest.injections[0].injected_language,
+            Some(SupportedLanguage::Toml)
+        );
+    }
+
+    #[test]
+    fn schema() {
+        // Check that every key in the given value is described by the given schema.
+        fn check(root: &Value, schema: &Value, value: &Value) -> bool {
+            if let Some(reference) = schema["$ref"].as_str() {
+                return check(root, root.pointer(&reference[1..]).unwrap(), value);
+            }
+            if let Some(alternatives) = schema["oneOf"].as_array() {
+                return alternatives
+                    .iter()
+                    .filter(|alternative| check(root, alternative, value))
+                    .count()
+                    == 1;
+            }
+            match value {
+                Value::Object(fields) => {
+                    let required = schema["required"].as_array().into_iter().flatten();
+                    required
+                        .map(|key| key.as_str().unwrap())
+                        .all(|key| fields.contains_key(key))
+                        && fields
+                            .iter()
+                            .all(|(key, field)| match schema["properties"].get(key) {
+                                Some(field_schema) => check(root, field_schema, field),
+                                None => match &schema["additionalProperties"] {
+                                    Value::Bool(false) => false,
+                                    additional => check(root, additional, field),
+                                },
+                            })
+                }
+                Value::Array(items) => items.iter().all(|item| check(root, &schema["items"], item)),
+                _ => true,
+            }
+        }
+
+        let schema: Value = serde_json::from_str(Manifest::SCHEMA).unwrap();
+        let manifest: Value = toml_edit::de::from_str(MAXIMAL_MANIFEST).unwrap();
+        assert!(check(&schema, &schema, &manifest));
+        let default_manifest: Value = toml_edit::de::from_str(Manifest::DEFAULT_CONTENT).unwrap();
+        assert!(check(&schema, &schema, &default_manifest));
+
+        let unknown_key: Value =
+            toml_edit::de::from_str("[vex]\nversion = '1'\ncolour = 'red'").unwrap();
+        assert!(!check(&schema, &schema, &unknown_key));
+        let unknown_parser: Value = toml_edit::de::from_str(indoc! {r#"
+            [vex]
+            version = "1"
+
+            [[external]]
+            id = "x"
+            command = "x"
+            parser.xml = "x"
+        "#})
+        .unwrap();
+        assert!(!check(&schema, &schema, &unknown_parser));
     }
 }
@@ -0,0 +1,299 @@
+use std::fmt::{self, Display};
+
+use camino::Utf8PathBuf;
+use log::error;
+
+use crate::{
+    check_metadata::CheckMetadata,
+    cli::{MaxConcurrentFileLimit, MaxProblems},
+    context::{self, Context, FilesConfig, PerCheckConfig},
+    error::{Error, IOAction},
+    logger,
+    plural::Plural,
+    result::Result,
+    scan::{self, ScanOptions},
+    scriptlets::{source, InitOptions, PreinitOptions, PreinitingStore, VexingStore},
+    severity::SeverityOverrides,
+    source_path::PrettyPath,
+    success,
+    supported_language::SupportedLanguage,
+    trigger::RawFilePattern,
+    verbosity::Verbosity,
+    vex_id::VexId,
+};
+
+/// The name of the file into which each example is written before it is scanned.
+const EXAMPLE_FILE_NAME: &str = "vex-example";
+
+pub fn doctest() -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let verbosity = logger::verbosity();
+    let store =
+        PreinitingStore::new_with_names(&source::sources_in_project(&ctx)?, &ctx.scriptlet_names)?
+            .preinit(PreinitOptions {
+                project: ctx.project.clone(),
+                severity_overrides: SeverityOverrides::default()
+                    .with_configured(ctx.checks.levels()),
+                verbosity,
+                ..PreinitOptions::default()
+            })?
+            .init(InitOptions {
+                verbosity,
+                config: ctx.config.clone(),
+            })?;
+    let metadata = CheckMetadata::new(&ctx, &store)?;
+
+    let (num_examples, failures) = run_examples(&ctx, &store, &metadata)?;
+    if num_examples == 0 {
+        log::warn!("no examples declared, add them with vex.declare(..., bad=[...], good=[...])");
+        return Ok(());
+    }
+    failures.iter().for_each(|failure| error!("{failure}"));
+    if !failures.is_empty() {
+        return Err(Error::ExamplesFailed {
+            failed: failures.len(),
+            total: num_examples,
+        });
+    }
+    success!(
+        "{} passed",
+        Plural::new(num_examples, "example", "examples")
+    );
+    Ok(())
+}
+
+/// Scan each declared example, returning the number of examples and those which did not behave as
+/// declared.
+fn run_examples(
+    ctx: &Context,
+    store: &VexingStore,
+    metadata: &CheckMetadata,
+) -> Result<(usize, Vec<ExampleFailure>)> {
+    let mut num_examples = 0;
+    let mut failures = vec![];
+    for (vex_id, info) in metadata.iter() {
+        let examples = &info.examples;
+        if examples.bad.is_empty() && examples.good.is_empty() {
+            continue;
+        }
+        let language = match info.languages.iter().collect::<Vec<_>>()[..] {
+            [language] => *language,
+            _ => return Err(Error::ExampleLanguage(vex_id.clone())),
+        };
+
+        for (kind, examples) in [
+            (ExampleKind::Bad, &examples.bad),
+            (ExampleKind::Good, &examples.good),
+        ] {
+            for (index, example) in examples.iter().enumerate() {
+                num_examples += 1;
+                let found = finds_problems(ctx, store, vex_id, language, example)?;
+                if found != (kind == ExampleKind::Bad) {
+                    failures.push(ExampleFailure {
+                        vex_id: vex_id.clone(),
+                        kind,
+                        index,
+                    });
+                }
+            }
+        }
+    }
+    Ok((num_examples, failures))
+}
+
+/// Whether the given check finds any problems in the given example.
+fn finds_problems(
+    ctx: &Context,
+    store: &VexingStore,
+    vex_id: &VexId,
+    language: SupportedLanguage,
+    example: &str,
+) -> Result<bool> {
+    let temp_dir = tempfile::tempdir().map_err(|cause| Error::IO {
+        path: PrettyPath::from("temp dir"),
+        action: IOAction::Create,
+        cause,
+    })?;
+    let temp_dir_path = Utf8PathBuf::try_from(temp_dir.path().to_path_buf())?;
+    let example_path = temp_dir_path.join(EXAMPLE_FILE_NAME);
+    std::fs::write(&example_path, example).map_err(|cause| Error::IO {
+        path: PrettyPath::new(&example_path),
+        action: IOAction::Write,
+        cause,
+    })?;
+
+    // Path rules written for the project could otherwise filter out or exempt the example.
+    let mut manifest = ctx.manifest.clone();
+    manifest.external.clear();
+    manifest.files = FilesConfig::default();
+    manifest.per_check = PerCheckConfig::default();
+    manifest
+        .languages
+        .associate(language, RawFilePattern::new(EXAMPLE_FILE_NAME.into()));
+    let example_ctx = Context::new_with_manifest(&temp_dir_path, manifest);
+    let run_data = scan::scan_project(
+        &example_ctx,
+        store,
+        ScanOptions {
+            max_problems: MaxProblems::Unlimited,
+            max_concurrent_files: MaxConcurrentFileLimit::new(1),
+            verbosity: Verbosity::Quiet,
+            fail_fast: true,
+            ..ScanOptions::default()
+        },
+    )?;
+    if run_data.num_files_scanned != 1 {
+        return Err(Error::ExampleNotScanned(vex_id.clone()));
+    }
+    Ok(run_data
+        .irritations
+        .iter()
+        .any(|irritation| irritation.vex_id() == vex_id))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ExampleKind {
+    Bad,
+    Good,
+}
+
+/// An example which did not behave as its check declared.
+#[derive(Debug, PartialEq, Eq)]
+struct ExampleFailure {
+    vex_id: VexId,
+    kind: ExampleKind,
+    index: usize,
+}
+
+impl Display for ExampleFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            vex_id,
+            kind,
+            index,
+        } = self;
+        match kind {
+            ExampleKind::Bad => write!(f, "{vex_id}: bad example {} found no problems", index + 1),
+            ExampleKind::Good => write!(f, "{vex_id}: good example {} found problems", index + 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use indoc::indoc;
+
+    use crate::scriptlets::source::TestSource;
+
+    use super::*;
+
+    fn run(scriptlet: &str) -> Result<(usize, Vec<String>)> {
+        run_with_manifest("[vex]\nversion = '1'", scriptlet)
+    }
+
+    fn run_with_manifest(manifest: &str, scriptlet: &str) -> Result<(usize, Vec<String>)> {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("vex.toml"), manifest).unwrap();
+        let ctx = Context::acquire(&root_path).unwrap();
+        let store = PreinitingStore::new(&[TestSource {
+            vex_dir: "vexes",
+            path: "vexes/test.star",
+            content: scriptlet,
+        }])
+        .unwrap()
+        .preinit(PreinitOptions::default())
+        .unwrap()
+        .init(InitOptions::default())
+        .unwrap();
+        let metadata = CheckMetadata::new(&ctx, &store).unwrap();
+        let (num_examples, failures) = run_examples(&ctx, &store, &metadata)?;
+        Ok((
+            num_examples,
+            failures.iter().map(ToString::to_string).collect(),
+        ))
+    }
+
+    #[test]
+    fn examples() {
+        let (num_examples, failures) = run(indoc! {r#"
+            def init():
+                vex.declare(
+                    'no-unwrap',
+                    languages=['rust'],
+                    bad=['fn main() { x.unwrap(); }', 'fn main() { x.expect("") }'],
+                    good=['fn main() { x?; }', 'fn main() { unwrap(); }'],
+                )
+                vex.declare('no-examples', languages=['rust', 'go'])
+                vex.observe('open_project', on_open_project)
+
+            def on_open_project(event):
+                vex.search(
+                    'rust',
+                    '(call_expression function: (field_expression field: (field_identifier) @name (#eq? @name "unwrap")))',
+                    on_match,
+                )
+                vex.search('rust', '(call_expression function: (identifier) @name)', on_match)
+
+            def on_match(event):
+                vex.warn('no-unwrap', 'unwrap found', at=event.captures['name'])
+        "#})
+        .unwrap();
+        assert_eq!(num_examples, 4);
+        assert_eq!(
+            failures,
+            [
+                "no-unwrap: bad example 2 found no problems",
+                "no-unwrap: good example 2 found problems",
+            ]
+        );
+    }
+
+    #[test]
+    fn project_path_rules_ignored() {
+        let (num_examples, failures) = run_with_manifest(
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [files]
+                ignore = ["vex-*"]
+                scan = "allowlist"
+                allow = ["src/"]
+
+                [check.no-unwrap]
+                exclude = ["vex-*"]
+            "#},
+            indoc! {r#"
+                def init():
+                    vex.declare('no-unwrap', languages=['rust'], bad=['fn main() { x.unwrap(); }'])
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search('rust', '(field_identifier) @name', on_match)
+
+                def on_match(event):
+                    vex.warn('no-unwrap', 'unwrap found', at=event.captures['name'])
+            "#},
+        )
+        .unwrap();
+        assert_eq!(num_examples, 1);
+        assert!(failures.is_empty(), "unexpected failures: {failures:?}");
+    }
+
+    #[test]
+    fn ambiguous_language() {
+        let err = run(indoc! {r#"
+            def init():
+                vex.declare('no-unwrap', languages=['rust', 'go'], bad=['x.unwrap()'])
+                vex.observe('open_project', lambda event: None)
+        "#})
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot tell which language the examples of 'no-unwrap' are written in, declare exactly one with languages=[...]"
+        );
+    }
+}
@@ -1,36 +1,76 @@
-use std::env;
+use std::fmt::{self, Display};
 
-use camino::Utf8PathBuf;
+use serde::Serialize as Serialise;
+use tree_sitter::{Node as TSNode, QueryCursor};
 
 use crate::{
     associations::Associations,
-    cli::DumpCmd,
-    context::Context,
-    error::{Error, IOAction},
+    cli::{DumpCmd, DumpFormat},
+    context::{self, Context},
+    error::Error,
+    query::Query,
     result::Result,
-    scriptlets::{NodePrinter, WhitespaceStyle},
-    source_file::SourceFile,
-    source_path::{PrettyPath, SourcePath},
+    scriptlets::{Location, NodePrinter, WhitespaceStyle},
+    source_file::{ParsedSourceFile, SourceFile},
+    source_path::SourcePath,
+    trigger::CaseSensitivity,
 };
 
 pub fn dump(cmd: DumpCmd) -> Result<()> {
-    let cwd = Utf8PathBuf::try_from(env::current_dir().map_err(|e| Error::IO {
-        path: PrettyPath::new(&cmd.path),
-        action: IOAction::Read,
-        cause: e,
-    })?)?;
+    let cwd = context::current_dir()?;
     let src_path = SourcePath::new_in(&cmd.path, &cwd);
+    // Acquiring the context first loads any grammars the given language may refer to.
+    let ctx = match Context::acquire(&cwd) {
+        Ok(ctx) => Some(ctx),
+        Err(Error::ManifestNotFound) => None,
+        Err(err) => return Err(err),
+    };
     let language = match cmd.language {
-        Some(l) => Some(l),
-        None => Context::acquire()
-            .ok()
+        Some(name) => Some(name.parse()?),
+        None => ctx
             .map(|ctx| ctx.associations())
             .transpose()?
-            .unwrap_or_else(Associations::base)
-            .get_language(&src_path)?,
+            .unwrap_or_else(|| Associations::base(CaseSensitivity::default()))
+            .detect_language(&src_path)?,
     };
     let src_file = SourceFile::new(src_path, language).parse()?;
 
+    if let Some(query) = &cmd.query {
+        let query = Query::new(src_file.language, query)?;
+        let matches = QueryMatches::new(&query, &src_file);
+        if cmd.format == DumpFormat::Json {
+            let json = if cmd.compact {
+                serde_json::to_string(&matches)
+            } else {
+                serde_json::to_string_pretty(&matches)
+            };
+            println!(
+                "{}",
+                json.expect("internal error: cannot serialise matches")
+            );
+        } else {
+            print!("{matches}");
+        }
+        if matches.0.is_empty() {
+            log::warn!("no matches");
+        }
+        return Ok(());
+    }
+
+    if cmd.format == DumpFormat::Json {
+        let tree = JsonNode::new(src_file.tree.root_node(), None);
+        let json = if cmd.compact {
+            serde_json::to_string(&tree)
+        } else {
+            serde_json::to_string_pretty(&tree)
+        };
+        println!(
+            "{}",
+            json.expect("internal error: cannot serialise syntax tree")
+        );
+        return Ok(());
+    }
+
     let capacity_estimate = 20 * src_file.tree.root_node().descendant_count();
     let mut buf = String::with_capacity(capacity_estimate);
     let format = if cmd.compact {
@@ -38,12 +78,106 @@ pub fn dump(cmd: DumpCmd) -> Result<()> {
     } else {
         WhitespaceStyle::Expanded
     };
-    NodePrinter::new(&mut buf, format).write(&src_file)?;
+    let printer = NodePrinter::new(&mut buf, format);
+    match cmd.format {
+        DumpFormat::Pretty => printer.with_snippets(),
+        _ => printer,
+    }
+    .write(&src_file)?;
     println!("{buf}");
 
     Ok(())
 }
 
+#[derive(Debug, Serialise)]
+#[serde(transparent)]
+struct QueryMatches<'a>(Vec<QueryMatch<'a>>);
+
+impl<'a> QueryMatches<'a> {
+    fn new(query: &'a Query, src_file: &'a ParsedSourceFile) -> Self {
+        let capture_names = query.capture_names();
+        let content = src_file.content.as_bytes();
+        let matches = QueryCursor::new()
+            .matches(query, src_file.tree.root_node(), content)
+            .map(|qmatch| QueryMatch {
+                pattern: qmatch.pattern_index,
+                captures: qmatch
+                    .captures
+                    .iter()
+                    .map(|capture| Capture {
+                        name: capture_names[capture.index as usize],
+                        kind: capture.node.grammar_name(),
+                        location: Location::of(&capture.node),
+                        text: &src_file.content[capture.node.byte_range()],
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self(matches)
+    }
+}
+
+impl Display for QueryMatches<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().enumerate().try_for_each(|(i, qmatch)| {
+            writeln!(f, "match {} (pattern {}):", i + 1, qmatch.pattern)?;
+            qmatch.captures.iter().try_for_each(|capture| {
+                let Capture {
+                    name,
+                    kind,
+                    location,
+                    text,
+                } = capture;
+                writeln!(f, "  @{name} ({kind}) ; {location} {text:?}")
+            })
+        })
+    }
+}
+
+#[derive(Debug, Serialise)]
+struct QueryMatch<'a> {
+    pattern: usize,
+    captures: Vec<Capture<'a>>,
+}
+
+#[derive(Debug, Serialise)]
+struct Capture<'a> {
+    name: &'a str,
+    kind: &'static str,
+    location: Location,
+    text: &'a str,
+}
+
+#[derive(Debug, Serialise)]
+struct JsonNode {
+    kind: &'static str,
+    field: Option<&'static str>,
+    named: bool,
+    location: Location,
+    start_byte: usize,
+    end_byte: usize,
+    children: Vec<JsonNode>,
+}
+
+impl JsonNode {
+    fn new(node: TSNode<'_>, field: Option<&'static str>) -> Self {
+        let children = node
+            .children(&mut node.walk())
+            .enumerate()
+            .map(|(i, child)| Self::new(child, node.field_name_for_child(i as u32)))
+            .collect();
+        Self {
+            kind: node.grammar_name(),
+            field,
+            named: node.is_named(),
+            location: Location::of(&node),
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            children,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::{
@@ -52,6 +186,7 @@ mod test {
         path,
     };
 
+    use camino::Utf8PathBuf;
     use clap::Parser;
     use indoc::indoc;
     use tempfile::TempDir;
@@ -104,6 +239,34 @@ mod test {
         dump(cmd).unwrap();
     }
 
+    #[test]
+    #[cfg(all(feature = "lang-java", feature = "lang-kotlin"))]
+    fn dump_jvm_files() {
+        let java_file = TestFile::new(
+            "src/Main.java",
+            indoc! {r#"
+                class Main {
+                    int add(int a, int b) {
+                        return a + b;
+                    }
+                }
+            "#},
+        );
+        let kotlin_file = TestFile::new(
+            "src/main.kt",
+            indoc! {r#"
+                fun add(a: Int, b: Int): Int {
+                    return a + b
+                }
+            "#},
+        );
+        for test_file in [java_file, kotlin_file] {
+            let args = Args::try_parse_from(["vex", "dump", test_file.path.as_str()]).unwrap();
+            let cmd = args.command.into_dump_cmd().unwrap();
+            dump(cmd).unwrap();
+        }
+    }
+
     #[test]
     fn dump_nonexistent_file() {
         let file_path = "/i/do/not/exist.rs";
@@ -161,6 +324,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn language_override() {
+        let test_file = TestFile::new("script", "fn main() {}");
+        let args =
+            Args::try_parse_from(["vex", "dump", test_file.path.as_str(), "--language", "rust"])
+                .unwrap();
+        dump(args.command.into_dump_cmd().unwrap()).unwrap();
+
+        let args = Args::try_parse_from([
+            "vex",
+            "dump",
+            test_file.path.as_str(),
+            "--language",
+            "cobol",
+        ])
+        .unwrap();
+        let err = dump(args.command.into_dump_cmd().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported language 'cobol'");
+    }
+
     #[test]
     fn unknown_extension() {
         let test_file = TestFile::new("file.unknown-extension", "");
@@ -202,5 +385,79 @@ mod test {
         assert!(compact_fmt.len() < pretty_fmt.len());
         assert!(!compact_fmt.contains('\n'));
         assert!(pretty_fmt.contains('\n'));
+
+        let snippet_fmt = {
+            let mut snippet_fmt = String::new();
+            NodePrinter::new(&mut snippet_fmt, WhitespaceStyle::Expanded)
+                .with_snippets()
+                .write(&test_file)
+                .unwrap();
+            snippet_fmt
+        };
+        assert!(snippet_fmt.contains(r#"name: (identifier) ; 1:6-7 "X""#));
+        assert!(snippet_fmt.contains(r#"(integer_literal) ; 1:17-18 "1""#));
+    }
+
+    #[test]
+    fn query_matches() {
+        let test_file = ParsedSourceFile::new_with_content(
+            SourcePath::new_in("test.rs".into(), "".into()),
+            indoc! {r#"
+                fn main() {
+                    let x = 1;
+                    let y = 2;
+                }
+            "#},
+            SupportedLanguage::Rust,
+        )
+        .unwrap();
+        let query = Query::new(
+            SupportedLanguage::Rust,
+            r#"(let_declaration pattern: (_) @pat value: (_) @val (#not-eq? @pat "y"))"#,
+        )
+        .unwrap();
+        let matches = QueryMatches::new(&query, &test_file);
+        assert_eq!(
+            matches.to_string(),
+            indoc! {r#"
+                match 1 (pattern 0):
+                  @pat (identifier) ; 2:8-9 "x"
+                  @val (integer_literal) ; 2:12-13 "1"
+            "#}
+        );
+
+        let json = serde_json::to_value(&matches).unwrap();
+        assert_eq!(json[0]["captures"][1]["name"], "val");
+        assert_eq!(json[0]["captures"][1]["text"], "1");
+
+        let query = Query::new(SupportedLanguage::Rust, "(string_literal) @str").unwrap();
+        assert_eq!(QueryMatches::new(&query, &test_file).to_string(), "");
+    }
+
+    #[test]
+    fn json_format() {
+        let test_file = ParsedSourceFile::new_with_content(
+            SourcePath::new_in("test.rs".into(), "".into()),
+            "const X: usize = 1;",
+            SupportedLanguage::Rust,
+        )
+        .unwrap();
+        let tree = serde_json::to_value(JsonNode::new(test_file.tree.root_node(), None)).unwrap();
+        assert_eq!(tree["kind"], "source_file");
+        assert_eq!(tree["field"], serde_json::Value::Null);
+
+        let item = &tree["children"][0];
+        assert_eq!(item["kind"], "const_item");
+        let name = &item["children"][1];
+        assert_eq!(name["kind"], "identifier");
+        assert_eq!(name["field"], "name");
+        assert_eq!(name["named"], true);
+        assert_eq!(name["start_byte"], 6);
+        assert_eq!(name["end_byte"], 7);
+        assert_eq!(
+            name["location"],
+            serde_json::json!({ "start_row": 1, "start_column": 6, "end_row": 1, "end_column": 7 })
+        );
+        assert_eq!(item["children"][0]["named"], false);
     }
 }
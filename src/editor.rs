@@ -0,0 +1,106 @@
+use std::{env, process::Command};
+
+use camino::Utf8Path;
+
+use crate::{error::Error, result::Result};
+
+/// The command used to open a file at a given position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Editor {
+    /// The user's preferred editor as given by `$VISUAL` or `$EDITOR`, which is passed `+line`
+    /// then the file path.
+    Env(Vec<String>),
+
+    /// A command from the manifest's `editor-cmd`, in which `{path}`, `{line}` and `{column}` are
+    /// substituted.
+    Template(Vec<String>),
+}
+
+impl Editor {
+    /// Use the given template if one is set, otherwise fall back to the environment.
+    pub fn new(template: Option<&str>) -> Result<Self> {
+        if let Some(template) = template {
+            return Ok(Self::Template(words(template)?));
+        }
+
+        let cmd = ["VISUAL", "EDITOR"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|cmd| !cmd.trim().is_empty())
+            .ok_or_else(|| {
+                Error::Editor("neither $VISUAL nor $EDITOR is set and no editor-cmd given".into())
+            })?;
+        Ok(Self::Env(words(&cmd)?))
+    }
+
+    /// Open the given file at the given line and column (both numbered from 1) and wait for the
+    /// editor to exit.
+    pub fn open(&self, path: &Utf8Path, line: usize, column: usize) -> Result<()> {
+        let mut command = self.command(path, line, column);
+        let program = command.get_program().to_string_lossy().into_owned();
+        let status = command
+            .status()
+            .map_err(|cause| Error::Editor(format!("cannot run {program}: {cause}")))?;
+        if !status.success() {
+            return Err(Error::Editor(format!("{program} exited with {status}")));
+        }
+        Ok(())
+    }
+
+    fn command(&self, path: &Utf8Path, line: usize, column: usize) -> Command {
+        match self {
+            Self::Env(words) => {
+                let mut command = Command::new(&words[0]);
+                command.args(&words[1..]).arg(format!("+{line}")).arg(path);
+                command
+            }
+            Self::Template(words) => {
+                let mut words = words.iter().map(|word| {
+                    word.replace("{path}", path.as_str())
+                        .replace("{line}", &line.to_string())
+                        .replace("{column}", &column.to_string())
+                });
+                let mut command = Command::new(words.next().unwrap_or_default());
+                command.args(words);
+                command
+            }
+        }
+    }
+}
+
+fn words(cmd: &str) -> Result<Vec<String>> {
+    let words: Vec<_> = cmd.split_whitespace().map(ToString::to_string).collect();
+    if words.is_empty() {
+        return Err(Error::Editor("editor command is empty".into()));
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn command() {
+        let args = |editor: Editor| {
+            let command = editor.command(Utf8Path::new("src/main.rs"), 10, 5);
+            [command.get_program()]
+                .into_iter()
+                .chain(command.get_args())
+                .map(|arg| arg.to_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            args(Editor::Env(words("code  --wait").unwrap())),
+            ["code", "--wait", "+10", "src/main.rs"]
+        );
+        assert_eq!(
+            args(Editor::new(Some("code --goto {path}:{line}:{column}")).unwrap()),
+            ["code", "--goto", "src/main.rs:10:5"]
+        );
+        assert_eq!(
+            Editor::new(Some(" ")).unwrap_err().to_string(),
+            "cannot open editor: editor command is empty"
+        );
+    }
+}
@@ -1,14 +1,16 @@
-use std::{fmt, io, num, path, str::Utf8Error};
+use std::{fmt, io, num, path, str::Utf8Error, sync::Arc};
 
 use derive_more::Display;
 use joinery::JoinableIterator;
 use strum::IntoEnumIterator;
 
 use crate::{
+    manifest_keys::UnknownKey,
     query::Query,
-    scriptlets::{action::Action, event::EventKind, LoadPath, Location},
+    scriptlets::{action::Action, event::EventKind, limits::ExceededLimit, LoadPath, Location},
     source_path::PrettyPath,
     supported_language::SupportedLanguage,
+    vex_id::VexId,
 };
 
 // TODO(kcza): box this!
@@ -22,6 +24,9 @@ pub enum Error {
     )]
     AlreadyInited { found_root: PrettyPath },
 
+    #[error("{0} already exists, to overwrite it, use --force")]
+    AlreadyExists(PrettyPath),
+
     #[error("cannot discern language of {path} as multiple patterns match (could be {language} or {other_language})")]
     AmbiguousLanguage {
         path: PrettyPath,
@@ -32,24 +37,89 @@ pub enum Error {
     #[error(transparent)]
     Clap(#[from] clap::Error),
 
+    #[error("cannot open editor: {0}")]
+    Editor(String),
+
+    #[error("checks '{first}' and '{second}' both have code '{code}'")]
+    DuplicateCode {
+        code: String,
+        first: VexId,
+        second: VexId,
+    },
+
     #[error("query is empty")]
     EmptyQuery,
 
+    #[error("cannot tell which language the examples of '{0}' are written in, declare exactly one with languages=[...]")]
+    ExampleLanguage(VexId),
+
+    #[error("cannot scan the examples of '{0}'")]
+    ExampleNotScanned(VexId),
+
+    #[error("{failed} of {total} examples failed")]
+    ExamplesFailed { failed: usize, total: usize },
+
+    #[error("cannot run external check '{id}': {reason}")]
+    ExternalCheck { id: String, reason: String },
+
+    #[error("{0} kept changing while being read")]
+    FileChanging(PrettyPath),
+
+    #[error("{0} appears to be binary")]
+    BinaryFile(PrettyPath),
+
+    #[error("{0} is not valid UTF-8")]
+    NotUtf8(PrettyPath),
+
     #[error(transparent)]
     Fmt(#[from] fmt::Error),
 
+    #[error("scriptlet '{scriptlet}' {exceeded} handling {event}, to allow more, raise handler-timeout or handler-step-limit in vex.toml")]
+    HandlerLimitExceeded {
+        scriptlet: Arc<str>,
+        event: &'static str,
+        exceeded: ExceededLimit,
+    },
+
     #[error(transparent)]
     FromPathBuf(#[from] camino::FromPathBufError),
 
+    #[error("invalid code '{0}': must be A-Z followed by A-Z or 0-9")]
+    InvalidCode(String),
+
+    #[error("invalid config option '{key}' in vex.toml: {reason}")]
+    InvalidConfig { key: String, reason: String },
+
+    #[error("invalid finding '{finding}': {reason}")]
+    InvalidFinding { finding: String, reason: String },
+
+    #[error("invalid injection into {language}: {reason}")]
+    InvalidInjection {
+        language: SupportedLanguage,
+        reason: &'static str,
+    },
+
     #[error("invalid vex ID '{raw_id}': {reason}")]
     InvalidID {
         raw_id: String,
         reason: InvalidIDReason,
     },
 
+    #[error("invalid pack name '{name}': {reason}")]
+    InvalidPackName {
+        name: String,
+        reason: InvalidIDReason,
+    },
+
     #[error("import cycle detected: {}", .0.iter().join_with(" -> "))]
     ImportCycle(Vec<PrettyPath>),
 
+    #[error("cannot import {format} results: {reason}")]
+    InvalidImport {
+        format: &'static str,
+        reason: String,
+    },
+
     #[error("cannot load {load}: {reason}")]
     InvalidLoad {
         load: String,
@@ -57,6 +127,19 @@ pub enum Error {
         reason: InvalidLoadReason,
     },
 
+    #[error("cannot parse output of external check '{id}': {reason}")]
+    InvalidExternalCheckOutput { id: String, reason: String },
+
+    #[error("cannot search with scope '{scope}' while {}: {reason}", .action.pretty_name())]
+    InvalidSearchScope {
+        scope: String,
+        action: Action,
+        reason: &'static str,
+    },
+
+    #[error("invalid tag '{0}': can only contain a-z, 0-9 and single '-'")]
+    InvalidTag(String),
+
     #[error("test invalid: {0}")]
     InvalidTest(String),
 
@@ -70,12 +153,36 @@ pub enum Error {
         cause: io::Error,
     },
 
+    #[error("cannot list changed files: {0}")]
+    Git(String),
+
+    #[error("cannot load grammar '{name}': {reason}")]
+    Grammar { name: String, reason: String },
+
     #[error(transparent)]
     Language(#[from] tree_sitter::LanguageError),
 
+    #[error(
+        "{0} support was not compiled into this build of vex (missing: {}), rebuild with `--features lang-{0}`",
+        SupportedLanguage::missing().join_with(", ")
+    )]
+    LanguageUnavailable(SupportedLanguage),
+
+    #[error("cannot find vex.lock, to create it, run `vex sync`")]
+    LockfileNotFound,
+
+    #[error("vex.lock is out of date: {}; to update it, run `vex sync`", .0.join(", "))]
+    LockfileOutdated(Vec<String>),
+
     #[error("cannot find manifest, try running `vex init` in the project’s root")]
     ManifestNotFound,
 
+    #[error("no active check searches {}, as required by require-checks-for in vex.toml", .0.iter().join_with(", "))]
+    MissingChecks(Vec<SupportedLanguage>),
+
+    #[error("no check matches --filter '{0}'")]
+    NoChecksSelected(String),
+
     #[error("cannot discern language of {0}")]
     NoKnownLanguage(PrettyPath),
 
@@ -85,9 +192,18 @@ pub enum Error {
     #[error("cannot find vexes directory at {0}")]
     NoVexesDir(PrettyPath),
 
+    #[error("no template for new {0} checks, expected one of: {}", crate::scaffold::TEMPLATE_LANGUAGES.iter().join_with(", "))]
+    NoTemplate(SupportedLanguage),
+
     #[error("{0} is not a check path")]
     NotACheckPath(PrettyPath),
 
+    #[error("cannot browse problems: stdout is not a terminal")]
+    NotATerminal,
+
+    #[error("{0} is outside the project")]
+    OutsideProject(PrettyPath),
+
     #[error(transparent)]
     ParseInt(#[from] num::ParseIntError),
 
@@ -101,9 +217,25 @@ pub enum Error {
     #[error(transparent)]
     Query(#[from] tree_sitter::QueryError),
 
+    #[error("problems increased: {}", .0.join(", "))]
+    RatchetExceeded(Vec<String>),
+
+    #[error("cannot find {0}, to create it, rerun with --update-ratchet")]
+    RatchetNotFound(PrettyPath),
+
     #[error("ignoring '*' makes other ignore ids redundant")]
     RedundantIgnore,
 
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+
+    #[error("scriptlets {first} and {second} are both named '{name}', rename one in the [scriptlet-names] table of vex.toml")]
+    ScriptletNameConflict {
+        name: String,
+        first: PrettyPath,
+        second: PrettyPath,
+    },
+
     #[error(transparent)]
     SetLogger(#[from] log::SetLoggerError),
 
@@ -119,6 +251,12 @@ pub enum Error {
     #[error(transparent)]
     Toml(#[from] toml_edit::de::Error),
 
+    #[error("{}", .0.iter().join_with("\n"))]
+    UnknownManifestKeys(Vec<UnknownKey>),
+
+    #[error("no check has code or id '{0}', to list every check, run `vex list checks`")]
+    UnknownCode(String),
+
     #[error(
         "unknown event '{name}'{}, expected one of: {}",
         suggestion.map(|suggestion| format!(" (did you mean '{suggestion}'?)")).unwrap_or_default(),
@@ -152,6 +290,12 @@ pub enum Error {
 
     #[error(transparent)]
     Utf8(#[from] Utf8Error),
+
+    #[error("vex.lang.{expected} cannot inspect {found} nodes")]
+    WrongLanguage {
+        expected: SupportedLanguage,
+        found: SupportedLanguage,
+    },
 }
 
 impl From<anyhow::Error> for Error {
@@ -264,6 +408,9 @@ pub enum InvalidLoadReason {
     #[display(fmt = "load path cannot be outside of the vexes directory")]
     OutsideDirectory,
 
+    #[display(fmt = "load path cannot leave its rule pack")]
+    OutsidePack,
+
     #[display(fmt = "load path invalid, see docs")] // TODO(kcza): link to spec once public.
     NonSpecific,
 }
@@ -0,0 +1,582 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    process::Command,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{info, log_enabled};
+use regex::Regex;
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    check_selection::CheckSelection,
+    context::Context,
+    error::Error,
+    irritation::Irritation,
+    result::Result,
+    severity::{Severity, SeverityOverrides},
+    source_path::PrettyPath,
+    vex_id::VexId,
+};
+
+/// A check implemented by a command outside of vex, as declared in the manifest.
+#[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExternalCheck {
+    pub id: String,
+
+    pub command: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    #[serde(default)]
+    pub severity: Severity,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// The exit codes which mean the command ran, such as those of tools which exit with 1 when
+    /// they find problems. Any other exit is an error.
+    #[serde(default = "ExternalCheck::default_exit_codes")]
+    pub exit_codes: Vec<i32>,
+
+    pub parser: OutputParser,
+}
+
+/// How to extract problems from the standard output of an external check.
+#[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputParser {
+    /// Match each line of output against a regex with named groups `message`, and optionally
+    /// `path` and `line`.
+    Regex(String),
+
+    /// Parse output as JSON, extracting fields with JSON pointers.
+    Json(JsonMapping),
+}
+
+#[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct JsonMapping {
+    /// Pointer to the array of problems, defaults to the whole document.
+    #[serde(default)]
+    pub items: String,
+
+    /// Pointer to the message of each problem.
+    pub message: String,
+
+    /// Pointer to the path of each problem.
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Pointer to the line of each problem.
+    #[serde(default)]
+    pub line: Option<String>,
+}
+
+/// A problem reported by a tool other than vex.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub message: String,
+    pub path: Option<String>,
+    pub line: Option<usize>,
+}
+
+/// The content of each file which findings are in, by path relative to the project root. Files
+/// which cannot be read have no content.
+pub type FindingFiles = HashMap<Utf8PathBuf, Option<String>>;
+
+impl Finding {
+    /// Read each file which the given findings are in, once however many problems are in it.
+    pub fn read_files<'a>(
+        project_root: &Utf8Path,
+        findings: impl IntoIterator<Item = &'a Self>,
+    ) -> FindingFiles {
+        let mut files = FindingFiles::new();
+        for path in findings
+            .into_iter()
+            .filter_map(|finding| finding.relative_path(project_root))
+        {
+            files
+                .entry(path)
+                .or_insert_with_key(|path| fs::read_to_string(project_root.join(path)).ok());
+        }
+        files
+    }
+
+    /// Convert into an irritation, taking the content of its file from those already read.
+    pub fn into_irritation(
+        self,
+        project_root: &Utf8Path,
+        files: &FindingFiles,
+        vex_id: VexId,
+        severity: Severity,
+    ) -> Irritation {
+        let path = self.relative_path(project_root);
+        let Self { message, line, .. } = self;
+        let content = path
+            .as_ref()
+            .and_then(|path| files.get(path))
+            .and_then(Option::as_deref);
+        let at = path.map(|path| {
+            let line = content.zip(line);
+            (PrettyPath::new(&path), line)
+        });
+        Irritation::external(vex_id, severity, &message, at)
+    }
+
+    fn relative_path(&self, project_root: &Utf8Path) -> Option<Utf8PathBuf> {
+        self.path
+            .as_ref()
+            .map(|path| relative_to(project_root, Utf8Path::new(path)))
+    }
+}
+
+/// Run each external check which is enabled and selected. As with scriptlets, levels configured
+/// in the manifest or passed on the command-line override each check's own severity. If only
+/// changed files are checked, problems in other files are dropped.
+pub fn run_external_checks(
+    ctx: &Context,
+    severity_overrides: &SeverityOverrides,
+    selection: &CheckSelection,
+    changed_files: Option<&BTreeSet<Utf8PathBuf>>,
+) -> Result<Vec<Irritation>> {
+    let mut irritations = vec![];
+    for check in &ctx.external {
+        if severity_overrides.is_disabled(&check.id) || !selection.selects(&check.id) {
+            continue;
+        }
+        let vex_id = VexId::try_from(check.id.clone())?;
+        let severity = severity_overrides.get(&vex_id).unwrap_or(check.severity);
+        irritations.extend(
+            check
+                .run(&ctx.project_root, vex_id, severity)?
+                .into_iter()
+                .filter(|irritation| {
+                    let (Some(changed_files), Some(path)) = (changed_files, irritation.path())
+                    else {
+                        return true;
+                    };
+                    changed_files.contains(Utf8Path::new(path.as_str()))
+                }),
+        );
+    }
+    Ok(irritations)
+}
+
+impl ExternalCheck {
+    fn default_exit_codes() -> Vec<i32> {
+        vec![0]
+    }
+
+    fn run(
+        &self,
+        project_root: &Utf8Path,
+        vex_id: VexId,
+        severity: Severity,
+    ) -> Result<Vec<Irritation>> {
+        if log_enabled!(log::Level::Info) {
+            info!("running external check {}", self.id);
+        }
+        let error = |reason: String| Error::ExternalCheck {
+            id: self.id.clone(),
+            reason,
+        };
+        let output = Command::new(&self.command)
+            .args(&self.args)
+            .current_dir(project_root)
+            .output()
+            .map_err(|cause| error(cause.to_string()))?;
+        // A command which crashed may print nothing, which would otherwise look like success.
+        if !output
+            .status
+            .code()
+            .is_some_and(|code| self.exit_codes.contains(&code))
+        {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.trim_end();
+            return Err(error(if stderr.is_empty() {
+                format!("{}", output.status)
+            } else {
+                format!("{}:\n{stderr}", output.status)
+            }));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let findings = self.parser.parse(&self.id, &stdout)?;
+
+        let files = Finding::read_files(project_root, &findings);
+        Ok(findings
+            .into_iter()
+            .map(|finding| finding.into_irritation(project_root, &files, vex_id.clone(), severity))
+            .collect())
+    }
+}
+
+fn relative_to(project_root: &Utf8Path, path: &Utf8Path) -> Utf8PathBuf {
+    path.strip_prefix(project_root)
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+impl OutputParser {
+    fn parse(&self, id: &str, output: &str) -> Result<Vec<Finding>> {
+        let invalid_output = |reason: String| Error::InvalidExternalCheckOutput {
+            id: id.to_string(),
+            reason,
+        };
+        match self {
+            Self::Regex(pattern) => {
+                let regex = Regex::new(pattern)?;
+                if !regex
+                    .capture_names()
+                    .flatten()
+                    .any(|name| name == "message")
+                {
+                    return Err(invalid_output(
+                        "regex has no capture group named `message`".into(),
+                    ));
+                }
+                output
+                    .lines()
+                    .filter_map(|line| regex.captures(line))
+                    .map(|captures| {
+                        let line = captures
+                            .name("line")
+                            .map(|line| line.as_str().parse())
+                            .transpose()?;
+                        Ok(Finding {
+                            message: captures["message"].to_string(),
+                            path: captures.name("path").map(|path| path.as_str().to_string()),
+                            line,
+                        })
+                    })
+                    .collect()
+            }
+            Self::Json(mapping) => {
+                let document: JsonValue = serde_json::from_str(output)
+                    .map_err(|cause| invalid_output(cause.to_string()))?;
+                let items = document
+                    .pointer(&mapping.items)
+                    .and_then(JsonValue::as_array)
+                    .ok_or_else(|| {
+                        invalid_output(format!("no array found at '{}'", mapping.items))
+                    })?;
+                items
+                    .iter()
+                    .map(|item| {
+                        let message = item
+                            .pointer(&mapping.message)
+                            .and_then(JsonValue::as_str)
+                            .ok_or_else(|| {
+                                invalid_output(format!("no message found at '{}'", mapping.message))
+                            })?
+                            .to_string();
+                        let path = mapping
+                            .path
+                            .as_ref()
+                            .and_then(|path| item.pointer(path))
+                            .and_then(JsonValue::as_str)
+                            .map(ToString::to_string);
+                        let line = mapping
+                            .line
+                            .as_ref()
+                            .and_then(|line| item.pointer(line))
+                            .and_then(|line| match line {
+                                JsonValue::Number(n) => n.as_u64().map(|n| n as usize),
+                                JsonValue::String(s) => s.parse().ok(),
+                                _ => None,
+                            });
+                        Ok(Finding {
+                            message,
+                            path,
+                            line,
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::{formatdoc, indoc};
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn parse_regex() {
+        let parser =
+            OutputParser::Regex(r"^(?<path>[^:]+):(?<line>[0-9]+): (?<message>.*)$".into());
+        let findings = parser
+            .parse(
+                "test",
+                indoc! {"
+                    src/main.rs:2: something is wrong
+                    this line is not a finding
+                    src/lib.rs:10: something else is wrong
+                "},
+            )
+            .unwrap();
+        assert_eq!(
+            findings,
+            [
+                Finding {
+                    message: "something is wrong".into(),
+                    path: Some("src/main.rs".into()),
+                    line: Some(2),
+                },
+                Finding {
+                    message: "something else is wrong".into(),
+                    path: Some("src/lib.rs".into()),
+                    line: Some(10),
+                },
+            ]
+        );
+
+        let err = OutputParser::Regex("(?<path>.*)".into())
+            .parse("test", "")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot parse output of external check 'test': regex has no capture group named `message`"
+        );
+    }
+
+    #[test]
+    fn parse_json() {
+        let parser = OutputParser::Json(JsonMapping {
+            items: "/problems".into(),
+            message: "/msg".into(),
+            path: Some("/location/file".into()),
+            line: Some("/location/line".into()),
+        });
+        let findings = parser
+            .parse(
+                "test",
+                indoc! {r#"
+                    {
+                        "problems": [
+                            { "msg": "first", "location": { "file": "src/main.rs", "line": 1 } },
+                            { "msg": "second", "location": { "file": "src/main.rs", "line": "2" } },
+                            { "msg": "third" }
+                        ]
+                    }
+                "#},
+            )
+            .unwrap();
+        assert_eq!(
+            findings,
+            [
+                Finding {
+                    message: "first".into(),
+                    path: Some("src/main.rs".into()),
+                    line: Some(1),
+                },
+                Finding {
+                    message: "second".into(),
+                    path: Some("src/main.rs".into()),
+                    line: Some(2),
+                },
+                Finding {
+                    message: "third".into(),
+                    path: None,
+                    line: None,
+                },
+            ]
+        );
+
+        parser.parse("test", "not json").unwrap_err();
+        parser.parse("test", "{}").unwrap_err();
+    }
+
+    #[test]
+    fn finding_files_read_once() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("main.rs"), "fn main() {}\n").unwrap();
+        let finding = |path: &str| Finding {
+            message: "problem".into(),
+            path: Some(path.into()),
+            line: Some(1),
+        };
+        let findings = [
+            finding("main.rs"),
+            finding(root_path.join("main.rs").as_str()),
+            finding("missing.rs"),
+        ];
+        let files = Finding::read_files(&root_path, &findings);
+        assert_eq!(
+            files,
+            FindingFiles::from([
+                ("main.rs".into(), Some("fn main() {}\n".into())),
+                ("missing.rs".into(), None),
+            ])
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn external_checks_merged() {
+        let irritations = VexTest::new("external-checks")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[external]]
+                id = "legacy-check"
+                command = "echo"
+                args = ["src/main.rs:2: bad line"]
+                severity = "error"
+                parser.regex = '^(?<path>[^:]+):(?<line>[0-9]+): (?<message>.*)$'
+            "#})
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + 2;
+                    }
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+
+        let irritation = &irritations[0];
+        assert_eq!(irritation.vex_id().to_string(), "legacy-check");
+        assert_eq!(irritation.severity(), Severity::Error);
+        assert_eq!(irritation.path().unwrap().as_str(), "src/main.rs");
+        let rendered = irritation.to_string();
+        assert!(rendered.contains("error[legacy-check]: bad line"));
+        assert!(rendered.contains("let x = 1 + 2;"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn external_check_exit_codes() {
+        let manifest = |exit_codes: &str| {
+            formatdoc! {r#"
+                [vex]
+                version = "1"
+
+                [[external]]
+                id = "legacy-check"
+                command = "sh"
+                args = ["-c", "echo 'src/main.rs:1: bad line'; echo 'lint failed' >&2; exit 3"]
+                {exit_codes}
+                parser.regex = '^(?<path>[^:]+):(?<line>[0-9]+): (?<message>.*)$'
+            "#}
+        };
+        let source = "fn main() {}\n";
+
+        VexTest::new("unexpected-exit-code")
+            .with_manifest(manifest(""))
+            .with_source_file("src/main.rs", source)
+            .returns_error(
+                "cannot run external check 'legacy-check': exit status: 3:\nlint failed",
+            );
+
+        let irritations = VexTest::new("expected-exit-code")
+            .with_manifest(manifest("exit-codes = [0, 3]"))
+            .with_source_file("src/main.rs", source)
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].message(), "bad line");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn external_checks_configured() {
+        let manifest = indoc! {r#"
+            [vex]
+            version = "1"
+
+            [checks]
+            quiet-check = "off"
+
+            [[external]]
+            id = "legacy-check"
+            command = "printf"
+            args = ["src/a.rs:1: bad line\nsrc/b.rs:1: bad line\n"]
+            parser.regex = '^(?<path>[^:]+):(?<line>[0-9]+): (?<message>.*)$'
+
+            [[external]]
+            id = "other-check"
+            command = "echo"
+            args = ["src/a.rs:1: other line"]
+            parser.regex = '^(?<path>[^:]+):(?<line>[0-9]+): (?<message>.*)$'
+
+            [[external]]
+            id = "quiet-check"
+            command = "echo"
+            args = ["src/a.rs:1: quiet line"]
+            parser.regex = '^(?<path>[^:]+):(?<line>[0-9]+): (?<message>.*)$'
+        "#};
+        let run = |test: VexTest| {
+            let mut irritations = test
+                .with_manifest(manifest)
+                .with_source_file("src/a.rs", "fn a() {}\n")
+                .with_source_file("src/b.rs", "fn b() {}\n")
+                .try_run()
+                .unwrap()
+                .irritations
+                .into_iter()
+                .map(|irritation| {
+                    format!(
+                        "{}:{}:{}",
+                        irritation.vex_id(),
+                        irritation.severity(),
+                        irritation.path().unwrap()
+                    )
+                })
+                .collect::<Vec<_>>();
+            irritations.sort();
+            irritations
+        };
+
+        assert_eq!(
+            run(VexTest::new("configured-levels")),
+            [
+                "legacy-check:warning:src/a.rs",
+                "legacy-check:warning:src/b.rs",
+                "other-check:warning:src/a.rs",
+            ]
+        );
+        assert_eq!(
+            run(
+                VexTest::new("overridden-levels").with_severity_overrides(SeverityOverrides::new(
+                    &[],
+                    &["quiet-check".into()],
+                    &["legacy-check".into()],
+                ))
+            ),
+            [
+                "legacy-check:error:src/a.rs",
+                "legacy-check:error:src/b.rs",
+                "other-check:warning:src/a.rs",
+                "quiet-check:warning:src/a.rs",
+            ]
+        );
+        assert_eq!(
+            run(VexTest::new("selected").with_selection(
+                CheckSelection::new(&["*-check".into()], &["other-check".into()]).unwrap()
+            )),
+            [
+                "legacy-check:warning:src/a.rs",
+                "legacy-check:warning:src/b.rs",
+            ]
+        );
+        assert_eq!(
+            run(VexTest::new("changed").with_changed_files(["src/b.rs"])),
+            ["legacy-check:warning:src/b.rs"]
+        );
+    }
+}
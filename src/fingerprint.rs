@@ -0,0 +1,260 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    str::FromStr,
+};
+
+use camino::Utf8PathBuf;
+
+use crate::{
+    cli::FingerprintCmd,
+    context::{self, Context},
+    error::{Error, IOAction},
+    result::Result,
+    source_path::PrettyPath,
+    vex_id::VexId,
+};
+
+pub fn fingerprint(cmd: FingerprintCmd) -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let finding: Finding = cmd.finding.parse()?;
+    let content =
+        fs::read_to_string(ctx.project_root.join(&finding.path)).map_err(|cause| Error::IO {
+            path: PrettyPath::new(&finding.path),
+            action: IOAction::Read,
+            cause,
+        })?;
+    println!("{}", finding.fingerprint(&content)?);
+    Ok(())
+}
+
+/// A problem found by a check, as written `path:line:column:check-id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// The path of the file containing the problem, relative to the project root.
+    pub path: Utf8PathBuf,
+
+    /// The 1-based line of the problem.
+    pub line: usize,
+
+    /// The 1-based column of the problem.
+    pub column: usize,
+
+    pub vex_id: VexId,
+}
+
+impl Finding {
+    /// Compute the fingerprint of this finding in the given file content.
+    ///
+    /// Line numbers and whitespace are not hashed, so the fingerprint survives edits elsewhere
+    /// in the file and reformatting. Instead, the words of the flagged line are hashed either
+    /// side of the column.
+    pub fn fingerprint(&self, content: &str) -> Result<Fingerprint> {
+        let invalid = |reason: String| Error::InvalidFinding {
+            finding: self.to_string(),
+            reason,
+        };
+        let line = content
+            .lines()
+            .nth(self.line.wrapping_sub(1))
+            .ok_or_else(|| invalid(format!("{} has no line {}", self.path, self.line)))?;
+        let indent = line.len() - line.trim_start().len();
+        let offset = self.column.wrapping_sub(1);
+        if offset < indent || offset >= line.len() || !line.is_char_boundary(offset) {
+            return Err(invalid(format!(
+                "line {} has no code at column {}",
+                self.line, self.column
+            )));
+        }
+
+        let mut hasher = Fnv1a::default();
+        hasher.write(Fingerprint::VERSION.as_bytes());
+        hasher.write(self.vex_id.as_ref().as_bytes());
+        hasher.write(PrettyPath::new(&self.path).as_str().as_bytes());
+        let (before, after) = line.split_at(offset);
+        before
+            .split_whitespace()
+            .for_each(|word| hasher.write(word.as_bytes()));
+        hasher.write(b"^");
+        after
+            .split_whitespace()
+            .for_each(|word| hasher.write(word.as_bytes()));
+        Ok(Fingerprint(hasher.finish()))
+    }
+}
+
+impl FromStr for Finding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidFinding {
+            finding: s.into(),
+            reason: reason.into(),
+        };
+        // Paths may contain colons, so the finding is split from the right.
+        let mut parts = s.rsplitn(4, ':');
+        let (Some(vex_id), Some(column), Some(line), Some(path)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("expected path:line:column:check-id"));
+        };
+        if path.is_empty() {
+            return Err(invalid("path is empty"));
+        }
+        Ok(Self {
+            path: path.into(),
+            line: line
+                .parse()
+                .map_err(|_| invalid("line must be a positive integer"))?,
+            column: column
+                .parse()
+                .map_err(|_| invalid("column must be a positive integer"))?,
+            vex_id: VexId::try_from(vex_id.to_owned())?,
+        })
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.path, self.line, self.column, self.vex_id
+        )
+    }
+}
+
+/// A stable identifier for a finding, which tools outside vex may use to refer to it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// Changing how fingerprints are computed requires this to be incremented.
+    const VERSION: &'static str = "vex-fingerprint-1";
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// FNV-1a, used as, unlike the standard library’s hashers, its output never changes.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    /// Hash the given field, separating it from the next.
+    fn write(&mut self, bytes: &[u8]) {
+        bytes.iter().chain([&0]).for_each(|byte| {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        });
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+
+    const SOURCE: &str = indoc! {r#"
+        fn main() {
+            let x = 1;
+            let y = 1;
+        }
+    "#};
+
+    fn finding(raw: &str) -> Finding {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            finding("C:/src/main.rs:2:13:some-check"),
+            Finding {
+                path: "C:/src/main.rs".into(),
+                line: 2,
+                column: 13,
+                vex_id: VexId::try_from("some-check".to_owned()).unwrap(),
+            }
+        );
+        assert_eq!(
+            "src/main.rs:2:some-check"
+                .parse::<Finding>()
+                .unwrap_err()
+                .to_string(),
+            "invalid finding 'src/main.rs:2:some-check': expected path:line:column:check-id"
+        );
+        assert_eq!(
+            "src/main.rs:two:13:some-check"
+                .parse::<Finding>()
+                .unwrap_err()
+                .to_string(),
+            "invalid finding 'src/main.rs:two:13:some-check': line must be a positive integer"
+        );
+    }
+
+    #[test]
+    fn stable() {
+        let fingerprint = finding("src/main.rs:2:13:some-check")
+            .fingerprint(SOURCE)
+            .unwrap();
+        assert_eq!(fingerprint.to_string().len(), 16);
+
+        // Moving and reindenting the line does not change the fingerprint.
+        let moved = format!("// header\n\n{}", SOURCE.replace("    let x", "\tlet  x"));
+        assert_eq!(
+            finding("src/main.rs:4:11:some-check")
+                .fingerprint(&moved)
+                .unwrap(),
+            fingerprint
+        );
+
+        // Other columns, lines, checks and files differ.
+        let other_findings = [
+            "src/main.rs:2:9:some-check",
+            "src/main.rs:3:13:some-check",
+            "src/main.rs:2:13:other-check",
+            "src/lib.rs:2:13:some-check",
+        ];
+        for other in other_findings {
+            assert_ne!(
+                finding(other).fingerprint(SOURCE).unwrap(),
+                fingerprint,
+                "{other}"
+            );
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(
+            finding("src/main.rs:10:1:some-check")
+                .fingerprint(SOURCE)
+                .unwrap_err()
+                .to_string(),
+            "invalid finding 'src/main.rs:10:1:some-check': src/main.rs has no line 10"
+        );
+        assert_eq!(
+            finding("src/main.rs:2:2:some-check")
+                .fingerprint(SOURCE)
+                .unwrap_err()
+                .to_string(),
+            "invalid finding 'src/main.rs:2:2:some-check': line 2 has no code at column 2"
+        );
+    }
+}
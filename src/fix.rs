@@ -0,0 +1,657 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs::{self, Permissions},
+    io::{BufRead, Write},
+    ops::Range,
+};
+
+use allocative::Allocative;
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{info, log_enabled};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use similar::TextDiff;
+use tempfile::NamedTempFile;
+
+use crate::{
+    error::{Error, IOAction},
+    irritation::Irritation,
+    prompt::{Choice, Prompt},
+    result::Result,
+    source_path::PrettyPath,
+};
+
+/// A suggested replacement for a range of a file's content.
+#[derive(Clone, Debug, PartialEq, Eq, Allocative, Deserialise, Serialise)]
+pub struct Fix {
+    pub path: PrettyPath,
+
+    #[allocative(skip)]
+    pub byte_range: Range<usize>,
+
+    /// The content of the range when the fix was suggested, so that fixes to files which have
+    /// since changed are not applied.
+    pub replaced: String,
+
+    pub replacement: String,
+}
+
+impl Ord for Fix {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (
+            &self.path,
+            self.byte_range.start,
+            self.byte_range.end,
+            &self.replacement,
+        )
+            .cmp(&(
+                &other.path,
+                other.byte_range.start,
+                other.byte_range.end,
+                &other.replacement,
+            ))
+    }
+}
+
+impl PartialOrd for Fix {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The content of a file before and after fixes are applied.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FixedFile {
+    pub path: PrettyPath,
+    pub original: String,
+    pub fixed: String,
+}
+
+impl FixedFile {
+    /// Render the changes to this file as a unified diff which `git apply` accepts.
+    pub fn diff(&self) -> String {
+        TextDiff::from_lines(&self.original, &self.fixed)
+            .unified_diff()
+            .header(
+                &format!("a/{}", self.path.as_str()),
+                &format!("b/{}", self.path.as_str()),
+            )
+            .to_string()
+    }
+
+    /// Render only the changed lines of this file, as the hunks of a unified diff with one line
+    /// of context.
+    pub fn hunks(&self) -> String {
+        TextDiff::from_lines(&self.original, &self.fixed)
+            .unified_diff()
+            .context_radius(1)
+            .to_string()
+    }
+}
+
+/// Write the fixed content of each file, keeping its permissions. Either every file is written or
+/// none are: new content is first written beside each file and only then moved into place, and
+/// files already replaced are restored if a later move fails. If `backup` is set, the original
+/// content of each file is first kept in a `.orig` file beside it, which is removed again if the
+/// files cannot all be written.
+pub fn write_files(project_root: &Utf8Path, fixed_files: &[FixedFile], backup: bool) -> Result<()> {
+    let staged = fixed_files
+        .iter()
+        .map(|fixed_file| {
+            let path = project_root.join(fixed_file.path.as_str());
+            let permissions = fs::metadata(&path)
+                .ok()
+                .map(|metadata| metadata.permissions());
+            let staged = stage(&path, &fixed_file.fixed, permissions.clone())?;
+            Ok((fixed_file, path, permissions, staged))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut backups = Vec::with_capacity(if backup { staged.len() } else { 0 });
+    if backup {
+        for (fixed_file, path, permissions, _) in &staged {
+            let backup_path = Utf8PathBuf::from(format!("{path}.orig"));
+            let backed_up = stage(&backup_path, &fixed_file.original, permissions.clone())
+                .and_then(|staged_backup| persist(staged_backup, &backup_path));
+            if let Err(err) = backed_up {
+                remove_backups(&backups);
+                return Err(err);
+            }
+            backups.push(backup_path);
+        }
+    }
+
+    // Staged files which are never moved into place are removed when dropped.
+    let mut replaced: Vec<(&FixedFile, Utf8PathBuf, Option<Permissions>)> =
+        Vec::with_capacity(staged.len());
+    for (fixed_file, path, permissions, staged) in staged {
+        if let Err(err) = persist(staged, &path) {
+            for (fixed_file, path, permissions) in replaced.into_iter().rev() {
+                let restored = stage(&path, &fixed_file.original, permissions)
+                    .and_then(|staged| persist(staged, &path));
+                if let Err(restore_err) = restored {
+                    log::warn!("{restore_err}");
+                }
+            }
+            remove_backups(&backups);
+            return Err(err);
+        }
+        replaced.push((fixed_file, path, permissions));
+    }
+    Ok(())
+}
+
+fn remove_backups(backups: &[Utf8PathBuf]) {
+    for backup in backups {
+        if let Err(err) = fs::remove_file(backup) {
+            log::warn!("cannot remove {backup}: {err}");
+        }
+    }
+}
+
+/// Write the given content to a new temporary file beside the given path.
+fn stage(
+    path: &Utf8Path,
+    content: &str,
+    permissions: Option<Permissions>,
+) -> Result<NamedTempFile> {
+    let io_error = |cause| Error::IO {
+        path: PrettyPath::new(path),
+        action: IOAction::Write,
+        cause,
+    };
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_str().is_empty() => parent,
+        _ => Utf8Path::new("."),
+    };
+    let mut staged = tempfile::Builder::new()
+        .prefix(&format!(".{}.", path.file_name().unwrap_or_default()))
+        .suffix(".tmp")
+        .tempfile_in(dir)
+        .map_err(io_error)?;
+    staged.write_all(content.as_bytes()).map_err(io_error)?;
+    if let Some(permissions) = permissions {
+        staged
+            .as_file()
+            .set_permissions(permissions)
+            .map_err(io_error)?;
+    }
+    staged.as_file().sync_all().map_err(io_error)?;
+    Ok(staged)
+}
+
+/// Atomically replace the file at the given path with a staged file.
+fn persist(staged: NamedTempFile, path: &Utf8Path) -> Result<()> {
+    staged.persist(path).map_err(|err| Error::IO {
+        path: PrettyPath::new(path),
+        action: IOAction::Write,
+        cause: err.error,
+    })?;
+    Ok(())
+}
+
+/// Compute the result of applying the given fixes. Files without fixes are omitted.
+pub fn fix_files<'a>(
+    project_root: &Utf8Path,
+    fixes: impl IntoIterator<Item = &'a Fix>,
+) -> Result<Vec<FixedFile>> {
+    let mut fixes_by_path: BTreeMap<&PrettyPath, Vec<&Fix>> = BTreeMap::new();
+    fixes
+        .into_iter()
+        .for_each(|fix| fixes_by_path.entry(&fix.path).or_default().push(fix));
+
+    fixes_by_path
+        .into_iter()
+        .map(|(path, fixes)| {
+            let file_path = project_root.join(path.as_str());
+            let original = fs::read_to_string(&file_path).map_err(|cause| Error::IO {
+                path: path.clone(),
+                action: IOAction::Read,
+                cause,
+            })?;
+            let fixed = apply(&original, fixes);
+            Ok(FixedFile {
+                path: path.clone(),
+                original,
+                fixed,
+            })
+        })
+        .filter(|fixed_file| {
+            fixed_file
+                .as_ref()
+                .map_or(true, |fixed_file| fixed_file.original != fixed_file.fixed)
+        })
+        .collect()
+}
+
+/// Render the change which the given fix would make, or `None` if it would change nothing.
+pub fn preview(project_root: &Utf8Path, fix: &Fix) -> Result<Option<String>> {
+    let [fixed_file] = &fix_files(project_root, [fix])?[..] else {
+        return Ok(None);
+    };
+    Ok(Some(fixed_file.hunks()))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Answer {
+    Accept,
+    Skip,
+    AcceptAllForCheck,
+    Quit,
+}
+
+/// Show each suggested fix as a diff and ask whether it should be applied.
+pub fn select_interactively<'a>(
+    project_root: &Utf8Path,
+    irritations: &'a [Irritation],
+    prompt: &mut Prompt<impl BufRead, impl Write>,
+) -> Result<Vec<&'a Fix>> {
+    const CHOICES: &[Choice<Answer>] = &[
+        Choice {
+            key: 'y',
+            help: "apply this fix",
+            value: Answer::Accept,
+        },
+        Choice {
+            key: 'n',
+            help: "do not apply this fix",
+            value: Answer::Skip,
+        },
+        Choice {
+            key: 'a',
+            help: "apply this fix and all later fixes from this check",
+            value: Answer::AcceptAllForCheck,
+        },
+        Choice {
+            key: 'q',
+            help: "quit; do not apply this fix or any later ones",
+            value: Answer::Quit,
+        },
+    ];
+
+    let mut accepted = vec![];
+    let mut accepted_checks = BTreeSet::new();
+    let fixable = irritations
+        .iter()
+        .filter_map(|irritation| Some((irritation, irritation.fix()?)));
+    for (irritation, fix) in fixable {
+        if accepted_checks.contains(irritation.vex_id()) {
+            accepted.push(fix);
+            continue;
+        }
+
+        let [fixed_file] = &fix_files(project_root, [fix])?[..] else {
+            continue;
+        };
+        write!(prompt.output(), "{irritation}\n{}", fixed_file.diff()).map_err(|cause| {
+            Error::IO {
+                path: PrettyPath::from("stdout"),
+                action: IOAction::Write,
+                cause,
+            }
+        })?;
+        match prompt.choose("apply this fix", CHOICES)? {
+            Some(Answer::Accept) => accepted.push(fix),
+            Some(Answer::Skip) => {}
+            Some(Answer::AcceptAllForCheck) => {
+                accepted.push(fix);
+                accepted_checks.insert(irritation.vex_id());
+            }
+            Some(Answer::Quit) | None => break,
+        }
+    }
+    Ok(accepted)
+}
+
+/// Render the given fixed files as a single patch.
+pub fn patch(fixed_files: &[FixedFile]) -> String {
+    fixed_files
+        .iter()
+        .fold(String::new(), |mut patch, fixed_file| {
+            write!(patch, "{}", fixed_file.diff()).expect("internal error: cannot write patch");
+            patch
+        })
+}
+
+/// Apply fixes to the given content. Where fixes overlap, the earliest is applied and the others
+/// are skipped, as are fixes whose range no longer holds the content it did when they were
+/// suggested.
+fn apply<'a>(content: &str, fixes: impl IntoIterator<Item = &'a Fix>) -> String {
+    let mut fixes: Vec<_> = fixes.into_iter().collect();
+    fixes.sort();
+    fixes.dedup();
+
+    let mut fixed = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for fix in fixes {
+        let Range { start, end } = fix.byte_range;
+        if content.get(start..end) != Some(fix.replaced.as_str()) {
+            log::warn!(
+                "skipping fix in {}: file changed since it was scanned",
+                fix.path
+            );
+            continue;
+        }
+        if start < cursor {
+            if log_enabled!(log::Level::Info) {
+                info!("skipping overlapping fix in {}", fix.path);
+            }
+            continue;
+        }
+        fixed.push_str(&content[cursor..start]);
+        fixed.push_str(&fix.replacement);
+        cursor = end;
+    }
+    fixed.push_str(&content[cursor..]);
+    fixed
+}
+
+#[cfg(test)]
+mod test {
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn apply_fixes() {
+        let content = "let x = 1 + 2;";
+        let fix = |byte_range: Range<usize>, replacement: &str| Fix {
+            path: PrettyPath::new(Utf8Path::new("main.rs")),
+            replaced: content[byte_range.clone()].into(),
+            byte_range,
+            replacement: replacement.into(),
+        };
+        assert_eq!(apply(content, &[]), content);
+        assert_eq!(
+            apply(content, &[fix(12..13, "3"), fix(8..9, "four")]),
+            "let x = four + 3;"
+        );
+        assert_eq!(
+            apply(content, &[fix(8..13, "3"), fix(8..9, "four")]),
+            "let x = four + 2;"
+        );
+        assert_eq!(
+            apply(content, &[fix(4..5, "y"), fix(4..5, "y")]),
+            "let y = 1 + 2;"
+        );
+
+        // Fixes suggested for content which has since changed are skipped.
+        assert_eq!(
+            apply("let é = 1 + 2;", &[fix(0..3, "var"), fix(4..5, "y")]),
+            "var é = 1 + 2;"
+        );
+        assert_eq!(apply("let x", &[fix(8..13, "3")]), "let x");
+    }
+
+    #[test]
+    fn patches() {
+        let irritations = VexTest::new("patches")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(binary_expression) @bin_expr', on_match)
+
+                    def on_match(event):
+                        vex.warn(
+                            'test',
+                            'expression can be simplified',
+                            at=event.captures['bin_expr'],
+                            fix='3',
+                        )
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + 2;
+                    }
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert!(irritations[0].to_string().contains("a fix is available"));
+
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::create_dir(root_path.join("src")).unwrap();
+        fs::write(
+            root_path.join("src/main.rs"),
+            indoc! {r#"
+                fn main() {
+                    let x = 1 + 2;
+                }
+            "#},
+        )
+        .unwrap();
+        let fixed_files =
+            fix_files(&root_path, irritations.iter().flat_map(Irritation::fix)).unwrap();
+        assert_eq!(
+            patch(&fixed_files),
+            indoc! {r#"
+                --- a/src/main.rs
+                +++ b/src/main.rs
+                @@ -1,3 +1,3 @@
+                 fn main() {
+                -    let x = 1 + 2;
+                +    let x = 3;
+                 }
+            "#}
+        );
+        assert_eq!(
+            preview(&root_path, irritations[0].fix().unwrap())
+                .unwrap()
+                .unwrap(),
+            indoc! {r#"
+                @@ -1,3 +1,3 @@
+                 fn main() {
+                -    let x = 1 + 2;
+                +    let x = 3;
+                 }
+            "#}
+        );
+
+        write_files(&root_path, &fixed_files, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(root_path.join("src/main.rs")).unwrap(),
+            indoc! {r#"
+                fn main() {
+                    let x = 3;
+                }
+            "#}
+        );
+    }
+
+    #[test]
+    fn writes() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::create_dir(root_path.join("src")).unwrap();
+        fs::write(root_path.join("src/main.rs"), "let x = 1;").unwrap();
+        fs::write(root_path.join("src/lib.rs"), "let y = 2;").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(root_path.join("src/main.rs"), Permissions::from_mode(0o751))
+                .unwrap();
+        }
+        let fixed_file = |path: &str, original: &str, fixed: &str| FixedFile {
+            path: PrettyPath::new(Utf8Path::new(path)),
+            original: original.into(),
+            fixed: fixed.into(),
+        };
+        let listing = || {
+            let mut listing: Vec<_> = fs::read_dir(root_path.join("src"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            listing.sort();
+            listing
+        };
+
+        write_files(
+            &root_path,
+            &[
+                fixed_file("src/lib.rs", "let y = 2;", "let y = 0;"),
+                fixed_file("src/main.rs", "let x = 1;", "let x = 0;"),
+            ],
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(root_path.join("src/main.rs")).unwrap(),
+            "let x = 0;"
+        );
+        assert_eq!(
+            fs::read_to_string(root_path.join("src/main.rs.orig")).unwrap(),
+            "let x = 1;"
+        );
+        assert_eq!(
+            listing(),
+            ["lib.rs", "lib.rs.orig", "main.rs", "main.rs.orig"]
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path| {
+                fs::metadata(root_path.join(path))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+            };
+            assert_eq!(mode("src/main.rs") & 0o777, 0o751);
+            assert_eq!(mode("src/main.rs.orig") & 0o777, 0o751);
+        }
+
+        // A directory cannot be replaced by a file, so the batch is rolled back.
+        fs::create_dir_all(root_path.join("src/z/nested")).unwrap();
+        let err = write_files(
+            &root_path,
+            &[
+                fixed_file("src/main.rs", "let x = 0;", "let x = 3;"),
+                fixed_file("src/z", "", "oh no"),
+            ],
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("cannot write "), "{err}");
+        assert_eq!(
+            fs::read_to_string(root_path.join("src/main.rs")).unwrap(),
+            "let x = 0;"
+        );
+        assert_eq!(
+            listing(),
+            ["lib.rs", "lib.rs.orig", "main.rs", "main.rs.orig", "z"]
+        );
+
+        // Backups made for a batch which is rolled back are removed.
+        fs::remove_file(root_path.join("src/lib.rs.orig")).unwrap();
+        fs::remove_file(root_path.join("src/main.rs.orig")).unwrap();
+        write_files(
+            &root_path,
+            &[
+                fixed_file("src/main.rs", "let x = 0;", "let x = 3;"),
+                fixed_file("src/z", "", "oh no"),
+            ],
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(listing(), ["lib.rs", "main.rs", "z"]);
+
+        // Nothing is written if any new content cannot be staged.
+        write_files(
+            &root_path,
+            &[
+                fixed_file("src/main.rs", "let x = 0;", "let x = 4;"),
+                fixed_file("src/missing/file.rs", "", "oh no"),
+            ],
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            fs::read_to_string(root_path.join("src/main.rs")).unwrap(),
+            "let x = 0;"
+        );
+    }
+
+    #[test]
+    fn interactive() {
+        const SOURCE: &str = "fn main() { let x = 1 + 2 + 3; }";
+        let irritations = VexTest::new("interactive")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        vex.warn('test', 'literal found', at=event.captures['lit'], fix='0')
+                "#},
+            )
+            .with_source_file("src/main.rs", SOURCE)
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 3);
+
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::create_dir(root_path.join("src")).unwrap();
+        fs::write(root_path.join("src/main.rs"), SOURCE).unwrap();
+        let fixed_content = |input: &str| {
+            let mut output = vec![];
+            let mut prompt = Prompt::new(input.as_bytes(), &mut output);
+            let fixes = select_interactively(&root_path, &irritations, &mut prompt).unwrap();
+            let fixed_files = fix_files(&root_path, fixes).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.contains("-fn main() { let x = 1 + 2 + 3; }"));
+            fixed_files
+                .into_iter()
+                .next()
+                .map(|fixed_file| fixed_file.fixed)
+        };
+
+        assert_eq!(
+            fixed_content("y\nn\ny\n").as_deref(),
+            Some("fn main() { let x = 0 + 2 + 0; }")
+        );
+        assert_eq!(
+            fixed_content("n\na\n").as_deref(),
+            Some("fn main() { let x = 1 + 0 + 0; }")
+        );
+        assert_eq!(
+            fixed_content("y\nq\n").as_deref(),
+            Some("fn main() { let x = 0 + 2 + 3; }")
+        );
+        assert_eq!(fixed_content("n\n").as_deref(), None);
+    }
+
+    #[test]
+    fn fix_requires_node() {
+        VexTest::new("fix-without-node")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('test', 'oh no!', fix='oh yes!')
+                "#},
+            )
+            .returns_error("cannot apply `fix` without an `at` argument containing a Node");
+    }
+}
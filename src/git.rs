@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{error::Error, result::Result};
+
+/// List the files under `dir` which differ from `base_ref`, including untracked files. Returned
+/// paths are relative to `dir`. Deleted files are not included. If `staged` is set, only changes
+/// staged in the index are considered, so untracked files are not included.
+pub fn changed_files(
+    dir: &Utf8Path,
+    base_ref: &str,
+    staged: bool,
+) -> Result<BTreeSet<Utf8PathBuf>> {
+    // Paths are separated by NULs as otherwise git quotes those with unusual characters.
+    let mut diff_args = vec!["diff", "--name-only", "-z", "--relative", "--diff-filter=d"];
+    if staged {
+        diff_args.push("--cached");
+    }
+    diff_args.extend([base_ref, "--"]);
+    let diffed = git(dir, &diff_args)?;
+    let untracked = if !staged {
+        git(dir, &["ls-files", "--others", "--exclude-standard", "-z"])?
+    } else {
+        String::new()
+    };
+    Ok(diffed
+        .split('\0')
+        .chain(untracked.split('\0'))
+        .filter(|path| !path.is_empty())
+        .map(Utf8PathBuf::from)
+        .collect())
+}
+
+/// Read the content staged in the index for each of the given files, whose paths are relative to
+/// `dir`. Symlinks, submodules and files with unresolved conflicts are omitted.
+pub fn staged_contents(
+    dir: &Utf8Path,
+    paths: &BTreeSet<Utf8PathBuf>,
+) -> Result<BTreeMap<Utf8PathBuf, Vec<u8>>> {
+    const REGULAR_FILE_MODES: [&str; 2] = ["100644", "100755"];
+
+    let index = git(dir, &["ls-files", "--stage", "-z"])?;
+    let (paths, blob_ids): (Vec<_>, Vec<_>) = index
+        .split('\0')
+        .filter_map(|entry| {
+            let (info, path) = entry.split_once('\t')?;
+            let mut info = info.split(' ');
+            let (mode, blob_id, stage) = (info.next()?, info.next()?, info.next()?);
+            let path = Utf8Path::new(path);
+            (REGULAR_FILE_MODES.contains(&mode) && stage == "0" && paths.contains(path))
+                .then(|| (path.to_owned(), blob_id))
+        })
+        .unzip();
+    let blobs = read_blobs(dir, &blob_ids)?;
+    Ok(paths.into_iter().zip(blobs).collect())
+}
+
+/// Read the content of each of the given blobs in a single batch.
+fn read_blobs(dir: &Utf8Path, blob_ids: &[&str]) -> Result<Vec<Vec<u8>>> {
+    let git_error = |cause: std::io::Error| Error::Git(cause.to_string());
+
+    let mut child = Command::new("git")
+        .args(["cat-file", "--batch"])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(git_error)?;
+    let mut stdin = child.stdin.take().expect("internal error: stdin not piped");
+    let input = blob_ids.iter().fold(String::new(), |mut input, blob_id| {
+        writeln!(input, "{blob_id}").expect("internal error: cannot write blob id");
+        input
+    });
+    // Output is read while input is written, so neither pipe can fill and block git.
+    let output = thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output();
+        writer
+            .join()
+            .expect("internal error: git writer panicked")
+            .and(output)
+    })
+    .map_err(git_error)?;
+    if !output.status.success() {
+        return Err(Error::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    // Each blob is written as `<id> <type> <size>\n<content>\n`.
+    let mut remaining = output.stdout.as_slice();
+    blob_ids
+        .iter()
+        .map(|blob_id| {
+            let malformed = || Error::Git(format!("cannot read blob {blob_id}"));
+            let header_len = remaining
+                .iter()
+                .position(|b| *b == b'\n')
+                .ok_or_else(malformed)?;
+            let header = String::from_utf8_lossy(&remaining[..header_len]);
+            let size: usize = header
+                .rsplit(' ')
+                .next()
+                .and_then(|size| size.parse().ok())
+                .ok_or_else(malformed)?;
+            let content_start = header_len + 1;
+            let content = remaining
+                .get(content_start..content_start + size)
+                .ok_or_else(malformed)?
+                .to_vec();
+            remaining = remaining
+                .get(content_start + size + 1..)
+                .unwrap_or_default();
+            Ok(content)
+        })
+        .collect()
+}
+
+/// The path of the given hook in the repository which contains `dir`. The hook need not exist.
+pub fn hook_path(dir: &Utf8Path, hook: &str) -> Result<Utf8PathBuf> {
+    let path = git(dir, &["rev-parse", "--git-path", &format!("hooks/{hook}")])?;
+    Ok(dir.join(path.trim()))
+}
+
+/// The path of `dir` relative to the root of the repository which contains it, ending with `/`
+/// unless empty.
+pub fn prefix(dir: &Utf8Path) -> Result<String> {
+    Ok(git(dir, &["rev-parse", "--show-prefix"])?.trim().to_owned())
+}
+
+fn git(dir: &Utf8Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|cause| Error::Git(cause.to_string()))?;
+    if !output.status.success() {
+        return Err(Error::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn changed() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let run = |args: &[&str]| git(&root_path, args).unwrap();
+
+        run(&["init", "--quiet"]);
+        run(&["config", "user.email", "vex@example.com"]);
+        run(&["config", "user.name", "vex"]);
+        fs::create_dir(root_path.join("src")).unwrap();
+        for file in [
+            "src/unchanged.rs",
+            "src/modified.rs",
+            "src/deleted.rs",
+            "src/naïve.rs",
+        ] {
+            fs::write(root_path.join(file), "fn main() {}").unwrap();
+        }
+        run(&["add", "."]);
+        run(&["commit", "--quiet", "-m", "initial"]);
+
+        fs::write(root_path.join("src/modified.rs"), "fn main() { 1 }").unwrap();
+        fs::write(root_path.join("src/naïve.rs"), "fn main() { 1 }").unwrap();
+        fs::write(root_path.join("src/untracked.rs"), "fn main() {}").unwrap();
+        fs::write(root_path.join("src/\"quoted\" name.rs"), "fn main() {}").unwrap();
+        fs::remove_file(root_path.join("src/deleted.rs")).unwrap();
+
+        // Paths which git would otherwise quote are returned as they are.
+        assert_eq!(
+            changed_files(&root_path, "HEAD", false).unwrap(),
+            BTreeSet::from_iter([
+                "src/\"quoted\" name.rs".into(),
+                "src/modified.rs".into(),
+                "src/naïve.rs".into(),
+                "src/untracked.rs".into(),
+            ]),
+        );
+        assert_eq!(
+            changed_files(&root_path.join("src"), "HEAD", false).unwrap(),
+            BTreeSet::from_iter([
+                "\"quoted\" name.rs".into(),
+                "modified.rs".into(),
+                "naïve.rs".into(),
+                "untracked.rs".into(),
+            ]),
+        );
+
+        changed_files(&root_path, "no-such-ref", false).unwrap_err();
+    }
+
+    #[test]
+    fn staged() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let run = |args: &[&str]| git(&root_path, args).unwrap();
+
+        run(&["init", "--quiet"]);
+        run(&["config", "user.email", "vex@example.com"]);
+        run(&["config", "user.name", "vex"]);
+        fs::create_dir(root_path.join("src")).unwrap();
+        for file in ["src/unchanged.rs", "src/staged.rs", "src/unstaged.rs"] {
+            fs::write(root_path.join(file), "fn main() {}").unwrap();
+        }
+        run(&["add", "."]);
+        run(&["commit", "--quiet", "-m", "initial"]);
+
+        fs::write(root_path.join("src/staged.rs"), "fn main() { 1 }").unwrap();
+        fs::write(root_path.join("src/new.rs"), "fn new() {}").unwrap();
+        run(&["add", "src/staged.rs", "src/new.rs"]);
+        fs::write(root_path.join("src/staged.rs"), "fn main() { 2 }").unwrap();
+        fs::write(root_path.join("src/unstaged.rs"), "fn main() { 3 }").unwrap();
+        fs::write(root_path.join("src/untracked.rs"), "fn main() {}").unwrap();
+
+        let changed = changed_files(&root_path.join("src"), "HEAD", true).unwrap();
+        assert_eq!(
+            changed,
+            BTreeSet::from_iter(["new.rs".into(), "staged.rs".into()]),
+        );
+        assert_eq!(
+            staged_contents(&root_path.join("src"), &changed).unwrap(),
+            BTreeMap::from_iter([
+                ("new.rs".into(), b"fn new() {}".to_vec()),
+                ("staged.rs".into(), b"fn main() { 1 }".to_vec()),
+            ]),
+        );
+
+        assert_eq!(prefix(&root_path).unwrap(), "");
+        assert_eq!(prefix(&root_path.join("src")).unwrap(), "src/");
+        let hook_path = hook_path(&root_path.join("src"), "pre-commit").unwrap();
+        assert!(hook_path.ends_with(".git/hooks/pre-commit"), "{hook_path}");
+    }
+}
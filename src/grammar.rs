@@ -0,0 +1,498 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    mem,
+    sync::{Mutex, OnceLock, RwLock},
+};
+
+use allocative::Allocative;
+use camino::{Utf8Path, Utf8PathBuf};
+use dupe::Dupe;
+use libloading::{Library, Symbol};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use tempfile::NamedTempFile;
+use tree_sitter::{Language, Query, LANGUAGE_VERSION, MIN_COMPATIBLE_LANGUAGE_VERSION};
+
+use crate::{
+    error::Error, lock::Lockfile, result::Result, supported_language::SupportedLanguage,
+    trigger::RawFilePattern,
+};
+
+/// A tree-sitter grammar declared in the manifest, to be loaded from a shared library.
+#[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct GrammarConfig {
+    pub name: String,
+
+    /// Path to the shared library, relative to the project root.
+    pub library: Utf8PathBuf,
+
+    /// The function which returns the grammar, by default `tree_sitter_<name>`.
+    #[serde(default)]
+    pub symbol: Option<String>,
+
+    #[serde(default)]
+    pub use_for: Vec<RawFilePattern<String>>,
+
+    /// The prefix which starts a single-line comment, used to insert suppressions.
+    #[serde(default)]
+    pub line_comment: Option<String>,
+}
+
+impl GrammarConfig {
+    /// Read only the grammars declared in the given manifest content, which can be done before
+    /// the languages they define are known.
+    pub fn all_in(raw_manifest: &str) -> Result<Vec<Self>> {
+        #[derive(Deserialise)]
+        struct GrammarsOnly {
+            #[serde(default)]
+            grammars: Vec<GrammarConfig>,
+        }
+        let GrammarsOnly { grammars } = toml_edit::de::from_str(raw_manifest)?;
+        Ok(grammars)
+    }
+}
+
+/// A grammar which was not built into vex.
+#[derive(Debug)]
+pub struct Grammar {
+    pub name: &'static str,
+    pub language: Language,
+    pub line_comment: Option<&'static str>,
+    ignore_query: OnceLock<Option<Query>>,
+}
+
+impl Grammar {
+    /// A query for `vex:ignore` markers, if this grammar has `comment` nodes.
+    pub fn ignore_query(&self) -> Option<&Query> {
+        self.ignore_query
+            .get_or_init(|| {
+                let raw = r#"
+                    (
+                        (comment) @marker (#match? @marker "^\\W*vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#;
+                Query::new(&self.language, raw).ok()
+            })
+            .as_ref()
+    }
+}
+
+/// An index into the process-wide grammar registry.
+#[derive(Copy, Clone, Debug, Dupe, PartialEq, Eq, Hash, PartialOrd, Ord, Allocative)]
+pub struct GrammarId(usize);
+
+impl GrammarId {
+    pub fn grammar(self) -> &'static Grammar {
+        GRAMMARS.read().expect("failed to lock GRAMMARS")[self.0]
+    }
+
+    pub fn iter() -> impl Iterator<Item = Self> {
+        let num_grammars = GRAMMARS.read().expect("failed to lock GRAMMARS").len();
+        (0..num_grammars).map(Self)
+    }
+
+    pub fn lookup(name: &str) -> Option<Self> {
+        GRAMMARS
+            .read()
+            .expect("failed to lock GRAMMARS")
+            .iter()
+            .position(|grammar| grammar.name == name)
+            .map(Self)
+    }
+}
+
+// Grammars are never unloaded, as parse trees and queries may refer to them at any time.
+static GRAMMARS: RwLock<Vec<&'static Grammar>> = RwLock::new(Vec::new());
+
+/// The grammars loaded from libraries, keyed by name and the SHA-256 hash of the library, so that
+/// loading the same project again reuses them rather than opening the library once more.
+static LOADED: Mutex<BTreeMap<(String, String), &'static Grammar>> = Mutex::new(BTreeMap::new());
+
+/// Load each of the given grammars, making them available as `SupportedLanguage`s. As loading a
+/// library runs its code, only libraries whose hashes match those in `vex.lock` are loaded.
+pub fn load_all(project_root: &Utf8Path, configs: &[GrammarConfig]) -> Result<()> {
+    if configs.is_empty() {
+        return Ok(());
+    }
+    let lockfile = match Lockfile::read(project_root) {
+        Ok(lockfile) => Some(lockfile),
+        Err(Error::LockfileNotFound) => None,
+        Err(err) => return Err(err),
+    };
+    configs
+        .iter()
+        .try_for_each(|config| load(project_root, config, lockfile.as_ref()).map(|_| ()))
+}
+
+fn load(
+    project_root: &Utf8Path,
+    config: &GrammarConfig,
+    lockfile: Option<&Lockfile>,
+) -> Result<GrammarId> {
+    let error = |reason: String| Error::Grammar {
+        name: config.name.clone(),
+        reason,
+    };
+
+    let library_path = project_root.join(&config.library);
+    let content = fs::read(&library_path)
+        .map_err(|err| error(format!("cannot read {library_path}: {err}")))?;
+    let sha256 = Lockfile::sha256(&content);
+    match lockfile.and_then(|lockfile| lockfile.locked_sha256(&config.name, &config.library)) {
+        Some(locked) if locked == sha256 => {}
+        Some(_) => {
+            return Err(error(format!(
+                "{} has changed since it was locked; to trust it, run `vex sync`",
+                config.library
+            )))
+        }
+        None => {
+            return Err(error(format!(
+                "{} is not locked in {}; to trust it, run `vex sync`",
+                config.library,
+                Lockfile::FILE_NAME
+            )))
+        }
+    }
+
+    let mut loaded = LOADED.lock().expect("failed to lock LOADED");
+    let key = (config.name.clone(), sha256);
+    if let Some(grammar) = loaded.get(&key) {
+        if grammar.line_comment == config.line_comment.as_deref() {
+            return Ok(install(grammar));
+        }
+        let id = register(
+            &config.name,
+            grammar.language.clone(),
+            config.line_comment.as_deref(),
+        )?;
+        loaded.insert(key, id.grammar());
+        return Ok(id);
+    }
+
+    let symbol = config
+        .symbol
+        .clone()
+        .unwrap_or_else(|| format!("tree_sitter_{}", config.name.replace('-', "_")));
+    // The bytes which were checked are loaded from a private copy, so the library cannot be
+    // swapped between being checked and being opened. Only someone able to replace files in the
+    // directory holding the copy could still do so, and they could equally have replaced the
+    // library before it was locked.
+    let checked_copy = private_copy(&library_path, &content)
+        .map_err(|err| error(format!("cannot copy {library_path}: {err}")))?;
+    let language = unsafe {
+        let library = Library::new(checked_copy.path())
+            .map_err(|err| error(format!("cannot open {library_path}: {err}")))?;
+        let constructor: Symbol<'_, unsafe extern "C" fn() -> Language> = library
+            .get(symbol.as_bytes())
+            .map_err(|err| error(format!("cannot find {symbol}: {err}")))?;
+        let language = constructor();
+        mem::forget(library);
+        language
+    };
+    let version = language.version();
+    if !(MIN_COMPATIBLE_LANGUAGE_VERSION..=LANGUAGE_VERSION).contains(&version) {
+        return Err(error(format!(
+            "incompatible version {version}, expected {MIN_COMPATIBLE_LANGUAGE_VERSION} to {LANGUAGE_VERSION}"
+        )));
+    }
+
+    let id = register(&config.name, language, config.line_comment.as_deref())?;
+    loaded.insert(key, id.grammar());
+    Ok(id)
+}
+
+/// Write the given library content to a new file which only this user may change. Where possible,
+/// the copy is put beside the library, so that it can be mapped as executable wherever the
+/// library could. As Windows cannot remove a library while it is loaded, copies are instead left
+/// in its temporary directory.
+fn private_copy(library_path: &Utf8Path, content: &[u8]) -> io::Result<NamedTempFile> {
+    let prefix = format!(".{}.", library_path.file_stem().unwrap_or_default());
+    let suffix = library_path
+        .extension()
+        .map(|extension| format!(".{extension}"))
+        .unwrap_or_default();
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix).suffix(&suffix);
+    let mut copy = if cfg!(windows) {
+        builder.tempfile()?
+    } else {
+        let dir = match library_path.parent() {
+            Some(parent) if !parent.as_str().is_empty() => parent,
+            _ => Utf8Path::new("."),
+        };
+        builder.tempfile_in(dir)?
+    };
+    copy.write_all(content)?;
+    copy.flush()?;
+    Ok(copy)
+}
+
+/// Add a grammar to the registry. A grammar with the same name replaces any previous one.
+pub fn register(name: &str, language: Language, line_comment: Option<&str>) -> Result<GrammarId> {
+    if SupportedLanguage::BUILTIN
+        .iter()
+        .any(|builtin| builtin.name() == name)
+    {
+        return Err(Error::Grammar {
+            name: name.into(),
+            reason: "name is already used by a built-in language".into(),
+        });
+    }
+
+    Ok(install(Box::leak(Box::new(Grammar {
+        name: String::leak(name.into()),
+        language,
+        line_comment: line_comment.map(|line_comment| String::leak(line_comment.into()) as &str),
+        ignore_query: OnceLock::new(),
+    }))))
+}
+
+/// Put a grammar into the registry, replacing any previous one with the same name.
+fn install(grammar: &'static Grammar) -> GrammarId {
+    let mut grammars = GRAMMARS.write().expect("failed to lock GRAMMARS");
+    match grammars
+        .iter()
+        .position(|existing| existing.name == grammar.name)
+    {
+        Some(index) => {
+            grammars[index] = grammar;
+            GrammarId(index)
+        }
+        None => {
+            grammars.push(grammar);
+            GrammarId(grammars.len() - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use indoc::indoc;
+
+    use crate::context::Context;
+
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn registered_grammars() {
+        let id = register("rust-like", tree_sitter_rust::language(), Some("//")).unwrap();
+        let language = SupportedLanguage::Dynamic(id);
+        assert_eq!(language.name(), "rust-like");
+        assert_eq!(language.to_string(), "rust-like");
+        assert_eq!("rust-like".parse::<SupportedLanguage>().unwrap(), language);
+        assert_eq!(language.line_comment(), Some("//"));
+        assert!(
+            language.ignore_query().is_none(),
+            "rust has no plain `comment` nodes"
+        );
+        assert!(SupportedLanguage::iter().any(|lang| lang == language));
+        assert_eq!(serde_json::to_string(&language).unwrap(), r#""rust-like""#);
+
+        assert_eq!(
+            register("rust", tree_sitter_rust::language(), None)
+                .unwrap_err()
+                .to_string(),
+            "cannot load grammar 'rust': name is already used by a built-in language"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lang-go")]
+    fn search_registered_grammar() {
+        let id = register("go-like", tree_sitter_go::language(), Some("//")).unwrap();
+        assert!(SupportedLanguage::Dynamic(id).ignore_query().is_some());
+        let irritations = crate::vextest::VexTest::new("registered-grammar")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [languages.go-like]
+                use-for = ["*.golike"]
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('go-like', '(function_declaration name: (identifier) @name)', on_match)
+
+                    def on_match(event):
+                        vex.warn('test', 'found function', at=event.captures['name'])
+                "#},
+            )
+            .with_source_file("main.golike", "package main\n\nfunc main() {}\n")
+            .with_source_file("main.go", "package main\n\nfunc other() {}\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].path().unwrap().as_str(), "main.golike");
+    }
+
+    #[test]
+    fn load_errors() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(
+            root_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[grammars]]
+                name = "missing"
+                library = "grammars/libtree-sitter-missing.so"
+                use-for = ["*.missing"]
+            "#},
+        )
+        .unwrap();
+        let err = Context::acquire(&root_path).unwrap_err().to_string();
+        assert!(
+            err.starts_with("cannot load grammar 'missing': cannot read "),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn unlocked_libraries_not_loaded() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(
+            root_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[grammars]]
+                name = "fake"
+                library = "fake.so"
+            "#},
+        )
+        .unwrap();
+        fs::write(root_path.join("fake.so"), "not really a library").unwrap();
+        assert_eq!(
+            Context::acquire(&root_path).unwrap_err().to_string(),
+            "cannot load grammar 'fake': fake.so is not locked in vex.lock; to trust it, run `vex sync`"
+        );
+
+        let grammars = [GrammarConfig {
+            name: "fake".into(),
+            library: "fake.so".into(),
+            symbol: None,
+            use_for: vec![],
+            line_comment: None,
+        }];
+        Lockfile::resolve(&root_path, &grammars)
+            .unwrap()
+            .write(&root_path)
+            .unwrap();
+        let err = Context::acquire(&root_path).unwrap_err().to_string();
+        assert!(
+            err.starts_with("cannot load grammar 'fake': cannot open "),
+            "unexpected error: {err}"
+        );
+        // The checked copy which was opened is removed.
+        let mut files = fs::read_dir(&root_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["fake.so", "vex.lock", "vex.toml"]);
+
+        fs::write(root_path.join("fake.so"), "a different library").unwrap();
+        assert_eq!(
+            Context::acquire(&root_path).unwrap_err().to_string(),
+            "cannot load grammar 'fake': fake.so has changed since it was locked; to trust it, run `vex sync`"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn loaded_libraries_reused() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(
+            root_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[grammars]]
+                name = "cached"
+                library = "cached.so"
+                line-comment = "//"
+            "#},
+        )
+        .unwrap();
+        let content = "not really a library";
+        fs::write(root_path.join("cached.so"), content).unwrap();
+        let grammars =
+            GrammarConfig::all_in(&fs::read_to_string(root_path.join("vex.toml")).unwrap())
+                .unwrap();
+        Lockfile::resolve(&root_path, &grammars)
+            .unwrap()
+            .write(&root_path)
+            .unwrap();
+
+        // A library with the same name and hash is not opened again, but its grammar is reused.
+        let cached = register("cached", tree_sitter_rust::language(), Some("//"))
+            .unwrap()
+            .grammar();
+        LOADED.lock().unwrap().insert(
+            ("cached".into(), Lockfile::sha256(content.as_bytes())),
+            cached,
+        );
+        register("cached", tree_sitter_rust::language(), None).unwrap();
+        Context::acquire(&root_path).unwrap();
+        let id = GrammarId::lookup("cached").unwrap();
+        assert!(std::ptr::eq(id.grammar(), cached));
+
+        fs::write(root_path.join("cached.so"), "a different library").unwrap();
+        Lockfile::resolve(&root_path, &grammars)
+            .unwrap()
+            .write(&root_path)
+            .unwrap();
+        let err = Context::acquire(&root_path).unwrap_err().to_string();
+        assert!(
+            err.starts_with("cannot load grammar 'cached': cannot open "),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "lang-python")]
+    fn associations() {
+        let id = register("star-like", tree_sitter_python::language(), Some("#")).unwrap();
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let config = GrammarConfig {
+            name: "star-like".into(),
+            library: "unused".into(),
+            symbol: None,
+            use_for: vec![RawFilePattern::new("*.sky".into())],
+            line_comment: None,
+        };
+        fs::write(root_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        let mut ctx = Context::acquire(&root_path).unwrap();
+        ctx.manifest.grammars.push(config);
+        let language = ctx
+            .associations()
+            .unwrap()
+            .get_language(&crate::source_path::SourcePath::new_in(
+                "build.sky".into(),
+                "".into(),
+            ))
+            .unwrap();
+        assert_eq!(language, Some(SupportedLanguage::Dynamic(id)));
+    }
+}
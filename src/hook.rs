@@ -0,0 +1,153 @@
+use std::fs;
+
+use camino::Utf8PathBuf;
+
+use crate::{
+    cli::InstallHookCmd,
+    context::{self, Context},
+    error::{Error, IOAction},
+    git,
+    result::Result,
+    source_path::PrettyPath,
+    success,
+};
+
+/// The command run before each commit.
+const CHECK_STAGED: &str = "vex check --changed --staged";
+
+pub fn install_hook(cmd: InstallHookCmd) -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let prefix = git::prefix(&ctx.project_root)?;
+    if cmd.pre_commit_config {
+        print!("{}", pre_commit_config(&prefix));
+        return Ok(());
+    }
+    let hook_path = install(&ctx, &prefix, cmd.force)?;
+    success!("installed {hook_path}, staged changes will be checked before each commit");
+    Ok(())
+}
+
+/// Write a git pre-commit hook which checks staged changes, returning its path.
+fn install(ctx: &Context, prefix: &str, force: bool) -> Result<Utf8PathBuf> {
+    let hook_path = git::hook_path(&ctx.project_root, "pre-commit")?;
+    if !force && hook_path.exists() {
+        return Err(Error::AlreadyExists(PrettyPath::new(&hook_path)));
+    }
+
+    let io_error = |action| {
+        let hook_path = &hook_path;
+        move |cause| Error::IO {
+            path: PrettyPath::new(hook_path),
+            action,
+            cause,
+        }
+    };
+    if let Some(hooks_dir) = hook_path.parent() {
+        fs::create_dir_all(hooks_dir).map_err(io_error(IOAction::Create))?;
+    }
+    fs::write(&hook_path, hook_script(prefix)).map_err(io_error(IOAction::Write))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))
+            .map_err(io_error(IOAction::Write))?;
+    }
+    Ok(hook_path)
+}
+
+/// A shell script which checks staged changes to the project at the given path from the root of
+/// the repository.
+fn hook_script(prefix: &str) -> String {
+    let cd = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("cd '{}' || exit 1\n", prefix.replace('\'', r"'\''"))
+    };
+    format!("#!/bin/sh\n# Installed by `vex install-hook`.\n{cd}exec {CHECK_STAGED}\n")
+}
+
+/// Configuration for the pre-commit framework which checks staged changes to the project at the
+/// given path from the root of the repository.
+fn pre_commit_config(prefix: &str) -> String {
+    let entry = if prefix.is_empty() {
+        CHECK_STAGED.to_owned()
+    } else {
+        format!(
+            r#"sh -c "cd '{}' && exec {CHECK_STAGED}""#,
+            prefix.replace('\'', r"'\''")
+        )
+    };
+    format!(
+        "repos:\n  - repo: local\n    hooks:\n      - id: vex\n        name: vex\n        entry: {entry}\n        language: system\n        pass_filenames: false\n"
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn install() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let status = Command::new("git")
+            .args(["init", "--quiet"])
+            .current_dir(&root_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let project_path = root_path.join("project");
+        fs::create_dir(&project_path).unwrap();
+        fs::write(project_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        let ctx = Context::acquire(&project_path).unwrap();
+        let prefix = git::prefix(&ctx.project_root).unwrap();
+        assert_eq!(prefix, "project/");
+
+        let hook_path = super::install(&ctx, &prefix, false).unwrap();
+        assert!(hook_path.ends_with(".git/hooks/pre-commit"));
+        assert_eq!(
+            fs::read_to_string(&hook_path).unwrap(),
+            indoc! {"
+                #!/bin/sh
+                # Installed by `vex install-hook`.
+                cd 'project/' || exit 1
+                exec vex check --changed --staged
+            "}
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&hook_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+
+        let err = super::install(&ctx, &prefix, false).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("already exists, to overwrite it, use --force"));
+        super::install(&ctx, &prefix, true).unwrap();
+    }
+
+    #[test]
+    fn pre_commit_configs() {
+        assert_eq!(
+            pre_commit_config(""),
+            indoc! {"
+                repos:
+                  - repo: local
+                    hooks:
+                      - id: vex
+                        name: vex
+                        entry: vex check --changed --staged
+                        language: system
+                        pass_filenames: false
+            "}
+        );
+        assert!(pre_commit_config("sub/dir/")
+            .contains(r#"entry: sh -c "cd 'sub/dir/' && exec vex check --changed --staged""#));
+    }
+}
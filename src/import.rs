@@ -0,0 +1,451 @@
+use std::fs;
+
+use camino::Utf8PathBuf;
+use clap::ValueEnum;
+use lsp_types::Url;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cli::ImportCmd,
+    context::Context,
+    error::{Error, IOAction},
+    external::Finding,
+    irritation::Irritation,
+    result::Result,
+    severity::Severity,
+    source_path::PrettyPath,
+    vex_id::VexId,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// Output of `eslint --format json`
+    EslintJson,
+
+    /// Output of `cargo clippy --message-format json`
+    ClippyJson,
+
+    /// Static Analysis Results Interchange Format
+    Sarif,
+}
+
+impl ImportFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::EslintJson => "eslint",
+            Self::ClippyJson => "clippy",
+            Self::Sarif => "sarif",
+        }
+    }
+}
+
+pub fn import(ctx: &Context, cmd: &ImportCmd) -> Result<Vec<Irritation>> {
+    let content = fs::read_to_string(&cmd.path).map_err(|cause| Error::IO {
+        path: PrettyPath::new(&cmd.path),
+        action: IOAction::Read,
+        cause,
+    })?;
+    let findings = parse(cmd.format, &content)?;
+    let files = Finding::read_files(
+        &ctx.project_root,
+        findings.iter().map(|(_, _, finding)| finding),
+    );
+    let mut irritations = findings
+        .into_iter()
+        .map(|(rule, severity, finding)| {
+            let vex_id = imported_id(cmd.format, rule.as_deref());
+            finding.into_irritation(&ctx.project_root, &files, vex_id, severity)
+        })
+        .collect::<Vec<_>>();
+    irritations.sort();
+    Ok(irritations)
+}
+
+fn parse(format: ImportFormat, content: &str) -> Result<Vec<(Option<String>, Severity, Finding)>> {
+    let invalid_input = |reason: String| Error::InvalidImport {
+        format: format.name(),
+        reason,
+    };
+    let as_string = |value: &JsonValue| value.as_str().map(ToString::to_string);
+    let as_line = |value: &JsonValue| value.as_u64().map(|line| line as usize);
+
+    match format {
+        ImportFormat::EslintJson => {
+            let document: JsonValue =
+                serde_json::from_str(content).map_err(|cause| invalid_input(cause.to_string()))?;
+            let files = document
+                .as_array()
+                .ok_or_else(|| invalid_input("expected an array of files".into()))?;
+            Ok(files
+                .iter()
+                .flat_map(|file| {
+                    let path = file.get("filePath").and_then(as_string);
+                    file.get("messages")
+                        .and_then(JsonValue::as_array)
+                        .into_iter()
+                        .flatten()
+                        .map(move |message| {
+                            let rule = message.get("ruleId").and_then(as_string);
+                            let severity = message.get("severity").and_then(JsonValue::as_u64);
+                            let severity = match severity {
+                                Some(2) => Severity::Error,
+                                Some(1) => Severity::Warning,
+                                _ => Severity::Advice,
+                            };
+                            let finding = Finding {
+                                message: message
+                                    .get("message")
+                                    .and_then(as_string)
+                                    .unwrap_or_default(),
+                                path: path.clone(),
+                                line: message.get("line").and_then(as_line),
+                            };
+                            (rule, severity, finding)
+                        })
+                })
+                .collect())
+        }
+        ImportFormat::ClippyJson => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str::<JsonValue>(line)
+                    .map_err(|cause| invalid_input(cause.to_string()))
+            })
+            .filter_map(|document| {
+                let document = match document {
+                    Ok(document) => document,
+                    Err(err) => return Some(Err(err)),
+                };
+                if document.get("reason").and_then(JsonValue::as_str) != Some("compiler-message") {
+                    return None;
+                }
+                let message = document.get("message")?;
+                let primary_span = message
+                    .get("spans")
+                    .and_then(JsonValue::as_array)?
+                    .iter()
+                    .find(|span| {
+                        span.get("is_primary").and_then(JsonValue::as_bool) == Some(true)
+                    })?;
+                let rule = message.pointer("/code/code").and_then(as_string);
+                let severity = match message.get("level").and_then(JsonValue::as_str) {
+                    Some("error") => Severity::Error,
+                    Some("warning") => Severity::Warning,
+                    _ => Severity::Advice,
+                };
+                let finding = Finding {
+                    message: message
+                        .get("message")
+                        .and_then(as_string)
+                        .unwrap_or_default(),
+                    path: primary_span.get("file_name").and_then(as_string),
+                    line: primary_span.get("line_start").and_then(as_line),
+                };
+                Some(Ok((rule, severity, finding)))
+            })
+            .collect(),
+        ImportFormat::Sarif => {
+            let document: JsonValue =
+                serde_json::from_str(content).map_err(|cause| invalid_input(cause.to_string()))?;
+            let runs = document
+                .get("runs")
+                .and_then(JsonValue::as_array)
+                .ok_or_else(|| invalid_input("expected a list of runs".into()))?;
+            Ok(runs
+                .iter()
+                .flat_map(|run| {
+                    run.get("results")
+                        .and_then(JsonValue::as_array)
+                        .into_iter()
+                        .flatten()
+                })
+                .map(|result| {
+                    let rule = result.get("ruleId").and_then(as_string);
+                    let severity = match result.get("level").and_then(JsonValue::as_str) {
+                        Some("error") => Severity::Error,
+                        Some("note") | Some("none") => Severity::Advice,
+                        _ => Severity::Warning,
+                    };
+                    let location = result.pointer("/locations/0/physicalLocation");
+                    let finding = Finding {
+                        message: result
+                            .pointer("/message/text")
+                            .and_then(as_string)
+                            .unwrap_or_default(),
+                        path: location
+                            .and_then(|location| location.pointer("/artifactLocation/uri"))
+                            .and_then(JsonValue::as_str)
+                            .map(uri_path),
+                        line: location
+                            .and_then(|location| location.pointer("/region/startLine"))
+                            .and_then(as_line),
+                    };
+                    (rule, severity, finding)
+                })
+                .collect())
+        }
+    }
+}
+
+/// The path named by a SARIF artifact URI. File URIs are decoded into absolute paths, which are
+/// later made relative to the project root, and other URIs are taken as relative paths.
+fn uri_path(uri: &str) -> String {
+    Url::parse(uri)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+        .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+        .map_or_else(|| uri.to_string(), Utf8PathBuf::into_string)
+}
+
+/// Derive a valid vex id from the name of a third-party rule, falling back to the name of the
+/// tool which reported it. Names too long to be ids are shortened and suffixed with part of their
+/// hash, so that distinct rules with the same prefix keep distinct ids.
+fn imported_id(format: ImportFormat, rule: Option<&str>) -> VexId {
+    let fallback = || VexId::try_from(format.name().to_string()).expect("tool name invalid");
+    let Some(rule) = rule else {
+        return fallback();
+    };
+
+    const MAX_ID_LEN: usize = 25;
+    const HASH_SUFFIX_LEN: usize = 6;
+    let mut id = String::with_capacity(rule.len());
+    for c in rule.chars().flat_map(char::to_lowercase) {
+        let c = if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            c
+        } else {
+            '-'
+        };
+        if c == '-' && (id.is_empty() || id.ends_with('-')) {
+            continue;
+        }
+        id.push(c);
+    }
+    let id = id
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '-')
+        .trim_end_matches('-');
+    let id = if id.len() <= MAX_ID_LEN {
+        id.to_string()
+    } else {
+        let hash = format!("{:x}", Sha256::digest(rule));
+        let prefix = id[..MAX_ID_LEN - HASH_SUFFIX_LEN - 1].trim_end_matches('-');
+        format!("{prefix}-{}", &hash[..HASH_SUFFIX_LEN])
+    };
+    VexId::try_from(id).unwrap_or_else(|_| fallback())
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn ids() {
+        let id = |format, rule| imported_id(format, rule).to_string();
+        assert_eq!(
+            id(ImportFormat::EslintJson, Some("no-unused-vars")),
+            "no-unused-vars"
+        );
+        assert_eq!(
+            id(
+                ImportFormat::EslintJson,
+                Some("@typescript-eslint/no-shadow")
+            ),
+            "typescript-eslint-247f6e"
+        );
+        assert_ne!(
+            id(
+                ImportFormat::EslintJson,
+                Some("@typescript-eslint/no-shadow-2")
+            ),
+            "typescript-eslint-247f6e"
+        );
+        assert_eq!(
+            id(ImportFormat::ClippyJson, Some("clippy::needless_return")),
+            "clippy-needless-return"
+        );
+        assert_eq!(id(ImportFormat::ClippyJson, Some("E0308")), "e0308");
+        assert_eq!(id(ImportFormat::Sarif, Some("42")), "sarif");
+        assert_eq!(id(ImportFormat::Sarif, None), "sarif");
+    }
+
+    #[test]
+    fn eslint() {
+        let findings = parse(
+            ImportFormat::EslintJson,
+            indoc! {r#"
+                [
+                    {
+                        "filePath": "src/index.js",
+                        "messages": [
+                            { "ruleId": "no-unused-vars", "severity": 2, "message": "'x' is unused", "line": 3 },
+                            { "ruleId": "semi", "severity": 1, "message": "Missing semicolon", "line": 4 }
+                        ]
+                    },
+                    { "filePath": "src/clean.js", "messages": [] }
+                ]
+            "#},
+        )
+        .unwrap();
+        assert_eq!(
+            findings,
+            [
+                (
+                    Some("no-unused-vars".into()),
+                    Severity::Error,
+                    Finding {
+                        message: "'x' is unused".into(),
+                        path: Some("src/index.js".into()),
+                        line: Some(3),
+                    }
+                ),
+                (
+                    Some("semi".into()),
+                    Severity::Warning,
+                    Finding {
+                        message: "Missing semicolon".into(),
+                        path: Some("src/index.js".into()),
+                        line: Some(4),
+                    }
+                ),
+            ]
+        );
+
+        parse(ImportFormat::EslintJson, "{}").unwrap_err();
+    }
+
+    #[test]
+    fn clippy() {
+        let findings = parse(
+            ImportFormat::ClippyJson,
+            indoc! {r#"
+                {"reason":"compiler-artifact","target":{}}
+                {"reason":"compiler-message","message":{"code":{"code":"clippy::needless_return"},"level":"warning","message":"unneeded `return` statement","spans":[{"file_name":"src/main.rs","line_start":5,"is_primary":true}]}}
+                {"reason":"compiler-message","message":{"code":null,"level":"warning","message":"1 warning emitted","spans":[]}}
+                {"reason":"build-finished","success":true}
+            "#},
+        )
+        .unwrap();
+        assert_eq!(
+            findings,
+            [(
+                Some("clippy::needless_return".into()),
+                Severity::Warning,
+                Finding {
+                    message: "unneeded `return` statement".into(),
+                    path: Some("src/main.rs".into()),
+                    line: Some(5),
+                }
+            )]
+        );
+
+        parse(ImportFormat::ClippyJson, "not json").unwrap_err();
+    }
+
+    #[test]
+    fn sarif() {
+        let findings = parse(
+            ImportFormat::Sarif,
+            indoc! {r#"
+                {
+                    "version": "2.1.0",
+                    "runs": [
+                        {
+                            "results": [
+                                {
+                                    "ruleId": "py/unused-import",
+                                    "level": "note",
+                                    "message": { "text": "Import of 'os' is not used." },
+                                    "locations": [
+                                        {
+                                            "physicalLocation": {
+                                                "artifactLocation": { "uri": "file:///src/main.py" },
+                                                "region": { "startLine": 1 }
+                                            }
+                                        }
+                                    ]
+                                },
+                                {
+                                    "ruleId": "global-problem",
+                                    "message": { "text": "Something is wrong." }
+                                }
+                            ]
+                        }
+                    ]
+                }
+            "#},
+        )
+        .unwrap();
+        assert_eq!(
+            findings,
+            [
+                (
+                    Some("py/unused-import".into()),
+                    Severity::Advice,
+                    Finding {
+                        message: "Import of 'os' is not used.".into(),
+                        path: Some("/src/main.py".into()),
+                        line: Some(1),
+                    }
+                ),
+                (
+                    Some("global-problem".into()),
+                    Severity::Warning,
+                    Finding {
+                        message: "Something is wrong.".into(),
+                        path: None,
+                        line: None,
+                    }
+                ),
+            ]
+        );
+
+        parse(ImportFormat::Sarif, "{}").unwrap_err();
+    }
+
+    #[test]
+    fn sarif_file_uris() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        fs::create_dir(root_path.join("src")).unwrap();
+        fs::write(root_path.join("src/my main.py"), "import os\n").unwrap();
+        let uri = Url::from_file_path(root_path.join("src/my main.py")).unwrap();
+        let results_path = root_path.join("results.sarif");
+        fs::write(
+            &results_path,
+            serde_json::json!({
+                "version": "2.1.0",
+                "runs": [{
+                    "results": [{
+                        "ruleId": "py/unused-import",
+                        "message": { "text": "Import of 'os' is not used." },
+                        "locations": [{
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": uri },
+                                "region": { "startLine": 1 },
+                            },
+                        }],
+                    }],
+                }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let ctx = Context::acquire(&root_path).unwrap();
+        let irritations = import(
+            &ctx,
+            &ImportCmd {
+                format: ImportFormat::Sarif,
+                path: results_path,
+            },
+        )
+        .unwrap();
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].path().unwrap().as_str(), "src/my main.py");
+    }
+}
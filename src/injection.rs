@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use log::{info, log_enabled};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use tree_sitter::QueryCursor;
+
+use crate::{
+    error::Error, query::Query, result::Result, scriptlets::artifact_cache::ArtifactCache,
+    source_file::ParsedSourceFile, supported_language::SupportedLanguage,
+};
+
+/// Nodes whose content is written in another language, as declared in the manifest.
+#[derive(Clone, Debug, Deserialise, Serialise, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct InjectionConfig {
+    /// The language of the files which contain the nodes.
+    pub language: SupportedLanguage,
+
+    /// A query which captures the embedded content as `@injection.content` and optionally the
+    /// name of its language as `@injection.language`.
+    pub query: String,
+
+    /// The language of the embedded content, unless named by the query.
+    #[serde(default)]
+    pub injected_language: Option<SupportedLanguage>,
+}
+
+/// An injection whose query has been compiled.
+#[derive(Debug)]
+pub struct Injection {
+    pub language: SupportedLanguage,
+    query: Arc<Query>,
+    content_index: u32,
+    language_index: Option<u32>,
+    injected_language: Option<SupportedLanguage>,
+}
+
+impl Injection {
+    const CONTENT_CAPTURE: &'static str = "injection.content";
+    const LANGUAGE_CAPTURE: &'static str = "injection.language";
+
+    pub fn compile_all(
+        configs: &[InjectionConfig],
+        artifact_cache: &ArtifactCache,
+    ) -> Result<Vec<Self>> {
+        configs
+            .iter()
+            .map(|config| Self::compile(config, artifact_cache))
+            .collect()
+    }
+
+    fn compile(config: &InjectionConfig, artifact_cache: &ArtifactCache) -> Result<Self> {
+        let InjectionConfig {
+            language,
+            query,
+            injected_language,
+        } = config;
+        let invalid = |reason| Error::InvalidInjection {
+            language: *language,
+            reason,
+        };
+        let query = artifact_cache.query(*language, query)?;
+        let content_index = query
+            .capture_index_for_name(Self::CONTENT_CAPTURE)
+            .ok_or_else(|| invalid("query must capture @injection.content"))?;
+        let language_index = query.capture_index_for_name(Self::LANGUAGE_CAPTURE);
+        if injected_language.is_none() && language_index.is_none() {
+            return Err(invalid(
+                "set injected-language or capture @injection.language in the query",
+            ));
+        }
+        Ok(Self {
+            language: *language,
+            query,
+            content_index,
+            language_index,
+            injected_language: *injected_language,
+        })
+    }
+
+    /// Whether content found by this injection may be written in the given language.
+    pub fn may_inject(&self, language: SupportedLanguage) -> bool {
+        self.language_index.is_some() || self.injected_language == Some(language)
+    }
+
+    /// Parse each piece of content embedded in the given file. Content which is not valid in
+    /// its language, or whose language is unknown, is skipped.
+    pub fn parse_all(&self, host: &ParsedSourceFile) -> Vec<ParsedSourceFile> {
+        let mut cursor = QueryCursor::new();
+        cursor
+            .matches(&self.query, host.tree.root_node(), host.content.as_bytes())
+            .filter_map(|qmatch| {
+                let named = self.language_index.and_then(|language_index| {
+                    qmatch
+                        .nodes_for_capture_index(language_index)
+                        .next()
+                        .and_then(|node| node.utf8_text(host.content.as_bytes()).ok())
+                });
+                let language = match named {
+                    Some(name) => match name.trim().parse() {
+                        Ok(language) => language,
+                        Err(_) => {
+                            if log_enabled!(log::Level::Info) {
+                                info!("skipping content embedded in {}: unknown language {name:?}", host.path);
+                            }
+                            return None;
+                        }
+                    },
+                    None => self.injected_language?,
+                };
+                let content = qmatch.nodes_for_capture_index(self.content_index).next()?;
+                match ParsedSourceFile::new_injected(host, content.range(), language) {
+                    Ok(parsed) => Some(parsed),
+                    Err(err) => {
+                        if log_enabled!(log::Level::Info) {
+                            info!("skipping content embedded in {}: {err}", host.path);
+                        }
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
@@ -1,30 +1,46 @@
 use std::{cmp::Ordering, fmt::Display, iter, ops::Range};
 
 use allocative::Allocative;
-use annotate_snippets::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation};
+use annotate_snippets::{
+    renderer::Margin, Annotation, AnnotationType, Slice, Snippet, SourceAnnotation,
+};
 use dupe::Dupe;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use starlark::values::{list::AllocList, AllocValue, Heap, StarlarkValue, Value};
 use starlark_derive::{
     starlark_attrs, starlark_value, NoSerialize, ProvidesStaticType, StarlarkAttrs, Trace,
 };
 
 use crate::{
+    cli::SortBy,
+    fix::Fix,
     logger,
     scriptlets::{main_annotation::MainAnnotation, Location, Node},
+    severity::Severity,
     source_path::PrettyPath,
     vex_id::VexId,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Allocative, Serialize, ProvidesStaticType)]
+/// A problem found by a vex.
+#[derive(Debug, Clone, PartialEq, Eq, Allocative, Deserialize, Serialize, ProvidesStaticType)]
 #[non_exhaustive]
 pub struct Irritation {
     vex_id: VexId,
+    severity: Severity,
     message: String,
     at: Option<(IrritationSource, Option<String>)>,
     show_also: Vec<(IrritationSource, String)>,
     info: Option<String>,
+    fix: Option<Box<Fix>>,
     pub(crate) rendered: String,
+
+    /// The stable code of the check which found this problem, if it declared one.
+    #[serde(default)]
+    code: Option<String>,
+
+    /// Whether this reports an error raised by a scriptlet, rather than a problem it found.
+    #[serde(skip)]
+    scriptlet_error: bool,
 }
 
 impl Irritation {
@@ -32,20 +48,179 @@ impl Irritation {
         &self.vex_id
     }
 
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn info(&self) -> Option<&str> {
+        self.info.as_deref()
+    }
+
     pub fn path(&self) -> Option<&PrettyPath> {
         self.at.as_ref().map(|(loc, _)| &loc.path)
     }
 
+    pub fn location(&self) -> Option<&Location> {
+        self.at.as_ref().map(|(loc, _)| &loc.location)
+    }
+
+    pub fn fix(&self) -> Option<&Fix> {
+        self.fix.as_deref()
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
+    /// Create an irritation from a problem found by an external tool. If the content of the
+    /// offending file is available, the given line (numbered from 1) is shown.
+    pub fn external(
+        vex_id: VexId,
+        severity: Severity,
+        message: &str,
+        at: Option<(PrettyPath, Option<(&str, usize)>)>,
+    ) -> Self {
+        Self::external_with_code(vex_id, None, severity, message, at)
+    }
+
+    fn external_with_code(
+        vex_id: VexId,
+        code: Option<&str>,
+        severity: Severity,
+        message: &str,
+        at: Option<(PrettyPath, Option<(&str, usize)>)>,
+    ) -> Self {
+        let line = at.as_ref().and_then(|(_, line)| {
+            let (content, row) = (*line)?;
+            let start = content
+                .split_inclusive('\n')
+                .take(row.checked_sub(1)?)
+                .map(str::len)
+                .sum::<usize>();
+            if start >= content.len() {
+                return None;
+            }
+            let end = content[start..]
+                .find(['\n', '\r'])
+                .map(|i| i + start)
+                .unwrap_or(content.len());
+            Some((row, start..end, &content[start..end]))
+        });
+
+        let (range, margin) = match &line {
+            Some((_, _, text)) => {
+                let mut ranges = [(0, text.chars().count())];
+                let margin = margin(text, &mut ranges);
+                (ranges[0], margin)
+            }
+            None => ((0, 1), None),
+        };
+        let id = title_id(&vex_id, code);
+        let snippet = Snippet {
+            title: Some(Annotation {
+                id: Some(&id),
+                label: Some(message),
+                annotation_type: severity.annotation_type(),
+            }),
+            slices: at
+                .iter()
+                .map(|(path, _)| match &line {
+                    Some((row, _, text)) => Slice {
+                        source: text,
+                        line_start: *row,
+                        origin: Some(path.as_str()),
+                        annotations: vec![SourceAnnotation {
+                            range,
+                            label: "",
+                            annotation_type: severity.annotation_type(),
+                        }],
+                        fold: false,
+                    },
+                    None => Slice {
+                        source: "...",
+                        line_start: 1,
+                        origin: Some(path.as_str()),
+                        annotations: vec![SourceAnnotation {
+                            range,
+                            label: "",
+                            annotation_type: severity.annotation_type(),
+                        }],
+                        fold: false,
+                    },
+                })
+                .collect(),
+            footer: Vec::with_capacity(0),
+        };
+        let rendered = logger::render_snippet(snippet, margin);
+
+        let at = at.map(|(path, _)| {
+            let source = match line {
+                Some((row, byte_range, text)) => IrritationSource {
+                    path,
+                    location: Location::of_line(row, text.len()),
+                    byte_range,
+                },
+                None => IrritationSource::whole_file(path),
+            };
+            (source, None)
+        });
+        Self {
+            vex_id,
+            severity,
+            message: message.to_string(),
+            at,
+            show_also: Vec::with_capacity(0),
+            info: None,
+            fix: None,
+            rendered,
+            code: code.map(ToOwned::to_owned),
+            scriptlet_error: false,
+        }
+    }
+
+    /// Create an irritation which reports an error raised by a scriptlet while handling an event
+    /// concerning the given file, if any.
+    pub(crate) fn scriptlet_error(
+        vex_id: VexId,
+        code: Option<&str>,
+        message: &str,
+        path: Option<PrettyPath>,
+    ) -> Self {
+        Self {
+            scriptlet_error: true,
+            ..Self::external_with_code(
+                vex_id,
+                code,
+                Severity::Error,
+                message,
+                path.map(|path| (path, None)),
+            )
+        }
+    }
+
+    pub(crate) fn is_scriptlet_error(&self) -> bool {
+        self.scriptlet_error
+    }
+
     pub fn to_value_on<'v>(&self, lenient: bool, heap: &'v Heap) -> Value<'v> {
         let Self {
             vex_id,
+            severity,
             message,
             at,
             show_also,
             info,
+            fix: _,
             rendered,
+            code: _,
+            scriptlet_error: _,
         } = self;
         let vex_id = heap.alloc(vex_id.as_ref());
+        let severity = heap.alloc(severity.name());
         let lenient = Value::new_bool(lenient);
         let message = heap.alloc(message);
         let at = at
@@ -64,6 +239,7 @@ impl Irritation {
         let rendered = rendered.clone();
         heap.alloc(IrritationValue {
             vex_id,
+            severity,
             lenient,
             message,
             at,
@@ -74,15 +250,32 @@ impl Irritation {
     }
 }
 
+impl Irritation {
+    /// Compare problems in the given order. Ties are broken by the natural order of problems,
+    /// which is total, so sorting gives the same result on every run and platform.
+    pub fn cmp_by(&self, other: &Self, sort_by: SortBy) -> Ordering {
+        match sort_by {
+            SortBy::File => Ordering::Equal,
+            SortBy::Check => self.vex_id.cmp(&other.vex_id),
+            SortBy::Severity => other.severity.cmp(&self.severity),
+        }
+        .then_with(|| self.cmp(other))
+    }
+}
+
 impl Ord for Irritation {
     fn cmp(&self, other: &Self) -> Ordering {
         let Self {
             vex_id,
+            severity,
             message,
             at,
             show_also,
             info,
+            fix,
             rendered: _,
+            code,
+            scriptlet_error: _,
         } = self;
 
         fn loc<S, T>(annot: &(S, T)) -> &S {
@@ -96,20 +289,26 @@ impl Ord for Irritation {
         return (
             at.as_ref().map(loc),
             vex_id,
+            severity,
             ComparableIterator(show_also.iter().map(loc)),
             info,
             at.as_ref().map(label),
             ComparableIterator(show_also.iter().map(label)),
             message,
+            fix,
+            code,
         )
             .cmp(&(
                 other.at.as_ref().map(loc),
                 &other.vex_id,
+                &other.severity,
                 ComparableIterator(other.show_also.iter().map(loc)),
                 &other.info,
                 other.at.as_ref().map(label),
                 ComparableIterator(other.show_also.iter().map(label)),
                 &other.message,
+                &other.fix,
+                &other.code,
             ));
 
         // ComparableIterator implements Ord on the lexicographic order of its contents.
@@ -170,6 +369,7 @@ impl Display for Irritation {
 #[derive(Clone, Debug, Allocative, NoSerialize, ProvidesStaticType, Trace)]
 struct IrritationValue<'v> {
     vex_id: Value<'v>,
+    severity: Value<'v>,
     lenient: Value<'v>,
     message: Value<'v>,
     at: Value<'v>,
@@ -180,6 +380,7 @@ struct IrritationValue<'v> {
 
 impl<'v> IrritationValue<'v> {
     const VEX_ID_ATTR_NAME: &'static str = "id";
+    const SEVERITY_ATTR_NAME: &'static str = "severity";
     const LENIENT_ATTR_NAME: &'static str = "lenient";
     const MESSAGE_ATTR_NAME: &'static str = "message";
     const AT_ATTR_NAME: &'static str = "at";
@@ -192,6 +393,7 @@ impl<'v> StarlarkValue<'v> for IrritationValue<'v> {
     fn dir_attr(&self) -> Vec<String> {
         [
             Self::VEX_ID_ATTR_NAME,
+            Self::SEVERITY_ATTR_NAME,
             Self::LENIENT_ATTR_NAME,
             Self::MESSAGE_ATTR_NAME,
             Self::AT_ATTR_NAME,
@@ -206,6 +408,7 @@ impl<'v> StarlarkValue<'v> for IrritationValue<'v> {
     fn get_attr(&self, attr: &str, _heap: &'v Heap) -> Option<Value<'v>> {
         match attr {
             Self::VEX_ID_ATTR_NAME => Some(self.vex_id.dupe()),
+            Self::SEVERITY_ATTR_NAME => Some(self.severity.dupe()),
             Self::LENIENT_ATTR_NAME => Some(self.lenient.dupe()),
             Self::MESSAGE_ATTR_NAME => Some(self.message.dupe()),
             Self::AT_ATTR_NAME => Some(self.at.dupe()),
@@ -218,6 +421,7 @@ impl<'v> StarlarkValue<'v> for IrritationValue<'v> {
     fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
         [
             Self::VEX_ID_ATTR_NAME,
+            Self::SEVERITY_ATTR_NAME,
             Self::LENIENT_ATTR_NAME,
             Self::MESSAGE_ATTR_NAME,
             Self::AT_ATTR_NAME,
@@ -240,7 +444,17 @@ impl Display for IrritationValue<'_> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Allocative, Serialize, StarlarkAttrs, ProvidesStaticType)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Allocative,
+    Deserialize,
+    Serialize,
+    StarlarkAttrs,
+    ProvidesStaticType,
+)]
 pub struct IrritationSource {
     path: PrettyPath,
     #[starlark(skip)]
@@ -308,22 +522,36 @@ impl Display for IrritationSource {
     }
 }
 
+/// The id shown in the title of a problem, followed by the code of its check if it has one.
+fn title_id(vex_id: &VexId, code: Option<&str>) -> String {
+    match code {
+        Some(code) => format!("{vex_id}/{code}"),
+        None => vex_id.to_string(),
+    }
+}
+
 pub struct IrritationRenderer<'v> {
     vex_id: VexId,
+    severity: Severity,
     message: &'v str,
     source: Option<MainAnnotation<'v>>,
     show_also: Vec<(Node<'v>, &'v str)>,
     info: Option<&'v str>,
+    fix: Option<&'v str>,
+    code: Option<&'v str>,
 }
 
 impl<'v> IrritationRenderer<'v> {
-    pub fn new(vex_id: VexId, message: &'v str) -> Self {
+    pub fn new(vex_id: VexId, severity: Severity, message: &'v str) -> Self {
         Self {
             vex_id,
+            severity,
             message,
             source: None,
             show_also: Vec::with_capacity(0),
             info: None,
+            fix: None,
+            code: None,
         }
     }
 
@@ -339,89 +567,138 @@ impl<'v> IrritationRenderer<'v> {
         self.info = Some(info);
     }
 
+    /// Suggest replacing the text of the main annotation's node with `replacement`.
+    pub fn set_fix(&mut self, replacement: &'v str) {
+        self.fix = Some(replacement);
+    }
+
+    /// Label the problem with the code of the check which found it, shown beside its id.
+    pub fn set_code(&mut self, code: &'v str) {
+        self.code = Some(code);
+    }
+
     pub fn render(self) -> Irritation {
         let Self {
             vex_id,
+            severity,
             source,
             message,
             show_also,
             info,
+            fix,
+            code,
         } = self;
 
         let file_name = source.as_ref().map(|source| source.pretty_path().as_str());
+        let node_slice = match &source {
+            Some(MainAnnotation::Node { node, .. }) => {
+                let range = {
+                    let start = iter::once(node)
+                        .chain(show_also.iter().map(|(node, _)| node))
+                        .map(|node| node.byte_range().start)
+                        .min()
+                        .unwrap();
+                    let end = iter::once(node)
+                        .chain(show_also.iter().map(|(node, _)| node))
+                        .map(|node| node.byte_range().end)
+                        .max()
+                        .unwrap();
+                    node.source_file.full_lines_range(start..end)
+                };
+                let text = &node.source_file.content[range.clone()];
+                let mut ranges: Vec<_> = iter::once(node)
+                    .chain(show_also.iter().map(|(node, _)| node))
+                    .map(|node| {
+                        (
+                            text[..node.start_byte() - range.start].chars().count(),
+                            text[..node.end_byte() - range.start].chars().count(),
+                        )
+                    })
+                    .collect();
+                let margin = margin(text, &mut ranges);
+                Some((text, ranges, margin))
+            }
+            _ => None,
+        };
+        let id = title_id(&vex_id, code);
+        let file_note = match &source {
+            Some(MainAnnotation::Path { path, .. }) => Some(format!("in {path}")),
+            _ => None,
+        };
         let snippet = Snippet {
             title: Some(Annotation {
-                id: Some(vex_id.as_ref()),
+                id: Some(&id),
                 label: Some(message),
-                annotation_type: AnnotationType::Warning,
+                annotation_type: severity.annotation_type(),
             }),
             slices: source
                 .iter()
-                .map(|annot| match annot {
-                    MainAnnotation::Path { path, label } => Slice {
-                        source: "...",
-                        line_start: 1,
-                        origin: Some(path.as_str()),
-                        annotations: vec![SourceAnnotation {
-                            range: (0, 1),
-                            label: label.unwrap_or_default(),
-                            annotation_type: AnnotationType::Warning,
-                        }],
-                        fold: false,
-                    },
+                .filter_map(|annot| match annot {
+                    // Whole files have no snippet to show, so are named in the footer.
+                    MainAnnotation::Path { .. } => None,
                     MainAnnotation::Node { node, label } => {
-                        let range = {
-                            let start = iter::once(node)
-                                .chain(show_also.iter().map(|(node, _)| node))
-                                .map(|node| node.byte_range().start)
-                                .min()
-                                .unwrap();
-                            let end = iter::once(node)
-                                .chain(show_also.iter().map(|(node, _)| node))
-                                .map(|node| node.byte_range().end)
-                                .max()
-                                .unwrap();
-                            node.source_file.full_lines_range(start..end)
-                        };
-                        Slice {
-                            source: &node.source_file.content[range.start..range.end],
+                        let (text, ranges, _) = node_slice
+                            .as_ref()
+                            .expect("internal error: node slice not computed");
+                        let labels =
+                            iter::once((label.unwrap_or_default(), severity.annotation_type()))
+                                .chain(
+                                    show_also
+                                        .iter()
+                                        .map(|(_, label)| (*label, AnnotationType::Info)),
+                                );
+                        Some(Slice {
+                            source: text,
                             line_start: 1 + node.start_position().row,
                             origin: file_name,
-                            annotations: [SourceAnnotation {
-                                range: (
-                                    node.start_byte() - range.start,
-                                    node.end_byte() - range.start,
-                                ),
-                                label: label.unwrap_or_default(),
-                                annotation_type: AnnotationType::Warning,
-                            }]
-                            .into_iter()
-                            .chain(show_also.iter().map(|(node, label)| SourceAnnotation {
-                                range: (
-                                    node.start_byte() - range.start,
-                                    node.end_byte() - range.start,
-                                ),
-                                label,
-                                annotation_type: AnnotationType::Info,
-                            }))
-                            .collect(),
+                            annotations: ranges
+                                .iter()
+                                .zip(labels)
+                                .map(|(range, (label, annotation_type))| SourceAnnotation {
+                                    range: *range,
+                                    label,
+                                    annotation_type,
+                                })
+                                .collect(),
                             fold: true,
-                        }
+                        })
                     }
                 })
                 .collect(),
-            footer: info
+            footer: file_note
+                .as_deref()
                 .into_iter()
-                .map(|info| Annotation {
+                .chain(source.iter().filter_map(|annot| match annot {
+                    MainAnnotation::Path { label, .. } => *label,
+                    MainAnnotation::Node { .. } => None,
+                }))
+                .map(|label| Annotation {
+                    id: None,
+                    label: Some(label),
+                    annotation_type: AnnotationType::Note,
+                })
+                .chain(info.map(|info| Annotation {
                     id: None,
                     label: Some(info),
                     annotation_type: AnnotationType::Info,
-                })
+                }))
+                .chain(fix.map(|_| Annotation {
+                    id: None,
+                    label: Some("a fix is available"),
+                    annotation_type: AnnotationType::Help,
+                }))
                 .collect(),
         };
 
-        let rendered = logger::render_snippet(snippet);
+        let margin = node_slice.as_ref().and_then(|(_, _, margin)| *margin);
+        let rendered = logger::render_snippet(snippet, margin);
         let message = message.to_string();
+        let replaced = fix.map(|_| match &source {
+            Some(MainAnnotation::Node { node, .. }) => {
+                node.source_file.content[node.byte_range()].to_owned()
+            }
+            _ => String::new(),
+        });
         let at = source.map(|source| match source {
             MainAnnotation::Path { path, label } => (
                 IrritationSource::whole_file(path.dupe()),
@@ -436,13 +713,186 @@ impl<'v> IrritationRenderer<'v> {
             .map(|(node, label)| (IrritationSource::at(&node), label.to_string()))
             .collect();
         let info = info.map(|e| e.to_string());
+        let fix = fix.zip(replaced).and_then(|(replacement, replaced)| {
+            let (source, _) = at.as_ref()?;
+            Some(Box::new(Fix {
+                path: source.path.dupe(),
+                byte_range: source.byte_range.clone(),
+                replaced,
+                replacement: replacement.to_string(),
+            }))
+        });
         Irritation {
             vex_id,
+            severity,
             message,
             at,
             show_also,
             info,
+            fix,
             rendered,
+            code: code.map(ToOwned::to_owned),
+            scriptlet_error: false,
+        }
+    }
+}
+
+/// Lines longer than this many characters, such as those of minified files, are cut down to the
+/// part around the problem when drawn.
+const MAX_LINE_LENGTH: usize = 160;
+
+/// The margin which cuts the long lines of a slice down to the part around its annotations, if
+/// any line is too long. The given annotation ranges, counted in characters, are limited to the
+/// part shown. The start of the first range, which gives the column shown after the path, is kept.
+fn margin(source: &str, ranges: &mut [(usize, usize)]) -> Option<Margin> {
+    let line_lens: Vec<_> = source.lines().map(|line| line.chars().count()).collect();
+    let max_line_len = line_lens.iter().copied().max().unwrap_or_default();
+    if max_line_len <= MAX_LINE_LENGTH {
+        return None;
+    }
+
+    if let [line_len] = line_lens[..] {
+        // Room is left for the ellipses which show where the line was cut.
+        let width = MAX_LINE_LENGTH - 12;
+        let lo = ranges.iter().map(|(start, _)| *start).min()?;
+        let hi = ranges.iter().map(|(_, end)| *end).max()?;
+        let (lo, hi) = if hi - lo <= width {
+            (lo, hi)
+        } else {
+            (ranges[0].0, ranges[0].0 + width)
+        };
+        for (start, end) in ranges.iter_mut() {
+            *start = (*start).clamp(lo, hi);
+            *end = (*end).clamp(*start, hi);
         }
+        let whitespace_left = source.chars().take_while(|c| c.is_whitespace()).count();
+        return Some(Margin::new(
+            whitespace_left.min(lo),
+            lo,
+            hi,
+            hi,
+            MAX_LINE_LENGTH,
+            line_len,
+        ));
+    }
+
+    // `annotate_snippets` can only cut the start of a lone line, so the lines of longer slices
+    // are only cut at their end.
+    let mut line_starts = Vec::with_capacity(line_lens.len());
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
+        line_starts.push(line_start);
+        line_start += line.chars().count();
+    }
+    let limit = |offset: usize| {
+        let index = line_starts
+            .partition_point(|line_start| *line_start <= offset)
+            .saturating_sub(1);
+        offset.min(line_starts[index] + MAX_LINE_LENGTH)
+    };
+    for (index, (start, end)) in ranges.iter_mut().enumerate() {
+        if index != 0 {
+            *start = limit(*start);
+        }
+        *end = limit(*end).max(*start);
+    }
+    Some(Margin::new(0, 0, 0, 0, MAX_LINE_LENGTH, max_line_len))
+}
+
+#[cfg(test)]
+mod test {
+    use joinery::JoinableIterator;
+
+    use super::*;
+
+    fn minified_js() -> String {
+        let prefix = (0..200).map(|i| format!("var a{i}={i};")).join_concat();
+        let suffix = (0..200).map(|i| format!("var b{i}={i};")).join_concat();
+        format!("{prefix}eval(x);{suffix}\nrun();\n")
+    }
+
+    #[test]
+    fn long_lines_cut() {
+        let source = minified_js();
+        let line = source.lines().next().unwrap();
+        let start = line.find("eval").unwrap();
+        let mut ranges = [(start, start + "eval(x)".len())];
+        assert!(margin(line, &mut ranges).is_some());
+        assert_eq!(ranges, [(start, start + "eval(x)".len())]);
+
+        // Ranges too wide to show are cut, keeping the start of the first.
+        let mut ranges = [(start, line.len()), (0, 10)];
+        margin(line, &mut ranges).unwrap();
+        assert_eq!(
+            ranges,
+            [(start, start + MAX_LINE_LENGTH - 12), (start, start)]
+        );
+
+        // Longer slices are only cut at the end of each line.
+        let run_start = source.find("run").unwrap();
+        let mut ranges = [(start, run_start + 3), (0, line.len())];
+        margin(&source, &mut ranges).unwrap();
+        assert_eq!(ranges, [(start, run_start + 3), (0, MAX_LINE_LENGTH)]);
+
+        assert!(margin("run();\n", &mut [(0, 3)]).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "lang-json")]
+    fn long_lines() {
+        use indoc::indoc;
+
+        use crate::vextest::VexTest;
+
+        let keys = (0..300).map(|i| format!(r#""k{i}":{i}"#)).join_with(",");
+        let content = format!(r#"{{{keys},"target":true}}"#);
+        let column = content.find("target").unwrap() + 1;
+        let irritations = VexTest::new("long-lines")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('json', '(pair key: (string (string_content) @key))', on_match)
+
+                    def on_match(event):
+                        key = event.captures['key']
+                        if str(key) == 'target':
+                            vex.warn('test', 'found target', at=(key, 'here'))
+                "#},
+            )
+            .with_source_file("dist/data.min.json", &content)
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+
+        let rendered = irritations[0].to_string();
+        assert!(
+            rendered.contains(&format!("dist/data.min.json:1:{column}")),
+            "wrong column in:\n{rendered}"
+        );
+        assert!(
+            rendered
+                .lines()
+                .all(|line| line.len() < 2 * MAX_LINE_LENGTH),
+            "line too long in:\n{rendered}"
+        );
+        let column_of = |line: &str, text: &str| line[..line.find(text).unwrap()].chars().count();
+        let source_line = rendered
+            .lines()
+            .find(|line| line.contains("\"target\""))
+            .unwrap();
+        let marker_line = rendered
+            .lines()
+            .find(|line| line.ends_with(" here"))
+            .unwrap();
+        assert!(source_line.contains("..."));
+        assert_eq!(
+            column_of(source_line, "target"),
+            column_of(marker_line, "------ here")
+        );
     }
 }
@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Write},
+};
+
+use crate::{
+    context::ProjectInfo, irritation::Irritation, plural::Plural, source_path::PrettyPath,
+    vex_id::VexId,
+};
+
+/// Problems found in a run, arranged as JUnit test results for CI test-report viewers.
+///
+/// Each check becomes a test suite containing one test case per file in which it found problems.
+/// Checks which found no problems are reported as a single passing test case.
+#[derive(Debug)]
+pub struct JUnitReport<'a> {
+    name: &'a str,
+    suites: BTreeMap<&'a VexId, BTreeMap<Option<&'a PrettyPath>, Vec<&'a Irritation>>>,
+}
+
+impl<'a> JUnitReport<'a> {
+    /// The name of the test case holding problems which concern the whole project.
+    const PROJECT_CASE_NAME: &'static str = "(project)";
+
+    /// The name of the test case of a check which found no problems.
+    const PASSED_CASE_NAME: &'static str = "(all files)";
+
+    pub fn new(
+        project: &'a ProjectInfo,
+        check_ids: impl IntoIterator<Item = &'a VexId>,
+        irritations: &'a [Irritation],
+    ) -> Self {
+        let mut suites: BTreeMap<_, BTreeMap<_, Vec<_>>> = check_ids
+            .into_iter()
+            .map(|vex_id| (vex_id, BTreeMap::new()))
+            .collect();
+        for irritation in irritations {
+            suites
+                .entry(irritation.vex_id())
+                .or_default()
+                .entry(irritation.path())
+                .or_default()
+                .push(irritation);
+        }
+        Self {
+            name: project.name.as_deref().unwrap_or("vex"),
+            suites,
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        self.write_xml(&mut xml)
+            .expect("internal error: cannot write to string");
+        xml
+    }
+
+    fn write_xml(&self, xml: &mut String) -> fmt::Result {
+        let num_tests = |cases: &BTreeMap<_, _>| cases.len().max(1);
+        writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            xml,
+            r#"<testsuites name="{}" tests="{}" failures="{}">"#,
+            Escaped(self.name),
+            self.suites.values().map(num_tests).sum::<usize>(),
+            self.suites.values().map(BTreeMap::len).sum::<usize>(),
+        )?;
+        for (vex_id, cases) in &self.suites {
+            let vex_id = Escaped(vex_id.as_ref());
+            writeln!(
+                xml,
+                r#"  <testsuite name="{vex_id}" tests="{}" failures="{}">"#,
+                num_tests(cases),
+                cases.len(),
+            )?;
+            if cases.is_empty() {
+                writeln!(
+                    xml,
+                    r#"    <testcase classname="{vex_id}" name="{}"/>"#,
+                    Self::PASSED_CASE_NAME,
+                )?;
+            }
+            for (path, irritations) in cases {
+                let name = path.map_or(Self::PROJECT_CASE_NAME, |path| path.as_str());
+                let severity = irritations
+                    .iter()
+                    .map(|irritation| irritation.severity())
+                    .max()
+                    .unwrap_or_default();
+                writeln!(
+                    xml,
+                    r#"    <testcase classname="{vex_id}" name="{}" file="{}">"#,
+                    Escaped(name),
+                    Escaped(path.map_or("", |path| path.as_str())),
+                )?;
+                write!(
+                    xml,
+                    r#"      <failure type="{severity}" message="found {}">"#,
+                    Plural::new(irritations.len(), "problem", "problems"),
+                )?;
+                for irritation in irritations {
+                    if let Some(location) = irritation.location() {
+                        write!(
+                            xml,
+                            "{}:{}: ",
+                            location.start_row,
+                            location.start_column + 1
+                        )?;
+                    }
+                    writeln!(xml, "{}", Escaped(irritation.message()))?;
+                }
+                writeln!(xml, "</failure>")?;
+                writeln!(xml, "    </testcase>")?;
+            }
+            writeln!(xml, "  </testsuite>")?;
+        }
+        writeln!(xml, "</testsuites>")
+    }
+}
+
+/// Text escaped for use in XML content and attribute values.
+struct Escaped<'a>(&'a str);
+
+impl Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.chars().try_for_each(|c| match c {
+            '&' => f.write_str("&amp;"),
+            '<' => f.write_str("&lt;"),
+            '>' => f.write_str("&gt;"),
+            '"' => f.write_str("&quot;"),
+            '\'' => f.write_str("&apos;"),
+            _ => f.write_char(c),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn report() {
+        let irritations = VexTest::new("junit")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+                        vex.warn('project-check', 'found <problem> & "more"')
+
+                    def on_match(event):
+                        vex.warn('literal-check', 'literal found', at=event.captures['lit'])
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {\n    let x = 1 + 2;\n}\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let project = ProjectInfo {
+            name: Some("example".into()),
+            ..ProjectInfo::default()
+        };
+        let quiet_check = VexId::try_from("quiet-check".to_owned()).unwrap();
+        let xml = JUnitReport::new(&project, [&quiet_check], &irritations).to_xml();
+        assert_eq!(
+            xml,
+            indoc! {r#"
+                <?xml version="1.0" encoding="UTF-8"?>
+                <testsuites name="example" tests="3" failures="2">
+                  <testsuite name="literal-check" tests="1" failures="1">
+                    <testcase classname="literal-check" name="src/main.rs" file="src/main.rs">
+                      <failure type="warning" message="found 2 problems">2:13: literal found
+                2:17: literal found
+                </failure>
+                    </testcase>
+                  </testsuite>
+                  <testsuite name="project-check" tests="1" failures="1">
+                    <testcase classname="project-check" name="(project)" file="">
+                      <failure type="warning" message="found 1 problem">found &lt;problem&gt; &amp; &quot;more&quot;
+                </failure>
+                    </testcase>
+                  </testsuite>
+                  <testsuite name="quiet-check" tests="1" failures="0">
+                    <testcase classname="quiet-check" name="(all files)"/>
+                  </testsuite>
+                </testsuites>
+            "#}
+        );
+    }
+}
@@ -0,0 +1,1136 @@
+//! Vex is a blazingly-fast, hackable linter whose checks are written in Starlark.
+//!
+//! Besides the `vex` command-line tool, this crate exposes the scanning engine so that other
+//! tools can run vexes directly. A scan has three stages: the project [`Context`] is acquired,
+//! scriptlets are loaded into a [`PreinitingStore`] and then initialised into a [`VexingStore`],
+//! and finally the project is scanned with [`scan_project`], producing [`Irritation`]s.
+//!
+//! ```no_run
+//! use vex::{
+//!     Context, InitOptions, MaxConcurrentFileLimit, PreinitOptions, PreinitingStore,
+//!     ScanOptions, SortBy,
+//! };
+//!
+//! # fn main() -> vex::Result<()> {
+//! let ctx = Context::acquire("path/to/project".as_ref())?;
+//! let store = PreinitingStore::new(&vex::sources_in_project(&ctx)?)?
+//!     .preinit(PreinitOptions::default())?
+//!     .init(InitOptions::default())?;
+//! let run_data = vex::scan_project(
+//!     &ctx,
+//!     &store,
+//!     ScanOptions {
+//!         max_concurrent_files: MaxConcurrentFileLimit::new(1),
+//!         sort_by: SortBy::Severity,
+//!         ..ScanOptions::default()
+//!     },
+//! )?;
+//! for irritation in &run_data.irritations {
+//!     if let (Some(path), Some(location)) = (irritation.path(), irritation.location()) {
+//!         println!("{path}:{}: {}", location.start_row, irritation.message());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#![deny(missing_debug_implementations)]
+
+#[cfg(test)]
+#[macro_use]
+extern crate pretty_assertions;
+
+mod associations;
+mod cache;
+mod check_metadata;
+mod check_selection;
+mod cli;
+#[doc(hidden)]
+pub mod commands;
+mod context;
+mod doctest;
+mod dump;
+mod editor;
+mod error;
+mod external;
+mod fingerprint;
+mod fix;
+mod git;
+mod grammar;
+mod hook;
+mod ignore_markers;
+mod import;
+mod injection;
+mod irritation;
+mod junit;
+mod lock;
+mod logger;
+mod lsp;
+mod manifest_keys;
+mod plural;
+mod prompt;
+mod query;
+mod query_plan;
+mod ratchet;
+mod result;
+mod results;
+mod scaffold;
+mod scan;
+mod scriptlets;
+mod severity;
+mod source_file;
+mod source_path;
+mod suggestion;
+mod summary;
+mod supported_language;
+mod tags;
+mod test;
+mod theme;
+mod timings;
+mod trigger;
+mod tui;
+mod verbosity;
+mod vex_id;
+
+#[cfg(test)]
+mod vextest;
+
+pub use crate::{
+    cache::Cache,
+    cli::{MaxConcurrentFileLimit, MaxProblems, SortBy},
+    context::{Context, Manifest},
+    error::Error,
+    fix::{Fix, FixedFile},
+    irritation::Irritation,
+    result::Result,
+    scan::{scan_project, ProjectRunData, ScanOptions},
+    scriptlets::{
+        artifact_cache::ArtifactCache,
+        intent_log::IntentLog,
+        source::{sources_in_dir, sources_in_project, FileSource, ScriptSource},
+        InitOptions, Location, PreinitOptions, PreinitingStore, VexingStore,
+    },
+    severity::{Severity, SeverityOverrides},
+    source_path::{PrettyPath, SourcePath},
+    supported_language::SupportedLanguage,
+    verbosity::Verbosity,
+    vex_id::VexId,
+};
+#[cfg(test)]
+mod test_ {
+    use std::collections::BTreeMap;
+
+    use indoc::{formatdoc, indoc};
+    use insta::assert_yaml_snapshot;
+    use joinery::JoinableIterator;
+
+    use crate::{
+        check_selection::CheckSelection,
+        cli::{MaxProblems, SortBy},
+        scan::ProjectRunData,
+        severity::Severity,
+        vextest::VexTest,
+    };
+
+    #[test]
+    fn max_problems() {
+        const MAX: u32 = 47;
+        let run_data = VexTest::new("max-problems")
+            .with_max_problems(MaxProblems::Limited(MAX))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search(
+                            'rust',
+                            '(integer_literal) @num',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        vex.warn('test', 'oh no a number!', at=(event.captures['num'], 'num'))
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        let x = 1 + 2 + 3 + 4 + 5 + 6 + 8 + 9 + 10;
+                        println!("{x}");
+                    }
+                "#},
+            )
+            .try_run()
+            .unwrap();
+        assert_eq!(run_data.irritations.len(), MAX as usize);
+        assert_eq!(
+            run_data.num_hidden.values().sum::<usize>(),
+            9 * 10 - MAX as usize
+        );
+        assert!(!run_data.stopped_early);
+        assert_eq!(
+            run_data.overflow_hint().unwrap(),
+            indoc! {"
+                43 more problems hidden by --max-problems (test: 43)
+                to see all problems, use `--max-problems unlimited` or narrow the scan with `--changed` or `--only-tags`"
+            }
+        );
+    }
+
+    #[test]
+    fn max_problems_per_check() {
+        let run = |max_problems_per_check: Option<u32>, manifest: &str| {
+            let test = VexTest::new("max-problems-per-check")
+                .with_manifest(formatdoc! {"
+                    [vex]
+                    version = '1'
+                    {manifest}
+                "})
+                .with_scriptlet(
+                    "vexes/test.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+                            vex.search('rust', '(identifier) @id', on_match)
+
+                        def on_match(event):
+                            if 'num' in event.captures:
+                                vex.warn('numbers', 'number', at=event.captures['num'])
+                            else:
+                                vex.warn('names', 'name', at=event.captures['id'])
+                    "#},
+                )
+                .with_source_file("src/main.rs", "fn main() { let x = 1 + 2 + 3 + 4; }");
+            match max_problems_per_check {
+                Some(max) => test.with_max_problems_per_check(max),
+                None => test,
+            }
+            .try_run()
+            .unwrap()
+        };
+        let counts = |run_data: &ProjectRunData| {
+            let mut counts = BTreeMap::<String, usize>::new();
+            for irritation in &run_data.irritations {
+                *counts.entry(irritation.vex_id().to_string()).or_default() += 1;
+            }
+            counts
+        };
+
+        let run_data = run(None, "");
+        assert_eq!(
+            counts(&run_data),
+            [("names".into(), 2), ("numbers".into(), 4)].into()
+        );
+        assert!(run_data.truncation_notes().is_empty());
+
+        let run_data = run(Some(3), "");
+        assert_eq!(
+            counts(&run_data),
+            [("names".into(), 2), ("numbers".into(), 3)].into()
+        );
+        assert_eq!(
+            run_data.truncation_notes(),
+            ["… and 1 more from check numbers"]
+        );
+
+        let run_data = run(None, "[check.numbers]\nmax-problems = 1");
+        assert_eq!(
+            counts(&run_data),
+            [("names".into(), 2), ("numbers".into(), 1)].into()
+        );
+        assert_eq!(
+            run_data.truncation_notes(),
+            ["… and 3 more from check numbers"]
+        );
+
+        let run_data = run(Some(1), "[check.numbers]\nmax-problems = 2");
+        assert_eq!(
+            counts(&run_data),
+            [("names".into(), 1), ("numbers".into(), 1)].into()
+        );
+        assert_eq!(
+            run_data.truncation_notes(),
+            [
+                "… and 1 more from check names",
+                "… and 3 more from check numbers"
+            ]
+        );
+        assert!(run_data.num_hidden.is_empty());
+    }
+
+    #[test]
+    #[cfg(all(feature = "lang-rust", feature = "lang-toml"))]
+    fn injections() {
+        const INJECTIONS: &str = indoc! {r#"
+            [vex]
+            version = "1"
+
+            [[injections]]
+            language = "rust"
+            query = '''
+                (macro_invocation
+                    macro: (identifier) @macro (#eq? @macro "config")
+                    (token_tree (string_literal (string_content) @injection.content)))
+            '''
+            injected-language = "toml"
+
+            [[injections]]
+            language = "rust"
+            query = '''
+                (macro_invocation
+                    macro: (identifier) @injection.language
+                    (token_tree (string_literal (string_content) @injection.content)))
+            '''
+        "#};
+        let irritations = VexTest::new("injections")
+            .with_manifest(INJECTIONS)
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('toml', '(pair (bare_key) @key)', on_match)
+
+                    def on_match(event):
+                        key = event.captures['key']
+                        vex.warn('keys', str(key), at=key)
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        config!("fixed = 1");
+                        toml!("named = 2");
+                        unknown!("unknown = 3");
+                        toml!("broken = ");
+                    }
+                "#},
+            )
+            .with_source_file("Cargo.toml", "outer = 4")
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irr| {
+                let location = irr.location().unwrap();
+                format!(
+                    "{}:{}:{} {}",
+                    irr.path().unwrap(),
+                    location.start_row,
+                    location.start_column,
+                    irr.message()
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            irritations,
+            [
+                "Cargo.toml:1:0 outer",
+                "src/main.rs:2:13 fixed",
+                "src/main.rs:3:11 named",
+            ]
+        );
+
+        VexTest::new("injection-without-content")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [[injections]]
+                language = "rust"
+                query = '(string_literal) @content'
+                injected-language = "toml"
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        pass
+                "#},
+            )
+            .returns_error("query must capture @injection.content");
+    }
+
+    #[test]
+    fn sort_order() {
+        let run = |sort_by| {
+            VexTest::new("sort-order")
+                .with_sort_by(sort_by)
+                .with_scriptlet(
+                    "vexes/test.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+                            vex.warn('zzz-project', 'project-wide')
+
+                        def on_match(event):
+                            num = event.captures['num']
+                            if str(num) == '1':
+                                vex.error('bbb-ones', 'one', at=num)
+                            else:
+                                vex.advice('aaa-others', 'other', at=num)
+                    "#},
+                )
+                .with_source_file("src/a.rs", "const A: (u32, u32) = (2, 1);")
+                .with_source_file("src/b.rs", "const B: (u32, u32) = (1, 3);")
+                .try_run()
+                .unwrap()
+                .irritations
+                .into_iter()
+                .map(|irr| {
+                    let path = irr.path().map(|path| path.to_string()).unwrap_or_default();
+                    format!("{path} {} {}", irr.vex_id(), irr.message())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            run(SortBy::File),
+            [
+                " zzz-project project-wide",
+                "src/a.rs aaa-others other",
+                "src/a.rs bbb-ones one",
+                "src/b.rs bbb-ones one",
+                "src/b.rs aaa-others other",
+            ]
+        );
+        assert_eq!(
+            run(SortBy::Check),
+            [
+                "src/a.rs aaa-others other",
+                "src/b.rs aaa-others other",
+                "src/a.rs bbb-ones one",
+                "src/b.rs bbb-ones one",
+                " zzz-project project-wide",
+            ]
+        );
+        assert_eq!(
+            run(SortBy::Severity),
+            [
+                "src/a.rs bbb-ones one",
+                "src/b.rs bbb-ones one",
+                " zzz-project project-wide",
+                "src/a.rs aaa-others other",
+                "src/b.rs aaa-others other",
+            ]
+        );
+        for sort_by in [SortBy::File, SortBy::Check, SortBy::Severity] {
+            assert_eq!(run(sort_by), run(sort_by));
+        }
+    }
+
+    #[test]
+    fn many_files() {
+        const NUM_FILES: usize = 200;
+        let run = |max_problems| {
+            (0..NUM_FILES)
+                .fold(VexTest::new("many-files"), |test, i| {
+                    test.with_source_file(format!("src/file_{i}.rs"), "const X: u32 = 1;")
+                })
+                .with_max_problems(max_problems)
+                .with_scriptlet(
+                    "vexes/test.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+
+                        def on_match(event):
+                            vex.warn('test', 'oh no a number!', at=event.captures['num'])
+                    "#},
+                )
+                .try_run()
+                .unwrap()
+        };
+
+        let run_data = run(MaxProblems::Unlimited);
+        assert_eq!(run_data.num_files_scanned as usize, NUM_FILES);
+        assert_eq!(run_data.irritations.len(), NUM_FILES);
+
+        // Files may be read ahead of being scanned, so stopping early must not block reading.
+        let run_data = run(MaxProblems::Limited(1));
+        assert_eq!(run_data.irritations.len(), 1);
+        assert!(run_data.stopped_early);
+    }
+
+    #[test]
+    fn max_problems_counts_reported() {
+        const NUM_FILES: usize = 100;
+        let run_data = (0..NUM_FILES)
+            .fold(VexTest::new("max-problems-counts-reported"), |test, i| {
+                test.with_source_file(format!("src/file_{i}.rs"), "const X: u32 = 1;")
+            })
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [checks]
+                quiet = "allow"
+
+                [check.exempt]
+                exclude = ["src/**"]
+
+                [check.limited]
+                max-problems = 1
+            "#})
+            .with_max_problems(MaxProblems::Limited(NUM_FILES as u32 + 1))
+            .with_report_level(Severity::Warning)
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        num = event.captures['num']
+                        vex.warn('repeated', 'oh no a number!', at=num)
+                        vex.warn('repeated', 'oh no a number!', at=num)
+                        vex.warn('exempt', 'oh no a number!', at=num)
+                        vex.warn('quiet', 'oh no a number!', at=num)
+                        vex.warn('limited', 'oh no a number!', at=num)
+                "#},
+            )
+            .try_run()
+            .unwrap();
+
+        // Only one problem from each file and one from the limited check are reported.
+        assert!(!run_data.stopped_early);
+        assert!(run_data.num_hidden.is_empty());
+        assert_eq!(run_data.num_files_scanned as usize, NUM_FILES);
+        let num_reported = run_data
+            .irritations
+            .iter()
+            .filter(|irritation| irritation.severity() >= Severity::Warning)
+            .count();
+        assert_eq!(num_reported, NUM_FILES + 1);
+    }
+
+    #[test]
+    fn unscannable_files() {
+        let test = |files_config: &str| {
+            VexTest::new("unscannable-files")
+                .with_manifest(format!("[vex]\nversion = '1'\n\n[files]\n{files_config}"))
+                .with_source_file("src/main.rs", "const X: u32 = 1;")
+                .with_source_file("src/big.rs", "const X: u32 = 1; // padding\n")
+                .with_source_file("src/blob.rs", "const X: u32 = 1;\0")
+                .with_scriptlet(
+                    "vexes/test.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+
+                        def on_match(event):
+                            vex.warn('test', 'oh no a number!', at=event.captures['num'])
+                    "#},
+                )
+        };
+
+        let run_data = test("max-file-size = 20").try_run().unwrap();
+        let paths: Vec<_> = run_data
+            .irritations
+            .iter()
+            .map(|irritation| irritation.path().unwrap().as_str())
+            .collect();
+        assert_eq!(paths, ["src/main.rs"]);
+
+        test("max-file-size = 20\nnon-utf8 = 'error'")
+            .returns_error("src/blob.rs appears to be binary");
+    }
+
+    #[test]
+    fn exempt_paths() {
+        let irritations = VexTest::new("exempt-paths")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [check.numbers]
+                exclude = ["legacy/**"]
+
+                [check.literals]
+                allow = ["src/**"]
+            "#})
+            .with_source_file("src/main.rs", "const X: u32 = 1;")
+            .with_source_file("src/legacy/old.rs", "const X: u32 = 1;")
+            .with_source_file("tests/main.rs", "const X: u32 = 1;")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        vex.warn('numbers', 'found number', at=event.captures['num'])
+                        vex.warn('literals', 'found literal', at=event.captures['num'])
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        let found: Vec<_> = irritations
+            .iter()
+            .map(|irritation| {
+                format!(
+                    "{}: {}",
+                    irritation.vex_id(),
+                    irritation.path().unwrap().as_str()
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "literals: src/legacy/old.rs",
+                "literals: src/main.rs",
+                "numbers: src/main.rs",
+                "numbers: tests/main.rs",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let irritations = VexTest::new("ignored-paths")
+            .with_source_file("src/main.rs", "const X: u32 = 1;")
+            .with_source_file("tests/main.rs", "const X: u32 = 1;")
+            .with_scriptlet(
+                "vexes/numbers.star",
+                indoc! {r#"
+                    def init():
+                        vex.ignore_paths(['tests/**'])
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('open_file', on_file_event)
+                        vex.observe('close_file', on_file_event)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_file_event(event):
+                        vex.warn('numbers', 'saw %s' % event.name, at=(event.path, 'here'))
+
+                    def on_match(event):
+                        vex.warn('numbers', 'found number', at=event.captures['num'])
+                "#},
+            )
+            .with_scriptlet(
+                "vexes/literals.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        vex.warn('literals', 'found literal', at=event.captures['num'])
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        let found: Vec<_> = irritations
+            .iter()
+            .map(|irritation| {
+                format!(
+                    "{}: {}: {}",
+                    irritation.vex_id(),
+                    irritation.path().unwrap().as_str(),
+                    irritation.message(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "numbers: src/main.rs: saw close_file",
+                "numbers: src/main.rs: saw open_file",
+                "literals: src/main.rs: found literal",
+                "numbers: src/main.rs: found number",
+                "literals: tests/main.rs: found literal",
+            ]
+        );
+
+        VexTest::new("ignored-paths-outside-init")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.ignore_paths(['tests/**'])
+                "#},
+            )
+            .returns_error("vex.ignore_paths unavailable while opening project");
+        VexTest::new("ignored-paths-invalid")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.ignore_paths(['[tests'])
+                "#},
+            )
+            .returns_error(r#"cannot compile "\[tests""#);
+    }
+
+    #[test]
+    fn selection() {
+        let run = |filter: &[&str], skip: &[&str]| {
+            let ids = |ids: &[&str]| ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+            VexTest::new("selection")
+                .with_selection(CheckSelection::new(&ids(filter), &ids(skip)).unwrap())
+                .with_source_file("src/main.rs", "const X: u32 = 1;")
+                .with_scriptlet(
+                    "vexes/numbers.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+
+                        def on_match(event):
+                            vex.warn('numbers', 'found number', at=event.captures['num'])
+                    "#},
+                )
+                .with_scriptlet("vexes/broken.star", "fail('broken scriptlet evaluated')")
+                .try_run()
+                .map(|run_data| run_data.irritations.len())
+        };
+
+        assert_eq!(run(&["num*"], &[]).unwrap(), 1);
+        assert_eq!(run(&[], &["broken"]).unwrap(), 1);
+        assert_eq!(run(&["numbers"], &["numbers"]).unwrap(), 0);
+        assert!(run(&[], &[])
+            .unwrap_err()
+            .to_string()
+            .contains("broken scriptlet evaluated"));
+    }
+
+    #[test]
+    fn shared_queries() {
+        let scriptlet = |id| {
+            formatdoc! {r#"
+                def init():
+                    vex.declare('{id}', allow_duplicates=True)
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search('rust', '(integer_literal) @num', on_match)
+                    vex.search('rust', '(integer_literal) @num', on_match)
+
+                def on_match(event):
+                    vex.warn('{id}', 'found %s' % event.captures['num'], at=event.captures['num'])
+            "#}
+        };
+        let irritations = VexTest::new("shared-queries")
+            .with_source_file("src/main.rs", "const X: u32 = 1;")
+            .with_scriptlet("vexes/a.star", scriptlet("check-a"))
+            .with_scriptlet("vexes/b.star", scriptlet("check-b"))
+            .try_run()
+            .unwrap()
+            .irritations;
+        let found: Vec<_> = irritations
+            .iter()
+            .map(|irritation| format!("{}: {}", irritation.vex_id(), irritation.message()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "check-a: found 1",
+                "check-a: found 1",
+                "check-b: found 1",
+                "check-b: found 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn single_pass_queries() {
+        let scriptlet = |id, query| {
+            formatdoc! {r#"
+                def init():
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search('rust', '{query}', on_match)
+
+                def on_match(event):
+                    captures = event.captures
+                    vex.warn('{id}', '%s' % sorted(captures.keys()), at=captures.values()[0])
+            "#}
+        };
+        let run = |single_pass| {
+            VexTest::new("single-pass-queries")
+                .with_manifest(format!(
+                    "[vex]\nversion = '1'\nsingle-pass-queries = {single_pass}"
+                ))
+                .with_source_file("src/main.rs", "const X: &str = \"x\";\nconst Y: u32 = 1;")
+                .with_scriptlet(
+                    "vexes/a.star",
+                    scriptlet("check-a", "(integer_literal) @num"),
+                )
+                .with_scriptlet(
+                    "vexes/b.star",
+                    scriptlet("check-b", "(const_item name: (identifier) @name) @item"),
+                )
+                .with_scriptlet(
+                    "vexes/c.star",
+                    scriptlet("check-c", "(string_literal) @num"),
+                )
+                .try_run()
+                .unwrap()
+                .irritations
+                .iter()
+                .map(|irritation| format!("{}: {}", irritation.vex_id(), irritation.message()))
+                .collect::<Vec<_>>()
+        };
+        let found = run(true);
+        assert_eq!(found, run(false));
+        assert_eq!(
+            found,
+            [
+                "check-b: [\"item\", \"name\"]",
+                "check-c: [\"num\"]",
+                "check-b: [\"item\", \"name\"]",
+                "check-a: [\"num\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn packs() {
+        let scriptlet = indoc! {r#"
+            def init():
+                vex.observe('open_project', on_open_project)
+
+            def on_open_project(event):
+                vex.search('rust', '(integer_literal) @num', on_match)
+
+            def on_match(event):
+                vex.warn('numbers', 'found number', at=event.captures['num'])
+        "#};
+        let irritations = VexTest::new("packs")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [check."acme:numbers"]
+                exclude = ["legacy/**"]
+            "#})
+            .with_source_file("src/main.rs", "const X: u32 = 1;")
+            .with_source_file("src/legacy/old.rs", "const X: u32 = 1;")
+            .with_scriptlet("vexes/test.star", scriptlet)
+            .with_pack_scriptlet("acme", "test.star", scriptlet)
+            .try_run()
+            .unwrap()
+            .irritations;
+        let found: Vec<_> = irritations
+            .iter()
+            .map(|irritation| {
+                format!(
+                    "{}: {}",
+                    irritation.vex_id(),
+                    irritation.path().unwrap().as_str()
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "numbers: src/legacy/old.rs",
+                "acme:numbers: src/main.rs",
+                "numbers: src/main.rs",
+            ]
+        );
+    }
+
+    #[test]
+    fn buffers() {
+        let run = |path| {
+            VexTest::new("buffers")
+                .with_manifest(indoc! {r#"
+                    [vex]
+                    version = "1"
+
+                    [files]
+                    ignore = ["generated/"]
+
+                    [check.numbers]
+                    exclude = ["legacy/**"]
+                "#})
+                .with_source_file("src/main.rs", "fn main() {}")
+                .with_source_file("src/lib.rs", "const X: u32 = 1;")
+                .with_buffer(path, "const X: u32 = 1 + 2;")
+                .with_scriptlet(
+                    "vexes/test.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+
+                        def on_match(event):
+                            vex.warn('numbers', 'found number', at=event.captures['num'])
+                    "#},
+                )
+                .try_run()
+                .unwrap()
+        };
+
+        let run_data = run("src/main.rs");
+        let found: Vec<_> = run_data
+            .irritations
+            .iter()
+            .map(|irritation| {
+                let location = irritation.location().unwrap();
+                format!(
+                    "{}:{}:{}",
+                    irritation.path().unwrap(),
+                    location.start_row,
+                    location.start_column
+                )
+            })
+            .collect();
+        assert_eq!(found, ["src/main.rs:1:15", "src/main.rs:1:19"]);
+        assert_eq!(run_data.num_files_scanned, 1);
+
+        assert_eq!(run("src/legacy/old.rs").irritations, []);
+        assert_eq!(run("generated/mod.rs").irritations, []);
+        assert_eq!(run(".hidden/main.rs").irritations, []);
+
+        let run_data = run("README.md");
+        assert_eq!(run_data.num_files_scanned, 0);
+        assert_eq!(run_data.num_skipped.get(".md"), Some(&1));
+    }
+
+    #[test]
+    fn buffers_skip_close_project() {
+        let run = |buffer: Option<&str>| {
+            let test = VexTest::new("buffers-skip-close-project")
+                .with_source_file("src/lib.rs", "const X: u32 = 1;")
+                .with_scriptlet(
+                    "vexes/test.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_file', on_open_file)
+                            vex.observe('close_project', on_close_project)
+
+                        def on_open_file(event):
+                            vex.warn('per-file', str(event.path), at=event.root)
+
+                        def on_close_project(event):
+                            vex.warn('project-wide', 'closed')
+                    "#},
+                );
+            let test = match buffer {
+                Some(path) => test.with_buffer(path, "const Y: u32 = 2;"),
+                None => test,
+            };
+            test.try_run()
+                .unwrap()
+                .irritations
+                .iter()
+                .map(|irritation| format!("{}: {}", irritation.vex_id(), irritation.message()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(None), ["project-wide: closed", "per-file: src/lib.rs"]);
+        assert_eq!(run(Some("src/main.rs")), ["per-file: src/main.rs"]);
+    }
+
+    #[test]
+    fn skipped_files() {
+        let run_data = VexTest::new("skipped-files")
+            .with_source_file("src/main.rs", "fn main() {}")
+            .with_source_file("README.md", "# hello")
+            .with_source_file("docs/guide.md", "# guide")
+            .with_source_file("Makefile", "all:")
+            .with_source_file("Cargo.lock", "")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        pass
+                "#},
+            )
+            .try_run()
+            .unwrap();
+        assert_eq!(
+            run_data.skipped_table().unwrap(),
+            indoc! {"
+                skipped 4 files in no known language:
+                  .md     2
+                  (none)  1
+                  .lock   1"
+            }
+        );
+    }
+
+    #[test]
+    fn required_checks() {
+        let test = |require: &str| {
+            VexTest::new("required-checks")
+                .with_manifest(format!(
+                    "[vex]\nversion = '1'\nrequire-checks-for = [{require}]"
+                ))
+                .with_scriptlet(
+                    "vexes/rust.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @num', on_match)
+
+                        def on_match(event):
+                            pass
+                    "#},
+                )
+                .with_scriptlet(
+                    "vexes/go.star",
+                    indoc! {r#"
+                        def init():
+                            vex.declare('go-check', languages=['go'])
+                    "#},
+                )
+        };
+        test("'rust', 'go'").try_run().unwrap();
+        test("'rust', 'python'").returns_error(
+            "^no active check searches python, as required by require-checks-for in vex.toml$",
+        );
+        test("'cobol'").returns_error("^unsupported language 'cobol'$");
+    }
+
+    #[test]
+    fn duplicate_warnings() {
+        let irritations = VexTest::new("duplicate-warnings")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('deduplicated')
+                        vex.declare('duplicated', allow_duplicates=True)
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('close_project', on_close_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+                        vex.search('rust', '((integer_literal) @num)', on_match)
+
+                    def on_match(event):
+                        num = event.captures['num']
+                        for vex_id in ['deduplicated', 'duplicated']:
+                            vex.warn(vex_id, 'integer', at=num)
+                        vex.warn('deduplicated', 'integer %s' % num, at=num)
+
+                    def on_close_project(event):
+                        for vex_id in ['deduplicated', 'duplicated']:
+                            vex.warn(vex_id, 'project')
+                            vex.warn(vex_id, 'project')
+                "#},
+            )
+            .with_source_file("src/main.rs", "const X: i32 = 1 + 2;")
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irr| {
+                let location = irr
+                    .location()
+                    .map(|location| format!(":{}", location.start_column))
+                    .unwrap_or_default();
+                format!("{} {}{location}", irr.vex_id(), irr.message())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            irritations,
+            [
+                "deduplicated project",
+                "duplicated project",
+                "duplicated project",
+                "deduplicated integer:15",
+                "deduplicated integer 1:15",
+                "duplicated integer:15",
+                "duplicated integer:15",
+                "deduplicated integer:19",
+                "deduplicated integer 2:19",
+                "duplicated integer:19",
+                "duplicated integer:19",
+            ]
+        );
+    }
+
+    #[test]
+    fn readme() {
+        // Dumb hacky test to serve until mdbook docs are made and tested.
+        let collate_snippets = |language| {
+            include_str!("../README.md")
+                .lines()
+                .scan(false, |collate_starlark, line| {
+                    Some(if let Some(stripped) = line.strip_prefix("```") {
+                        *collate_starlark = stripped.starts_with(language);
+                        None
+                    } else if *collate_starlark {
+                        Some(line)
+                    } else {
+                        None
+                    })
+                })
+                .flatten()
+                .join_with("\n")
+                .to_string()
+        };
+        let collated_starlark_snippets = collate_snippets("python");
+        let collated_rust_snippets = collate_snippets("rust");
+        let irritations = VexTest::new("README-snippets")
+            .with_scriptlet("vexes/distracting_operand.star", collated_starlark_snippets)
+            .with_source_file("src/main.rs", collated_rust_snippets)
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irr| irr.to_string())
+            .collect::<Vec<_>>();
+        assert_yaml_snapshot!(irritations);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cli::SyncCmd,
+    context::{self, Manifest},
+    error::{Error, IOAction},
+    grammar::GrammarConfig,
+    result::Result,
+    source_path::PrettyPath,
+    success,
+};
+
+pub fn sync(cmd: SyncCmd) -> Result<()> {
+    // Grammars are not loaded, as only those already locked may be.
+    let (project_root, raw_manifest) = Manifest::acquire_content(&context::current_dir()?)?;
+    let lockfile = Lockfile::resolve(&project_root, &GrammarConfig::all_in(&raw_manifest)?)?;
+    if cmd.locked {
+        lockfile.verify(&Lockfile::read(&project_root)?)?;
+        success!("{} is up to date", Lockfile::FILE_NAME);
+    } else {
+        lockfile.write(&project_root)?;
+        success!("wrote {}", Lockfile::FILE_NAME);
+    }
+    Ok(())
+}
+
+/// The exact artifacts a project depends on, as recorded in `vex.lock`, so that every machine
+/// which checks the project can be sure it finds the same problems.
+#[derive(Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+pub struct Lockfile {
+    version: LockfileVersion,
+
+    #[serde(default, rename = "grammar", skip_serializing_if = "Vec::is_empty")]
+    grammars: Vec<LockedGrammar>,
+}
+
+impl Lockfile {
+    pub const FILE_NAME: &'static str = "vex.lock";
+
+    const HEADER: &'static str =
+        "# This file is generated by `vex sync`, do not edit it by hand.\n\n";
+
+    /// Compute the lockfile which describes the project as it is now.
+    pub fn resolve(project_root: &Utf8Path, grammars: &[GrammarConfig]) -> Result<Self> {
+        let grammars = grammars
+            .iter()
+            .map(|config| {
+                let content =
+                    fs::read(project_root.join(&config.library)).map_err(|cause| Error::IO {
+                        path: PrettyPath::new(&config.library),
+                        action: IOAction::Read,
+                        cause,
+                    })?;
+                Ok(LockedGrammar {
+                    name: config.name.clone(),
+                    library: config.library.clone(),
+                    sha256: Self::sha256(&content),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: LockfileVersion::V1,
+            grammars,
+        })
+    }
+
+    pub fn read(project_root: &Utf8Path) -> Result<Self> {
+        let path = project_root.join(Self::FILE_NAME);
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(cause) if cause.kind() == ErrorKind::NotFound => {
+                return Err(Error::LockfileNotFound)
+            }
+            Err(cause) => {
+                return Err(Error::IO {
+                    path: PrettyPath::from(Self::FILE_NAME),
+                    action: IOAction::Read,
+                    cause,
+                })
+            }
+        };
+        Ok(toml_edit::de::from_str(&raw)?)
+    }
+
+    pub fn write(&self, project_root: &Utf8Path) -> Result<()> {
+        let content = toml_edit::ser::to_string_pretty(self)
+            .expect("internal error: cannot serialise lockfile");
+        fs::write(
+            project_root.join(Self::FILE_NAME),
+            format!("{}{content}", Self::HEADER),
+        )
+        .map_err(|cause| Error::IO {
+            path: PrettyPath::from(Self::FILE_NAME),
+            action: IOAction::Write,
+            cause,
+        })
+    }
+
+    /// The hash of the given library as locked, if it is.
+    pub fn locked_sha256(&self, name: &str, library: &Utf8Path) -> Option<&str> {
+        self.grammars
+            .iter()
+            .find(|grammar| grammar.name == name && grammar.library == library)
+            .map(|grammar| grammar.sha256.as_str())
+    }
+
+    pub fn sha256(content: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(content))
+    }
+
+    /// Check that the given locked state matches this one, listing the differences if not.
+    fn verify(&self, locked: &Self) -> Result<()> {
+        let current: BTreeMap<_, _> = self
+            .grammars
+            .iter()
+            .map(|grammar| (&grammar.name, grammar))
+            .collect();
+        let locked: BTreeMap<_, _> = locked
+            .grammars
+            .iter()
+            .map(|grammar| (&grammar.name, grammar))
+            .collect();
+        let mut differences = vec![];
+        for (name, grammar) in &current {
+            match locked.get(name) {
+                None => differences.push(format!("grammar '{name}' is not locked")),
+                Some(locked_grammar) if locked_grammar != grammar => {
+                    differences.push(format!("grammar '{name}' has changed"))
+                }
+                Some(_) => {}
+            }
+        }
+        for name in locked.keys().filter(|name| !current.contains_key(*name)) {
+            differences.push(format!("grammar '{name}' is locked but no longer declared"));
+        }
+        if !differences.is_empty() {
+            return Err(Error::LockfileOutdated(differences));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+enum LockfileVersion {
+    #[default]
+    #[serde(rename = "1")]
+    V1,
+}
+
+/// A grammar plugin, identified by the hash of its shared library.
+#[derive(Debug, PartialEq, Eq, Deserialise, Serialise)]
+struct LockedGrammar {
+    name: String,
+    library: Utf8PathBuf,
+    sha256: String,
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::context::Context;
+
+    use super::*;
+
+    fn new_project(manifest: &str) -> (tempfile::TempDir, Context) {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("vex.toml"), manifest).unwrap();
+        let ctx =
+            Context::new_with_manifest(&root_path, toml_edit::de::from_str(manifest).unwrap());
+        (root_dir, ctx)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (_root_dir, ctx) = new_project(indoc! {r#"
+            [vex]
+            version = "1"
+
+            [[grammars]]
+            name = "foo"
+            library = "grammars/foo.so"
+        "#});
+        let library_path = ctx.project_root.join("grammars/foo.so");
+        fs::create_dir_all(library_path.parent().unwrap()).unwrap();
+        fs::write(&library_path, "not really a library").unwrap();
+
+        assert_eq!(
+            Lockfile::read(&ctx.project_root).unwrap_err().to_string(),
+            "cannot find vex.lock, to create it, run `vex sync`"
+        );
+
+        let lockfile = Lockfile::resolve(&ctx.project_root, &ctx.grammars).unwrap();
+        lockfile.write(&ctx.project_root).unwrap();
+        assert_eq!(
+            fs::read_to_string(ctx.project_root.join(Lockfile::FILE_NAME)).unwrap(),
+            indoc! {r#"
+                # This file is generated by `vex sync`, do not edit it by hand.
+
+                version = "1"
+
+                [[grammar]]
+                name = "foo"
+                library = "grammars/foo.so"
+                sha256 = "8c94411e7c8b87bd238af6ff286f5e356e89b0b393364163b58e2ca433a40fe5"
+            "#}
+        );
+        let locked = Lockfile::read(&ctx.project_root).unwrap();
+        assert_eq!(locked, lockfile);
+        lockfile.verify(&locked).unwrap();
+    }
+
+    #[test]
+    fn outdated() {
+        let grammar = |name: &str, sha256: &str| LockedGrammar {
+            name: name.into(),
+            library: format!("{name}.so").into(),
+            sha256: sha256.into(),
+        };
+        let locked = Lockfile {
+            version: LockfileVersion::V1,
+            grammars: vec![grammar("changed", "aaa"), grammar("removed", "bbb")],
+        };
+        let current = Lockfile {
+            version: LockfileVersion::V1,
+            grammars: vec![grammar("added", "ccc"), grammar("changed", "ddd")],
+        };
+        assert_eq!(
+            current.verify(&locked).unwrap_err().to_string(),
+            "vex.lock is out of date: grammar 'added' is not locked, grammar 'changed' has changed, grammar 'removed' is locked but no longer declared; to update it, run `vex sync`"
+        );
+    }
+}
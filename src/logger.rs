@@ -1,30 +1,45 @@
-use std::{process::ExitCode, sync::Mutex};
+use std::{
+    process::ExitCode,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use annotate_snippets::{AnnotationType, Renderer, Snippet};
-use lazy_static::lazy_static;
-use log::{kv::Key, Level, Log, Metadata, Record};
-use owo_colors::Style;
+use annotate_snippets::{renderer::Margin, AnnotationType, Renderer, Snippet};
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+use serde_json::{json, Map};
 
-use crate::{result::Result, verbosity::Verbosity};
+use crate::{cli::LogFormat, result::Result, theme, timings, verbosity::Verbosity};
 
 pub static NUM_ERRS: Mutex<u32> = Mutex::new(0);
-pub static NUM_WARNINGS: Mutex<u32> = Mutex::new(0);
 
 static mut VERBOSITY: Verbosity = Verbosity::Terse;
 
-pub fn init(level: Verbosity) -> Result<()> {
+pub fn init(level: Verbosity, timings: bool, format: LogFormat) -> Result<()> {
     unsafe { VERBOSITY = level };
-    let level = level.into();
-    log::set_boxed_logger(Box::new(Logger { level }))?;
-    log::set_max_level(level.to_level_filter());
+    let level: Level = level.into();
+    log::set_boxed_logger(Box::new(Logger {
+        level,
+        timings,
+        format,
+    }))?;
+    let max_level = if timings {
+        level.to_level_filter().max(LevelFilter::Info)
+    } else {
+        level.to_level_filter()
+    };
+    log::set_max_level(max_level);
+    if timings {
+        timings::enable();
+    }
     Ok(())
 }
 
 pub fn exit_code() -> ExitCode {
     if *NUM_ERRS.lock().expect("failed to lock NUM_ERRS") > 0 {
         ExitCode::from(u8::MAX)
-    } else if *NUM_WARNINGS.lock().expect("failed to lock NUM_WARNINGS") > 0 {
-        ExitCode::from(1)
     } else {
         ExitCode::SUCCESS
     }
@@ -42,18 +57,6 @@ macro_rules! error {
     }}
 }
 
-#[macro_export]
-macro_rules! warn {
-    ($($arg:tt)+) => {{
-        *$crate::logger::NUM_WARNINGS.lock().expect("failed to lock NUM_WARNINGS") += 1;
-        ::log::warn!($($arg)+)
-    }}
-}
-
-lazy_static! {
-    pub static ref SUCCESS_STYLE: Style = Style::new().green().bold();
-}
-
 #[macro_export]
 macro_rules! success {
     ($($arg:tt)+) => {
@@ -62,7 +65,7 @@ macro_rules! success {
             ::log::warn!(
                 custom=true;
                 "{}: {}",
-                "success".if_supports_color(::owo_colors::Stream::Stdout, |text| text.style(*$crate::logger::SUCCESS_STYLE)),
+                "success".if_supports_color(::owo_colors::Stream::Stdout, |text| text.style($crate::theme::current().success_style())),
                 format!($($arg)+),
             )
         }
@@ -71,12 +74,14 @@ macro_rules! success {
 
 struct Logger {
     level: Level,
+    timings: bool,
+    format: LogFormat,
 }
 
 impl Log for Logger {
     #[inline]
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() <= self.level
+        metadata.level() <= self.level || (self.timings && metadata.target() == timings::TARGET)
     }
 
     #[inline]
@@ -88,7 +93,9 @@ impl Log for Logger {
 
         let level = metadata.level();
         let kvs = record.key_values();
-        if level >= Level::Trace {
+        if self.format == LogFormat::Json {
+            eprintln!("{}", json_line(record, SystemTime::now()));
+        } else if level >= Level::Trace {
             eprintln!("trace: {}", record.args());
         } else if kvs.get(Key::from_str("custom")).is_some() {
             eprintln!("{}", record.args())
@@ -104,13 +111,63 @@ impl Log for Logger {
                 footer: Vec::with_capacity(0),
                 slices: Vec::with_capacity(0),
             };
-            eprintln!("{}", render_snippet(snippet));
+            eprintln!("{}", render_snippet(snippet, None));
         };
     }
 
     fn flush(&self) {}
 }
 
+/// Render the given record as a single line of JSON, with its key-values as extra fields.
+fn json_line(record: &Record<'_>, time: SystemTime) -> String {
+    struct Fields(Map<String, serde_json::Value>);
+
+    impl<'kvs> VisitSource<'kvs> for Fields {
+        fn visit_pair(
+            &mut self,
+            key: Key<'kvs>,
+            value: Value<'kvs>,
+        ) -> std::result::Result<(), kv::Error> {
+            if key.as_str() == "custom" {
+                return Ok(()); // Only affects how text is drawn.
+            }
+            let value = if let Some(value) = value.to_u64() {
+                json!(value)
+            } else if let Some(value) = value.to_i64() {
+                json!(value)
+            } else if let Some(value) = value.to_f64() {
+                json!(value)
+            } else if let Some(value) = value.to_bool() {
+                json!(value)
+            } else {
+                json!(value.to_string())
+            };
+            self.0.insert(key.as_str().to_owned(), value);
+            Ok(())
+        }
+    }
+
+    let timestamp_ms = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    let mut fields = Fields(Map::new());
+    fields.0.insert("timestamp_ms".into(), json!(timestamp_ms));
+    fields.0.insert(
+        "level".into(),
+        json!(record.level().as_str().to_ascii_lowercase()),
+    );
+    fields.0.insert("target".into(), json!(record.target()));
+    fields
+        .0
+        .insert("message".into(), json!(record.args().to_string()));
+    record
+        .key_values()
+        .visit(&mut fields)
+        .expect("internal error: cannot visit log fields");
+    serde_json::Value::Object(fields.0).to_string()
+}
+
 fn annotation_type_of(level: Level) -> AnnotationType {
     match level {
         Level::Error => AnnotationType::Error,
@@ -121,12 +178,50 @@ fn annotation_type_of(level: Level) -> AnnotationType {
     }
 }
 
-pub fn render_snippet(snippet: Snippet) -> String {
+pub fn render_snippet(snippet: Snippet, margin: Option<Margin>) -> String {
     if !cfg!(test) {
-        Renderer::styled()
+        theme::current().renderer()
     } else {
         Renderer::plain()
     }
+    .margin(margin)
     .render(snippet)
     .to_string()
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn json_lines() {
+        let time = UNIX_EPOCH + Duration::from_millis(1234);
+        let kvs: &[(&str, Value<'_>)] = &[
+            ("custom", Value::from(true)),
+            ("phase", Value::from("parse")),
+            ("path", Value::from("src/main.rs")),
+            ("elapsed_ms", Value::from(1.5)),
+        ];
+        let record = Record::builder()
+            .level(Level::Info)
+            .target(timings::TARGET)
+            .args(format_args!("timing"))
+            .key_values(&kvs)
+            .build();
+        let line: serde_json::Value = serde_json::from_str(&json_line(&record, time)).unwrap();
+        assert_eq!(
+            line,
+            json!({
+                "timestamp_ms": 1234,
+                "level": "info",
+                "target": "vex::timings",
+                "message": "timing",
+                "phase": "parse",
+                "path": "src/main.rs",
+                "elapsed_ms": 1.5,
+            })
+        );
+    }
+}
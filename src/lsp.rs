@@ -0,0 +1,606 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{self, BufRead, Write},
+};
+
+use camino::Utf8PathBuf;
+use log::{info, log_enabled, warn};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Exit, Notification, PublishDiagnostics, ShowMessage,
+    },
+    request::{Initialize, Request, Shutdown},
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, InitializeResult, MessageType,
+    NumberOrString, Position, PublishDiagnosticsParams, Range, SaveOptions, ServerCapabilities,
+    ServerInfo, ShowMessageParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+};
+use serde::{de::DeserializeOwned, Serialize as Serialise};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    associations::Associations,
+    context::Context,
+    error::{Error, IOAction},
+    irritation::Irritation,
+    result::Result,
+    scan,
+    scriptlets::VexingStore,
+    severity::Severity,
+    source_file::SourceFile,
+    source_path::{PrettyPath, SourcePath},
+    verbosity::Verbosity,
+};
+
+/// Serve diagnostics over the language server protocol until the client exits.
+pub fn serve(
+    ctx: &Context,
+    store: &VexingStore,
+    verbosity: Verbosity,
+    mut input: impl BufRead,
+    output: impl Write,
+) -> Result<()> {
+    let mut server = Server {
+        ctx,
+        store,
+        associations: ctx.associations()?,
+        verbosity,
+        output,
+        documents: HashMap::new(),
+    };
+    while let Some(message) = read_message(&mut input)? {
+        let keep_serving = match message {
+            Incoming::Message(message) => server.handle(message)?,
+            Incoming::Malformed(reason) => {
+                server.respond_error(None, PARSE_ERROR, reason)?;
+                true
+            }
+        };
+        if !keep_serving {
+            break;
+        }
+    }
+    Ok(())
+}
+
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// A message read from the client.
+#[derive(Debug)]
+enum Incoming {
+    Message(JsonValue),
+
+    /// A message which could not be parsed, along with the reason why.
+    Malformed(String),
+}
+
+struct Server<'a, W> {
+    ctx: &'a Context,
+    store: &'a VexingStore,
+    associations: Associations,
+    verbosity: Verbosity,
+    output: W,
+    documents: HashMap<Url, Document>,
+}
+
+#[derive(Default)]
+struct Document {
+    content: String,
+
+    /// The hash of the content last scanned, along with the diagnostics found.
+    diagnostics: Option<(u64, Vec<Diagnostic>)>,
+}
+
+impl<W: Write> Server<'_, W> {
+    /// Handle a single message, returning whether to continue serving.
+    fn handle(&mut self, message: JsonValue) -> Result<bool> {
+        let id = message.get("id").cloned();
+        let params = message.get("params").cloned().unwrap_or_default();
+        match message.get("method").and_then(JsonValue::as_str) {
+            Some(Initialize::METHOD) => {
+                let result = InitializeResult {
+                    capabilities: ServerCapabilities {
+                        text_document_sync: Some(TextDocumentSyncCapability::Options(
+                            TextDocumentSyncOptions {
+                                open_close: Some(true),
+                                change: Some(TextDocumentSyncKind::FULL),
+                                save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                                    include_text: Some(false),
+                                })),
+                                ..Default::default()
+                            },
+                        )),
+                        ..Default::default()
+                    },
+                    server_info: Some(ServerInfo {
+                        name: "vex".into(),
+                        version: Some(env!("CARGO_PKG_VERSION").into()),
+                    }),
+                };
+                self.respond(id, result)?;
+            }
+            Some(Shutdown::METHOD) => self.respond(id, ())?,
+            Some(Exit::METHOD) => return Ok(false),
+            Some(DidOpenTextDocument::METHOD) => {
+                let Some(params): Option<DidOpenTextDocumentParams> =
+                    self.parse_params(id, params)?
+                else {
+                    return Ok(true);
+                };
+                let uri = params.text_document.uri;
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        content: params.text_document.text,
+                        diagnostics: None,
+                    },
+                );
+                self.publish_diagnostics(uri)?;
+            }
+            Some(DidChangeTextDocument::METHOD) => {
+                let Some(params): Option<DidChangeTextDocumentParams> =
+                    self.parse_params(id, params)?
+                else {
+                    return Ok(true);
+                };
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents
+                        .entry(params.text_document.uri)
+                        .or_default()
+                        .content = change.text;
+                }
+            }
+            Some(DidSaveTextDocument::METHOD) => {
+                let Some(params): Option<DidSaveTextDocumentParams> =
+                    self.parse_params(id, params)?
+                else {
+                    return Ok(true);
+                };
+                let uri = params.text_document.uri;
+                if let Some(text) = params.text {
+                    self.documents.entry(uri.clone()).or_default().content = text;
+                }
+                self.publish_diagnostics(uri)?;
+            }
+            Some(DidCloseTextDocument::METHOD) => {
+                let Some(params): Option<DidCloseTextDocumentParams> =
+                    self.parse_params(id, params)?
+                else {
+                    return Ok(true);
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.notify::<PublishDiagnostics>(PublishDiagnosticsParams {
+                    uri,
+                    diagnostics: vec![],
+                    version: None,
+                })?;
+            }
+            Some(method) => {
+                if id.is_some() {
+                    self.respond_error(id, METHOD_NOT_FOUND, format!("unknown method '{method}'"))?;
+                } else if log_enabled!(log::Level::Info) {
+                    info!("ignoring {method} notification");
+                }
+            }
+            None => {}
+        }
+        Ok(true)
+    }
+
+    fn publish_diagnostics(&mut self, uri: Url) -> Result<()> {
+        let Some(document) = self.documents.get(&uri) else {
+            return Ok(());
+        };
+        let content_hash = {
+            let mut hasher = DefaultHasher::new();
+            document.content.hash(&mut hasher);
+            hasher.finish()
+        };
+        let diagnostics = match &document.diagnostics {
+            Some((hash, diagnostics)) if *hash == content_hash => diagnostics.clone(),
+            _ => match self.scan(&uri, &document.content) {
+                Ok(diagnostics) => diagnostics,
+                Err(err) => {
+                    return self.notify::<ShowMessage>(ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: err.to_string(),
+                    });
+                }
+            },
+        };
+        if let Some(document) = self.documents.get_mut(&uri) {
+            document.diagnostics = Some((content_hash, diagnostics.clone()));
+        }
+        self.notify::<PublishDiagnostics>(PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        })
+    }
+
+    fn scan(&self, uri: &Url, content: &str) -> Result<Vec<Diagnostic>> {
+        let Some(path) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+            .filter(|path| path.starts_with(self.ctx.project_root.as_str()))
+        else {
+            return Ok(vec![]);
+        };
+        let source_path = SourcePath::new(&path, &self.ctx.project_root);
+        let language = self.associations.get_language(&source_path)?;
+        let file = SourceFile::new(source_path, language);
+        let irritations = scan::scan_single_file(
+            self.ctx,
+            self.store,
+            &file,
+            content.to_owned(),
+            self.verbosity,
+        )?;
+        Ok(irritations
+            .iter()
+            .filter(|irritation| irritation.path() == Some(&file.path().pretty_path))
+            .filter_map(|irritation| diagnostic(irritation, content))
+            .collect())
+    }
+
+    /// Parse the params of a message. Invalid params are answered with an error if the message
+    /// is a request and are otherwise dropped, so that one bad message does not stop the server.
+    fn parse_params<P: DeserializeOwned>(
+        &mut self,
+        id: Option<JsonValue>,
+        params: JsonValue,
+    ) -> Result<Option<P>> {
+        match serde_json::from_value(params) {
+            Ok(params) => Ok(Some(params)),
+            Err(err) if id.is_some() => {
+                self.respond_error(id, INVALID_PARAMS, err.to_string())?;
+                Ok(None)
+            }
+            Err(err) => {
+                warn!("dropping notification with invalid params: {err}");
+                Ok(None)
+            }
+        }
+    }
+
+    fn respond(&mut self, id: Option<JsonValue>, result: impl Serialise) -> Result<()> {
+        self.write(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result,
+        }))
+    }
+
+    fn respond_error(
+        &mut self,
+        id: Option<JsonValue>,
+        code: i32,
+        message: impl Into<String>,
+    ) -> Result<()> {
+        self.write(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": code,
+                "message": message.into(),
+            },
+        }))
+    }
+
+    fn notify<N: Notification>(&mut self, params: N::Params) -> Result<()> {
+        self.write(json!({
+            "jsonrpc": "2.0",
+            "method": N::METHOD,
+            "params": params,
+        }))
+    }
+
+    fn write(&mut self, message: JsonValue) -> Result<()> {
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())
+            .and_then(|_| self.output.flush())
+            .map_err(|cause| Error::IO {
+                path: PrettyPath::from("stdout"),
+                action: IOAction::Write,
+                cause,
+            })
+    }
+}
+
+fn diagnostic(irritation: &Irritation, content: &str) -> Option<Diagnostic> {
+    let location = irritation.location()?;
+    let position = |row: usize, column: usize| {
+        // Locations count bytes along each line but clients count UTF-16 code units.
+        let line = row.saturating_sub(1);
+        let character = content
+            .split('\n')
+            .nth(line)
+            .unwrap_or_default()
+            .char_indices()
+            .take_while(|(index, _)| *index < column)
+            .map(|(_, char)| char.len_utf16())
+            .sum::<usize>();
+        Position {
+            line: line as u32,
+            character: character as u32,
+        }
+    };
+    let message = match irritation.info() {
+        Some(info) => format!("{}\n{info}", irritation.message()),
+        None => irritation.message().to_string(),
+    };
+    Some(Diagnostic {
+        range: Range {
+            start: position(location.start_row, location.start_column),
+            end: position(location.end_row, location.end_column),
+        },
+        severity: Some(match irritation.severity() {
+            Severity::Advice => DiagnosticSeverity::INFORMATION,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Error => DiagnosticSeverity::ERROR,
+        }),
+        code: Some(NumberOrString::String(irritation.vex_id().to_string())),
+        source: Some("vex".into()),
+        message,
+        ..Default::default()
+    })
+}
+
+/// The longest message which will be read. Larger bodies are not skipped, as they could not be
+/// told apart from a corrupt header.
+const MAX_CONTENT_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Read a single message, returning `None` if input has ended. Only failing to read input or a
+/// message too long to read is an error, malformed messages are returned for the server to answer.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Incoming>> {
+    let read_error = |cause| Error::IO {
+        path: PrettyPath::from("stdin"),
+        action: IOAction::Read,
+        cause,
+    };
+
+    let mut content_length = Ok(None);
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).map_err(read_error)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value
+                    .trim()
+                    .parse::<usize>()
+                    .map(Some)
+                    .map_err(|err| format!("invalid Content-Length header: {err}"));
+            }
+        }
+    }
+    let content_length = match content_length {
+        Ok(Some(content_length)) => content_length,
+        Ok(None) => {
+            return Ok(Some(Incoming::Malformed(
+                "missing Content-Length header".into(),
+            )))
+        }
+        Err(reason) => return Ok(Some(Incoming::Malformed(reason))),
+    };
+    if content_length > MAX_CONTENT_LENGTH {
+        return Err(read_error(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content-Length {content_length} exceeds the limit of {MAX_CONTENT_LENGTH}"),
+        )));
+    }
+
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content).map_err(read_error)?;
+    Ok(Some(match serde_json::from_slice(&content) {
+        Ok(message) => Incoming::Message(message),
+        Err(err) => Incoming::Malformed(err.to_string()),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fmt::Write as _, fs};
+
+    use camino::Utf8Path;
+    use indoc::indoc;
+
+    use crate::scriptlets::{source::TestSource, InitOptions, PreinitOptions, PreinitingStore};
+
+    use super::*;
+
+    #[test]
+    fn diagnostics_published() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let uri = Url::from_file_path(root_path.join("src/main.rs")).unwrap();
+        let responses = serve_messages(
+            &root_path,
+            &[
+                json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}),
+                json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+                did_open(&uri, "fn main() {\n    let x = 10;\n}\n"),
+                json!({"jsonrpc": "2.0", "id": 2, "method": "unknown/method"}),
+                json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+                json!({"jsonrpc": "2.0", "method": "exit"}),
+            ],
+        );
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "vex");
+        assert_eq!(
+            responses[1],
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {
+                    "uri": uri,
+                    "diagnostics": [{
+                        "range": {
+                            "start": {"line": 1, "character": 12},
+                            "end": {"line": 1, "character": 14},
+                        },
+                        "severity": 2,
+                        "code": "test",
+                        "source": "vex",
+                        "message": "found literal",
+                    }],
+                },
+            })
+        );
+        assert_eq!(responses[2]["error"]["code"], -32601);
+        assert_eq!(
+            responses[3],
+            json!({"jsonrpc": "2.0", "id": 3, "result": null})
+        );
+    }
+
+    #[test]
+    fn diagnostics_count_utf16() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let uri = Url::from_file_path(root_path.join("src/main.rs")).unwrap();
+        let responses = serve_messages(
+            &root_path,
+            &[
+                did_open(&uri, "fn main() {\n    let s = \"é𝄞\"; let x = 10;\n}\n"),
+                json!({"jsonrpc": "2.0", "method": "exit"}),
+            ],
+        );
+        assert_eq!(responses.len(), 1);
+        assert_eq!(
+            responses[0]["params"]["diagnostics"][0]["range"],
+            json!({
+                "start": {"line": 1, "character": 27},
+                "end": {"line": 1, "character": 29},
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_messages() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        let input = [
+            json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {}}).to_string(),
+            json!({"jsonrpc": "2.0", "id": 1, "method": "textDocument/didOpen", "params": {}})
+                .to_string(),
+            "{not json".into(),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}).to_string(),
+        ]
+        .iter()
+        .fold(
+            // A header which cannot be parsed is skipped along with its empty body.
+            "Content-Length: many\r\n\r\n".to_owned(),
+            |input, body| frame(input, body),
+        );
+        let input = frame(
+            input,
+            &json!({"jsonrpc": "2.0", "method": "exit"}).to_string(),
+        );
+
+        let responses = serve_input(&root_path, &input);
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["id"], JsonValue::Null);
+        assert_eq!(responses[0]["error"]["code"], -32700);
+        assert_eq!(responses[1]["id"], 1);
+        assert_eq!(responses[1]["error"]["code"], -32602);
+        assert_eq!(responses[2]["id"], JsonValue::Null);
+        assert_eq!(responses[2]["error"]["code"], -32700);
+        assert_eq!(
+            responses[3],
+            json!({"jsonrpc": "2.0", "id": 2, "result": null})
+        );
+    }
+
+    #[test]
+    fn oversized_messages() {
+        let input = format!("Content-Length: {}\r\n\r\n{{}}", MAX_CONTENT_LENGTH + 1);
+        assert_eq!(
+            read_message(&mut input.as_bytes()).unwrap_err().to_string(),
+            "cannot read stdin: Content-Length 67108865 exceeds the limit of 67108864"
+        );
+    }
+
+    fn did_open(uri: &Url, text: &str) -> JsonValue {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "rust",
+                    "version": 1,
+                    "text": text,
+                },
+            },
+        })
+    }
+
+    fn frame(mut input: String, body: &str) -> String {
+        write!(input, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        input
+    }
+
+    fn serve_messages(root_path: &Utf8Path, messages: &[JsonValue]) -> Vec<JsonValue> {
+        let input = messages.iter().fold(String::new(), |input, message| {
+            frame(input, &message.to_string())
+        });
+        serve_input(root_path, &input)
+    }
+
+    /// Serve the given input in a project with a check which flags integer literals, returning
+    /// the messages sent in response.
+    fn serve_input(root_path: &Utf8Path, input: &str) -> Vec<JsonValue> {
+        fs::write(root_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        let ctx = Context::acquire(root_path).unwrap();
+        let store = PreinitingStore::new(&[TestSource {
+            vex_dir: "vexes".into(),
+            path: Utf8PathBuf::from("vexes/test.star"),
+            content: indoc! {r#"
+                def init():
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search('rust', '(integer_literal) @lit', on_match)
+
+                def on_match(event):
+                    vex.warn('test', 'found literal', at=event.captures['lit'])
+            "#},
+        }])
+        .unwrap()
+        .preinit(PreinitOptions::default())
+        .unwrap()
+        .init(InitOptions::default())
+        .unwrap();
+
+        let mut output = vec![];
+        serve(
+            &ctx,
+            &store,
+            Verbosity::default(),
+            input.as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+
+        let mut output = &output[..];
+        std::iter::from_fn(|| match read_message(&mut output).unwrap()? {
+            Incoming::Message(message) => Some(message),
+            Incoming::Malformed(reason) => panic!("malformed output: {reason}"),
+        })
+        .collect()
+    }
+}
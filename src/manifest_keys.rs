@@ -0,0 +1,214 @@
+use std::{fmt::Display, ops::Range};
+
+use serde_json::Value;
+use toml_edit::{ImDocument, Item, Key, TableLike};
+
+use crate::{context::Manifest, result::Result, suggestion::suggest};
+
+/// A key in the manifest which its schema does not describe, such as a misspelt option.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// The dotted path to the key, such as `files.ignroe`.
+    pub path: String,
+
+    /// The known key nearest to this one, if any is close.
+    pub suggestion: Option<String>,
+
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            path,
+            suggestion,
+            line,
+            column,
+        } = self;
+        write!(
+            f,
+            "unknown key '{path}' in {} at line {line}, column {column}",
+            Manifest::FILE_NAME
+        )?;
+        if let Some(suggestion) = suggestion {
+            write!(f, ", did you mean '{suggestion}'?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find every key in the given manifest content which is not described by the manifest schema.
+pub fn unknown_keys(raw_data: &str) -> Result<Vec<UnknownKey>> {
+    let schema: Value =
+        serde_json::from_str(Manifest::SCHEMA).expect("internal error: manifest schema invalid");
+    let document = ImDocument::parse(raw_data).map_err(toml_edit::de::Error::from)?;
+    let mut unknown_keys = vec![];
+    find_unknown_keys(
+        &schema,
+        &schema,
+        Node::Table(document.as_table()),
+        "",
+        &mut unknown_keys,
+    );
+    unknown_keys
+        .into_iter()
+        .map(|(path, suggestion, span)| {
+            let before = &raw_data[..span.start];
+            let line = before.lines().count().max(1) + usize::from(before.ends_with('\n'));
+            let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+            Ok(UnknownKey {
+                path,
+                suggestion,
+                line,
+                column,
+            })
+        })
+        .collect()
+}
+
+fn find_unknown_keys(
+    root: &Value,
+    schema: &Value,
+    node: Node<'_>,
+    path: &str,
+    unknown_keys: &mut Vec<(String, Option<String>, Range<usize>)>,
+) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let schema = root
+            .pointer(&reference[1..])
+            .expect("internal error: manifest schema has dangling reference");
+        return find_unknown_keys(root, schema, node, path, unknown_keys);
+    }
+    if let Some(alternatives) = schema["oneOf"].as_array() {
+        // Keys are only reported against the alternative which fits best.
+        let best = alternatives
+            .iter()
+            .map(|alternative| {
+                let mut found = vec![];
+                find_unknown_keys(root, alternative, node.clone(), path, &mut found);
+                found
+            })
+            .min_by_key(Vec::len);
+        unknown_keys.extend(best.into_iter().flatten());
+        return;
+    }
+
+    match node {
+        Node::Table(table) => {
+            for (name, item) in table.iter() {
+                let key_path = if path.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{path}.{name}")
+                };
+                if let Some(field_schema) = schema["properties"].get(name) {
+                    find_unknown_keys(root, field_schema, item.into(), &key_path, unknown_keys);
+                    continue;
+                }
+                match &schema["additionalProperties"] {
+                    Value::Bool(false) => {
+                        let known = schema["properties"]
+                            .as_object()
+                            .into_iter()
+                            .flat_map(|properties| properties.keys().map(String::as_str));
+                        let suggestion = suggest(name, known).map(str::to_owned);
+                        let span = table
+                            .key(name)
+                            .and_then(Key::span)
+                            .expect("internal error: parsed key has no span");
+                        unknown_keys.push((key_path, suggestion, span));
+                    }
+                    additional => {
+                        find_unknown_keys(root, additional, item.into(), &key_path, unknown_keys)
+                    }
+                }
+            }
+        }
+        Node::Array(nodes) => nodes.into_iter().enumerate().for_each(|(index, node)| {
+            let node_path = format!("{path}[{index}]");
+            find_unknown_keys(root, &schema["items"], node, &node_path, unknown_keys)
+        }),
+        Node::Scalar => {}
+    }
+}
+
+/// The shape of a TOML item, abstracting over how its tables and arrays were written.
+#[derive(Clone)]
+enum Node<'a> {
+    Table(&'a dyn TableLike),
+    Array(Vec<Node<'a>>),
+    Scalar,
+}
+
+impl<'a> From<&'a Item> for Node<'a> {
+    fn from(item: &'a Item) -> Self {
+        match item {
+            Item::Table(table) => Self::Table(table),
+            Item::ArrayOfTables(tables) => {
+                Self::Array(tables.iter().map(|table| Self::Table(table)).collect())
+            }
+            Item::Value(value) => value.into(),
+            Item::None => Self::Scalar,
+        }
+    }
+}
+
+impl<'a> From<&'a toml_edit::Value> for Node<'a> {
+    fn from(value: &'a toml_edit::Value) -> Self {
+        match value {
+            toml_edit::Value::InlineTable(table) => Self::Table(table),
+            toml_edit::Value::Array(values) => Self::Array(values.iter().map(Self::from).collect()),
+            _ => Self::Scalar,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn unknown_keys_found() {
+        let unknown = unknown_keys(indoc! {r#"
+            [vex]
+            version = "2"
+            verison = "2"
+
+            [files]
+            ignroe = ["target/"]
+            max-file-size = 1000
+
+            [languages.python]
+            use-for = ["*.star"]
+            interpreter = ["pypy3"]
+
+            [config.some-scriptlet]
+            anything = { goes = true }
+
+            [[external]]
+            id = "x"
+            command = "x"
+            parser.regex = "(?<message>.*)"
+            parser.regx = "x"
+
+            [project]
+            name = "example"
+            created = 2024-01-01
+        "#})
+        .unwrap();
+        let unknown = unknown.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            unknown,
+            [
+                "unknown key 'vex.verison' in vex.toml at line 3, column 1, did you mean 'version'?",
+                "unknown key 'files.ignroe' in vex.toml at line 6, column 1, did you mean 'ignore'?",
+                "unknown key 'languages.python.interpreter' in vex.toml at line 11, column 1, did you mean 'interpreters'?",
+                "unknown key 'external[0].parser.regx' in vex.toml at line 20, column 8, did you mean 'regex'?",
+                "unknown key 'project.created' in vex.toml at line 24, column 1",
+            ]
+        );
+    }
+}
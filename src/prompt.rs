@@ -0,0 +1,126 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    error::{Error, IOAction},
+    result::Result,
+    source_path::PrettyPath,
+};
+
+/// A line-based prompt which asks the user to pick from a fixed set of single-key choices.
+#[derive(Debug)]
+pub struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Choice<T> {
+    pub key: char,
+    pub help: &'static str,
+    pub value: T,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    pub fn output(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Ask a question until one of the given choices is picked. Returns `None` if input ends
+    /// before a choice is made.
+    pub fn choose<T: Copy>(&mut self, question: &str, choices: &[Choice<T>]) -> Result<Option<T>> {
+        let keys = choices
+            .iter()
+            .map(|choice| choice.key.to_string())
+            .chain(["?".into()])
+            .collect::<Vec<_>>()
+            .join(",");
+        loop {
+            write!(self.output, "{question} [{keys}]? ").map_err(Self::write_error)?;
+            self.output.flush().map_err(Self::write_error)?;
+
+            let mut answer = String::new();
+            let num_read = self
+                .input
+                .read_line(&mut answer)
+                .map_err(|cause| Error::IO {
+                    path: PrettyPath::from("stdin"),
+                    action: IOAction::Read,
+                    cause,
+                })?;
+            if num_read == 0 {
+                return Ok(None);
+            }
+
+            let mut answer_chars = answer.trim().chars();
+            let picked = match (answer_chars.next(), answer_chars.next()) {
+                (Some(key), None) => choices.iter().find(|choice| choice.key == key),
+                _ => None,
+            };
+            if let Some(choice) = picked {
+                return Ok(Some(choice.value));
+            }
+
+            for choice in choices {
+                writeln!(self.output, "{} - {}", choice.key, choice.help)
+                    .map_err(Self::write_error)?;
+            }
+            writeln!(self.output, "? - print help").map_err(Self::write_error)?;
+        }
+    }
+
+    fn write_error(cause: io::Error) -> Error {
+        Error::IO {
+            path: PrettyPath::from("stdout"),
+            action: IOAction::Write,
+            cause,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn choose() {
+        let choices = [
+            Choice {
+                key: 'y',
+                help: "yes",
+                value: true,
+            },
+            Choice {
+                key: 'n',
+                help: "no",
+                value: false,
+            },
+        ];
+        let run = |input: &str| {
+            let mut output = vec![];
+            let choice = Prompt::new(input.as_bytes(), &mut output)
+                .choose("continue", &choices)
+                .unwrap();
+            (choice, String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(run("y\n"), (Some(true), "continue [y,n,?]? ".into()));
+        assert_eq!(run(" n \n"), (Some(false), "continue [y,n,?]? ".into()));
+        assert_eq!(
+            run("?\nyes\ny\n"),
+            (
+                Some(true),
+                [
+                    "continue [y,n,?]? y - yes\nn - no\n? - print help\n",
+                    "continue [y,n,?]? y - yes\nn - no\n? - print help\n",
+                    "continue [y,n,?]? ",
+                ]
+                .concat()
+            )
+        );
+        assert_eq!(run(""), (None, "continue [y,n,?]? ".into()));
+    }
+}
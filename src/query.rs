@@ -1,5 +1,6 @@
 use std::ops::Deref;
 
+use joinery::JoinableIterator;
 use tree_sitter::Query as TSQuery;
 
 use crate::{
@@ -7,7 +8,12 @@ use crate::{
 };
 
 #[derive(Debug)]
-pub struct Query(TSQuery);
+pub struct Query {
+    ts_query: TSQuery,
+
+    /// The source from which this query was compiled.
+    source: String,
+}
 
 impl Query {
     pub const KNOWN_OPERATORS: [&'static str; 8] = [
@@ -43,7 +49,7 @@ impl Query {
             return Err(Error::EmptyQuery);
         }
         let sanitised_query = format!("({query}\n)"); // TODO(kcza): remove me!
-        let query = TSQuery::new(language.ts_language(), &sanitised_query)?;
+        let query = TSQuery::new(language.ts_language()?, &sanitised_query)?;
 
         if query.pattern_count() == 0 {
             return Err(Error::EmptyQuery);
@@ -68,7 +74,25 @@ impl Query {
             }
         }
 
-        Ok(Self(query))
+        Ok(Self {
+            ts_query: query,
+            source: sanitised_query,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Compile a single query which contains the patterns of each given query, in order.
+    pub fn combine(language: SupportedLanguage, queries: &[&Query]) -> Result<Self> {
+        let source = queries
+            .iter()
+            .map(|query| query.source.as_str())
+            .join_with("\n")
+            .to_string();
+        let ts_query = TSQuery::new(language.ts_language()?, &source)?;
+        Ok(Self { ts_query, source })
     }
 }
 
@@ -76,6 +100,6 @@ impl Deref for Query {
     type Target = TSQuery;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.ts_query
     }
 }
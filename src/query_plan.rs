@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use dupe::Dupe;
+
+use crate::{
+    query::Query, result::Result, scriptlets::artifact_cache::ArtifactCache,
+    supported_language::SupportedLanguage,
+};
+
+/// The queries to run over a file, each paired with the callbacks which subscribe to its matches.
+///
+/// Queries with identical source are compiled once by the artifact cache, so are merged here into
+/// a single step. Each step therefore walks the tree once, however many checks declared it.
+#[derive(Debug)]
+pub struct QueryPlan<'a, S> {
+    steps: Vec<QueryStep<'a, S>>,
+}
+
+#[derive(Debug)]
+pub struct QueryStep<'a, S> {
+    pub query: &'a Arc<Query>,
+
+    /// The callbacks to notify of each match, in the order in which they were declared.
+    pub subscribers: Vec<&'a S>,
+}
+
+impl<'a, S> QueryPlan<'a, S> {
+    pub fn new(searches: impl IntoIterator<Item = (&'a Arc<Query>, &'a S)>) -> Self {
+        let mut steps: Vec<QueryStep<'a, S>> = Vec::new();
+        for (query, subscriber) in searches {
+            match steps.iter_mut().find(|step| Arc::ptr_eq(step.query, query)) {
+                Some(step) => step.subscribers.push(subscriber),
+                None => steps.push(QueryStep {
+                    query,
+                    subscribers: vec![subscriber],
+                }),
+            }
+        }
+        Self { steps }
+    }
+
+    /// The walks over a file needed to run this plan. Usually, each step is run in its own pass,
+    /// but if `single_pass` is set, all steps are combined into one.
+    pub fn passes(
+        &self,
+        language: SupportedLanguage,
+        artifact_cache: &ArtifactCache,
+        single_pass: bool,
+    ) -> Result<Vec<QueryPass<'_, 'a, S>>> {
+        if !single_pass || self.steps.len() <= 1 {
+            return Ok(self
+                .steps
+                .iter()
+                .map(|step| QueryPass {
+                    query: step.query.dupe(),
+                    combined: false,
+                    steps: vec![(0, step)],
+                })
+                .collect());
+        }
+
+        let queries: Vec<_> = self.steps.iter().map(|step| &**step.query).collect();
+        let query = artifact_cache.combined_query(language, &queries)?;
+        let mut pattern_offset = 0;
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                let offset = pattern_offset;
+                pattern_offset += step.query.pattern_count();
+                (offset, step)
+            })
+            .collect();
+        Ok(vec![QueryPass {
+            query,
+            combined: true,
+            steps,
+        }])
+    }
+}
+
+/// A single walk over a file's tree with one compiled query.
+#[derive(Debug)]
+pub struct QueryPass<'p, 'a, S> {
+    pub query: Arc<Query>,
+
+    /// Whether the query combines those of several steps.
+    pub combined: bool,
+
+    /// Each step run by this pass, with the index in the pass's query of its first pattern.
+    steps: Vec<(usize, &'p QueryStep<'a, S>)>,
+}
+
+impl<'p, 'a, S> QueryPass<'p, 'a, S> {
+    pub fn steps(&self) -> impl Iterator<Item = &'p QueryStep<'a, S>> + '_ {
+        self.steps.iter().map(|(_, step)| *step)
+    }
+
+    /// The step which owns the pattern at the given index, with the index of its first pattern.
+    pub fn step_for(&self, pattern_index: usize) -> (usize, &'p QueryStep<'a, S>) {
+        let pos = self
+            .steps
+            .partition_point(|(offset, _)| *offset <= pattern_index);
+        self.steps[pos - 1]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{scriptlets::artifact_cache::ArtifactCache, supported_language::SupportedLanguage};
+
+    use super::*;
+
+    #[test]
+    fn merges_identical_queries() {
+        let artifact_cache = ArtifactCache::new();
+        let query = |raw| artifact_cache.query(SupportedLanguage::Rust, raw).unwrap();
+        let searches = [
+            (query("(integer_literal) @lit"), "a"),
+            (query("(string_literal) @lit"), "b"),
+            (query("(integer_literal) @lit"), "c"),
+            (query("(integer_literal) @lit"), "a"),
+        ];
+
+        let plan = QueryPlan::new(searches.iter().map(|(query, name)| (query, name)));
+        let passes = plan
+            .passes(SupportedLanguage::Rust, &artifact_cache, false)
+            .unwrap();
+        let steps = passes
+            .iter()
+            .flat_map(|pass| pass.steps())
+            .map(|step| {
+                step.subscribers
+                    .iter()
+                    .map(|name| **name)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(steps, [vec!["a", "c", "a"], vec!["b"]]);
+    }
+
+    #[test]
+    fn single_pass() {
+        let artifact_cache = ArtifactCache::new();
+        let query = |raw| artifact_cache.query(SupportedLanguage::Rust, raw).unwrap();
+        let searches = [
+            (query("(integer_literal) @lit"), "a"),
+            (query("(string_literal) @lit (string_literal) @lit2"), "b"),
+            (query("(identifier) @id"), "c"),
+        ];
+        let plan = QueryPlan::new(searches.iter().map(|(query, name)| (query, name)));
+
+        let passes = plan
+            .passes(SupportedLanguage::Rust, &artifact_cache, false)
+            .unwrap();
+        assert_eq!(passes.len(), 3);
+        assert!(passes.iter().all(|pass| !pass.combined));
+
+        let passes = plan
+            .passes(SupportedLanguage::Rust, &artifact_cache, true)
+            .unwrap();
+        assert_eq!(passes.len(), 1);
+        let pass = &passes[0];
+        assert!(pass.combined);
+        assert_eq!(pass.query.pattern_count(), 3);
+        let owner = |pattern_index| {
+            let (offset, step) = pass.step_for(pattern_index);
+            (offset, *step.subscribers[0])
+        };
+        assert_eq!(owner(0), (0, "a"));
+        assert_eq!(owner(1), (1, "b"));
+        assert_eq!(owner(2), (2, "c"));
+
+        let again = plan
+            .passes(SupportedLanguage::Rust, &artifact_cache, true)
+            .unwrap();
+        assert!(Arc::ptr_eq(&pass.query, &again[0].query));
+    }
+}
@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind};
+
+use camino::Utf8Path;
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+
+use crate::{
+    error::{Error, IOAction},
+    irritation::Irritation,
+    result::Result,
+    source_path::PrettyPath,
+    vex_id::VexId,
+};
+
+/// The most problems each check may find, so that existing problems are tolerated but new ones
+/// are not.
+#[derive(Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+pub struct Ratchet {
+    version: RatchetVersion,
+
+    #[serde(default)]
+    counts: BTreeMap<String, usize>,
+}
+
+impl Ratchet {
+    const HEADER: &'static str =
+        "# This file is generated by `vex check --update-ratchet`, do not edit it by hand.\n\n";
+
+    /// Count the problems found by each check, including those hidden from the output.
+    pub fn count(irritations: &[Irritation], num_hidden: &BTreeMap<VexId, usize>) -> Self {
+        let mut counts = BTreeMap::<String, usize>::new();
+        for irritation in irritations {
+            *counts.entry(irritation.vex_id().to_string()).or_default() += 1;
+        }
+        for (vex_id, num_hidden) in num_hidden {
+            *counts.entry(vex_id.to_string()).or_default() += num_hidden;
+        }
+        Self {
+            version: RatchetVersion::V1,
+            counts,
+        }
+    }
+
+    /// Read the ratchet at the given path, or `None` if it does not exist.
+    pub fn read(path: &Utf8Path) -> Result<Option<Self>> {
+        let raw = match fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(cause) if cause.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(cause) => {
+                return Err(Error::IO {
+                    path: PrettyPath::new(path),
+                    action: IOAction::Read,
+                    cause,
+                })
+            }
+        };
+        Ok(Some(toml_edit::de::from_str(&raw)?))
+    }
+
+    pub fn write(&self, path: &Utf8Path) -> Result<()> {
+        let content = toml_edit::ser::to_string_pretty(self)
+            .expect("internal error: cannot serialise ratchet");
+        fs::write(path, format!("{}{content}", Self::HEADER)).map_err(|cause| Error::IO {
+            path: PrettyPath::new(path),
+            action: IOAction::Write,
+            cause,
+        })
+    }
+
+    /// Check that no check found more problems than this ratchet allows, listing those which did.
+    pub fn verify(&self, current: &Self) -> Result<()> {
+        let increases: Vec<_> = current
+            .counts
+            .iter()
+            .filter_map(|(vex_id, &count)| {
+                let allowed = self.allowed(vex_id);
+                (count > allowed).then(|| format!("'{vex_id}' found {count}, up from {allowed}"))
+            })
+            .collect();
+        if !increases.is_empty() {
+            return Err(Error::RatchetExceeded(increases));
+        }
+        Ok(())
+    }
+
+    /// Lower the allowed counts to those found now. Counts are never raised.
+    pub fn tighten(&self, current: &Self) -> Self {
+        let counts = current
+            .counts
+            .iter()
+            .map(|(vex_id, &count)| (vex_id.clone(), count.min(self.allowed(vex_id))))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        Self {
+            version: RatchetVersion::V1,
+            counts,
+        }
+    }
+
+    fn allowed(&self, vex_id: &str) -> usize {
+        self.counts.get(vex_id).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
+enum RatchetVersion {
+    #[default]
+    #[serde(rename = "1")]
+    V1,
+}
+
+#[cfg(test)]
+mod test {
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+
+    use super::*;
+
+    fn ratchet(counts: &[(&str, usize)]) -> Ratchet {
+        Ratchet {
+            version: RatchetVersion::V1,
+            counts: counts
+                .iter()
+                .map(|(vex_id, count)| (vex_id.to_string(), *count))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::try_from(root_dir.path().join("ratchet.toml")).unwrap();
+        assert_eq!(Ratchet::read(&path).unwrap(), None);
+
+        let ratchet = ratchet(&[("big-numbers", 3), ("long-lines", 1)]);
+        ratchet.write(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            indoc! {r#"
+                # This file is generated by `vex check --update-ratchet`, do not edit it by hand.
+
+                version = "1"
+
+                [counts]
+                big-numbers = 3
+                long-lines = 1
+            "#}
+        );
+        assert_eq!(Ratchet::read(&path).unwrap(), Some(ratchet));
+    }
+
+    #[test]
+    fn verify() {
+        let baseline = ratchet(&[("big-numbers", 3), ("long-lines", 1)]);
+        baseline
+            .verify(&ratchet(&[("big-numbers", 3), ("long-lines", 0)]))
+            .unwrap();
+        assert_eq!(
+            baseline
+                .verify(&ratchet(&[("big-numbers", 4), ("new-check", 1)]))
+                .unwrap_err()
+                .to_string(),
+            "problems increased: 'big-numbers' found 4, up from 3, 'new-check' found 1, up from 0"
+        );
+    }
+
+    #[test]
+    fn tighten() {
+        let baseline = ratchet(&[("big-numbers", 3), ("long-lines", 1), ("fixed", 2)]);
+        assert_eq!(
+            baseline.tighten(&ratchet(&[
+                ("big-numbers", 2),
+                ("long-lines", 5),
+                ("new", 1)
+            ])),
+            ratchet(&[("big-numbers", 2), ("long-lines", 1)]),
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use serde::Serialize as Serialise;
+
+use crate::{context::ProjectInfo, irritation::Irritation, severity::Severity};
+
+/// The version of the results schema. Fields may be added without changing this; removing,
+/// renaming or changing the meaning of a field requires it to be incremented.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A JSON Schema which describes serialised `Results`.
+pub const SCHEMA: &str = include_str!("schemas/results.json");
+
+/// The machine-readable form of the problems found in a run.
+#[derive(Debug, Serialise)]
+pub struct Results<'a> {
+    schema_version: u32,
+    project: &'a ProjectInfo,
+    problems: Vec<Problem<'a>>,
+}
+
+impl<'a> Results<'a> {
+    pub fn new(project: &'a ProjectInfo, irritations: &'a [Irritation]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            project,
+            problems: irritations.iter().map(Problem::new).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("internal error: cannot serialise results")
+    }
+}
+
+#[derive(Debug, Serialise)]
+struct Problem<'a> {
+    id: &'a str,
+    code: Option<&'a str>,
+    severity: Severity,
+    message: &'a str,
+    path: Option<&'a str>,
+    location: Option<Location>,
+    info: Option<&'a str>,
+    fix: Option<Fix<'a>>,
+}
+
+impl<'a> Problem<'a> {
+    fn new(irritation: &'a Irritation) -> Self {
+        Self {
+            id: irritation.vex_id().as_ref(),
+            code: irritation.code(),
+            severity: irritation.severity(),
+            message: irritation.message(),
+            path: irritation.path().map(|path| path.as_str()),
+            location: irritation.location().map(|location| Location {
+                start_row: location.start_row,
+                start_column: location.start_column + 1,
+                end_row: location.end_row,
+                end_column: location.end_column + 1,
+            }),
+            info: irritation.info(),
+            fix: irritation.fix().map(|fix| Fix {
+                start_byte: fix.byte_range.start,
+                end_byte: fix.byte_range.end,
+                replacement: &fix.replacement,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialise)]
+struct Location {
+    start_row: usize,
+    start_column: usize,
+    end_row: usize,
+    end_column: usize,
+}
+
+#[derive(Debug, Serialise)]
+struct Fix<'a> {
+    start_byte: usize,
+    end_byte: usize,
+    replacement: &'a str,
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use indoc::indoc;
+    use serde_json::Value;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn matches_schema() {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+
+        let irritations = VexTest::new("results")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('literal-check', code='LIT1')
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+                        vex.warn('project-check', 'project-wide problem')
+
+                    def on_match(event):
+                        vex.warn('literal-check', 'literal found', at=event.captures['lit'], fix='0')
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { let x = 1; }")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let project = ProjectInfo {
+            name: Some("example".into()),
+            team: None,
+            links: [("docs".into(), "https://example.com".into())].into(),
+        };
+        let results: Value =
+            serde_json::from_str(&Results::new(&project, &irritations).to_json()).unwrap();
+        assert_eq!(results["schema_version"], SCHEMA_VERSION);
+
+        // Every field emitted must be documented and every required field must be emitted.
+        let keys = |value: &Value| -> BTreeSet<String> {
+            value.as_object().unwrap().keys().cloned().collect()
+        };
+        let required = |def: &Value| -> BTreeSet<String> {
+            def["required"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|key| key.as_str().unwrap().to_owned())
+                .collect()
+        };
+        let problem_def = &schema["$defs"]["problem"];
+        assert_eq!(keys(&results), required(&schema));
+        assert_eq!(keys(&results), keys(&schema["properties"]));
+        for problem in results["problems"].as_array().unwrap() {
+            assert_eq!(keys(problem), required(problem_def));
+            assert_eq!(keys(problem), keys(&problem_def["properties"]));
+        }
+
+        assert_eq!(
+            keys(&results["project"]),
+            required(&schema["properties"]["project"])
+        );
+        assert_eq!(results["project"]["name"], "example");
+        assert_eq!(results["project"]["links"]["docs"], "https://example.com");
+
+        let problem = &results["problems"][0];
+        assert_eq!(problem["id"], "project-check");
+        assert_eq!(problem["code"], Value::Null);
+        assert_eq!(problem["path"], Value::Null);
+        let problem = &results["problems"][1];
+        assert_eq!(problem["id"], "literal-check");
+        assert_eq!(problem["code"], "LIT1");
+        assert_eq!(problem["severity"], "warning");
+        assert_eq!(problem["path"], "src/main.rs");
+        assert_eq!(
+            keys(&problem["location"]),
+            required(&schema["$defs"]["location"])
+        );
+        assert_eq!(problem["location"]["start_column"], 21);
+        assert_eq!(keys(&problem["fix"]), required(&schema["$defs"]["fix"]));
+        assert_eq!(problem["fix"]["start_byte"], 20);
+    }
+}
@@ -0,0 +1,517 @@
+use std::{fmt::Write, fs};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{
+    cli::{InitTemplate, NewCmd},
+    context::{self, Context},
+    error::{Error, IOAction},
+    result::Result,
+    source_path::PrettyPath,
+    success,
+    supported_language::SupportedLanguage,
+    vex_id::VexId,
+};
+
+pub fn scaffold(cmd: NewCmd) -> Result<()> {
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    let (check_path, test_path) = create(&ctx, &cmd)?;
+    success!("created {check_path} and {test_path}, run `vex test` to try them out");
+    Ok(())
+}
+
+/// Write a starter scriptlet for a new check and a scriptlet which tests it, returning their
+/// paths.
+fn create(ctx: &Context, cmd: &NewCmd) -> Result<(PrettyPath, PrettyPath)> {
+    let vex_id = VexId::try_from(cmd.name.clone())?;
+    let language: SupportedLanguage = cmd.language.parse()?;
+    let template = Template::for_language(language)?;
+
+    let vexes_dir = Utf8Path::new(ctx.run.vexes_dir.as_str());
+    let check_path = vexes_dir.join(format!("{vex_id}.star"));
+    let test_path = vexes_dir.join(format!("{vex_id}-test.star"));
+    if !cmd.force {
+        if let Some(existing) = [&check_path, &test_path]
+            .into_iter()
+            .find(|path| ctx.project_root.join(path).exists())
+        {
+            return Err(Error::AlreadyExists(PrettyPath::new(existing)));
+        }
+    }
+
+    write(ctx, &check_path, template.check(&vex_id, language))?;
+    write(ctx, &test_path, template.test(&vex_id, language))?;
+    Ok((PrettyPath::new(&check_path), PrettyPath::new(&test_path)))
+}
+
+/// Write the starter checks for the given template into a new project, returning their paths.
+pub fn add_starter_checks(
+    ctx: &Context,
+    template: InitTemplate,
+    force: bool,
+) -> Result<Vec<PrettyPath>> {
+    let vexes_dir = Utf8Path::new(ctx.run.vexes_dir.as_str());
+    let checks = starter_checks(template)
+        .into_iter()
+        .map(|(vex_id, content)| (vexes_dir.join(format!("{vex_id}.star")), content))
+        .collect::<Vec<_>>();
+    if !force {
+        if let Some((existing, _)) = checks
+            .iter()
+            .find(|(path, _)| ctx.project_root.join(path).exists())
+        {
+            return Err(Error::AlreadyExists(PrettyPath::new(existing)));
+        }
+    }
+
+    checks
+        .into_iter()
+        .map(|(path, content)| {
+            write(ctx, &path, content)?;
+            Ok(PrettyPath::new(&path))
+        })
+        .collect()
+}
+
+fn write(ctx: &Context, path: &Utf8PathBuf, content: String) -> Result<()> {
+    fs::write(ctx.project_root.join(path), content).map_err(|cause| Error::IO {
+        path: PrettyPath::new(path),
+        action: IOAction::Write,
+        cause,
+    })
+}
+
+/// The id and source of each starter check in the given template.
+fn starter_checks(template: InitTemplate) -> Vec<(&'static str, String)> {
+    let languages: &[SupportedLanguage] = match template {
+        InitTemplate::Rust => &[SupportedLanguage::Rust],
+        InitTemplate::Python => &[SupportedLanguage::Python],
+        InitTemplate::Mixed => &[SupportedLanguage::Python, SupportedLanguage::Rust],
+    };
+    let mut checks = vec![("no-todo-comments", no_todo_comments(languages))];
+    if languages.contains(&SupportedLanguage::Rust) {
+        checks.push(("no-dbg-macro", NO_DBG_MACRO.into()));
+    }
+    if languages.contains(&SupportedLanguage::Python) {
+        checks.push(("no-breakpoint", NO_BREAKPOINT.into()));
+    }
+    checks
+}
+
+/// A check which finds TODO comments in the given languages. Examples are only declared for a
+/// single language, as `vex doctest` requires.
+fn no_todo_comments(languages: &[SupportedLanguage]) -> String {
+    let comments = |language| match language {
+        SupportedLanguage::Rust => (
+            "[(line_comment) (block_comment)] @comment",
+            "// TODO: handle errors\\nfn main() {}\\n",
+            "// Errors are handled by the caller.\\nfn main() {}\\n",
+        ),
+        SupportedLanguage::Python => (
+            "(comment) @comment",
+            "# TODO: handle errors\\ndef main(): pass\\n",
+            "# Errors are handled by the caller.\\ndef main(): pass\\n",
+        ),
+        _ => unreachable!("internal error: no starter check for {language}"),
+    };
+
+    let language_names = languages
+        .iter()
+        .map(|language| format!("'{language}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let examples = match languages {
+        [language] => {
+            let (_, bad, good) = comments(*language);
+            format!("\n        bad=['{bad}'],\n        good=['{good}'],")
+        }
+        _ => String::new(),
+    };
+    let searches = languages
+        .iter()
+        .fold(String::new(), |mut searches, language| {
+            let (query, _, _) = comments(*language);
+            writeln!(
+                searches,
+                "    vex.search('{language}', '{query}', on_match)"
+            )
+            .expect("internal error: cannot write search");
+            searches
+        });
+    format!(
+        r#"def init():
+    vex.declare(
+        'no-todo-comments',
+        description='finds TODO comments, which are better tracked as issues',
+        languages=[{language_names}],{examples}
+    )
+    vex.observe('open_project', on_open_project)
+
+def on_open_project(event):
+{searches}
+def on_match(event):
+    comment = event.captures['comment']
+    if 'TODO' not in str(comment):
+        return
+    vex.warn(
+        'no-todo-comments',
+        'found TODO comment',
+        at=(comment, 'consider tracking this in an issue'),
+    )
+"#
+    )
+}
+
+const NO_DBG_MACRO: &str = r#"def init():
+    vex.declare(
+        'no-dbg-macro',
+        description='finds uses of dbg!, which are usually left over from debugging',
+        languages=['rust'],
+        bad=['fn main() { dbg!(1); }'],
+        good=['fn main() { println!("{}", 1); }'],
+    )
+    vex.observe('open_project', on_open_project)
+
+def on_open_project(event):
+    vex.search(
+        'rust',
+        '(macro_invocation macro: (identifier) @name) @macro',
+        on_match,
+    )
+
+def on_match(event):
+    if str(event.captures['name']) != 'dbg':
+        return
+    vex.warn(
+        'no-dbg-macro',
+        'found dbg! macro',
+        at=(event.captures['macro'], 'remove this before committing'),
+    )
+"#;
+
+const NO_BREAKPOINT: &str = r#"def init():
+    vex.declare(
+        'no-breakpoint',
+        description='finds calls to breakpoint(), which are usually left over from debugging',
+        languages=['python'],
+        bad=['def main():\n    breakpoint()\n'],
+        good=['def main():\n    print(1)\n'],
+    )
+    vex.observe('open_project', on_open_project)
+
+def on_open_project(event):
+    vex.search(
+        'python',
+        '(call function: (identifier) @name) @call',
+        on_match,
+    )
+
+def on_match(event):
+    if str(event.captures['name']) != 'breakpoint':
+        return
+    vex.warn(
+        'no-breakpoint',
+        'found call to breakpoint()',
+        at=(event.captures['call'], 'remove this before committing'),
+    )
+"#;
+
+/// The languages for which new checks can be scaffolded. Data languages such as JSON have no
+/// function calls for the template to find, so are excluded.
+pub const TEMPLATE_LANGUAGES: [SupportedLanguage; 6] = [
+    SupportedLanguage::Go,
+    SupportedLanguage::Java,
+    SupportedLanguage::Kotlin,
+    SupportedLanguage::Python,
+    SupportedLanguage::Ruby,
+    SupportedLanguage::Rust,
+];
+
+/// The language-specific parts of a new check.
+struct Template {
+    extension: &'static str,
+
+    /// A query which captures the name of each called function as `@name` and the whole call as
+    /// `@call`.
+    query: &'static str,
+
+    /// Code which calls `todo`.
+    bad: &'static str,
+
+    /// Code which does not call `todo`.
+    good: &'static str,
+}
+
+impl Template {
+    fn for_language(language: SupportedLanguage) -> Result<Self> {
+        match language {
+            SupportedLanguage::Go => Ok(Self {
+                extension: "go",
+                query: "(call_expression function: (identifier) @name) @call",
+                bad: "package main\n\nfunc main() {\n    todo()\n}\n",
+                good: "package main\n\nfunc main() {\n    done()\n}\n",
+            }),
+            SupportedLanguage::Java => Ok(Self {
+                extension: "java",
+                query: "(method_invocation name: (identifier) @name) @call",
+                bad: "class Main {\n    void main() {\n        todo();\n    }\n}\n",
+                good: "class Main {\n    void main() {\n        done();\n    }\n}\n",
+            }),
+            SupportedLanguage::Kotlin => Ok(Self {
+                extension: "kt",
+                query: "(call_expression (simple_identifier) @name) @call",
+                bad: "fun main() {\n    todo()\n}\n",
+                good: "fun main() {\n    done()\n}\n",
+            }),
+            SupportedLanguage::Python => Ok(Self {
+                extension: "py",
+                query: "(call function: (identifier) @name) @call",
+                bad: "def main():\n    todo()\n",
+                good: "def main():\n    done()\n",
+            }),
+            SupportedLanguage::Ruby => Ok(Self {
+                extension: "rb",
+                query: "(call method: (identifier) @name) @call",
+                bad: "def main\n  todo()\nend\n",
+                good: "def main\n  done()\nend\n",
+            }),
+            SupportedLanguage::Rust => Ok(Self {
+                extension: "rs",
+                query: "(call_expression function: (identifier) @name) @call",
+                bad: "fn main() {\n    todo();\n}\n",
+                good: "fn main() {\n    done();\n}\n",
+            }),
+            SupportedLanguage::Json
+            | SupportedLanguage::Toml
+            | SupportedLanguage::Yaml
+            | SupportedLanguage::Dynamic(_) => Err(Error::NoTemplate(language)),
+        }
+    }
+
+    fn check(&self, vex_id: &VexId, language: SupportedLanguage) -> String {
+        let Self { query, .. } = self;
+        format!(
+            r#"def init():
+    vex.declare(
+        '{vex_id}',
+        description='finds calls to todo()',
+        languages=['{language}'],
+    )
+    vex.observe('open_project', on_open_project)
+
+def on_open_project(event):
+    # This example query finds every call to a function, capturing the function's name as
+    # `@name` and the whole call as `@call`. To write a query of your own, view the syntax tree
+    # of a file with `vex dump --format pretty <path>`, then try the query out with
+    # `vex dump <path> --query '<query>'`.
+    vex.search(
+        '{language}',
+        '{query}',
+        on_match,
+    )
+
+def on_match(event):
+    if str(event.captures['name']) != 'todo':
+        return
+    vex.warn('{vex_id}', 'found call to todo()', at=event.captures['call'])
+"#
+        )
+    }
+
+    fn test(&self, vex_id: &VexId, language: SupportedLanguage) -> String {
+        let Self {
+            extension,
+            bad,
+            good,
+            ..
+        } = self;
+        let indent = |code: &str| textwrap::indent(code, "            ");
+        let (bad, good) = (indent(bad), indent(good));
+        format!(
+            r#"# Tests for {vex_id}, run with `vex test`.
+
+def init():
+    vex.observe('pre_test_run', on_pre_test_run)
+    vex.observe('post_test_run', on_post_test_run)
+
+BAD = '{vex_id}/bad.{extension}'
+GOOD = '{vex_id}/good.{extension}'
+
+def on_pre_test_run(event):
+    # Code in which {vex_id} should find problems.
+    vex.scan(
+        BAD,
+        '{language}',
+        '''
+{bad}        ''',
+    )
+
+    # Code in which {vex_id} should find no problems.
+    vex.scan(
+        GOOD,
+        '{language}',
+        '''
+{good}        ''',
+    )
+
+def on_post_test_run(event):
+    warnings = event.warnings
+    if BAD not in warnings or '{vex_id}' not in warnings[BAD]:
+        fail('{vex_id} found no problems in %s' % BAD)
+    if GOOD in warnings and '{vex_id}' in warnings[GOOD]:
+        fail('{vex_id} found problems in %s' % GOOD)
+"#
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{scriptlets::source, test};
+
+    use super::*;
+
+    fn new_project() -> (tempfile::TempDir, Context) {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::write(root_path.join("vex.toml"), "[vex]\nversion = '1'").unwrap();
+        fs::create_dir(root_path.join("vexes")).unwrap();
+        let ctx = Context::acquire(&root_path).unwrap();
+        (root_dir, ctx)
+    }
+
+    fn new_cmd(name: &str, language: &str) -> NewCmd {
+        NewCmd {
+            name: name.into(),
+            language: language.into(),
+            force: false,
+        }
+    }
+
+    #[test]
+    fn generated_tests_pass() {
+        for language in TEMPLATE_LANGUAGES {
+            if !language.is_available() {
+                continue;
+            }
+            let (_root_dir, ctx) = new_project();
+            let (check_path, _) = create(&ctx, &new_cmd("no-todo", language.name())).unwrap();
+            let run_tests = || test::run_tests(&source::sources_in_project(&ctx).unwrap());
+            run_tests().unwrap();
+
+            // The test fails if the check stops finding problems.
+            let check_path = ctx.project_root.join(check_path.as_str());
+            let check = fs::read_to_string(&check_path).unwrap();
+            fs::write(&check_path, check.replace("!= 'todo'", "!= 'other'")).unwrap();
+            assert!(
+                run_tests()
+                    .unwrap_err()
+                    .to_string()
+                    .contains("no-todo found no problems in no-todo/bad"),
+                "{language} test did not fail"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "lang-python", feature = "lang-rust"))]
+    fn starter_checks_find_problems() {
+        use crate::vextest::VexTest;
+
+        for template in [
+            InitTemplate::Rust,
+            InitTemplate::Python,
+            InitTemplate::Mixed,
+        ] {
+            let checks = starter_checks(template);
+            let run_data = checks
+                .iter()
+                .fold(VexTest::new("starter-checks"), |test, (vex_id, content)| {
+                    test.with_scriptlet(format!("vexes/{vex_id}.star"), content.as_str())
+                })
+                .with_source_file(
+                    "src/main.rs",
+                    "// TODO: remove\nfn main() {\n    dbg!(1);\n}\n",
+                )
+                .with_source_file("src/main.py", "# TODO: remove\nbreakpoint()\n")
+                .try_run()
+                .unwrap();
+            let mut found = run_data
+                .irritations
+                .iter()
+                .map(|irritation| {
+                    format!("{} in {}", irritation.vex_id(), irritation.path().unwrap())
+                })
+                .collect::<Vec<_>>();
+            found.sort();
+
+            let expected: &[_] = match template {
+                InitTemplate::Rust => &[
+                    "no-dbg-macro in src/main.rs",
+                    "no-todo-comments in src/main.rs",
+                ],
+                InitTemplate::Python => &[
+                    "no-breakpoint in src/main.py",
+                    "no-todo-comments in src/main.py",
+                ],
+                InitTemplate::Mixed => &[
+                    "no-breakpoint in src/main.py",
+                    "no-dbg-macro in src/main.rs",
+                    "no-todo-comments in src/main.py",
+                    "no-todo-comments in src/main.rs",
+                ],
+            };
+            assert_eq!(found, expected, "unexpected problems for {template:?}");
+        }
+    }
+
+    #[test]
+    fn existing_starter_checks() {
+        let (_root_dir, ctx) = new_project();
+        let paths = add_starter_checks(&ctx, InitTemplate::Rust, false).unwrap();
+        assert_eq!(
+            paths.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["vexes/no-todo-comments.star", "vexes/no-dbg-macro.star"]
+        );
+        assert_eq!(
+            add_starter_checks(&ctx, InitTemplate::Mixed, false)
+                .unwrap_err()
+                .to_string(),
+            "vexes/no-todo-comments.star already exists, to overwrite it, use --force"
+        );
+        add_starter_checks(&ctx, InitTemplate::Mixed, true).unwrap();
+    }
+
+    #[test]
+    fn existing() {
+        let (_root_dir, ctx) = new_project();
+        create(&ctx, &new_cmd("no-todo", "rust")).unwrap();
+        assert_eq!(
+            create(&ctx, &new_cmd("no-todo", "rust"))
+                .unwrap_err()
+                .to_string(),
+            "vexes/no-todo.star already exists, to overwrite it, use --force"
+        );
+        create(
+            &ctx,
+            &NewCmd {
+                force: true,
+                ..new_cmd("no-todo", "python")
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            create(&ctx, &new_cmd("no-todo", "cobol"))
+                .unwrap_err()
+                .to_string(),
+            "unsupported language 'cobol'"
+        );
+        assert_eq!(
+            create(&ctx, &new_cmd("no-todo", "json"))
+                .unwrap_err()
+                .to_string(),
+            "no template for new json checks, expected one of: go, java, kotlin, python, ruby, rust"
+        );
+    }
+}
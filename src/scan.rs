@@ -1,130 +1,395 @@
 use std::{
-    ops::Deref,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
     },
+    thread,
 };
 
+use camino::{Utf8Path, Utf8PathBuf};
 use dupe::Dupe;
+use indicatif::{ProgressBar, ProgressStyle};
+use joinery::JoinableIterator;
 use log::{info, log_enabled};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use starlark::values::FrozenHeap;
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use starlark::values::{AllocValue, FrozenHeap};
 use tree_sitter::QueryCursor;
 
 use crate::{
-    cli::{MaxConcurrentFileLimit, MaxProblems},
-    context::Context,
+    cache::Cache,
+    check_metadata::CheckMetadata,
+    cli::{MaxConcurrentFileLimit, MaxProblems, SortBy},
+    context::{CheckPaths, Context, NonUtf8Policy},
+    error::Error,
+    external,
+    injection::Injection,
     irritation::Irritation,
+    plural::Plural,
     query::Query,
+    query_plan::{QueryPlan, QueryStep},
     result::Result,
     scriptlets::{
         action::Action,
-        event::{EventKind, MatchEvent, OpenFileEvent, OpenProjectEvent},
+        artifact_cache::{ArtifactCache, LazyParsedFile},
+        event::{
+            CloseFileEvent, CloseProjectEvent, EventKind, MatchEvent, OpenFileEvent,
+            OpenProjectEvent,
+        },
         handler_module::HandlerModule,
+        intent_log::IntentLog,
         intents::Intent,
-        query_cache::QueryCache,
+        limits::HandlerLimits,
         query_captures::QueryCaptures,
+        shared_store::SharedStore,
         Observable, ObserveOptions, Observer, PrintHandler, VexingStore,
     },
-    source_file::{self, SourceFile},
+    severity::Severity,
+    source_file::{self, ParsedSourceFile, SourceFile},
+    source_path::{PrettyPath, SourcePath},
     supported_language::SupportedLanguage,
+    tags::CheckTags,
+    theme,
+    timings::Timer,
     verbosity::Verbosity,
+    vex_id::VexId,
 };
 
+/// The number of files whose content may be read before they are scanned.
+const READ_AHEAD_LIMIT: usize = 64;
+
+/// The outcome of scanning a project.
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct ProjectRunData {
     pub irritations: Vec<Irritation>,
     pub num_files_scanned: u64,
     pub num_bytes_scanned: u64,
+
+    /// The number of problems found by each check but not reported due to `max_problems`.
+    pub num_hidden: BTreeMap<VexId, usize>,
+
+    /// The number of problems found by each check but not reported as it reached its own limit.
+    pub num_truncated: BTreeMap<VexId, usize>,
+
+    /// Whether some files were left unscanned as `max_problems` had been reached.
+    pub stopped_early: bool,
+
+    /// The number of files left unscanned as their language is unknown, by extension.
+    pub num_skipped: BTreeMap<String, usize>,
+}
+
+impl ProjectRunData {
+    /// Explain which problems were hidden due to `max_problems`, if any.
+    pub fn overflow_hint(&self) -> Option<String> {
+        let hidden = if !self.num_hidden.is_empty() {
+            let num_hidden = self.num_hidden.values().sum::<usize>();
+            let by_check = self
+                .num_hidden
+                .iter()
+                .map(|(vex_id, count)| format!("{vex_id}: {count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "{}{} hidden by --max-problems ({by_check})",
+                if self.stopped_early { "at least " } else { "" },
+                Plural::new(num_hidden, "more problem", "more problems"),
+            )
+        } else if self.stopped_early {
+            "some files were not checked as --max-problems was reached".into()
+        } else {
+            return None;
+        };
+        Some(format!(
+            "{hidden}\nto see all problems, use `--max-problems unlimited` or narrow the scan with `--changed` or `--only-tags`"
+        ))
+    }
+
+    /// Note how many problems each check which reached its own limit did not report.
+    pub fn truncation_notes(&self) -> Vec<String> {
+        let ellipsis = theme::current().ellipsis();
+        self.num_truncated
+            .iter()
+            .map(|(vex_id, count)| format!("{ellipsis} and {count} more from check {vex_id}"))
+            .collect()
+    }
+
+    /// Tabulate the files left unscanned as their language is unknown, most common extension
+    /// first, if any.
+    pub fn skipped_table(&self) -> Option<String> {
+        if self.num_skipped.is_empty() {
+            return None;
+        }
+        let mut rows: Vec<_> = self.num_skipped.iter().collect();
+        rows.sort_by(|(ext, count), (other_ext, other_count)| {
+            other_count.cmp(count).then_with(|| ext.cmp(other_ext))
+        });
+        let width = rows
+            .iter()
+            .map(|(ext, _)| ext.len())
+            .max()
+            .unwrap_or_default();
+        let mut table = format!(
+            "skipped {} in no known language:",
+            Plural::new(self.num_skipped.values().sum::<usize>(), "file", "files"),
+        );
+        for (ext, count) in rows {
+            table.push_str(&format!("\n  {ext:width$}  {count}"));
+        }
+        Some(table)
+    }
+}
+
+/// A bar which counts scanned files and names the latest.
+fn progress_bar(num_files: usize) -> ProgressBar {
+    let style = ProgressStyle::with_template("{bar:30} {pos}/{len} files {wide_msg}")
+        .expect("internal error: invalid progress template")
+        .progress_chars(theme::current().progress_chars());
+    ProgressBar::new(num_files as u64).with_style(style)
+}
+
+/// Whether the given file is larger than the manifest allows, in which case it is skipped.
+fn exceeds_max_size(ctx: &Context, file: &SourceFile) -> bool {
+    let Some(max_file_size) = ctx.files.max_file_size else {
+        return false;
+    };
+    let Ok(metadata) = fs::metadata(file.path().abs_path.as_str()) else {
+        return false; // Reading will report the problem.
+    };
+    let exceeded = metadata.len() > max_file_size;
+    if exceeded && log_enabled!(log::Level::Info) {
+        info!(
+            "skipping {}: {} bytes exceeds max-file-size of {max_file_size}",
+            file.path(),
+            metadata.len()
+        );
+    }
+    exceeded
 }
 
+/// How a skipped file is grouped in the table of skipped files.
+fn extension_label(file: &SourceFile) -> String {
+    match file.path().abs_path.extension() {
+        Some(ext) => format!(".{ext}"),
+        None => "(none)".into(),
+    }
+}
+
+/// Options which control how a project is scanned.
+#[derive(Debug, Default)]
+pub struct ScanOptions<'a> {
+    pub changed_files: Option<&'a BTreeSet<Utf8PathBuf>>,
+
+    /// Content staged in git to scan in place of that in the working tree, by path relative to
+    /// the project root.
+    pub staged_contents: Option<&'a BTreeMap<Utf8PathBuf, Vec<u8>>>,
+
+    pub cache: Option<&'a Cache>,
+    pub only_tags: &'a [String],
+    pub sort_by: SortBy,
+    pub max_problems: MaxProblems,
+
+    /// The least severe problems which are reported, if not all are. Others do not count
+    /// towards `max_problems`.
+    pub report_level: Option<Severity>,
+
+    /// The most problems to report from any one check, in addition to the limits set for
+    /// individual checks in the manifest.
+    pub max_problems_per_check: Option<u32>,
+
+    pub max_concurrent_files: MaxConcurrentFileLimit,
+    pub verbosity: Verbosity,
+
+    /// Artifacts left by an earlier run which may be reused, otherwise a new cache is used.
+    pub artifact_cache: Option<&'a ArtifactCache>,
+
+    /// Whether an error raised by a scriptlet stops the scan, rather than being reported as a
+    /// problem found by that scriptlet.
+    pub fail_fast: bool,
+
+    /// Where to record every intent declared during the scan, if anywhere.
+    pub intent_log: Option<&'a IntentLog>,
+
+    /// Whether to follow symlinks, even if the manifest does not ask to.
+    pub follow_symlinks: bool,
+
+    /// Whether to draw a bar on stderr showing how many files have been scanned.
+    pub progress: bool,
+}
+
+/// Run all vexes in the store over the files in the project, returning all problems found.
 pub fn scan_project(
     ctx: &Context,
     store: &VexingStore,
-    max_problems: MaxProblems,
-    max_concurrent_files: MaxConcurrentFileLimit,
-    verbosity: Verbosity,
+    opts: ScanOptions<'_>,
 ) -> Result<ProjectRunData> {
-    let files = source_file::sources_in_dir(ctx, max_concurrent_files)?;
+    let ScanOptions {
+        changed_files,
+        staged_contents,
+        cache,
+        only_tags,
+        sort_by,
+        max_problems,
+        report_level,
+        max_problems_per_check,
+        max_concurrent_files,
+        verbosity,
+        artifact_cache,
+        fail_fast,
+        intent_log,
+        follow_symlinks,
+        progress,
+    } = opts;
+    let keep_going = !fail_fast;
+    let walk_timer = Timer::start("walk");
+    let mut files = source_file::sources_in_dir(ctx, max_concurrent_files, follow_symlinks)?;
+    if let Some(changed_files) = changed_files {
+        files.retain(|file| {
+            let changed = changed_files.contains(file.path().pretty_path.as_ref());
+            if !changed && log_enabled!(log::Level::Info) {
+                info!("skipping {}: unchanged", file.path());
+            }
+            changed
+        });
+    }
+    walk_timer.stop();
 
-    let project_queries_hint = store.project_queries_hint();
-    let file_queries_hint = store.file_queries_hint();
+    let new_artifact_cache;
+    let artifact_cache = match artifact_cache {
+        Some(artifact_cache) => artifact_cache,
+        None => {
+            new_artifact_cache = ArtifactCache::with_capacity(
+                store.project_queries_hint() + store.file_queries_hint(),
+            );
+            &new_artifact_cache
+        }
+    };
 
-    let query_cache = QueryCache::with_capacity(project_queries_hint + file_queries_hint);
+    let injections = Injection::compile_all(&ctx.injections, artifact_cache)?;
 
-    let mut irritations = vec![];
+    // Results which depend on state shared between files cannot be cached per file.
+    let cache = cache.filter(|_| store.observers_for(EventKind::CloseProject).is_empty());
+    let shared_store = SharedStore::default();
     let frozen_heap = store.frozen_heap();
-    let project_queries = {
-        let mut project_queries = Vec::with_capacity(project_queries_hint);
-
-        let event = OpenProjectEvent::new(ctx.project_root.dupe());
-        let handler_module = HandlerModule::new();
-        let observe_opts = ObserveOptions {
-            action: Action::Vexing(event.kind()),
-            query_cache: Some(&query_cache),
-            ignore_markers: None,
-            print_handler: &PrintHandler::new(verbosity, event.kind().name()),
-        };
-        store.observers_for(event.kind()).observe(
-            &handler_module,
-            handler_module.heap().alloc(event),
-            observe_opts,
-        )?;
-        handler_module
-            .into_intents_on(frozen_heap.deref())?
+    let (mut irritations, project_queries) = open_project(
+        ctx,
+        store,
+        &shared_store,
+        artifact_cache,
+        &frozen_heap,
+        verbosity,
+        keep_going,
+        intent_log,
+    )?;
+    check_coverage(ctx, store, &project_queries)?;
+
+    // Files are read on a separate thread so that disk I/O overlaps with parsing and querying.
+    // Content is only read ahead if it will certainly be needed.
+    let reported_counter =
+        ReportedCounter::new(ctx, store, only_tags, report_level, max_problems_per_check)?;
+    let stopped_early = AtomicBool::new(false);
+    let file_queries_possible = !store.observers_for(EventKind::OpenFile).is_empty();
+    let needs_content = |language| {
+        cache.is_some()
+            || staged_contents.is_some()
+            || file_queries_possible
+            || project_queries.iter().any(|(l, _, _)| *l == language)
+            || injections
+                .iter()
+                .any(|injection| injection.language == language)
+    };
+    let progress_bar = if progress {
+        progress_bar(files.len())
+    } else {
+        ProgressBar::hidden()
+    };
+    let files = &files;
+    let (runs, num_skipped): (Vec<_>, _) = thread::scope(|scope| {
+        let (read_tx, read_rx) = mpsc::sync_channel(READ_AHEAD_LIMIT);
+        let skipped_progress_bar = progress_bar.clone();
+        let reader = scope.spawn(move || {
+            let mut num_skipped = BTreeMap::<_, usize>::new();
+            for file in files {
+                let Some(language) = file.language() else {
+                    *num_skipped.entry(extension_label(file)).or_default() += 1;
+                    skipped_progress_bar.inc(1);
+                    continue;
+                };
+                if exceeds_max_size(ctx, file) {
+                    skipped_progress_bar.inc(1);
+                    continue;
+                }
+                let staged_content = staged_contents.and_then(|staged_contents| {
+                    staged_contents.get(file.path().pretty_path.as_ref())
+                });
+                let content = needs_content(language)
+                    .then(|| match staged_content {
+                        Some(staged_content) => file.decode(staged_content.clone()),
+                        None => file.read(),
+                    })
+                    .transpose();
+                if read_tx.send((file, language, content)).is_err() {
+                    break; // Scanning stopped early.
+                }
+            }
+            num_skipped
+        });
+
+        let runs = read_rx
             .into_iter()
-            .for_each(|intent| match intent {
-                Intent::Find {
+            .par_bridge()
+            .map(|(file, language, content)| {
+                let opts = VexFileOptions {
+                    store,
+                    shared_store: &shared_store,
                     language,
-                    query,
-                    on_match,
-                } => project_queries.push((language, query, on_match)),
-                Intent::Observe { .. } => panic!("internal error: non-init observe"),
-                Intent::Warn(irr) => irritations.push(irr),
-                Intent::ScanFile { .. } => {
-                    panic!("internal error: unexpected ScanFile intent declared")
+                    project_queries: &project_queries,
+                    injections: &injections,
+                    artifact_cache,
+                    cache,
+                    verbosity,
+                    limits: ctx.run.handler_limits(),
+                    single_pass_queries: ctx.run.single_pass_queries,
+                    keep_going,
+                    intent_log,
+                };
+                progress_bar.set_message(file.path().pretty_path.to_string());
+                let run = content.and_then(|content| scan_file(file, content, opts));
+                progress_bar.inc(1);
+                match run {
+                    Err(Error::FileChanging(path)) => {
+                        log::warn!("skipping {path}: it kept changing while being read");
+                        Ok(FileRunData::default())
+                    }
+                    Err(err @ (Error::BinaryFile(_) | Error::NotUtf8(_)))
+                        if ctx.files.non_utf8 != NonUtf8Policy::Error =>
+                    {
+                        if log_enabled!(log::Level::Info) {
+                            info!("skipping: {err}");
+                        }
+                        Ok(FileRunData::default())
+                    }
+                    run => run,
                 }
-            });
-        project_queries
-    };
-
-    let total_irritations = AtomicUsize::new(0);
-    let runs: Vec<_> = files
-        .par_iter()
-        .filter_map(|file| match file.language() {
-            Some(language) => Some((file, language)),
-            None => {
-                if log_enabled!(log::Level::Info) {
-                    info!("skipping {}", file.path());
+            })
+            .take_any_while(|file_scan_result| {
+                let run = match file_scan_result {
+                    Ok(run) => run,
+                    Err(_) => return true,
+                };
+                let prev_total_irritations = reported_counter.add(&run.irritations);
+                let exceeded = max_problems.is_exceeded_by(prev_total_irritations);
+                if exceeded {
+                    stopped_early.store(true, Ordering::Relaxed);
                 }
-                None
-            }
-        })
-        .map(|(file, language)| {
-            let opts = VexFileOptions {
-                store,
-                language,
-                project_queries: &project_queries,
-                query_cache: &query_cache,
-                verbosity,
-            };
-            scan_file(file, opts)
-        })
-        .take_any_while(|file_scan_result| {
-            let run = match file_scan_result {
-                Ok(run) => run,
-                Err(_) => return true,
-            };
-            let new_irritations = run.irritations.len();
-            let prev_total_irritations = if new_irritations > 0 {
-                total_irritations.fetch_add(new_irritations, Ordering::Relaxed)
-            } else {
-                total_irritations.load(Ordering::Relaxed)
-            };
-            !max_problems.is_exceeded_by(prev_total_irritations)
-        })
-        .collect::<Result<_>>()?;
+                !exceeded
+            })
+            .collect::<Result<_>>();
+        let num_skipped = reader.join().expect("internal error: file reader panicked");
+        runs.map(|runs| (runs, num_skipped))
+    })?;
+    progress_bar.finish_and_clear();
 
     let num_files_scanned = runs.len() as u64;
     let num_bytes_scanned = runs.iter().map(|run| run.num_bytes_scanned).sum();
@@ -132,22 +397,506 @@ pub fn scan_project(
         irritations.extend(run.irritations);
     }
 
-    irritations.sort();
-    if let MaxProblems::Limited(max) = max_problems {
-        let max = max as usize;
-        if max < irritations.len() {
-            irritations.truncate(max);
+    // The project is closed only once every file has been closed.
+    let event = CloseProjectEvent::new(ctx.project_root.dupe());
+    irritations.extend(close(
+        store.observers_for(EventKind::CloseProject),
+        &shared_store,
+        store.check_metadata(),
+        event,
+        EventKind::CloseProject,
+        artifact_cache,
+        verbosity,
+        ctx.run.handler_limits(),
+        keep_going,
+        intent_log,
+    )?);
+
+    irritations.extend(external::run_external_checks(
+        ctx,
+        store.severity_overrides(),
+        store.selection(),
+        changed_files,
+    )?);
+    deduplicate(store, &mut irritations);
+    apply_exemptions(ctx, &mut irritations)?;
+    let (num_truncated, num_hidden) = select(
+        ctx,
+        store,
+        &mut irritations,
+        only_tags,
+        sort_by,
+        max_problems_per_check,
+        max_problems,
+        report_level,
+    )?;
+
+    Ok(ProjectRunData {
+        irritations,
+        num_files_scanned,
+        num_bytes_scanned,
+        num_hidden,
+        num_truncated,
+        stopped_early: stopped_early.into_inner(),
+        num_skipped,
+    })
+}
+
+/// Scan content which has not been saved to the given path, such as an editor's buffer, running
+/// the checks which a scan of the project would run on the file at that path. As no other file is
+/// scanned, `close_project` handlers and external checks are not run.
+pub fn scan_buffer(
+    ctx: &Context,
+    store: &VexingStore,
+    path: &SourcePath,
+    content: String,
+    opts: ScanOptions<'_>,
+) -> Result<ProjectRunData> {
+    let ScanOptions {
+        only_tags,
+        sort_by,
+        max_problems,
+        report_level,
+        max_problems_per_check,
+        verbosity,
+        ..
+    } = opts;
+    if !source_file::is_scanned(ctx, &path.abs_path)? {
+        return Ok(ProjectRunData::default());
+    }
+    let language = ctx
+        .associations()?
+        .detect_language_of_content(path, &content)?;
+    let file = SourceFile::new(path.dupe(), language);
+    if language.is_none() {
+        return Ok(ProjectRunData {
+            num_skipped: [(extension_label(&file), 1)].into(),
+            ..ProjectRunData::default()
+        });
+    }
+    if let Some(max_file_size) = ctx.files.max_file_size {
+        if content.len() as u64 > max_file_size {
+            if log_enabled!(log::Level::Info) {
+                info!(
+                    "skipping {path}: {} bytes exceeds max-file-size of {max_file_size}",
+                    content.len(),
+                );
+            }
+            return Ok(ProjectRunData::default());
         }
     }
 
+    let num_bytes_scanned = content.len() as u64;
+    let mut irritations = scan_single_file(ctx, store, &file, content, verbosity)?;
+    let (num_truncated, num_hidden) = select(
+        ctx,
+        store,
+        &mut irritations,
+        only_tags,
+        sort_by,
+        max_problems_per_check,
+        max_problems,
+        report_level,
+    )?;
     Ok(ProjectRunData {
         irritations,
-        num_files_scanned,
+        num_files_scanned: 1,
         num_bytes_scanned,
+        num_hidden,
+        num_truncated,
+        ..ProjectRunData::default()
     })
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// Keep only problems found by checks with any of the given tags and sort them in the given
+/// order, hiding any beyond the limit of the check which found them and then any reported beyond
+/// `max_problems`. Returns how many problems each check had truncated by its own limit and how
+/// many were hidden.
+#[allow(clippy::too_many_arguments)]
+fn select(
+    ctx: &Context,
+    store: &VexingStore,
+    irritations: &mut Vec<Irritation>,
+    only_tags: &[String],
+    sort_by: SortBy,
+    max_problems_per_check: Option<u32>,
+    max_problems: MaxProblems,
+    report_level: Option<Severity>,
+) -> Result<(BTreeMap<VexId, usize>, BTreeMap<VexId, usize>)> {
+    if !only_tags.is_empty() {
+        let check_tags = CheckTags::new(ctx, store)?;
+        irritations.retain(|irr| check_tags.has_any(irr.vex_id(), only_tags));
+    }
+
+    let sort_timer = Timer::start("sort");
+    irritations.sort_by(|irritation, other| irritation.cmp_by(other, sort_by));
+    sort_timer.stop();
+
+    let check_limits = ctx.per_check.max_problems();
+    let mut num_truncated = BTreeMap::new();
+    if max_problems_per_check.is_some() || !check_limits.is_empty() {
+        let mut num_kept: BTreeMap<VexId, usize> = BTreeMap::new();
+        irritations.retain(|irritation| {
+            let vex_id = irritation.vex_id();
+            let limit = max_problems_per_check
+                .into_iter()
+                .chain(check_limits.get(vex_id.as_ref()).copied())
+                .min();
+            let num_kept = num_kept.entry(vex_id.clone()).or_default();
+            if limit.is_some_and(|limit| *num_kept >= limit as usize) {
+                *num_truncated.entry(vex_id.clone()).or_default() += 1;
+                return false;
+            }
+            *num_kept += 1;
+            true
+        });
+    }
+
+    // Problems which are not reported are kept, as they may still fail the run.
+    let mut num_hidden = BTreeMap::new();
+    if let MaxProblems::Limited(max) = max_problems {
+        let mut num_reported = 0;
+        irritations.retain(|irritation| {
+            if !is_reported(irritation, report_level) {
+                return true;
+            }
+            num_reported += 1;
+            if num_reported <= max {
+                return true;
+            }
+            *num_hidden.entry(irritation.vex_id().clone()).or_default() += 1;
+            false
+        });
+    }
+    Ok((num_truncated, num_hidden))
+}
+
+fn is_reported(irritation: &Irritation, report_level: Option<Severity>) -> bool {
+    report_level.map_or(true, |report_level| irritation.severity() >= report_level)
+}
+
+/// Counts the problems found so far which will be reported once the scan completes. Problems
+/// which will later be dropped as duplicates, as exempt, for lacking a selected tag, for being
+/// beyond their check's limit or for being below the report level are not counted, so that they
+/// cannot stop a scan before `max_problems` are reported.
+struct ReportedCounter<'a> {
+    store: &'a VexingStore,
+    project_root: &'a Utf8Path,
+    exemptions: BTreeMap<&'a str, CheckPaths>,
+    only_tags: Option<(&'a [String], CheckTags)>,
+    report_level: Option<Severity>,
+    max_problems_per_check: Option<u32>,
+    check_limits: BTreeMap<&'a str, u32>,
+    state: Mutex<ReportedCount>,
+}
+
+#[derive(Default)]
+struct ReportedCount {
+    total: usize,
+    by_check: BTreeMap<VexId, usize>,
+}
+
+impl<'a> ReportedCounter<'a> {
+    fn new(
+        ctx: &'a Context,
+        store: &'a VexingStore,
+        only_tags: &'a [String],
+        report_level: Option<Severity>,
+        max_problems_per_check: Option<u32>,
+    ) -> Result<Self> {
+        let only_tags = if only_tags.is_empty() {
+            None
+        } else {
+            Some((only_tags, CheckTags::new(ctx, store)?))
+        };
+        Ok(Self {
+            store,
+            project_root: &ctx.project_root,
+            exemptions: ctx.per_check.exemptions(ctx.files.case_sensitivity())?,
+            only_tags,
+            report_level,
+            max_problems_per_check,
+            check_limits: ctx.per_check.max_problems(),
+            state: Mutex::default(),
+        })
+    }
+
+    /// Count the problems found in a single file which will be reported, returning the total
+    /// counted before them.
+    fn add(&self, irritations: &[Irritation]) -> usize {
+        let unique = unique_irritations(self.store, irritations);
+        let mut state = self
+            .state
+            .lock()
+            .expect("internal error: counter lock poisoned");
+        let prev_total = state.total;
+        for (irritation, _) in irritations.iter().zip(unique).filter(|(_, unique)| *unique) {
+            let vex_id = irritation.vex_id();
+            if !is_reported(irritation, self.report_level)
+                || is_exempt(self.project_root, &self.exemptions, irritation)
+                || self
+                    .only_tags
+                    .as_ref()
+                    .is_some_and(|(only_tags, check_tags)| !check_tags.has_any(vex_id, only_tags))
+            {
+                continue;
+            }
+            let limit = self
+                .max_problems_per_check
+                .into_iter()
+                .chain(self.check_limits.get(vex_id.as_ref()).copied())
+                .min();
+            let num_kept = state.by_check.entry(vex_id.clone()).or_default();
+            if limit.is_some_and(|limit| *num_kept >= limit as usize) {
+                continue;
+            }
+            *num_kept += 1;
+            state.total += 1;
+        }
+        prev_total
+    }
+}
+
+type ProjectQueries = Vec<(SupportedLanguage, Arc<Query>, Observer)>;
+
+/// Ensure that every language listed in `require-checks-for` is searched by some check, either
+/// with a query from `open_project` or by declaring that it searches that language.
+fn check_coverage(
+    ctx: &Context,
+    store: &VexingStore,
+    project_queries: &ProjectQueries,
+) -> Result<()> {
+    let covered: BTreeSet<_> = project_queries
+        .iter()
+        .map(|(language, _, _)| *language)
+        .chain(
+            store
+                .check_metadata()
+                .iter()
+                .flat_map(|(_, info)| info.languages.iter().copied()),
+        )
+        .collect();
+    let mut uncovered = ctx
+        .run
+        .require_checks_for
+        .iter()
+        .map(|language| language.parse())
+        .filter(|language| !matches!(language, Ok(language) if covered.contains(language)))
+        .collect::<Result<Vec<SupportedLanguage>>>()?;
+    if uncovered.is_empty() {
+        return Ok(());
+    }
+    uncovered.sort();
+    uncovered.dedup();
+    Err(Error::MissingChecks(uncovered))
+}
+
+/// Fire the `open_project` event, returning the problems found and the queries to run on each
+/// file.
+#[allow(clippy::too_many_arguments)]
+fn open_project(
+    ctx: &Context,
+    store: &VexingStore,
+    shared_store: &SharedStore,
+    artifact_cache: &ArtifactCache,
+    frozen_heap: &FrozenHeap,
+    verbosity: Verbosity,
+    keep_going: bool,
+    intent_log: Option<&IntentLog>,
+) -> Result<(Vec<Irritation>, ProjectQueries)> {
+    let mut irritations = vec![];
+    let mut project_queries = Vec::with_capacity(store.project_queries_hint());
+
+    let event = OpenProjectEvent::new(ctx.project_root.dupe());
+    let handler_module = HandlerModule::new();
+    let observe_opts = ObserveOptions {
+        action: Action::Vexing(event.kind()),
+        artifact_cache: Some(artifact_cache),
+        ignore_markers: None,
+        shared_store: Some(shared_store),
+        check_metadata: Some(store.check_metadata()),
+        print_handler: &PrintHandler::new(verbosity, event.kind().name()),
+        limits: ctx.run.handler_limits(),
+        keep_going,
+        intent_log,
+    };
+    store.observers_for(event.kind()).observe(
+        &handler_module,
+        handler_module.heap().alloc(event),
+        observe_opts,
+    )?;
+    handler_module
+        .into_intents_on(frozen_heap)?
+        .into_iter()
+        .try_for_each(|intent| {
+            match intent {
+                Intent::Find {
+                    language,
+                    query,
+                    on_match,
+                } => project_queries.push((language, query, on_match)),
+                Intent::Warn(irr) => irritations.push(irr),
+                _ => return Err(intent.unavailable_while(Action::Vexing(EventKind::OpenProject))),
+            }
+            Ok(())
+        })?;
+    Ok((irritations, project_queries))
+}
+
+/// Fire an event which follows the scan of a file or of the project, returning the problems
+/// found.
+#[allow(clippy::too_many_arguments)]
+fn close<E>(
+    observers: &[Observer],
+    shared_store: &SharedStore,
+    check_metadata: &CheckMetadata,
+    event: E,
+    event_kind: EventKind,
+    artifact_cache: &ArtifactCache,
+    verbosity: Verbosity,
+    limits: HandlerLimits,
+    keep_going: bool,
+    intent_log: Option<&IntentLog>,
+) -> Result<Vec<Irritation>>
+where
+    E: for<'v> AllocValue<'v>,
+{
+    if observers.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let handler_module = HandlerModule::new();
+    let observe_opts = ObserveOptions {
+        action: Action::Vexing(event_kind),
+        artifact_cache: Some(artifact_cache),
+        ignore_markers: None,
+        shared_store: Some(shared_store),
+        check_metadata: Some(check_metadata),
+        print_handler: &PrintHandler::new(verbosity, event_kind.name()),
+        limits,
+        keep_going,
+        intent_log,
+    };
+    observers.observe(
+        &handler_module,
+        handler_module.heap().alloc(event),
+        observe_opts,
+    )?;
+    handler_module
+        .into_intents_on(&FrozenHeap::new())?
+        .into_iter()
+        .map(|intent| match intent {
+            Intent::Warn(irr) => Ok(irr),
+            _ => Err(intent.unavailable_while(Action::Vexing(event_kind))),
+        })
+        .collect()
+}
+
+/// Scan a single file whose content may differ from that on disk, such as an unsaved document in
+/// an editor. Only problems found in the file itself are returned.
+pub fn scan_single_file(
+    ctx: &Context,
+    store: &VexingStore,
+    file: &SourceFile,
+    content: String,
+    verbosity: Verbosity,
+) -> Result<Vec<Irritation>> {
+    let Some(language) = file.language() else {
+        return Ok(vec![]);
+    };
+
+    let artifact_cache =
+        ArtifactCache::with_capacity(store.project_queries_hint() + store.file_queries_hint());
+    let shared_store = SharedStore::default();
+    let frozen_heap = store.frozen_heap();
+    let (_, project_queries) = open_project(
+        ctx,
+        store,
+        &shared_store,
+        &artifact_cache,
+        &frozen_heap,
+        verbosity,
+        true,
+        None,
+    )?;
+    let injections = Injection::compile_all(&ctx.injections, &artifact_cache)?;
+    let opts = VexFileOptions {
+        store,
+        shared_store: &shared_store,
+        language,
+        project_queries: &project_queries,
+        injections: &injections,
+        artifact_cache: &artifact_cache,
+        cache: None,
+        verbosity,
+        limits: ctx.run.handler_limits(),
+        single_pass_queries: ctx.run.single_pass_queries,
+        keep_going: true,
+        intent_log: None,
+    };
+    let mut irritations = scan_file_content(file, Some(content), opts)?.irritations;
+    deduplicate(store, &mut irritations);
+    apply_exemptions(ctx, &mut irritations)?;
+    Ok(irritations)
+}
+
+/// Drop repeated problems, such as those found by overlapping queries, which share a check, a
+/// message and a location. Checks which declare `allow_duplicates` keep every problem.
+fn deduplicate(store: &VexingStore, irritations: &mut Vec<Irritation>) {
+    let mut keep = unique_irritations(store, irritations).into_iter();
+    irritations.retain(|_| {
+        keep.next()
+            .expect("internal error: irritation count changed")
+    });
+}
+
+/// Whether each problem is the first with its id, message and location, or was raised by a check
+/// which allows duplicates.
+fn unique_irritations(store: &VexingStore, irritations: &[Irritation]) -> Vec<bool> {
+    let check_metadata = store.check_metadata();
+    let mut seen = HashSet::new();
+    irritations
+        .iter()
+        .map(|irritation| {
+            let allows_duplicates = check_metadata
+                .get(irritation.vex_id())
+                .is_some_and(|info| info.allow_duplicates);
+            allows_duplicates
+                || seen.insert((
+                    irritation.vex_id().as_ref(),
+                    irritation.message(),
+                    irritation.path().map(PrettyPath::as_str),
+                    irritation.location(),
+                ))
+        })
+        .collect()
+}
+
+/// Drop problems found in paths which the manifest exempts from the check which found them.
+fn apply_exemptions(ctx: &Context, irritations: &mut Vec<Irritation>) -> Result<()> {
+    let exemptions = ctx.per_check.exemptions(ctx.files.case_sensitivity())?;
+    if exemptions.is_empty() {
+        return Ok(());
+    }
+    irritations.retain(|irritation| !is_exempt(&ctx.project_root, &exemptions, irritation));
+    Ok(())
+}
+
+fn is_exempt(
+    project_root: &Utf8Path,
+    exemptions: &BTreeMap<&str, CheckPaths>,
+    irritation: &Irritation,
+) -> bool {
+    let (Some(path), Some(paths)) = (
+        irritation.path(),
+        exemptions.get(irritation.vex_id().as_ref()),
+    ) else {
+        return false;
+    };
+    paths.exempts(&project_root.join(path.as_str()))
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialise, Serialise)]
 pub struct FileRunData {
     pub irritations: Vec<Irritation>,
     pub num_bytes_scanned: u64,
@@ -155,118 +904,303 @@ pub struct FileRunData {
 
 pub struct VexFileOptions<'a> {
     store: &'a VexingStore,
+    shared_store: &'a SharedStore,
     language: SupportedLanguage,
     project_queries: &'a [(SupportedLanguage, Arc<Query>, Observer)],
-    query_cache: &'a QueryCache,
+    injections: &'a [Injection],
+    artifact_cache: &'a ArtifactCache,
+    cache: Option<&'a Cache>,
     verbosity: Verbosity,
+    limits: HandlerLimits,
+    single_pass_queries: bool,
+    keep_going: bool,
+    intent_log: Option<&'a IntentLog>,
+}
+
+fn scan_file(
+    file: &SourceFile,
+    content: Option<String>,
+    opts: VexFileOptions<'_>,
+) -> Result<FileRunData> {
+    let Some(cache) = opts.cache else {
+        return scan_file_content(file, content, opts);
+    };
+
+    let content = match content {
+        Some(content) => content,
+        None => file.read()?,
+    };
+    let key = cache.key(file.path(), &content);
+    if let Some(run_data) = cache.get(key) {
+        if log_enabled!(log::Level::Info) {
+            info!("using cached results for {}", file.path());
+        }
+        return Ok(run_data);
+    }
+    let file_store = opts.shared_store.for_file();
+    let opts = VexFileOptions {
+        shared_store: &file_store,
+        ..opts
+    };
+    let run_data = scan_file_content(file, Some(content), opts)?;
+    if file_store.used() {
+        // Values in the store may have been written while scanning other files, and values
+        // written here would be lost if these results were reused, so they are not cached.
+        return Ok(run_data);
+    }
+    if run_data
+        .irritations
+        .iter()
+        .any(Irritation::is_scriptlet_error)
+    {
+        // Errors may be transient, such as a timeout, so are not cached.
+        return Ok(run_data);
+    }
+    if let Err(err) = cache.put(key, &run_data) {
+        if log_enabled!(log::Level::Info) {
+            info!("{err}");
+        }
+    }
+    Ok(run_data)
 }
 
-fn scan_file(file: &SourceFile, opts: VexFileOptions<'_>) -> Result<FileRunData> {
+fn scan_file_content(
+    file: &SourceFile,
+    content: Option<String>,
+    opts: VexFileOptions<'_>,
+) -> Result<FileRunData> {
     let VexFileOptions {
         store,
+        shared_store,
         language,
         project_queries,
-        query_cache,
+        injections,
+        artifact_cache,
+        cache: _,
         verbosity,
+        limits,
+        single_pass_queries,
+        keep_going,
+        intent_log,
     } = opts;
 
     let mut irritations = Vec::new();
 
     let frozen_heap = FrozenHeap::new();
+    let mut content = content;
+    let mut parsed_file = None;
+    let mut skipped = false;
     let file_queries = {
         let mut file_queries = Vec::with_capacity(store.file_queries_hint());
-        let path = file.path().pretty_path.dupe();
-
-        let event = OpenFileEvent::new(path);
-        let handler_module = HandlerModule::new();
-        let observe_opts = ObserveOptions {
-            action: Action::Vexing(event.kind()),
-            query_cache: Some(query_cache),
-            ignore_markers: None,
-            print_handler: &PrintHandler::new(verbosity, event.kind().name()),
-        };
-        store.observers_for(event.kind()).observe(
-            &handler_module,
-            handler_module.heap().alloc(event),
-            observe_opts,
-        )?;
-        handler_module
-            .into_intents_on(&frozen_heap)?
-            .into_iter()
-            .for_each(|intent| match intent {
-                Intent::Find {
-                    language,
-                    query,
-                    on_match,
-                } => file_queries.push((language, query, on_match)),
-                Intent::Observe { .. } => panic!("internal error: non-init observe"),
-                Intent::Warn(irr) => irritations.push(irr.clone()),
-                Intent::ScanFile { .. } => {
-                    panic!("internal error: unexpected ScanFile intent declared")
-                }
-            });
+        let observers = store.observers_for_path(EventKind::OpenFile, &file.path().pretty_path);
+        if !observers.is_empty() {
+            // Observers may inspect the content, so it is read before the event fires.
+            let file_content: Arc<str> = match content.take() {
+                Some(content) => content.into(),
+                None => file.read()?.into(),
+            };
+            let path = file.path().pretty_path.dupe();
+            let lazy_parsed_file = LazyParsedFile::new(file, file_content.dupe(), artifact_cache);
+            let event = OpenFileEvent::new(path, language, file_content.dupe(), &lazy_parsed_file);
+            let handler_module = HandlerModule::new();
+            let observe_opts = ObserveOptions {
+                action: Action::Vexing(event.kind()),
+                artifact_cache: Some(artifact_cache),
+                ignore_markers: None,
+                shared_store: Some(shared_store),
+                check_metadata: Some(store.check_metadata()),
+                print_handler: &PrintHandler::new(verbosity, event.kind().name()),
+                limits,
+                keep_going,
+                intent_log,
+            };
+            (&*observers).observe(
+                &handler_module,
+                handler_module.heap().alloc(event),
+                observe_opts,
+            )?;
+            handler_module
+                .into_intents_on(&frozen_heap)?
+                .into_iter()
+                .try_for_each(|intent| {
+                    match intent {
+                        // Searches declared while opening a file only ever run on that file.
+                        Intent::Find {
+                            language,
+                            query,
+                            on_match,
+                        } => file_queries.push((language, query, on_match)),
+                        Intent::Warn(irr) => irritations.push(irr),
+                        Intent::SkipFile => skipped = true,
+                        _ => {
+                            return Err(
+                                intent.unavailable_while(Action::Vexing(EventKind::OpenFile))
+                            )
+                        }
+                    }
+                    Ok(())
+                })?;
+            content = Some(file_content.to_string());
+            parsed_file = lazy_parsed_file.into_parsed();
+        }
         file_queries
     };
 
-    if project_queries
+    if skipped {
+        // A scriptlet opted out of this file, so it is never parsed.
+        return Ok(FileRunData {
+            irritations,
+            num_bytes_scanned: 0,
+        });
+    }
+
+    let close_file = |irritations: &mut Vec<Irritation>| {
+        let event = CloseFileEvent::new(file.path().pretty_path.dupe(), language);
+        irritations.extend(close(
+            &store.observers_for_path(EventKind::CloseFile, &file.path().pretty_path),
+            shared_store,
+            store.check_metadata(),
+            event,
+            EventKind::CloseFile,
+            artifact_cache,
+            verbosity,
+            limits,
+            keep_going,
+            intent_log,
+        )?);
+        Result::Ok(())
+    };
+
+    let searches_for = |language| -> Vec<_> {
+        project_queries
+            .iter()
+            .chain(file_queries.iter())
+            .filter(|(l, _, on_match)| {
+                *l == language && !store.ignores(on_match.scriptlet(), &file.path().pretty_path)
+            })
+            .map(|(_, query, on_match)| (query, on_match))
+            .collect()
+    };
+    let searches = searches_for(language);
+    let injections: Vec<_> = injections
         .iter()
-        .chain(file_queries.iter())
-        .all(|(l, _, _)| *l != language)
-    {
-        // The user did not request a scan of this type of file.
+        .filter(|injection| {
+            injection.language == language
+                && project_queries
+                    .iter()
+                    .chain(file_queries.iter())
+                    .any(|(l, _, _)| injection.may_inject(*l))
+        })
+        .collect();
+    if searches.is_empty() && injections.is_empty() {
+        // The user did not request a scan of this type of file, or every search ignores it.
+        close_file(&mut irritations)?;
         return Ok(FileRunData {
             irritations,
             num_bytes_scanned: 0,
         });
     }
 
-    let parsed_file = file.parse()?;
+    let parse_timer = Timer::start_for("parse", &file.path().pretty_path).about(language);
+    let parsed_file = match parsed_file {
+        Some(parsed_file) => parsed_file,
+        None => {
+            let content = match content {
+                Some(content) => content,
+                None => file.read()?,
+            };
+            artifact_cache.parse(file, content)?
+        }
+    };
     let ignore_markers = parsed_file.ignore_markers()?;
-    project_queries
-        .iter()
-        .chain(file_queries.iter())
-        .filter(|(l, _, _)| *l == language)
-        .try_for_each(|(_, query, on_match)| {
-            QueryCursor::new()
+    parse_timer.stop();
+    let query_timer = Timer::start_for("query", &file.path().pretty_path);
+    let mut cursor = QueryCursor::new();
+    let mut run_searches = |parsed_file: &ParsedSourceFile,
+                            searches: Vec<(&Arc<Query>, &Observer)>| {
+        let language = parsed_file.language;
+        let query_plan = QueryPlan::new(searches);
+        let query_passes = query_plan.passes(language, artifact_cache, single_pass_queries)?;
+        query_passes.iter().try_for_each(|pass| {
+            let check_timer = Timer::start_unlogged("query").about(
+                pass.steps()
+                    .flat_map(|step| &step.subscribers)
+                    .map(|on_match| on_match.scriptlet())
+                    .collect::<BTreeSet<_>>()
+                    .iter()
+                    .join_with(", ")
+                    .to_string(),
+            );
+            cursor
                 .matches(
-                    query,
+                    &pass.query,
                     parsed_file.tree.root_node(),
                     parsed_file.content.as_bytes(),
                 )
                 .try_for_each(|qmatch| {
-                    let handler_module = HandlerModule::new();
-                    let event = {
-                        let path = parsed_file.path.pretty_path.dupe();
-                        let captures =
-                            QueryCaptures::new(query, qmatch, &parsed_file, handler_module.heap());
-                        handler_module.heap().alloc(MatchEvent::new(path, captures))
-                    };
-                    let observe_opts = ObserveOptions {
-                        action: Action::Vexing(EventKind::Match),
-                        query_cache: Some(query_cache),
-                        ignore_markers: Some(&ignore_markers),
-                        print_handler: &PrintHandler::new(verbosity, EventKind::Match.name()),
-                    };
-                    on_match.observe(&handler_module, event, observe_opts)?;
-                    handler_module
-                        .into_intents_on(&frozen_heap)?
-                        .into_iter()
-                        .for_each(|intent| match intent {
-                            Intent::Find { .. } => {
-                                panic!("internal error: find intended during find")
-                            }
-                            Intent::Observe { .. } => {
-                                panic!("internal error: non-init observe")
-                            }
-                            Intent::Warn(irr) => irritations.push(irr),
-                            Intent::ScanFile { .. } => {
-                                panic!("internal error: unexpected ScanFile intent declared")
-                            }
-                        });
-
-                    Result::Ok(())
-                })
-        })?;
+                    let (pattern_offset, QueryStep { query, subscribers }) =
+                        pass.step_for(qmatch.pattern_index);
+                    subscribers.iter().try_for_each(|on_match| {
+                        let handler_module = HandlerModule::new();
+                        let event = {
+                            let path = parsed_file.path.pretty_path.dupe();
+                            let heap = handler_module.heap();
+                            let captures = if pass.combined {
+                                QueryCaptures::new_combined(
+                                    query,
+                                    &pass.query,
+                                    pattern_offset,
+                                    &qmatch,
+                                    parsed_file,
+                                    heap,
+                                )
+                            } else {
+                                QueryCaptures::new(query, &qmatch, parsed_file, heap)
+                            };
+                            heap.alloc(MatchEvent::new(path, captures))
+                        };
+                        let observe_opts = ObserveOptions {
+                            action: Action::Vexing(EventKind::Match),
+                            artifact_cache: Some(artifact_cache),
+                            ignore_markers: Some(&ignore_markers),
+                            shared_store: Some(shared_store),
+                            check_metadata: Some(store.check_metadata()),
+                            print_handler: &PrintHandler::new(verbosity, EventKind::Match.name()),
+                            limits,
+                            keep_going,
+                            intent_log,
+                        };
+                        on_match.observe(&handler_module, event, observe_opts)?;
+                        handler_module
+                            .into_intents_on(&frozen_heap)?
+                            .into_iter()
+                            .try_for_each(|intent| {
+                                match intent {
+                                    Intent::Warn(irr) => irritations.push(irr),
+                                    _ => {
+                                        return Err(intent
+                                            .unavailable_while(Action::Vexing(EventKind::Match)))
+                                    }
+                                }
+                                Ok(())
+                            })
+                    })
+                })?;
+            check_timer.stop();
+            Result::Ok(())
+        })
+    };
+    run_searches(&parsed_file, searches)?;
+    for injection in injections {
+        for injected_file in injection.parse_all(&parsed_file) {
+            let searches = searches_for(injected_file.language);
+            if !searches.is_empty() {
+                run_searches(&injected_file, searches)?;
+            }
+        }
+    }
+    query_timer.stop();
+    close_file(&mut irritations)?;
     let num_bytes_scanned = parsed_file.content.len() as u64;
     Ok(FileRunData {
         irritations,
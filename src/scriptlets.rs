@@ -1,16 +1,21 @@
 pub mod action;
 pub mod app_object;
+pub mod artifact_cache;
 pub mod event;
 pub mod extra_data;
 pub mod handler_module;
+pub mod intent_log;
 pub mod intents;
+mod lang;
+pub mod limits;
 pub mod main_annotation;
 mod node;
 mod observers;
 mod print_handler;
-pub mod query_cache;
 pub mod query_captures;
+mod re;
 mod scriptlet;
+pub mod shared_store;
 pub mod source;
 mod store;
 
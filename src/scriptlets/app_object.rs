@@ -1,20 +1,25 @@
 use std::{fmt::Display, sync::Arc};
 
 use allocative::Allocative;
-use camino::Utf8Path;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use derive_new::new;
+use dupe::Dupe;
 use starlark::{
     environment::{Methods, MethodsBuilder, MethodsStatic},
     eval::Evaluator,
-    starlark_module,
+    starlark_module, starlark_simple_value,
     values::{
         list::UnpackList, none::NoneType, Heap, NoSerialize, ProvidesStaticType, StarlarkValue,
-        StringValue, Value,
+        StringValue, Value, ValueLike,
     },
 };
 use starlark_derive::starlark_value;
+use strum::IntoEnumIterator;
 
 use crate::{
+    check_metadata::{self, Examples},
+    check_selection::CheckSelection,
+    context::ProjectInfo,
     error::Error,
     irritation::IrritationRenderer,
     query::Query,
@@ -24,23 +29,57 @@ use crate::{
         event::EventKind,
         extra_data::{TempData, UnfrozenRetainedData},
         intents::UnfrozenIntent,
-        main_annotation::MainAnnotation,
+        lang::LangObject,
+        main_annotation::{MainAnnotation, SecondaryAnnotation},
         observers::UnfrozenObserver,
+        shared_store::StoreView,
         Node,
     },
+    severity::{Severity, SeverityOverrides},
+    source_file::ParsedSourceFile,
     source_path::PrettyPath,
     supported_language::SupportedLanguage,
+    tags,
+    trigger::{CaseSensitivity, RawFilePattern},
     vex_id::VexId,
 };
 
+/// Text parsed by `vex.lint_text`, kept on the heap for as long as its nodes.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+struct ParsedText(ParsedSourceFile);
+
+starlark_simple_value!(ParsedText);
+#[starlark_value(type = "ParsedText")]
+impl<'v> StarlarkValue<'v> for ParsedText {}
+
+impl Display for ParsedText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ParsedText")
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, new, ProvidesStaticType, NoSerialize, Allocative)]
 pub struct AppObject {
     lenient: bool,
+
+    #[allocative(skip)]
+    project: ProjectInfo,
+
+    severity_overrides: SeverityOverrides,
+
+    #[allocative(skip)]
+    selection: CheckSelection,
 }
 
 impl AppObject {
     pub const NAME: &'static str = "vex";
     const LENIENT_ATTR_NAME: &'static str = "lenient";
+    const PROJECT_ATTR_NAME: &'static str = "project";
+    const ATTR_NAMES: [&'static str; 3] = [
+        Self::LENIENT_ATTR_NAME,
+        Self::PROJECT_ATTR_NAME,
+        LangObject::NAME,
+    ];
 
     #[allow(clippy::type_complexity)]
     #[starlark_module]
@@ -50,6 +89,7 @@ impl AppObject {
             #[starlark(require=pos)] language: &'v str,
             #[starlark(require=pos)] query: StringValue<'v>,
             #[starlark(require=pos)] on_match: Value<'v>,
+            #[starlark(require=named)] scope: Option<&str>,
             eval: &mut Evaluator<'v, '_>,
         ) -> anyhow::Result<NoneType> {
             AppObject::check_attr_available(
@@ -60,18 +100,23 @@ impl AppObject {
                     Action::Vexing(EventKind::OpenFile),
                 ],
             )?;
+            AppObject::check_search_scope(eval, scope)?;
 
             let ret_data = UnfrozenRetainedData::get_from(eval.module());
             let language = language.parse::<SupportedLanguage>()?;
             let query = {
                 let temp_data = TempData::get_from(eval);
-                if let Some(query_cache) = temp_data.query_cache {
-                    query_cache.get_or_create(language, query)?
+                if let Some(artifact_cache) = temp_data.artifact_cache {
+                    artifact_cache.query(language, &query)?
                 } else {
                     Arc::new(Query::new(language, &query)?)
                 }
             };
-            let on_match = UnfrozenObserver::new(on_match);
+            let on_match = UnfrozenObserver::new(
+                on_match,
+                AppObject::scriptlet(eval),
+                AppObject::namespace(eval),
+            );
             ret_data.declare_intent(UnfrozenIntent::Find {
                 language,
                 query,
@@ -90,76 +135,316 @@ impl AppObject {
             AppObject::check_attr_available(eval, "vex.observe", &[Action::Initing])?;
 
             let ret_data = UnfrozenRetainedData::get_from(eval.module());
-            let event_kind = event.parse()?;
-            let observer = UnfrozenObserver::new(observer);
-            ret_data.declare_intent(UnfrozenIntent::Observe {
-                event_kind,
-                observer,
+            let event_kinds = if event == EventKind::WILDCARD {
+                EventKind::iter().filter(EventKind::parseable).collect()
+            } else {
+                vec![event.parse()?]
+            };
+            let scriptlet = AppObject::scriptlet(eval);
+            let namespace = AppObject::namespace(eval);
+            event_kinds.into_iter().for_each(|event_kind| {
+                ret_data.declare_intent(UnfrozenIntent::Observe {
+                    event_kind,
+                    observer: UnfrozenObserver::new(observer, scriptlet.dupe(), namespace.clone()),
+                })
             });
 
             Ok(NoneType)
         }
 
         #[allow(clippy::too_many_arguments)]
-        fn warn<'v>(
+        fn declare<'v>(
             #[starlark(this)] _this: Value<'v>,
             #[starlark(require=pos)] vex_id: &'v str,
+            #[starlark(require=named)] tags: Option<UnpackList<String>>,
+            #[starlark(require=named)] description: Option<&'v str>,
+            #[starlark(require=named)] code: Option<&'v str>,
+            #[starlark(require=named)] languages: Option<UnpackList<&'v str>>,
+            #[starlark(require=named)] bad: Option<UnpackList<String>>,
+            #[starlark(require=named)] good: Option<UnpackList<String>>,
+            #[starlark(require=named, default = false)] allow_duplicates: bool,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<NoneType> {
+            AppObject::check_attr_available(eval, "vex.declare", &[Action::Initing])?;
+
+            let vex_id = AppObject::vex_id(eval, vex_id)?;
+            let tags = tags.map(|tags| tags.items).unwrap_or_default();
+            tags.iter().try_for_each(|tag| tags::validate_tag(tag))?;
+            let description = description.map(ToOwned::to_owned);
+            let code = code
+                .map(|code| check_metadata::validate_code(code).map(|_| code.to_owned()))
+                .transpose()?;
+            let languages = languages
+                .map(|languages| languages.items)
+                .unwrap_or_default()
+                .into_iter()
+                .map(str::parse)
+                .collect::<Result<_>>()?;
+            let examples = Examples {
+                bad: bad.map(|bad| bad.items).unwrap_or_default(),
+                good: good.map(|good| good.items).unwrap_or_default(),
+            };
+            let ret_data = UnfrozenRetainedData::get_from(eval.module());
+            ret_data.declare_intent(UnfrozenIntent::Declare {
+                vex_id,
+                tags,
+                description,
+                code,
+                languages,
+                examples,
+                allow_duplicates,
+            });
+
+            Ok(NoneType)
+        }
+
+        fn ignore_paths<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] patterns: UnpackList<String>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<NoneType> {
+            AppObject::check_attr_available(eval, "vex.ignore_paths", &[Action::Initing])?;
+
+            let patterns = patterns
+                .items
+                .into_iter()
+                .map(RawFilePattern::new)
+                .collect::<Vec<_>>();
+            patterns.iter().try_for_each(|pattern| {
+                pattern.clone().compile(CaseSensitivity::default())?;
+                Result::Ok(())
+            })?;
+            let ret_data = UnfrozenRetainedData::get_from(eval.module());
+            ret_data.declare_intent(UnfrozenIntent::IgnorePaths(patterns));
+
+            Ok(NoneType)
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn advice<'v>(
+            #[starlark(this)] this: Value<'v>,
+            #[starlark(require=pos)] vex_id: &'v str,
             #[starlark(require=pos)] message: &'v str,
             #[starlark(require=named)] at: Option<MainAnnotation<'v>>,
-            #[starlark(require=named)] show_also: Option<UnpackList<(Node<'v>, &'v str)>>,
+            #[starlark(require=named)] path: Option<&'v str>,
+            #[starlark(require=named)] show_also: Option<UnpackList<SecondaryAnnotation<'v>>>,
             #[starlark(require=named)] info: Option<&'v str>,
+            #[starlark(require=named)] fix: Option<&'v str>,
             eval: &mut Evaluator<'v, '_>,
         ) -> anyhow::Result<NoneType> {
-            AppObject::check_attr_available(
+            AppObject::check_attr_available(eval, "vex.advice", AppObject::EMIT_ACTIONS)?;
+            AppObject::emit(
+                this,
+                Severity::Advice,
+                vex_id,
+                message,
+                at,
+                path,
+                show_also,
+                info,
+                fix,
                 eval,
-                "vex.warn",
-                &[
-                    Action::Vexing(EventKind::OpenProject),
-                    Action::Vexing(EventKind::OpenFile),
-                    Action::Vexing(EventKind::Match),
-                ],
             )?;
+            Ok(NoneType)
+        }
 
-            if matches!((&at, &show_also), (None, Some(_)))
-                || matches!(
-                    (&at, &show_also),
-                    (Some(MainAnnotation::Path { .. }), Some(_))
-                )
-            {
-                return Err(Error::InvalidWarnCall(
-                    "cannot display `show_also` without an `at` argument containing a Node",
-                )
-                .into());
-            }
+        #[allow(clippy::too_many_arguments)]
+        fn warn<'v>(
+            #[starlark(this)] this: Value<'v>,
+            #[starlark(require=pos)] vex_id: &'v str,
+            #[starlark(require=pos)] message: &'v str,
+            #[starlark(require=named)] at: Option<MainAnnotation<'v>>,
+            #[starlark(require=named)] path: Option<&'v str>,
+            #[starlark(require=named)] show_also: Option<UnpackList<SecondaryAnnotation<'v>>>,
+            #[starlark(require=named)] info: Option<&'v str>,
+            #[starlark(require=named)] fix: Option<&'v str>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<NoneType> {
+            AppObject::check_attr_available(eval, "vex.warn", AppObject::EMIT_ACTIONS)?;
+            AppObject::emit(
+                this,
+                Severity::Warning,
+                vex_id,
+                message,
+                at,
+                path,
+                show_also,
+                info,
+                fix,
+                eval,
+            )?;
+            Ok(NoneType)
+        }
 
-            let vex_id = VexId::try_from(vex_id.to_string())?;
+        #[allow(clippy::too_many_arguments)]
+        fn error<'v>(
+            #[starlark(this)] this: Value<'v>,
+            #[starlark(require=pos)] vex_id: &'v str,
+            #[starlark(require=pos)] message: &'v str,
+            #[starlark(require=named)] at: Option<MainAnnotation<'v>>,
+            #[starlark(require=named)] path: Option<&'v str>,
+            #[starlark(require=named)] show_also: Option<UnpackList<SecondaryAnnotation<'v>>>,
+            #[starlark(require=named)] info: Option<&'v str>,
+            #[starlark(require=named)] fix: Option<&'v str>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<NoneType> {
+            AppObject::check_attr_available(eval, "vex.error", AppObject::EMIT_ACTIONS)?;
+            AppObject::emit(
+                this,
+                Severity::Error,
+                vex_id,
+                message,
+                at,
+                path,
+                show_also,
+                info,
+                fix,
+                eval,
+            )?;
+            Ok(NoneType)
+        }
 
-            let temp_data = TempData::get_from(eval);
-            let ignored = at.as_ref().and_then(|at| at.node()).is_some_and(|node| {
-                temp_data.ignore_markers.is_some_and(|ignore_markers| {
-                    ignore_markers.is_ignored(node.byte_range().start, &vex_id)
-                })
-            });
-            if ignored {
-                return Ok(NoneType);
-            }
+        fn config<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] key: &'v str,
+            #[starlark(require=named)] default: Option<Value<'v>>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Value<'v>> {
+            AppObject::check_attr_available(eval, "vex.config", &[Action::Initing])?;
 
-            let ret_data = UnfrozenRetainedData::get_from(eval.module());
-            let mut irritation_renderer = IrritationRenderer::new(vex_id, message);
-            if let Some(at) = at {
-                irritation_renderer.set_source(at)
-            }
-            if let Some(show_also) = show_also {
-                irritation_renderer.set_show_also(show_also.items);
-            }
-            if let Some(info) = info {
-                irritation_renderer.set_info(info);
+            let scope = TempData::get_from(eval)
+                .config
+                .expect("internal error: config not set while initing");
+            let Some(value) = scope.get(key) else {
+                return default.ok_or_else(|| {
+                    Error::InvalidConfig {
+                        key: scope.key_path(key),
+                        reason: "required but not set".into(),
+                    }
+                    .into()
+                });
+            };
+            let value = eval.heap().alloc(value);
+            if let Some(default) = default {
+                if !default.is_none() && default.get_type() != value.get_type() {
+                    return Err(Error::InvalidConfig {
+                        key: scope.key_path(key),
+                        reason: format!(
+                            "expected {} but found {}",
+                            default.get_type(),
+                            value.get_type()
+                        ),
+                    }
+                    .into());
+                }
             }
-            ret_data.declare_intent(UnfrozenIntent::Warn(irritation_renderer.render()));
+            Ok(value)
+        }
+
+        fn languages<'v>(#[starlark(this)] _this: Value<'v>) -> anyhow::Result<Vec<String>> {
+            Ok(SupportedLanguage::iter()
+                .filter(SupportedLanguage::is_available)
+                .map(|language| language.name().to_owned())
+                .collect())
+        }
+
+        fn has_language<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] language: &'v str,
+        ) -> anyhow::Result<bool> {
+            Ok(language
+                .parse::<SupportedLanguage>()
+                .is_ok_and(|language| language.is_available()))
+        }
+
+        /// The full lines of source which the given node spans, without line endings.
+        fn lines_of<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] node: Node<'v>,
+        ) -> anyhow::Result<Vec<String>> {
+            let source_file = node.source_file;
+            let lines_range = source_file.full_lines_range(node.start_byte()..node.end_byte());
+            Ok(source_file.content[lines_range]
+                .lines()
+                .map(Into::into)
+                .collect())
+        }
 
+        /// The whitespace which starts the line on which the given node starts.
+        fn indent_of<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] node: Node<'v>,
+        ) -> anyhow::Result<String> {
+            let source_file = node.source_file;
+            let line_start = source_file
+                .full_lines_range(node.start_byte()..node.start_byte())
+                .start;
+            let line = &source_file.content[line_start..];
+            let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+            Ok(line[..indent_len].into())
+        }
+
+        /// The given text with the whitespace common to the start of each line removed.
+        fn dedent<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] text: &'v str,
+        ) -> anyhow::Result<String> {
+            Ok(textwrap::dedent(text))
+        }
+
+        /// Parse the given text as the given language, returning its root node, or `None` if it
+        /// is not valid in that language.
+        fn lint_text<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] language: &'v str,
+            #[starlark(require=pos)] text: &'v str,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Option<Node<'v>>> {
+            AppObject::check_attr_available(eval, "vex.lint_text", AppObject::EMIT_ACTIONS)?;
+
+            let language = language.parse::<SupportedLanguage>()?;
+            let parsed = match ParsedSourceFile::new_from_text(text, language) {
+                Ok(parsed) => parsed,
+                Err(Error::UnparseableAsLanguage { .. }) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            // The heap is only collected between top-level statements, which never run while
+            // handling events, so the parsed text outlives every node which refers to it.
+            let parsed = eval
+                .heap()
+                .alloc_simple(ParsedText(parsed))
+                .downcast_ref::<ParsedText>()
+                .expect("internal error: parsed text has wrong type");
+            Ok(Some(Node::new(parsed.0.tree.root_node(), &parsed.0)))
+        }
+
+        fn skip_file<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            eval: &mut Evaluator<'_, '_>,
+        ) -> anyhow::Result<NoneType> {
+            AppObject::check_attr_available(
+                eval,
+                "vex.skip_file",
+                &[Action::Vexing(EventKind::OpenFile)],
+            )?;
+
+            let ret_data = UnfrozenRetainedData::get_from(eval.module());
+            ret_data.declare_intent(UnfrozenIntent::SkipFile);
             Ok(NoneType)
         }
 
+        fn store<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<StoreView> {
+            AppObject::check_attr_available(eval, "vex.store", AppObject::EMIT_ACTIONS)?;
+
+            let shared_store = TempData::get_from(eval)
+                .shared_store
+                .expect("internal error: shared store not set");
+            Ok(shared_store.view(AppObject::scriptlet(eval)))
+        }
+
         fn scan<'v>(
             #[starlark(this)] _this: Value<'v>,
             #[starlark(require=pos)] file_name: &'v str,
@@ -186,6 +471,202 @@ impl AppObject {
         }
     }
 
+    const EMIT_ACTIONS: &'static [Action] = &[
+        Action::Vexing(EventKind::OpenProject),
+        Action::Vexing(EventKind::OpenFile),
+        Action::Vexing(EventKind::Match),
+        Action::Vexing(EventKind::CloseFile),
+        Action::Vexing(EventKind::CloseProject),
+    ];
+
+    #[allow(clippy::too_many_arguments)]
+    fn emit<'v>(
+        this: Value<'v>,
+        severity: Severity,
+        vex_id: &'v str,
+        message: &'v str,
+        at: Option<MainAnnotation<'v>>,
+        path: Option<&'v str>,
+        show_also: Option<UnpackList<SecondaryAnnotation<'v>>>,
+        info: Option<&'v str>,
+        fix: Option<&'v str>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> Result<()> {
+        let at = match (at, path) {
+            (Some(_), Some(_)) => {
+                return Err(Error::InvalidWarnCall("cannot pass both `at` and `path`"))
+            }
+            (None, Some(path)) => Some(MainAnnotation::Path {
+                path: AppObject::project_path(path)?,
+                label: None,
+            }),
+            (at, None) => at,
+        };
+        if matches!((&at, &show_also), (None, Some(_)))
+            || matches!(
+                (&at, &show_also),
+                (Some(MainAnnotation::Path { .. }), Some(_))
+            )
+        {
+            return Err(Error::InvalidWarnCall(
+                "cannot display `show_also` without an `at` argument containing a Node",
+            ));
+        }
+        if let (Some(at), Some(show_also)) = (&at, &show_also) {
+            let at_path = at.pretty_path();
+            if show_also
+                .items
+                .iter()
+                .any(|also| &also.node.source_file.path.pretty_path != at_path)
+            {
+                return Err(Error::InvalidWarnCall(
+                    "cannot display `show_also` nodes from a different file to the `at` node",
+                ));
+            }
+        }
+        let from_text = at
+            .as_ref()
+            .and_then(|at| at.node())
+            .into_iter()
+            .chain(
+                show_also
+                    .iter()
+                    .flat_map(|show_also| &show_also.items)
+                    .map(|also| &also.node),
+            )
+            .any(|node| node.source_file.from_text);
+        if from_text {
+            return Err(Error::InvalidWarnCall(
+                "cannot report problems at nodes parsed by `vex.lint_text`, use the node containing the text instead",
+            ));
+        }
+        if fix.is_some() && at.as_ref().and_then(|at| at.node()).is_none() {
+            return Err(Error::InvalidWarnCall(
+                "cannot apply `fix` without an `at` argument containing a Node",
+            ));
+        }
+
+        let vex_id = AppObject::vex_id(eval, vex_id)?;
+
+        let temp_data = TempData::get_from(eval);
+        let ignored = at.as_ref().and_then(|at| at.node()).is_some_and(|node| {
+            temp_data.ignore_markers.is_some_and(|ignore_markers| {
+                ignore_markers.is_ignored(node.byte_range().start, &vex_id)
+            })
+        });
+        if ignored {
+            return Ok(());
+        }
+
+        let app = this.downcast_ref::<AppObject>();
+        let disabled = app.is_some_and(|app| {
+            app.severity_overrides.is_disabled(vex_id.as_ref())
+                || !app.selection.selects(vex_id.as_ref())
+        });
+        if disabled {
+            return Ok(());
+        }
+        let severity = app
+            .map(|app| &app.severity_overrides)
+            .and_then(|overrides| overrides.get(&vex_id))
+            .unwrap_or(severity);
+
+        let code = temp_data
+            .check_metadata
+            .and_then(|check_metadata| check_metadata.code_of(&vex_id));
+        let ret_data = UnfrozenRetainedData::get_from(eval.module());
+        let mut irritation_renderer = IrritationRenderer::new(vex_id, severity, message);
+        if let Some(code) = code {
+            irritation_renderer.set_code(code);
+        }
+        if let Some(at) = at {
+            irritation_renderer.set_source(at)
+        }
+        if let Some(show_also) = show_also {
+            irritation_renderer.set_show_also(
+                show_also
+                    .items
+                    .into_iter()
+                    .map(|also| (also.node, also.label))
+                    .collect(),
+            );
+        }
+        if let Some(info) = info {
+            irritation_renderer.set_info(info);
+        }
+        if let Some(fix) = fix {
+            irritation_renderer.set_fix(fix);
+        }
+        ret_data.declare_intent(UnfrozenIntent::Warn(irritation_renderer.render()));
+
+        Ok(())
+    }
+
+    /// Interpret a path given by a scriptlet, which must lie within the project.
+    fn project_path(path: &str) -> Result<PrettyPath> {
+        let path = Utf8Path::new(path);
+        let mut normalised = Utf8PathBuf::new();
+        for component in path.components() {
+            match component {
+                Utf8Component::Normal(component) => normalised.push(component),
+                Utf8Component::CurDir => {}
+                Utf8Component::ParentDir | Utf8Component::RootDir | Utf8Component::Prefix(_) => {
+                    return Err(Error::InvalidWarnCall(
+                        "`path` must be relative to the project root and within it",
+                    ))
+                }
+            }
+        }
+        if normalised.as_str().is_empty() {
+            return Err(Error::InvalidWarnCall("`path` must name a file"));
+        }
+        Ok(PrettyPath::new(&normalised))
+    }
+
+    fn scriptlet(eval: &Evaluator<'_, '_>) -> Arc<str> {
+        TempData::get_from(eval)
+            .scriptlet
+            .expect("internal error: scriptlet name not set")
+            .dupe()
+    }
+
+    fn namespace(eval: &Evaluator<'_, '_>) -> Option<Arc<str>> {
+        TempData::get_from(eval).namespace.map(Arc::from)
+    }
+
+    /// The id of the given check, within the namespace of the running scriptlet's rule pack.
+    fn vex_id(eval: &Evaluator<'_, '_>, raw_id: &str) -> Result<VexId> {
+        let vex_id = VexId::try_from(raw_id.to_owned())?;
+        match TempData::get_from(eval).namespace {
+            Some(namespace) => VexId::try_from(format!("{namespace}:{vex_id}")),
+            None => Ok(vex_id),
+        }
+    }
+
+    /// Check that a search with the given scope can be declared now. Searches declared while
+    /// opening the project cover every file, while those declared while opening a file cover only
+    /// that file.
+    fn check_search_scope(eval: &Evaluator<'_, '_>, scope: Option<&str>) -> Result<()> {
+        let Some(scope) = scope else {
+            return Ok(());
+        };
+        let action = TempData::get_from(eval).action;
+        let reason = match (scope, action) {
+            ("project", Action::Vexing(EventKind::OpenProject))
+            | ("file", Action::Vexing(EventKind::OpenFile)) => return Ok(()),
+            ("project", _) => "project-wide searches must be declared in an open_project handler",
+            ("file", _) => {
+                "no file is open, file-wide searches must be declared in an open_file handler"
+            }
+            _ => "expected 'file' or 'project'",
+        };
+        Err(Error::InvalidSearchScope {
+            scope: scope.into(),
+            action,
+            reason,
+        })
+    }
+
     fn check_attr_available(
         eval: &Evaluator<'_, '_>,
         attr_path: &'static str,
@@ -211,21 +692,24 @@ impl<'v> StarlarkValue<'v> for AppObject {
     }
 
     fn dir_attr(&self) -> Vec<String> {
-        [Self::LENIENT_ATTR_NAME]
-            .into_iter()
-            .map(Into::into)
-            .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
-    fn get_attr(&self, attr: &str, _: &'v Heap) -> Option<Value<'v>> {
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
         match attr {
             Self::LENIENT_ATTR_NAME => Some(Value::new_bool(self.lenient)),
+            Self::PROJECT_ATTR_NAME => {
+                let project = serde_json::to_value(&self.project)
+                    .expect("internal error: cannot serialise project info");
+                Some(heap.alloc(&project))
+            }
+            LangObject::NAME => Some(heap.alloc(LangObject)),
             _ => None,
         }
     }
 
     fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
-        attr == Self::LENIENT_ATTR_NAME
+        Self::ATTR_NAMES.contains(&attr)
     }
 }
 
@@ -240,7 +724,11 @@ mod test {
     use indoc::{formatdoc, indoc};
     use insta::assert_yaml_snapshot;
 
-    use crate::vextest::VexTest;
+    use crate::{
+        severity::{Severity, SeverityOverrides},
+        supported_language::SupportedLanguage,
+        vextest::VexTest,
+    };
 
     #[test]
     fn warn_valid() {
@@ -387,6 +875,125 @@ mod test {
         assert_yaml_snapshot!(irritations);
     }
 
+    #[test]
+    fn warn_unlabelled_show_also() {
+        let irritations = VexTest::new("unlabelled-show-also")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search(
+                            'rust',
+                            '(binary_expression left: (integer_literal) @l right: (integer_literal) @r) @bin_expr',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        l = event.captures['l']
+                        r = event.captures['r']
+                        vex.warn(
+                            'test',
+                            'declared here, used there',
+                            at=(event.captures['bin_expr'], 'whole'),
+                            show_also=[l, (r, 'right')],
+                            info='note',
+                        )
+                "#},
+            )
+            .with_source_file("main.rs", "fn main() {\n    let x = 1 + 2;\n}\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(
+            irritations[0].to_string(),
+            indoc! {"
+                warning[test]: declared here, used there
+                 --> main.rs:2:13
+                  |
+                2 |     let x = 1 + 2;
+                  |             ----- whole
+                  |             -
+                  |                 - info: right
+                  |
+                  = info: note"
+            }
+        );
+    }
+
+    #[test]
+    fn warn_project_level() {
+        let irritations = VexTest::new("project-level")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('test', 'no location')
+                        vex.warn('test', 'string path', path='./docs/LICENSE', info='some info')
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irr| irr.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            irritations,
+            [
+                "warning[test]: no location",
+                indoc! {"
+                    warning[test]: string path
+                     = note: in docs/LICENSE
+                     = info: some info"
+                },
+            ]
+        );
+
+        for (path, expected_error) in [
+            (
+                "'/etc/passwd'",
+                "`path` must be relative to the project root",
+            ),
+            (
+                "'../outside'",
+                "`path` must be relative to the project root",
+            ),
+            ("'.'", "`path` must name a file"),
+        ] {
+            VexTest::new("invalid-path")
+                .with_scriptlet(
+                    "vexes/test.star",
+                    formatdoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.warn('test', 'message', path={path})
+                    "#},
+                )
+                .returns_error(expected_error);
+        }
+        VexTest::new("path-and-at")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('test', 'message', at=event.path, path='LICENSE')
+                "#},
+            )
+            .returns_error("cannot pass both `at` and `path`");
+    }
+
     #[test]
     fn warn_invalid() {
         const VEX_NAME: &str = "name-of-vex";
@@ -573,6 +1180,7 @@ mod test {
                     "vexes/test.star",
                     indoc! {r#"
                         def init():
+                            vex.declare('test', allow_duplicates=True)
                             lenient = vex.lenient
                             def on_open_project(event):
                                 vex.warn("test", "vex.lenient=%s" % lenient)
@@ -618,4 +1226,412 @@ mod test {
         test_leniency(true);
         test_leniency(false);
     }
+
+    #[test]
+    fn search_scope() {
+        let test = |event: &str, scope: &str| {
+            VexTest::new(format!("search-scope-{event}"))
+                .with_scriptlet(
+                    "vexes/test.star",
+                    formatdoc! {r#"
+                        def init():
+                            vex.observe('{event}', on_open)
+
+                        def on_open(event):
+                            if '{event}' == 'open_project' or event.path.name == 'a.rs':
+                                vex.search('rust', '(integer_literal) @num', on_match{scope})
+
+                        def on_match(event):
+                            vex.warn('test', 'found', at=event.captures['num'])
+                    "#},
+                )
+                .with_source_file("src/a.rs", "const A: u32 = 1;")
+                .with_source_file("src/b.rs", "const B: u32 = 2;")
+        };
+        let num_found = |event, scope| test(event, scope).try_run().unwrap().irritations.len();
+        assert_eq!(num_found("open_project", ""), 2);
+        assert_eq!(num_found("open_project", ", scope='project'"), 2);
+        assert_eq!(num_found("open_file", ""), 1);
+        assert_eq!(num_found("open_file", ", scope='file'"), 1);
+
+        test("open_file", ", scope='project'").returns_error(
+            "cannot search with scope 'project' while opening file: project-wide searches must be declared in an open_project handler",
+        );
+        test("open_project", ", scope='file'").returns_error(
+            "cannot search with scope 'file' while opening project: no file is open, file-wide searches must be declared in an open_file handler",
+        );
+        test("open_project", ", scope='everywhere'").returns_error(
+            "cannot search with scope 'everywhere' while opening project: expected 'file' or 'project'",
+        );
+    }
+
+    #[test]
+    fn project() {
+        let irritations = VexTest::new("project")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [project]
+                name = "example"
+                links.docs = "https://example.com/docs"
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        name = vex.project['name']
+                        def on_open_project(event):
+                            project = vex.project
+                            vex.warn('test', '%s,%s,%s,%s' % (name, project['name'], project['team'], project['links']['docs']))
+                        vex.observe('open_project', on_open_project)
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(
+            irritations[0].message(),
+            "example,example,None,https://example.com/docs"
+        );
+    }
+
+    #[test]
+    fn languages() {
+        let irritations = VexTest::new("languages")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('test', 'languages=%s' % ','.join(vex.languages()))
+                        for language in ['rust', 'cobol']:
+                            vex.warn('test', 'has %s=%s' % (language, vex.has_language(language)))
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irr| irr.message().to_owned())
+            .collect::<Vec<_>>();
+        // Grammars registered by other tests may follow the built-in languages.
+        let builtin_languages = SupportedLanguage::BUILTIN
+            .iter()
+            .filter(|language| language.is_available())
+            .map(|language| language.name())
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(irritations.len(), 3);
+        assert_eq!(irritations[0], "has cobol=False");
+        assert_eq!(
+            irritations[1],
+            format!(
+                "has rust={}",
+                if cfg!(feature = "lang-rust") {
+                    "True"
+                } else {
+                    "False"
+                }
+            )
+        );
+        assert!(
+            irritations[2].starts_with(&format!("languages={builtin_languages}")),
+            "unexpected languages: {}",
+            irritations[2]
+        );
+    }
+
+    #[test]
+    fn severity() {
+        let irritations = VexTest::new("severities")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.advice('test-advice', 'some advice')
+                        vex.warn('test-warning', 'some warning')
+                        vex.error('test-error', 'some error')
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(
+            irritations
+                .iter()
+                .map(|irr| (irr.vex_id().to_string(), irr.severity()))
+                .collect::<Vec<_>>(),
+            [
+                ("test-advice".into(), Severity::Advice),
+                ("test-error".into(), Severity::Error),
+                ("test-warning".into(), Severity::Warning),
+            ]
+        );
+        let rendered = irritations
+            .iter()
+            .map(|irr| irr.to_string())
+            .collect::<Vec<_>>();
+        assert!(rendered[0].starts_with("help[test-advice]: some advice"));
+        assert!(rendered[1].starts_with("error[test-error]: some error"));
+        assert!(rendered[2].starts_with("warning[test-warning]: some warning"));
+    }
+
+    #[test]
+    fn severity_overrides() {
+        let ids = |ids: &[&str]| ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let irritations = VexTest::new("severity-overrides")
+            .with_severity_overrides(SeverityOverrides::new(
+                &ids(&["test-error"]),
+                &ids(&["test-advice"]),
+                &ids(&["test-warning"]),
+            ))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.advice('test-advice', 'some advice')
+                        vex.warn('test-warning', 'some warning')
+                        vex.error('test-error', 'some error')
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(
+            irritations
+                .iter()
+                .map(|irr| (irr.vex_id().to_string(), irr.severity()))
+                .collect::<Vec<_>>(),
+            [
+                ("test-advice".into(), Severity::Warning),
+                ("test-error".into(), Severity::Advice),
+                ("test-warning".into(), Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "lang-rust", feature = "lang-toml"))]
+    fn lint_text() {
+        let irritations = VexTest::new("lint-text")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(string_literal) @str', on_match)
+
+                    def on_match(event):
+                        literal = event.captures['str']
+                        root = vex.lint_text('toml', str(literal)[1:-1])
+                        if root == None:
+                            vex.warn('test', 'invalid toml', at=literal)
+                            return
+                        check['eq'](root.kind, 'document')
+                        check['true'](vex.lint_text('toml', 'a = 1').structurally_equal(root))
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    const VALID: &str = "x = 2";
+                    const INVALID: &str = "x = ";
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].message(), "invalid toml");
+        assert_eq!(irritations[0].location().unwrap().start_row, 2);
+
+        VexTest::new("warn-at-text")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('test', 'message', at=vex.lint_text('toml', 'a = 1'))
+                "#},
+            )
+            .returns_error("cannot report problems at nodes parsed by `vex.lint_text`");
+        VexTest::new("lint-text-while-initing")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.lint_text('toml', 'a = 1')
+                "#},
+            )
+            .returns_error("vex.lint_text unavailable while initing");
+    }
+
+    #[test]
+    fn text_utilities() {
+        VexTest::new("text-utilities")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(if_expression) @if', on_match)
+
+                    def on_match(event):
+                        node = event.captures['if']
+                        check['eq'](vex.indent_of(node), '    ')
+                        check['eq'](vex.lines_of(node), [
+                            '    let y = if x {{',
+                            '        1',
+                            '    }} else {{',
+                            '        2',
+                            '    }};',
+                        ])
+                        check['eq'](
+                            vex.dedent('\n'.join(vex.lines_of(node)[1:4])),
+                            '    1\n}} else {{\n    2',
+                        )
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let y = if x {
+                            1
+                        } else {
+                            2
+                        };
+                    }
+                "#},
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn config() {
+        const MANIFEST: &str = indoc! {r#"
+            [vex]
+            version = "1"
+
+            [config.long-functions]
+            max_length = 80
+            name = "long"
+        "#};
+        let irritations = VexTest::new("config")
+            .with_manifest(MANIFEST)
+            .with_scriptlet(
+                "vexes/long-functions.star",
+                indoc! {r#"
+                    def init():
+                        max_length = vex.config('max_length', default=50)
+                        name = vex.config('name')
+                        min_length = vex.config('min_length', default=1)
+                        message = '%s: %d..%d' % (name, min_length, max_length)
+                        vex.observe('open_project', lambda event: vex.warn('test', message))
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert!(irritations[0].to_string().contains("long: 1..80"));
+
+        VexTest::new("config-wrong-type")
+            .with_manifest(MANIFEST)
+            .with_scriptlet(
+                "vexes/long-functions.star",
+                indoc! {r#"
+                    def init():
+                        vex.config('name', default=50)
+                "#},
+            )
+            .returns_error(
+                "invalid config option 'config.long-functions.name' in vex.toml: expected int but found string",
+            );
+        VexTest::new("config-missing")
+            .with_manifest(MANIFEST)
+            .with_scriptlet(
+                "vexes/other.star",
+                indoc! {r#"
+                    def init():
+                        vex.config('max_length')
+                "#},
+            )
+            .returns_error(
+                "invalid config option 'config.other.max_length' in vex.toml: required but not set",
+            );
+        VexTest::new("config-outside-init")
+            .with_manifest(MANIFEST)
+            .with_scriptlet(
+                "vexes/long-functions.star",
+                indoc! {r#"
+                    vex.config('max_length')
+                "#},
+            )
+            .returns_error("vex.config unavailable while preiniting");
+    }
+
+    #[test]
+    fn configured_checks() {
+        let irritations = VexTest::new("configured-checks")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [checks]
+                todo-comments = "off"
+                no-unwrap = "deny"
+            "#})
+            .with_scriptlet(
+                "vexes/todo-comments.star",
+                indoc! {r#"
+                    fail('disabled scriptlet was loaded')
+                "#},
+            )
+            .with_scriptlet(
+                "vexes/no-unwrap.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('no-unwrap', 'unwrap found')
+                        vex.warn('todo-comments', 'todo found')
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(
+            irritations
+                .iter()
+                .map(|irr| (irr.vex_id().to_string(), irr.severity()))
+                .collect::<Vec<_>>(),
+            [("no-unwrap".into(), Severity::Error)]
+        );
+    }
 }
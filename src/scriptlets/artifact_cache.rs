@@ -0,0 +1,302 @@
+use std::{
+    cell::OnceCell,
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use camino::Utf8Path;
+use dupe::Dupe;
+use joinery::JoinableIterator;
+use log::{info, log_enabled};
+use regex::Regex;
+use starlark::values::Trace;
+
+use crate::{
+    query::Query,
+    result::Result,
+    source_file::{ParsedSourceFile, SourceFile},
+    supported_language::SupportedLanguage,
+};
+
+/// Artifacts which are expensive to produce and may be needed more than once in a run.
+///
+/// Compiled queries are always reused. Parsed files are reused only if the cache retains them,
+/// as most runs parse each file just once, in which case holding onto every tree until the run
+/// ends would only cost memory.
+#[derive(Debug)]
+pub struct ArtifactCache {
+    queries: RwLock<HashMap<SupportedLanguage, HashMap<String, Arc<Query>>>>,
+    combined_queries: RwLock<HashMap<SupportedLanguage, HashMap<String, Arc<Query>>>>,
+    regexes: RwLock<HashMap<String, Regex>>,
+    parsed_files: Option<RwLock<HashMap<Arc<Utf8Path>, Arc<ParsedSourceFile>>>>,
+}
+
+impl ArtifactCache {
+    pub fn new() -> Self {
+        Self {
+            queries: RwLock::new(HashMap::new()),
+            combined_queries: RwLock::new(HashMap::new()),
+            regexes: RwLock::new(HashMap::new()),
+            parsed_files: None,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            queries: RwLock::new(HashMap::with_capacity(capacity)),
+            combined_queries: RwLock::new(HashMap::new()),
+            regexes: RwLock::new(HashMap::new()),
+            parsed_files: None,
+        }
+    }
+
+    /// Create a cache which also retains every file it parses, for runs which scan the same files
+    /// more than once.
+    pub fn retaining_parsed_files() -> Self {
+        Self {
+            queries: RwLock::new(HashMap::new()),
+            combined_queries: RwLock::new(HashMap::new()),
+            regexes: RwLock::new(HashMap::new()),
+            parsed_files: Some(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Compile the given query, unless it has already been compiled for the given language.
+    pub fn query(&self, language: SupportedLanguage, raw_query: &str) -> Result<Arc<Query>> {
+        if let Some(query) = self
+            .queries
+            .read()
+            .expect("internal error: cache lock poisoned")
+            .get(&language)
+            .and_then(|queries| queries.get(raw_query))
+        {
+            return Ok(query.dupe());
+        }
+
+        let query = Arc::new(Query::new(language, raw_query)?);
+        self.queries
+            .write()
+            .expect("internal error: cache lock poisoned")
+            .entry(language)
+            .or_default()
+            .insert(raw_query.to_owned(), query.dupe());
+        Ok(query)
+    }
+
+    /// Combine the given queries into one, unless the same queries have already been combined.
+    pub fn combined_query(
+        &self,
+        language: SupportedLanguage,
+        queries: &[&Query],
+    ) -> Result<Arc<Query>> {
+        let key = queries
+            .iter()
+            .map(|query| query.source())
+            .join_with("\n")
+            .to_string();
+        if let Some(query) = self
+            .combined_queries
+            .read()
+            .expect("internal error: cache lock poisoned")
+            .get(&language)
+            .and_then(|queries| queries.get(&key))
+        {
+            return Ok(query.dupe());
+        }
+
+        let query = Arc::new(Query::combine(language, queries)?);
+        self.combined_queries
+            .write()
+            .expect("internal error: cache lock poisoned")
+            .entry(language)
+            .or_default()
+            .insert(key, query.dupe());
+        Ok(query)
+    }
+
+    /// Compile the given regex, unless it has already been compiled.
+    pub fn regex(&self, pattern: &str) -> Result<Regex> {
+        if let Some(regex) = self
+            .regexes
+            .read()
+            .expect("internal error: cache lock poisoned")
+            .get(pattern)
+        {
+            return Ok(regex.clone());
+        }
+
+        let regex = Regex::new(pattern)?;
+        self.regexes
+            .write()
+            .expect("internal error: cache lock poisoned")
+            .insert(pattern.to_owned(), regex.clone());
+        Ok(regex)
+    }
+
+    /// Parse the given content of the given file, unless a retained parse of the same content is
+    /// available.
+    pub fn parse(&self, file: &SourceFile, content: String) -> Result<Arc<ParsedSourceFile>> {
+        let Some(parsed_files) = &self.parsed_files else {
+            return Ok(Arc::new(file.parse_content(content)?));
+        };
+
+        let abs_path = &file.path().abs_path;
+        if let Some(parsed_file) = parsed_files
+            .read()
+            .expect("internal error: cache lock poisoned")
+            .get(abs_path)
+            .filter(|parsed_file| parsed_file.content == content)
+        {
+            if log_enabled!(log::Level::Info) {
+                info!("reusing parse of {}", file.path());
+            }
+            return Ok(parsed_file.dupe());
+        }
+
+        let parsed_file = Arc::new(file.parse_content(content)?);
+        parsed_files
+            .write()
+            .expect("internal error: cache lock poisoned")
+            .insert(abs_path.dupe(), parsed_file.dupe());
+        Ok(parsed_file)
+    }
+}
+
+/// A file which is parsed through the cache only once its tree is first needed.
+#[derive(Debug)]
+pub struct LazyParsedFile<'a> {
+    file: &'a SourceFile,
+    content: Arc<str>,
+    artifact_cache: &'a ArtifactCache,
+    parsed_file: OnceCell<Arc<ParsedSourceFile>>,
+}
+
+impl<'a> LazyParsedFile<'a> {
+    pub fn new(file: &'a SourceFile, content: Arc<str>, artifact_cache: &'a ArtifactCache) -> Self {
+        Self {
+            file,
+            content,
+            artifact_cache,
+            parsed_file: OnceCell::new(),
+        }
+    }
+
+    /// Parse the file, unless it has already been parsed.
+    pub fn get(&self) -> Result<&ParsedSourceFile> {
+        if let Some(parsed_file) = self.parsed_file.get() {
+            return Ok(parsed_file);
+        }
+        let parsed_file = self
+            .artifact_cache
+            .parse(self.file, self.content.to_string())?;
+        Ok(self.parsed_file.get_or_init(|| parsed_file))
+    }
+
+    /// The parsed file, if it was ever needed.
+    pub fn into_parsed(self) -> Option<Arc<ParsedSourceFile>> {
+        self.parsed_file.into_inner()
+    }
+}
+
+unsafe impl<'v> Trace<'v> for &'v ArtifactCache {
+    fn trace(&mut self, _tracer: &starlark::values::Tracer<'v>) {}
+}
+
+impl Default for ArtifactCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr;
+
+    use crate::source_path::SourcePath;
+
+    use super::*;
+
+    #[test]
+    fn recompilation_avoided() {
+        let cache = ArtifactCache::with_capacity(2);
+
+        let query_1_ptr = Arc::as_ptr(
+            &cache
+                .query(SupportedLanguage::Rust, "(source_file) @file")
+                .unwrap(),
+        );
+        let query_1_again_ptr = Arc::as_ptr(
+            &cache
+                .query(SupportedLanguage::Rust, "(source_file) @file")
+                .unwrap(),
+        );
+        assert!(
+            ptr::eq(query_1_ptr, query_1_again_ptr),
+            "duplication not avoided"
+        );
+
+        let query_2_ptr = Arc::as_ptr(
+            &cache
+                .query(SupportedLanguage::Rust, "(binary_expression) @bin")
+                .unwrap(),
+        );
+        assert!(!ptr::eq(query_1_ptr, query_2_ptr), "returned same query");
+    }
+
+    #[test]
+    #[cfg(all(feature = "lang-go", feature = "lang-rust"))]
+    fn same_query_different_language() {
+        let cache = ArtifactCache::with_capacity(2);
+        let query = "(source_file) @foo";
+
+        let rust_query_ptr = Arc::as_ptr(&cache.query(SupportedLanguage::Rust, query).unwrap());
+        let go_query_ptr = Arc::as_ptr(&cache.query(SupportedLanguage::Go, query).unwrap());
+        assert!(!ptr::eq(rust_query_ptr, go_query_ptr));
+    }
+
+    #[test]
+    fn regex_reused() {
+        let cache = ArtifactCache::new();
+        assert_eq!(cache.regex("fo+").unwrap().as_str(), "fo+");
+        assert_eq!(cache.regexes.read().unwrap().len(), 1);
+        cache.regex("fo+").unwrap();
+        assert_eq!(cache.regexes.read().unwrap().len(), 1);
+        cache.regex("(").unwrap_err();
+    }
+
+    #[test]
+    fn reparse_avoided() {
+        let file = |path| {
+            SourceFile::new(
+                SourcePath::new(Utf8Path::new(path), Utf8Path::new("/project")),
+                Some(SupportedLanguage::Rust),
+            )
+        };
+        let main_rs = file("/project/src/main.rs");
+        let lib_rs = file("/project/src/lib.rs");
+        let content = "fn main() {}";
+
+        let transient_cache = ArtifactCache::new();
+        assert!(!Arc::ptr_eq(
+            &transient_cache.parse(&main_rs, content.into()).unwrap(),
+            &transient_cache.parse(&main_rs, content.into()).unwrap(),
+        ));
+
+        let cache = ArtifactCache::retaining_parsed_files();
+        let parsed = cache.parse(&main_rs, content.into()).unwrap();
+        assert!(
+            Arc::ptr_eq(&parsed, &cache.parse(&main_rs, content.into()).unwrap()),
+            "reparse not avoided"
+        );
+        assert!(
+            !Arc::ptr_eq(&parsed, &cache.parse(&lib_rs, content.into()).unwrap()),
+            "returned parse of other file"
+        );
+        let edited = cache.parse(&main_rs, "fn main() { }".into()).unwrap();
+        assert!(
+            !Arc::ptr_eq(&parsed, &edited),
+            "returned parse of stale content"
+        );
+        assert_eq!(edited.content, "fn main() { }");
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     collections::{btree_map::Entry, BTreeMap},
     fmt::Display,
     str::FromStr,
+    sync::Arc,
 };
 
 use allocative::Allocative;
@@ -10,7 +11,8 @@ use derive_new::new;
 use dupe::{Dupe, OptionDupedExt};
 use smallvec::{smallvec, SmallVec};
 use starlark::{
-    starlark_simple_value,
+    environment::{Methods, MethodsBuilder, MethodsStatic},
+    starlark_module, starlark_simple_value,
     values::{
         dict::AllocDict, AllocValue, Heap, NoSerialize, ProvidesStaticType, StarlarkValue, Trace,
         Value, ValueError,
@@ -20,8 +22,13 @@ use starlark_derive::starlark_value;
 use strum::{Display, EnumIter, IntoEnumIterator};
 
 use crate::{
-    error::Error, irritation::Irritation, result::Result, scriptlets::QueryCaptures,
-    source_path::PrettyPath, suggestion::suggest,
+    error::Error,
+    irritation::Irritation,
+    result::Result,
+    scriptlets::{artifact_cache::LazyParsedFile, Node, QueryCaptures},
+    source_path::PrettyPath,
+    suggestion::suggest,
+    supported_language::SupportedLanguage,
 };
 
 const PATH_ATTR_NAME: &str = "path";
@@ -32,14 +39,24 @@ pub enum EventKind {
     OpenProject,
     OpenFile,
     Match,
+    CloseFile,
+    CloseProject,
     PreTestRun,
     PostTestRun,
 }
 
 impl EventKind {
+    /// The name passed to `vex.observe` to observe every parseable event.
+    pub const WILDCARD: &'static str = "*";
+
     pub fn parseable(&self) -> bool {
         match self {
-            Self::OpenProject | Self::OpenFile | Self::PreTestRun | Self::PostTestRun => true,
+            Self::OpenProject
+            | Self::OpenFile
+            | Self::CloseFile
+            | Self::CloseProject
+            | Self::PreTestRun
+            | Self::PostTestRun => true,
             Self::Match => false,
         }
     }
@@ -49,16 +66,33 @@ impl EventKind {
             Self::OpenProject => "open_project",
             Self::OpenFile => "open_file",
             Self::Match => "match",
+            Self::CloseFile => "close_file",
+            Self::CloseProject => "close_project",
             Self::PreTestRun => "pre_test_run",
             Self::PostTestRun => "post_test_run",
         }
     }
 
+    /// The attributes of the events of this kind passed to handlers.
+    pub fn attr_names(&self) -> &'static [&'static str] {
+        match self {
+            Self::OpenProject => &OpenProjectEvent::ATTR_NAMES,
+            Self::OpenFile => &OpenFileEvent::ATTR_NAMES,
+            Self::Match => &MatchEvent::ATTR_NAMES,
+            Self::CloseFile => &CloseFileEvent::ATTR_NAMES,
+            Self::CloseProject => &CloseProjectEvent::ATTR_NAMES,
+            Self::PreTestRun => &PreTestRunEvent::ATTR_NAMES,
+            Self::PostTestRun => &PostTestRunEvent::ATTR_NAMES,
+        }
+    }
+
     pub fn pretty_name(&self) -> &'static str {
         match self {
             Self::OpenProject => "opening project",
             Self::OpenFile => "opening file",
             Self::Match => "handling match",
+            Self::CloseFile => "closing file",
+            Self::CloseProject => "closing project",
             Self::PreTestRun => "setting up test run",
             Self::PostTestRun => "inspecting test run",
         }
@@ -72,6 +106,8 @@ impl FromStr for EventKind {
         match s {
             "open_project" => Ok(Self::OpenProject),
             "open_file" => Ok(Self::OpenFile),
+            "close_file" => Ok(Self::CloseFile),
+            "close_project" => Ok(Self::CloseProject),
             "pre_test_run" => Ok(Self::PreTestRun),
             "post_test_run" => Ok(Self::PostTestRun),
             _ => Err(Error::UnknownEvent {
@@ -87,6 +123,30 @@ impl FromStr for EventKind {
     }
 }
 
+/// Methods common to all events.
+#[starlark_module]
+fn event_methods(builder: &mut MethodsBuilder) {
+    fn to_dict<'v>(this: Value<'v>, heap: &'v Heap) -> starlark::Result<Value<'v>> {
+        let name = this
+            .get_attr(NAME_ATTR_NAME, heap)?
+            .and_then(Value::unpack_str);
+        let kind = EventKind::iter()
+            .find(|kind| Some(kind.name()) == name)
+            .expect("internal error: event has unknown name");
+        let entries = kind
+            .attr_names()
+            .iter()
+            .map(|attr| {
+                let value = this
+                    .get_attr(attr, heap)?
+                    .expect("internal error: event attribute missing");
+                Ok((*attr, value))
+            })
+            .collect::<starlark::Result<Vec<_>>>()?;
+        Ok(heap.alloc(AllocDict(entries)))
+    }
+}
+
 #[derive(new, Clone, Debug, Dupe, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
 pub struct OpenProjectEvent {
     #[allocative(skip)]
@@ -95,6 +155,8 @@ pub struct OpenProjectEvent {
 starlark_simple_value!(OpenProjectEvent);
 
 impl OpenProjectEvent {
+    const ATTR_NAMES: [&'static str; 2] = [NAME_ATTR_NAME, PATH_ATTR_NAME];
+
     pub fn kind(&self) -> EventKind {
         EventKind::OpenProject
     }
@@ -102,11 +164,13 @@ impl OpenProjectEvent {
 
 #[starlark_value(type = "OpenProjectEvent")]
 impl<'v> StarlarkValue<'v> for OpenProjectEvent {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
     fn dir_attr(&self) -> Vec<String> {
-        [NAME_ATTR_NAME, PATH_ATTR_NAME]
-            .into_iter()
-            .map(Into::into)
-            .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
@@ -118,7 +182,7 @@ impl<'v> StarlarkValue<'v> for OpenProjectEvent {
     }
 
     fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
-        [NAME_ATTR_NAME, PATH_ATTR_NAME].contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
     }
 }
 
@@ -128,42 +192,91 @@ impl Display for OpenProjectEvent {
     }
 }
 
-#[derive(new, Clone, Debug, Dupe, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
-pub struct OpenFileEvent {
+#[derive(new, Clone, Debug, Dupe, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct OpenFileEvent<'v> {
     #[allocative(skip)]
     path: PrettyPath,
+
+    language: SupportedLanguage,
+
+    #[allocative(skip)]
+    content: Arc<str>,
+
+    /// The file's tree, parsed only if a scriptlet asks for its root.
+    #[allocative(skip)]
+    parsed_file: &'v LazyParsedFile<'v>,
 }
-starlark_simple_value!(OpenFileEvent);
 
-impl OpenFileEvent {
+// SAFETY: no field refers to a value on a Starlark heap, so there is nothing for the garbage
+// collector to mark or move. The `LazyParsedFile` is owned by the scan of the file rather than by
+// the heap, and is only dropped after the module whose heap holds this event.
+unsafe impl<'v> Trace<'v> for OpenFileEvent<'v> {
+    fn trace(&mut self, _tracer: &starlark::values::Tracer<'v>) {}
+}
+
+impl OpenFileEvent<'_> {
+    const LANGUAGE_ATTR_NAME: &'static str = "language";
+    const SIZE_ATTR_NAME: &'static str = "size";
+    const CONTENT_ATTR_NAME: &'static str = "content";
+    const ROOT_ATTR_NAME: &'static str = "root";
+    const ATTR_NAMES: [&'static str; 6] = [
+        NAME_ATTR_NAME,
+        PATH_ATTR_NAME,
+        Self::LANGUAGE_ATTR_NAME,
+        Self::SIZE_ATTR_NAME,
+        Self::CONTENT_ATTR_NAME,
+        Self::ROOT_ATTR_NAME,
+    ];
+
     pub fn kind(&self) -> EventKind {
         EventKind::OpenFile
     }
 }
 
 #[starlark_value(type = "OpenFileEvent")]
-impl<'v> StarlarkValue<'v> for OpenFileEvent {
+impl<'v> StarlarkValue<'v> for OpenFileEvent<'v> {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
     fn dir_attr(&self) -> Vec<String> {
-        [NAME_ATTR_NAME, PATH_ATTR_NAME]
-            .into_iter()
-            .map(Into::into)
-            .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
         match attr {
             NAME_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.kind().name()))),
             PATH_ATTR_NAME => Some(heap.alloc(self.path.dupe())),
+            Self::LANGUAGE_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.language.name()))),
+            Self::SIZE_ATTR_NAME => Some(heap.alloc(self.content.len())),
+            Self::CONTENT_ATTR_NAME => Some(heap.alloc(heap.alloc_str(&self.content))),
+            Self::ROOT_ATTR_NAME => {
+                // A file which cannot be parsed is reported when the scan parses it.
+                let root = match self.parsed_file.get() {
+                    Ok(parsed_file) => {
+                        heap.alloc(Node::new(parsed_file.tree.root_node(), parsed_file))
+                    }
+                    Err(_) => Value::new_none(),
+                };
+                Some(root)
+            }
             _ => None,
         }
     }
 
     fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
-        [NAME_ATTR_NAME, PATH_ATTR_NAME].contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
+    }
+}
+
+impl<'v> AllocValue<'v> for OpenFileEvent<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex_no_freeze(self)
     }
 }
 
-impl Display for OpenFileEvent {
+impl Display for OpenFileEvent<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         <Self as StarlarkValue>::TYPE.fmt(f)
     }
@@ -180,6 +293,11 @@ pub struct MatchEvent<'v> {
 
 impl MatchEvent<'_> {
     const QUERY_CAPTURES_ATTR_NAME: &'static str = "captures";
+    const ATTR_NAMES: [&'static str; 3] = [
+        NAME_ATTR_NAME,
+        PATH_ATTR_NAME,
+        Self::QUERY_CAPTURES_ATTR_NAME,
+    ];
 
     pub fn kind(&self) -> EventKind {
         EventKind::Match
@@ -188,15 +306,13 @@ impl MatchEvent<'_> {
 
 #[starlark_value(type = "MatchEvent")]
 impl<'v> StarlarkValue<'v> for MatchEvent<'v> {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
     fn dir_attr(&self) -> Vec<String> {
-        [
-            NAME_ATTR_NAME,
-            PATH_ATTR_NAME,
-            Self::QUERY_CAPTURES_ATTR_NAME,
-        ]
-        .into_iter()
-        .map(Into::into)
-        .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
@@ -209,12 +325,7 @@ impl<'v> StarlarkValue<'v> for MatchEvent<'v> {
     }
 
     fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
-        [
-            NAME_ATTR_NAME,
-            PATH_ATTR_NAME,
-            Self::QUERY_CAPTURES_ATTR_NAME,
-        ]
-        .contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
     }
 }
 
@@ -230,10 +341,107 @@ impl Display for MatchEvent<'_> {
     }
 }
 
+#[derive(new, Clone, Debug, Dupe, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct CloseFileEvent {
+    #[allocative(skip)]
+    path: PrettyPath,
+
+    language: SupportedLanguage,
+}
+starlark_simple_value!(CloseFileEvent);
+
+impl CloseFileEvent {
+    const LANGUAGE_ATTR_NAME: &'static str = "language";
+    const ATTR_NAMES: [&'static str; 3] =
+        [NAME_ATTR_NAME, PATH_ATTR_NAME, Self::LANGUAGE_ATTR_NAME];
+
+    pub fn kind(&self) -> EventKind {
+        EventKind::CloseFile
+    }
+}
+
+#[starlark_value(type = "CloseFileEvent")]
+impl<'v> StarlarkValue<'v> for CloseFileEvent {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        match attr {
+            NAME_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.kind().name()))),
+            PATH_ATTR_NAME => Some(heap.alloc(self.path.dupe())),
+            Self::LANGUAGE_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.language.name()))),
+            _ => None,
+        }
+    }
+
+    fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
+        Self::ATTR_NAMES.contains(&attr)
+    }
+}
+
+impl Display for CloseFileEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as StarlarkValue>::TYPE.fmt(f)
+    }
+}
+
+#[derive(new, Clone, Debug, Dupe, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct CloseProjectEvent {
+    #[allocative(skip)]
+    path: PrettyPath,
+}
+starlark_simple_value!(CloseProjectEvent);
+
+impl CloseProjectEvent {
+    const ATTR_NAMES: [&'static str; 2] = [NAME_ATTR_NAME, PATH_ATTR_NAME];
+
+    pub fn kind(&self) -> EventKind {
+        EventKind::CloseProject
+    }
+}
+
+#[starlark_value(type = "CloseProjectEvent")]
+impl<'v> StarlarkValue<'v> for CloseProjectEvent {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        match attr {
+            NAME_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.kind().name()))),
+            PATH_ATTR_NAME => Some(heap.alloc(self.path.dupe())),
+            _ => None,
+        }
+    }
+
+    fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
+        Self::ATTR_NAMES.contains(&attr)
+    }
+}
+
+impl Display for CloseProjectEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as StarlarkValue>::TYPE.fmt(f)
+    }
+}
+
 #[derive(new, Clone, Dupe, Debug, ProvidesStaticType, NoSerialize, Allocative, Trace)]
 pub struct PreTestRunEvent;
 
 impl PreTestRunEvent {
+    const ATTR_NAMES: [&'static str; 1] = [NAME_ATTR_NAME];
+
     pub fn kind(&self) -> EventKind {
         EventKind::PreTestRun
     }
@@ -247,8 +455,13 @@ impl Display for PreTestRunEvent {
 
 #[starlark_value(type = "PreTestRunEvent")]
 impl<'v> StarlarkValue<'v> for PreTestRunEvent {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
     fn dir_attr(&self) -> Vec<String> {
-        [NAME_ATTR_NAME].into_iter().map(Into::into).collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
@@ -259,7 +472,7 @@ impl<'v> StarlarkValue<'v> for PreTestRunEvent {
     }
 
     fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
-        [NAME_ATTR_NAME].contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
     }
 }
 
@@ -276,6 +489,7 @@ pub struct PostTestRunEvent<'v> {
 
 impl<'v> PostTestRunEvent<'v> {
     const COLLATED_IRRITATIONS_ATTR_NAME: &'static str = "warnings";
+    const ATTR_NAMES: [&'static str; 2] = [NAME_ATTR_NAME, Self::COLLATED_IRRITATIONS_ATTR_NAME];
 
     pub fn new(
         irritations_iter: impl IntoIterator<Item = (Irritation, bool)>,
@@ -298,11 +512,13 @@ impl Display for PostTestRunEvent<'_> {
 
 #[starlark_value(type = "PostTestRunEvent")]
 impl<'v> StarlarkValue<'v> for PostTestRunEvent<'v> {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(event_methods)
+    }
+
     fn dir_attr(&self) -> Vec<String> {
-        [NAME_ATTR_NAME, Self::COLLATED_IRRITATIONS_ATTR_NAME]
-            .into_iter()
-            .map(Into::into)
-            .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
@@ -314,7 +530,7 @@ impl<'v> StarlarkValue<'v> for PostTestRunEvent<'v> {
     }
 
     fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
-        [NAME_ATTR_NAME, Self::COLLATED_IRRITATIONS_ATTR_NAME].contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
     }
 }
 
@@ -568,7 +784,7 @@ mod test {
                             )
 
                         def on_{event_name}(event):
-                            check['attrs'](event, ['{attrs_repr}'])
+                            check['attrs'](event, ['{attrs_repr}', 'to_dict'])
                             check['eq'](event.name, '{event_name}')
 
                             if 'path' in ['{attrs_repr}']:
@@ -600,7 +816,11 @@ mod test {
 
     #[test]
     fn on_open_file_event() {
-        test_event_common_properties("open_file", "OpenFileEvent", &["name", "path"]);
+        test_event_common_properties(
+            "open_file",
+            "OpenFileEvent",
+            &["name", "path", "language", "size", "content", "root"],
+        );
 
         let run = VexTest::new("many-matching-triggers-one-event")
             .with_scriptlet(
@@ -623,6 +843,61 @@ mod test {
         assert_eq!(1, run.irritations.len());
     }
 
+    #[test]
+    fn root() {
+        let irritations = VexTest::new("root")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_file', on_open_file)
+
+                    def on_open_file(event):
+                        for node, _ in event.root.walk():
+                            if node.kind == 'integer_literal':
+                                vex.warn('test', 'found literal', at=node)
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {\n    let x = 1 + 2;\n}\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 2, "{irritations:?}");
+    }
+
+    #[test]
+    fn skip_file() {
+        let run = VexTest::new("skip-file")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('open_file', on_open_file)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_open_file(event):
+                        if event.language != 'rust':
+                            vex.warn('test', 'unexpected language %s' % event.language)
+                        if event.size != len(event.content):
+                            vex.warn('test', 'unexpected size %d' % event.size)
+                        if event.content.startswith('// @generated'):
+                            vex.skip_file()
+
+                    def on_match(event):
+                        vex.warn('test', 'found literal', at=event.captures['lit'])
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { let x = 1; }")
+            .with_source_file("src/gen.rs", "// @generated\nfn gen() { let y = 2; }")
+            .try_run()
+            .unwrap();
+        assert_eq!(run.irritations.len(), 1);
+        assert_eq!(run.irritations[0].path().unwrap().as_str(), "src/main.rs");
+    }
+
     #[test]
     fn on_match_event() {
         test_event_common_properties("match", "MatchEvent", &["name", "captures", "path"]);
@@ -667,6 +942,83 @@ mod test {
             .assert_irritation_free();
     }
 
+    #[test]
+    fn on_close_file_event() {
+        test_event_common_properties(
+            "close_file",
+            "CloseFileEvent",
+            &["name", "path", "language"],
+        );
+    }
+
+    #[test]
+    fn on_close_project_event() {
+        test_event_common_properties("close_project", "CloseProjectEvent", &["name", "path"]);
+    }
+
+    #[test]
+    fn wildcard() {
+        let irritations = VexTest::new("wildcard")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('*', on_any)
+
+                    def on_any(event):
+                        fields = event.to_dict()
+                        if fields['name'] != event.name:
+                            fail('bad name')
+                        vex.warn('test', '%s: %s' % (event.name, ','.join(sorted(fields.keys()))))
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {}")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let messages: Vec<_> = irritations.iter().map(|irr| irr.message()).collect();
+        assert_eq!(
+            messages,
+            [
+                "close_file: language,name,path",
+                "close_project: name,path",
+                "open_file: content,language,name,path,root,size",
+                "open_project: name,path",
+            ]
+        );
+    }
+
+    #[test]
+    fn close_events() {
+        let irritations = VexTest::new("close-events")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_file', on_open_file)
+                        vex.observe('close_file', on_close_file)
+                        vex.observe('close_project', on_close_project)
+
+                    def on_open_file(event):
+                        if 'skipped.rs' in event.path:
+                            vex.skip_file()
+
+                    def on_close_file(event):
+                        vex.warn('test', 'closed %s' % event.path, at=event.path)
+
+                    def on_close_project(event):
+                        vex.warn('test', 'closed project')
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {}")
+            .with_source_file("src/skipped.rs", "fn skipped() {}")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let messages: Vec<_> = irritations.iter().map(|irr| irr.message()).collect();
+        assert_eq!(messages, ["closed project", "closed src/main.rs"]);
+    }
+
     #[test]
     fn on_pre_test_run_event() {
         test_event_common_properties("pre_test_run", "PreTestRunEvent", &["name"]);
@@ -1,3 +1,5 @@
+use std::{cell::Ref, sync::Arc};
+
 use allocative::Allocative;
 use derive_more::Display;
 use starlark::{
@@ -8,11 +10,14 @@ use starlark::{
 use starlark_derive::{starlark_value, NoSerialize, Trace};
 
 use crate::{
+    check_metadata::CheckMetadata,
+    context::ConfigScope,
     ignore_markers::IgnoreMarkers,
     scriptlets::{
         action::Action,
+        artifact_cache::ArtifactCache,
         intents::{UnfrozenIntent, UnfrozenIntents},
-        query_cache::QueryCache,
+        shared_store::SharedStore,
         Intents,
     },
 };
@@ -33,7 +38,7 @@ impl<'v> UnfrozenRetainedData<'v> {
         module.set_extra_value(module.heap().alloc(self))
     }
 
-    pub fn get_from(module: &'v Module) -> &Self {
+    pub fn get_from(module: &'v Module) -> &'v Self {
         module
             .extra_value()
             .expect("Module extra not set")
@@ -48,6 +53,11 @@ impl<'v> UnfrozenRetainedData<'v> {
     pub fn declare_intent(&self, intent: UnfrozenIntent<'v>) {
         self.intents.declare(intent)
     }
+
+    /// The intents declared after the first `start`.
+    pub fn intents_since(&self, start: usize) -> Ref<'_, [UnfrozenIntent<'v>]> {
+        Ref::map(self.intents.borrow(), |intents| &intents[start..])
+    }
 }
 
 #[starlark_value(type = "RetainedData")]
@@ -101,8 +111,19 @@ impl<'v> AllocValue<'v> for RetainedData {
 #[derive(Debug, ProvidesStaticType)]
 pub struct TempData<'v> {
     pub action: Action,
-    pub query_cache: Option<&'v QueryCache>,
+    pub config: Option<ConfigScope<'v>>,
+    pub artifact_cache: Option<&'v ArtifactCache>,
     pub ignore_markers: Option<&'v IgnoreMarkers>,
+
+    /// The name of the scriptlet whose code is running, once known.
+    pub scriptlet: Option<&'v Arc<str>>,
+
+    /// The rule pack of the scriptlet whose code is running, which prefixes the ids of its checks.
+    pub namespace: Option<&'v str>,
+    pub shared_store: Option<&'v SharedStore>,
+
+    /// The checks declared by all scriptlets, whose codes label the problems they find.
+    pub check_metadata: Option<&'v CheckMetadata>,
 }
 
 impl<'v> TempData<'v> {
@@ -0,0 +1,210 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Mutex,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize as Serialise;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    error::{Error, IOAction},
+    result::Result,
+    scriptlets::intents::UnfrozenIntent,
+    source_path::PrettyPath,
+};
+
+/// A record of every intent declared by an observer during a run, written as one JSON object per
+/// line, for debugging how events are dispatched.
+#[derive(Debug)]
+pub struct IntentLog {
+    path: Utf8PathBuf,
+    out: Mutex<BufWriter<File>>,
+}
+
+impl IntentLog {
+    pub fn create(path: &Utf8Path) -> Result<Self> {
+        let file = File::create(path).map_err(|cause| Error::IO {
+            path: PrettyPath::new(path),
+            action: IOAction::Create,
+            cause,
+        })?;
+        Ok(Self {
+            path: path.to_owned(),
+            out: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Record that the given scriptlet declared the given intent while handling the given event.
+    pub fn record(
+        &self,
+        event: &str,
+        scriptlet: &str,
+        path: Option<&PrettyPath>,
+        intent: &UnfrozenIntent<'_>,
+    ) -> Result<()> {
+        let (intent, details) = describe(intent);
+        let entry = Entry {
+            event,
+            scriptlet,
+            path: path.map(PrettyPath::as_str),
+            intent,
+            details,
+        };
+        let mut line =
+            serde_json::to_string(&entry).expect("internal error: cannot serialise intent");
+        line.push('\n');
+        self.out
+            .lock()
+            .expect("internal error: intent log lock poisoned")
+            .write_all(line.as_bytes())
+            .map_err(|cause| self.write_error(cause))
+    }
+
+    /// Flush every recorded intent to disk.
+    pub fn finish(self) -> Result<()> {
+        let Self { path, out } = self;
+        out.into_inner()
+            .expect("internal error: intent log lock poisoned")
+            .flush()
+            .map_err(|cause| Error::IO {
+                path: PrettyPath::new(&path),
+                action: IOAction::Write,
+                cause,
+            })
+    }
+
+    fn write_error(&self, cause: std::io::Error) -> Error {
+        Error::IO {
+            path: PrettyPath::new(&self.path),
+            action: IOAction::Write,
+            cause,
+        }
+    }
+}
+
+#[derive(Serialise)]
+struct Entry<'a> {
+    event: &'a str,
+    scriptlet: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a str>,
+
+    intent: &'static str,
+
+    #[serde(skip_serializing_if = "JsonValue::is_null")]
+    details: JsonValue,
+}
+
+/// The name of the given intent and the details needed to tell it apart from others.
+fn describe(intent: &UnfrozenIntent<'_>) -> (&'static str, JsonValue) {
+    match intent {
+        UnfrozenIntent::Find {
+            language, query, ..
+        } => (
+            "find",
+            json!({
+                "language": language.name(),
+                "captures": query.capture_names(),
+            }),
+        ),
+        UnfrozenIntent::Observe { event_kind, .. } => {
+            ("observe", json!({ "event": event_kind.name() }))
+        }
+        UnfrozenIntent::Warn(irritation) => (
+            "warn",
+            json!({
+                "id": irritation.vex_id().to_string(),
+                "severity": irritation.severity().name(),
+                "message": irritation.message(),
+                "path": irritation.path().map(PrettyPath::as_str),
+            }),
+        ),
+        UnfrozenIntent::SkipFile => ("skip_file", JsonValue::Null),
+        UnfrozenIntent::ScanFile {
+            file_name,
+            language,
+            ..
+        } => (
+            "scan_file",
+            json!({
+                "path": file_name.as_str(),
+                "language": language.name(),
+            }),
+        ),
+        UnfrozenIntent::Declare { vex_id, .. } => ("declare", json!({ "id": vex_id.to_string() })),
+        UnfrozenIntent::IgnorePaths(patterns) => (
+            "ignore_paths",
+            json!({ "patterns": patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>() }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn records_intents() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let log_path = Utf8PathBuf::try_from(root_dir.path().join("intents.jsonl")).unwrap();
+        let intent_log = IntentLog::create(&log_path).unwrap();
+        VexTest::new("intent-log")
+            .with_intent_log(&intent_log)
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        vex.warn('literal', 'found literal', at=event.captures['num'])
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {\n    let x = 1;\n}\n")
+            .try_run()
+            .unwrap();
+        intent_log.finish().unwrap();
+
+        let mut lines: Vec<JsonValue> = std::fs::read_to_string(&log_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        // The project is in a fresh temporary directory.
+        lines[0].as_object_mut().unwrap().remove("path").unwrap();
+        assert_eq!(
+            lines,
+            [
+                json!({
+                    "event": "open_project",
+                    "scriptlet": "test",
+                    "intent": "find",
+                    "details": { "language": "rust", "captures": ["num"] },
+                }),
+                json!({
+                    "event": "match",
+                    "scriptlet": "test",
+                    "path": "src/main.rs",
+                    "intent": "warn",
+                    "details": {
+                        "id": "literal",
+                        "severity": "warning",
+                        "message": "found literal",
+                        "path": "src/main.rs",
+                    },
+                }),
+            ]
+        );
+    }
+}
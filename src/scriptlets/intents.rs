@@ -9,10 +9,14 @@ use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType, Trace};
 
 use crate::source_path::PrettyPath;
 use crate::{
+    check_metadata::Examples,
+    error::Error,
     irritation::Irritation,
     query::Query,
-    scriptlets::{event::EventKind, observers::UnfrozenObserver, Observer},
+    scriptlets::{action::Action, event::EventKind, observers::UnfrozenObserver, Observer},
     supported_language::SupportedLanguage,
+    trigger::RawFilePattern,
+    vex_id::VexId,
 };
 
 #[derive(Debug, Display, ProvidesStaticType, NoSerialize, Allocative, Trace)]
@@ -83,11 +87,22 @@ pub enum UnfrozenIntent<'v> {
         observer: UnfrozenObserver<'v>,
     },
     Warn(Irritation),
+    SkipFile,
     ScanFile {
         file_name: PrettyPath,
         language: SupportedLanguage,
         content: String,
     },
+    Declare {
+        vex_id: VexId,
+        tags: Vec<String>,
+        description: Option<String>,
+        code: Option<String>,
+        languages: Vec<SupportedLanguage>,
+        examples: Examples,
+        allow_duplicates: bool,
+    },
+    IgnorePaths(#[allocative(skip)] Vec<RawFilePattern<String>>),
 }
 
 impl<'v> Freeze for UnfrozenIntent<'v> {
@@ -118,6 +133,7 @@ impl<'v> Freeze for UnfrozenIntent<'v> {
                 }
             }
             Self::Warn(irr) => Intent::Warn(irr),
+            Self::SkipFile => Intent::SkipFile,
             Self::ScanFile {
                 file_name,
                 language,
@@ -127,6 +143,24 @@ impl<'v> Freeze for UnfrozenIntent<'v> {
                 language,
                 content,
             },
+            Self::Declare {
+                vex_id,
+                tags,
+                description,
+                code,
+                languages,
+                examples,
+                allow_duplicates,
+            } => Intent::Declare {
+                vex_id,
+                tags,
+                description,
+                code,
+                languages,
+                examples,
+                allow_duplicates,
+            },
+            Self::IgnorePaths(patterns) => Intent::IgnorePaths(patterns),
         })
     }
 }
@@ -181,9 +215,43 @@ pub enum Intent {
         observer: Observer,
     },
     Warn(Irritation),
+    SkipFile,
     ScanFile {
         file_name: PrettyPath,
         language: SupportedLanguage,
         content: String,
     },
+    Declare {
+        vex_id: VexId,
+        tags: Vec<String>,
+        description: Option<String>,
+        code: Option<String>,
+        languages: Vec<SupportedLanguage>,
+        examples: Examples,
+        allow_duplicates: bool,
+    },
+    IgnorePaths(#[allocative(skip)] Vec<RawFilePattern<String>>),
+}
+
+impl Intent {
+    /// The function whose call declared this intent.
+    pub fn declared_by(&self) -> &'static str {
+        match self {
+            Self::Find { .. } => "vex.search",
+            Self::Observe { .. } => "vex.observe",
+            Self::Warn(_) => "vex.warn",
+            Self::SkipFile => "vex.skip_file",
+            Self::ScanFile { .. } => "vex.scan",
+            Self::Declare { .. } => "vex.declare",
+            Self::IgnorePaths(_) => "vex.ignore_paths",
+        }
+    }
+
+    /// The error for an intent declared while it cannot be acted upon.
+    pub fn unavailable_while(&self, action: Action) -> Error {
+        Error::ActionUnavailable {
+            what: self.declared_by(),
+            action,
+        }
+    }
 }
@@ -0,0 +1,424 @@
+use std::fmt::Display;
+
+use allocative::Allocative;
+use starlark::{
+    environment::{Methods, MethodsBuilder, MethodsStatic},
+    starlark_module, starlark_simple_value,
+    values::{Heap, NoSerialize, ProvidesStaticType, StarlarkValue, Value},
+};
+use starlark_derive::starlark_value;
+
+use crate::{error::Error, scriptlets::Node, supported_language::SupportedLanguage};
+
+/// The `vex.lang` object, whose attributes hold the helpers for each built-in language.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct LangObject;
+
+impl LangObject {
+    pub const NAME: &'static str = "lang";
+}
+
+starlark_simple_value!(LangObject);
+#[starlark_value(type = "Lang")]
+impl<'v> StarlarkValue<'v> for LangObject {
+    fn dir_attr(&self) -> Vec<String> {
+        SupportedLanguage::BUILTIN
+            .iter()
+            .map(|language| language.name().into())
+            .collect()
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        let language = SupportedLanguage::BUILTIN
+            .into_iter()
+            .find(|language| language.name() == attr)?;
+        Some(heap.alloc(LanguageHelpers { language }))
+    }
+
+    fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
+        SupportedLanguage::BUILTIN
+            .iter()
+            .any(|language| language.name() == attr)
+    }
+}
+
+impl Display for LangObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "vex.{}", Self::NAME)
+    }
+}
+
+/// Helpers which understand the grammar of one language, such as `vex.lang.rust`.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct LanguageHelpers {
+    language: SupportedLanguage,
+}
+
+impl LanguageHelpers {
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        /// The function which contains the given node, or the node itself if it is a function.
+        fn enclosing_fn<'v>(
+            #[starlark(this)] this: &LanguageHelpers,
+            #[starlark(require=pos)] node: Node<'v>,
+        ) -> anyhow::Result<Option<Node<'v>>> {
+            this.check_language(&node)?;
+            Ok(this.enclosing_fn(node))
+        }
+
+        /// The name of the function which contains the given node.
+        fn fn_name<'v>(
+            #[starlark(this)] this: &LanguageHelpers,
+            #[starlark(require=pos)] node: Node<'v>,
+        ) -> anyhow::Result<Option<&'v str>> {
+            this.check_language(&node)?;
+            Ok(this
+                .enclosing_fn(node)
+                .and_then(|function| this.fn_name(&function)))
+        }
+
+        /// Whether the function which contains the given node is a test.
+        fn is_test_fn<'v>(
+            #[starlark(this)] this: &LanguageHelpers,
+            #[starlark(require=pos)] node: Node<'v>,
+        ) -> anyhow::Result<bool> {
+            this.check_language(&node)?;
+            Ok(this
+                .enclosing_fn(node)
+                .is_some_and(|function| this.is_test_fn(&function)))
+        }
+    }
+
+    fn check_language(&self, node: &Node<'_>) -> Result<(), Error> {
+        let found = node.source_file.language;
+        if found != self.language {
+            return Err(Error::WrongLanguage {
+                expected: self.language,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    /// The kinds of node which declare a function or method.
+    fn fn_kinds(&self) -> &'static [&'static str] {
+        match self.language {
+            SupportedLanguage::Go => &["function_declaration", "method_declaration"],
+            SupportedLanguage::Java => &["method_declaration", "constructor_declaration"],
+            SupportedLanguage::Kotlin => &["function_declaration"],
+            SupportedLanguage::Python => &["function_definition"],
+            SupportedLanguage::Ruby => &["method", "singleton_method"],
+            SupportedLanguage::Rust => &["function_item"],
+            SupportedLanguage::Json
+            | SupportedLanguage::Toml
+            | SupportedLanguage::Yaml
+            | SupportedLanguage::Dynamic(_) => &[],
+        }
+    }
+
+    fn enclosing_fn<'v>(&self, node: Node<'v>) -> Option<Node<'v>> {
+        let fn_kinds = self.fn_kinds();
+        let mut current = Some(node);
+        while let Some(node) = current {
+            if fn_kinds.contains(&node.kind()) {
+                return Some(node);
+            }
+            current = node.parent();
+        }
+        None
+    }
+
+    fn fn_name<'v>(&self, function: &Node<'v>) -> Option<&'v str> {
+        let name = match self.language {
+            SupportedLanguage::Kotlin => {
+                let mut cursor = function.walk();
+                let name = function
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "simple_identifier")?;
+                Node::new(name, function.source_file)
+            }
+            _ => function.child_by_field_name("name")?,
+        };
+        Some(name.text())
+    }
+
+    fn is_test_fn(&self, function: &Node<'_>) -> bool {
+        let Some(name) = self.fn_name(function) else {
+            return false;
+        };
+        match self.language {
+            SupportedLanguage::Go => {
+                function
+                    .source_file
+                    .path
+                    .pretty_path
+                    .as_str()
+                    .ends_with("_test.go")
+                    && ["Test", "Benchmark", "Fuzz"]
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+            }
+            SupportedLanguage::Java | SupportedLanguage::Kotlin => {
+                self.annotations(function).any(|annotation| {
+                    let path = annotation
+                        .trim_start_matches('@')
+                        .split('(')
+                        .next()
+                        .unwrap_or_default()
+                        .trim();
+                    path == "Test" || path.ends_with(".Test")
+                })
+            }
+            SupportedLanguage::Python | SupportedLanguage::Ruby => name.starts_with("test"),
+            SupportedLanguage::Rust => self.annotations(function).any(|attribute| {
+                let path = attribute
+                    .trim_start_matches("#[")
+                    .split(['(', ']'])
+                    .next()
+                    .unwrap_or_default()
+                    .trim();
+                path == "test" || path.ends_with("::test")
+            }),
+            SupportedLanguage::Json
+            | SupportedLanguage::Toml
+            | SupportedLanguage::Yaml
+            | SupportedLanguage::Dynamic(_) => false,
+        }
+    }
+
+    /// The source text of each annotation or attribute attached to the given function.
+    fn annotations<'v>(&self, function: &Node<'v>) -> impl Iterator<Item = &'v str> {
+        let source_file = function.source_file;
+        let mut annotations = Vec::new();
+        match self.language {
+            SupportedLanguage::Java | SupportedLanguage::Kotlin => {
+                let mut cursor = function.walk();
+                let modifiers = function
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "modifiers");
+                if let Some(modifiers) = modifiers {
+                    let mut cursor = modifiers.walk();
+                    annotations.extend(
+                        modifiers
+                            .named_children(&mut cursor)
+                            .filter(|child| child.kind().ends_with("annotation"))
+                            .map(|child| Node::new(child, source_file).text()),
+                    );
+                }
+            }
+            SupportedLanguage::Rust => {
+                let mut sibling = function.prev_named_sibling();
+                while let Some(node) = sibling {
+                    match node.kind() {
+                        "attribute_item" => annotations.push(Node::new(node, source_file).text()),
+                        "line_comment" | "block_comment" => {}
+                        _ => break,
+                    }
+                    sibling = node.prev_named_sibling();
+                }
+            }
+            _ => {}
+        }
+        annotations.into_iter()
+    }
+}
+
+starlark_simple_value!(LanguageHelpers);
+#[starlark_value(type = "LanguageHelpers")]
+impl<'v> StarlarkValue<'v> for LanguageHelpers {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(LanguageHelpers::methods)
+    }
+}
+
+impl Display for LanguageHelpers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "vex.{}.{}", LangObject::NAME, self.language)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    #[test]
+    fn rust_helpers() {
+        let irritations = VexTest::new("rust-helpers")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        num = event.captures['num']
+                        vex.warn(
+                            'test',
+                            '%s in %s (test=%s)' % (num, vex.lang.rust.fn_name(num), vex.lang.rust.is_test_fn(num)),
+                            at=num,
+                        )
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    const X: u32 = 1;
+
+                    fn main() {
+                        let _ = 2;
+                    }
+
+                    #[cfg(test)]
+                    mod test {
+                        #[test]
+                        // A comment between the attribute and the function.
+                        fn works() {
+                            let _ = 3;
+                        }
+
+                        #[tokio::test]
+                        async fn works_async() {
+                            let _ = 4;
+                        }
+                    }
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irritation| irritation.message().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            irritations,
+            [
+                "1 in None (test=False)",
+                "2 in main (test=False)",
+                "3 in works (test=True)",
+                "4 in works_async (test=True)",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "lang-go",
+        feature = "lang-java",
+        feature = "lang-kotlin",
+        feature = "lang-python",
+        feature = "lang-ruby"
+    ))]
+    fn other_languages() {
+        let irritations = VexTest::new("other-language-helpers")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_file', on_open_file)
+
+                    def on_open_file(event):
+                        helpers = getattr(vex.lang, str(event.language))
+                        for node, _ in event.root.walk():
+                            if helpers.enclosing_fn(node) == node:
+                                vex.warn('test', '%s: %s (test=%s)' % (event.language, helpers.fn_name(node), helpers.is_test_fn(node)), at=node)
+                "#},
+            )
+            .with_source_file(
+                "src/main_test.go",
+                indoc! {r#"
+                    package main
+
+                    func helper() {}
+
+                    func TestMain(t *testing.T) {}
+                "#},
+            )
+            .with_source_file(
+                "src/Main.java",
+                indoc! {r#"
+                    class Main {
+                        void helper() {}
+
+                        @Test
+                        void works() {}
+                    }
+                "#},
+            )
+            .with_source_file(
+                "src/main.kt",
+                indoc! {r#"
+                    fun helper() {}
+
+                    @org.junit.Test
+                    fun works() {}
+                "#},
+            )
+            .with_source_file(
+                "src/main.py",
+                indoc! {r#"
+                    def helper():
+                        pass
+
+                    def test_works():
+                        pass
+                "#},
+            )
+            .with_source_file(
+                "src/main.rb",
+                indoc! {r#"
+                    def helper
+                    end
+
+                    def test_works
+                    end
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations
+            .into_iter()
+            .map(|irritation| irritation.message().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            irritations,
+            [
+                "java: helper (test=False)",
+                "java: works (test=True)",
+                "kotlin: helper (test=False)",
+                "kotlin: works (test=True)",
+                "python: helper (test=False)",
+                "python: test_works (test=True)",
+                "ruby: helper (test=False)",
+                "ruby: test_works (test=True)",
+                "go: helper (test=False)",
+                "go: TestMain (test=True)",
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_language() {
+        VexTest::new("wrong-language")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        vex.lang.go.fn_name(event.captures['num'])
+                "#},
+            )
+            .with_source_file("src/main.rs", "const X: u32 = 1;")
+            .returns_error("vex.lang.go cannot inspect rust nodes");
+    }
+}
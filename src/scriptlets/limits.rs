@@ -0,0 +1,159 @@
+use std::{
+    fmt::{self, Display},
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use dupe::Dupe;
+use starlark::{
+    codemap::FileSpanRef,
+    eval::{BeforeStmtFuncDyn, Evaluator},
+};
+
+/// Bounds on the work done by a single call to an event handler, so that a buggy scriptlet
+/// cannot hang a run.
+#[derive(Copy, Clone, Debug, Dupe, PartialEq, Eq)]
+pub struct HandlerLimits {
+    pub timeout: Option<Duration>,
+    pub max_steps: Option<u64>,
+}
+
+impl HandlerLimits {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn is_unlimited(&self) -> bool {
+        self.timeout.is_none() && self.max_steps.is_none()
+    }
+
+    /// Run the given evaluation, abandoning it if it exceeds these limits.
+    pub fn enforce<'v, 'a, T>(
+        self,
+        eval: &mut Evaluator<'v, 'a>,
+        f: impl FnOnce(&mut Evaluator<'v, 'a>) -> T,
+    ) -> Result<T, ExceededLimit> {
+        if self.is_unlimited() {
+            return Ok(f(eval));
+        }
+
+        let step_counter: Box<dyn BeforeStmtFuncDyn<'a>> = Box::new(StepCounter {
+            limits: self,
+            start: Instant::now(),
+            steps: 0,
+        });
+        eval.before_stmt_for_dap(step_counter.into());
+
+        // Starlark offers no way to interrupt evaluation, so exceeding a limit unwinds the stack
+        // without invoking the panic hook.
+        panic::catch_unwind(AssertUnwindSafe(|| f(eval))).map_err(|payload| {
+            match payload.downcast::<ExceededLimit>() {
+                Ok(exceeded) => *exceeded,
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        })
+    }
+}
+
+impl Default for HandlerLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Self::DEFAULT_TIMEOUT),
+            max_steps: None,
+        }
+    }
+}
+
+/// A limit which a handler exceeded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExceededLimit {
+    Timeout(Duration),
+    MaxSteps(u64),
+}
+
+impl Display for ExceededLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(timeout) => write!(f, "took longer than {}s", timeout.as_secs_f64()),
+            Self::MaxSteps(max_steps) => write!(f, "ran more than {max_steps} statements"),
+        }
+    }
+}
+
+/// Counts the statements run by one call to a handler, unwinding once it exceeds its limits.
+struct StepCounter {
+    limits: HandlerLimits,
+    start: Instant,
+    steps: u64,
+}
+
+impl StepCounter {
+    /// The number of statements run between each check of the clock.
+    const CLOCK_INTERVAL: u64 = 1024;
+}
+
+impl<'a> BeforeStmtFuncDyn<'a> for StepCounter {
+    fn call<'v>(&mut self, _span: FileSpanRef, _eval: &mut Evaluator<'v, 'a>) {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                panic::resume_unwind(Box::new(ExceededLimit::MaxSteps(max_steps)));
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.steps % Self::CLOCK_INTERVAL == 0 && self.start.elapsed() > timeout {
+                panic::resume_unwind(Box::new(ExceededLimit::Timeout(timeout)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    const BUSY_SCRIPTLET: &str = indoc! {r#"
+        def init():
+            vex.observe('open_project', on_open_project)
+
+        def on_open_project(event):
+            total = 0
+            for i in range(1000000000):
+                total += i
+    "#};
+
+    #[test]
+    fn step_limit() {
+        VexTest::new("step-limit")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+                handler-step-limit = 100
+            "#})
+            .with_scriptlet("vexes/busy.star", BUSY_SCRIPTLET)
+            .returns_error("scriptlet 'busy' ran more than 100 statements handling open_project, to allow more, raise handler-timeout or handler-step-limit in vex.toml");
+        VexTest::new("under-step-limit")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+                handler-step-limit = 100
+            "#})
+            .with_scriptlet(
+                "vexes/quick.star",
+                BUSY_SCRIPTLET.replace("1000000000", "10"),
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn timeout() {
+        VexTest::new("timeout")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+                handler-timeout = 1
+            "#})
+            .with_scriptlet("vexes/busy.star", BUSY_SCRIPTLET)
+            .returns_error("scriptlet 'busy' took longer than 1s handling open_project");
+    }
+}
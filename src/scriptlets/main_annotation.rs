@@ -69,3 +69,32 @@ impl<'v> UnpackValue<'v> for MainAnnotation<'v> {
         }
     }
 }
+
+/// A node shown alongside the main annotation of a problem, such as where something it uses was
+/// declared.
+#[derive(Debug, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct SecondaryAnnotation<'v> {
+    pub node: Node<'v>,
+
+    #[allocative(skip)]
+    pub label: &'v str,
+}
+
+impl<'v> Display for SecondaryAnnotation<'v> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[starlark_value(type = "Node|(Node, str)")]
+impl<'v> StarlarkValue<'v> for SecondaryAnnotation<'v> {}
+
+impl<'v> UnpackValue<'v> for SecondaryAnnotation<'v> {
+    fn unpack_value(value: Value<'v>) -> Option<Self> {
+        if let Some((node, label)) = <(Node<'_>, &str)>::unpack_value(value) {
+            Some(Self { node, label })
+        } else {
+            Node::unpack_value(value).map(|node| Self { node, label: "" })
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     cell::{Cell, RefCell},
     fmt::{Display, Write},
     hash::Hasher,
+    mem,
     ops::Deref,
 };
 
@@ -10,21 +11,26 @@ use derive_more::Display;
 use derive_new::new;
 use dupe::{Dupe, OptionDupedExt};
 use paste::paste;
-use serde::Serialize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use starlark::{
     collections::StarlarkHasher,
     environment::{Methods, MethodsBuilder, MethodsStatic},
+    eval::Evaluator,
     starlark_simple_value,
     values::{
-        AllocValue, Demand, Heap, NoSerialize, ProvidesStaticType, StarlarkValue, Trace,
-        UnpackValue, Value, ValueError,
+        none::NoneType, AllocValue, Demand, Heap, NoSerialize, ProvidesStaticType, StarlarkValue,
+        Trace, UnpackValue, Value, ValueError, ValueLike,
     },
 };
 use starlark_derive::{starlark_attrs, starlark_module, starlark_value, StarlarkAttrs};
 use strum::EnumIs;
 use tree_sitter::{Node as TSNode, Point, TreeCursor};
 
-use crate::{result::Result, source_file::ParsedSourceFile};
+use crate::{
+    error::Error, result::Result, scriptlets::extra_data::TempData, source_file::ParsedSourceFile,
+    theme,
+};
 
 #[derive(new, Clone, Debug, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
 pub struct Node<'v> {
@@ -42,9 +48,17 @@ unsafe impl<'v> Trace<'v> for Node<'v> {
 impl<'v> Node<'v> {
     const KIND_ATTR_NAME: &'static str = "kind";
     const LOCATION_ATTR_NAME: &'static str = "location";
+    const START_BYTE_ATTR_NAME: &'static str = "start_byte";
+    const END_BYTE_ATTR_NAME: &'static str = "end_byte";
+    const ATTR_NAMES: [&'static str; 4] = [
+        Self::KIND_ATTR_NAME,
+        Self::LOCATION_ATTR_NAME,
+        Self::START_BYTE_ATTR_NAME,
+        Self::END_BYTE_ATTR_NAME,
+    ];
 
     #[inline]
-    fn parent(&self) -> Option<Self> {
+    pub fn parent(&self) -> Option<Self> {
         self.ts_node
             .parent()
             .map(|ts_node| Self::new(ts_node, self.source_file))
@@ -88,6 +102,45 @@ impl<'v> Node<'v> {
             .map(|ts_node| Self::new(ts_node, self.source_file))
     }
 
+    /// The source text spanned by this node.
+    pub fn text(&self) -> &'v str {
+        &self.source_file.content[self.byte_range()]
+    }
+
+    /// Whether this node and the other have the same kinds of nodes in the same places, and if
+    /// `ignore_text` is unset, the same text in each leaf. Comments and other extras are skipped.
+    pub fn structurally_equal(&self, other: &Node<'_>, ignore_text: bool) -> bool {
+        if self.source_file.language != other.source_file.language
+            || self.kind() != other.kind()
+            || self.is_named() != other.is_named()
+        {
+            return false;
+        }
+
+        fn children<'a>(node: &Node<'a>) -> Vec<(Option<&'static str>, TSNode<'a>)> {
+            (0..node.child_count())
+                .filter_map(|i| {
+                    let child = node.ts_node.child(i)?;
+                    (!child.is_extra()).then(|| (node.field_name_for_child(i as u32), child))
+                })
+                .collect()
+        }
+        let (children, other_children) = (children(self), children(other));
+        if children.is_empty() && other_children.is_empty() {
+            return ignore_text || self.text() == other.text();
+        }
+        children.len() == other_children.len()
+            && children.into_iter().zip(other_children).all(
+                |((field, child), (other_field, other_child))| {
+                    field == other_field
+                        && Node::new(child, self.source_file).structurally_equal(
+                            &Node::new(other_child, other.source_file),
+                            ignore_text,
+                        )
+                },
+            )
+    }
+
     pub fn to_complete_sexp(&self) -> Result<String> {
         let mut expr = String::new();
         NodePrinter::new(&mut expr, WhitespaceStyle::Compact).write_node(self, None)?;
@@ -132,6 +185,11 @@ impl<'v> Node<'v> {
             Ok(ChildrenIterable::new(this))
         }
 
+        /// A walker over this node and its descendants, which yields each with its depth.
+        fn walk<'v>(this: Node<'v>) -> starlark::Result<TreeWalker<'v>> {
+            Ok(TreeWalker::new(this))
+        }
+
         fn num_children<'v>(this: Node<'v>) -> starlark::Result<usize> {
             Ok(this.child_count())
         }
@@ -139,6 +197,34 @@ impl<'v> Node<'v> {
         fn expr<'v>(this: Node<'v>) -> starlark::Result<String> {
             this.to_complete_sexp().map_err(starlark::Error::new_other)
         }
+
+        /// The kinds and fields of this node and its descendants, without their text.
+        fn sexp<'v>(this: Node<'v>) -> starlark::Result<String> {
+            Ok(this.to_sexp())
+        }
+
+        /// Whether this node has the same shape as another, optionally with the same text.
+        fn structurally_equal<'v>(
+            this: Node<'v>,
+            #[starlark(require = pos)] other: Node<'v>,
+            #[starlark(require = named, default = true)] ignore_text: bool,
+        ) -> starlark::Result<bool> {
+            Ok(this.structurally_equal(&other, ignore_text))
+        }
+
+        /// Whether the given regex matches anywhere in this node's text.
+        fn text_matches<'v>(
+            this: Node<'v>,
+            #[starlark(require = pos)] pattern: &str,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> starlark::Result<bool> {
+            let regex = match TempData::get_from(eval).artifact_cache {
+                Some(artifact_cache) => artifact_cache.regex(pattern),
+                None => Regex::new(pattern).map_err(Error::from),
+            }
+            .map_err(starlark::Error::new_other)?;
+            Ok(regex.is_match(this.text()))
+        }
     }
 }
 
@@ -206,22 +292,21 @@ impl<'v> StarlarkValue<'v> for Node<'v> {
     }
 
     fn dir_attr(&self) -> Vec<String> {
-        [Self::KIND_ATTR_NAME, Self::LOCATION_ATTR_NAME]
-            .into_iter()
-            .map(Into::into)
-            .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
         match attr {
             Self::KIND_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.grammar_name()))),
             Self::LOCATION_ATTR_NAME => Some(heap.alloc(Location::of(self))),
+            Self::START_BYTE_ATTR_NAME => Some(heap.alloc(self.start_byte())),
+            Self::END_BYTE_ATTR_NAME => Some(heap.alloc(self.end_byte())),
             _ => None,
         }
     }
 
     fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
-        [Self::KIND_ATTR_NAME, Self::LOCATION_ATTR_NAME].contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
     }
 
     fn get_methods() -> Option<&'static Methods> {
@@ -377,22 +462,226 @@ impl<'v> AllocValue<'v> for ChildrenIterator<'v> {
     }
 }
 
+/// A cursor over a node's subtree which may be moved by hand or iterated, yielding each node and
+/// its depth below the walk's starting node, depth-first in source order.
+#[derive(Debug, Display, Allocative, NoSerialize, ProvidesStaticType, Trace)]
+#[display(fmt = "TreeWalker")]
+struct TreeWalker<'v> {
+    top: Node<'v>,
+    state: RefCell<TreeWalkerState<'v>>,
+}
+
+#[derive(Debug, Allocative, Trace)]
+struct TreeWalkerState<'v> {
+    current: Node<'v>,
+    depth: usize,
+
+    /// Whether iteration has already yielded the current node.
+    visited: bool,
+
+    skip_subtree: bool,
+    stopped: bool,
+}
+
+impl<'v> TreeWalker<'v> {
+    const NODE_ATTR_NAME: &'static str = "node";
+    const DEPTH_ATTR_NAME: &'static str = "depth";
+    const ATTR_NAMES: [&'static str; 2] = [Self::NODE_ATTR_NAME, Self::DEPTH_ATTR_NAME];
+
+    fn new(top: Node<'v>) -> Self {
+        let state = RefCell::new(TreeWalkerState {
+            current: top.dupe(),
+            depth: 0,
+            visited: false,
+            skip_subtree: false,
+            stopped: false,
+        });
+        Self { top, state }
+    }
+
+    fn from_value(value: Value<'v>) -> &'v Self {
+        value
+            .downcast_ref()
+            .expect("internal error: method called on wrong type")
+    }
+
+    fn goto_first_child(&self) -> bool {
+        let state = &mut *self.state.borrow_mut();
+        let Some(child) = state.current.child(0) else {
+            return false;
+        };
+        state.move_to(child, state.depth + 1);
+        true
+    }
+
+    fn goto_next_sibling(&self) -> bool {
+        let state = &mut *self.state.borrow_mut();
+        if state.current == self.top {
+            return false;
+        }
+        let Some(next_sibling) = state.current.next_sibling() else {
+            return false;
+        };
+        state.move_to(next_sibling, state.depth);
+        true
+    }
+
+    fn goto_parent(&self) -> bool {
+        let state = &mut *self.state.borrow_mut();
+        if state.current == self.top {
+            return false;
+        }
+        let Some(parent) = state.current.parent() else {
+            return false;
+        };
+        state.move_to(parent, state.depth - 1);
+        true
+    }
+
+    /// Move to the next node to yield, if any.
+    fn advance(&self) -> Option<(Node<'v>, usize)> {
+        let state = &mut *self.state.borrow_mut();
+        if state.stopped {
+            return None;
+        }
+        if !state.visited {
+            state.visited = true;
+            return Some((state.current.dupe(), state.depth));
+        }
+
+        let skip_subtree = mem::take(&mut state.skip_subtree);
+        if !skip_subtree {
+            if let Some(child) = state.current.child(0) {
+                state.move_to(child, state.depth + 1);
+                state.visited = true;
+                return Some((state.current.dupe(), state.depth));
+            }
+        }
+        loop {
+            if state.current == self.top {
+                state.stopped = true;
+                return None;
+            }
+            if let Some(next_sibling) = state.current.next_sibling() {
+                state.move_to(next_sibling, state.depth);
+                state.visited = true;
+                return Some((state.current.dupe(), state.depth));
+            }
+            let Some(parent) = state.current.parent() else {
+                state.stopped = true;
+                return None;
+            };
+            state.move_to(parent, state.depth - 1);
+        }
+    }
+
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        /// Move to the current node's first child, returning whether it has one.
+        fn goto_first_child<'v>(this: Value<'v>) -> starlark::Result<bool> {
+            Ok(TreeWalker::from_value(this).goto_first_child())
+        }
+
+        /// Move to the current node's next sibling, returning whether it has one.
+        fn goto_next_sibling<'v>(this: Value<'v>) -> starlark::Result<bool> {
+            Ok(TreeWalker::from_value(this).goto_next_sibling())
+        }
+
+        /// Move to the current node's parent, returning whether it has one within the walk.
+        fn goto_parent<'v>(this: Value<'v>) -> starlark::Result<bool> {
+            Ok(TreeWalker::from_value(this).goto_parent())
+        }
+
+        /// Continue iteration after the current node's descendants.
+        fn skip_subtree<'v>(this: Value<'v>) -> starlark::Result<NoneType> {
+            TreeWalker::from_value(this).state.borrow_mut().skip_subtree = true;
+            Ok(NoneType)
+        }
+
+        /// End iteration.
+        fn stop<'v>(this: Value<'v>) -> starlark::Result<NoneType> {
+            TreeWalker::from_value(this).state.borrow_mut().stopped = true;
+            Ok(NoneType)
+        }
+    }
+}
+
+impl<'v> TreeWalkerState<'v> {
+    fn move_to(&mut self, node: Node<'v>, depth: usize) {
+        self.current = node;
+        self.depth = depth;
+        self.visited = false;
+    }
+}
+
+#[starlark_value(type = "TreeWalker")]
+impl<'v> StarlarkValue<'v> for TreeWalker<'v> {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(Self::methods)
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        match attr {
+            Self::NODE_ATTR_NAME => Some(heap.alloc(self.state.borrow().current.dupe())),
+            Self::DEPTH_ATTR_NAME => Some(heap.alloc(self.state.borrow().depth)),
+            _ => None,
+        }
+    }
+
+    fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
+        Self::ATTR_NAMES.contains(&attr)
+    }
+
+    unsafe fn iterate(&self, me: Value<'v>, _heap: &'v Heap) -> starlark::Result<Value<'v>> {
+        // Iteration shares the walker's position, so moving it inside a loop steers the loop.
+        Ok(me)
+    }
+
+    unsafe fn iter_next(&self, _: usize, heap: &'v Heap) -> Option<Value<'v>> {
+        self.advance()
+            .map(|(node, depth)| heap.alloc((node, depth)))
+    }
+
+    unsafe fn iter_stop(&self) {}
+}
+
+impl<'v> AllocValue<'v> for TreeWalker<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex_no_freeze(self)
+    }
+}
+
 pub struct NodePrinter<'w, W> {
     whitespace_style: WhitespaceStyle, // TODO(kcza): what's the idiomatic name here?
+    snippets: bool,
     curr_indent: u32,
     out: &'w mut W,
 }
 
 impl<'w, W: Write> NodePrinter<'w, W> {
+    const MAX_SNIPPET_LEN: usize = 40;
+
     pub fn new(out: &'w mut W, format: WhitespaceStyle) -> Self {
         let curr_indent = 0;
         Self {
             whitespace_style: format,
+            snippets: false,
             curr_indent,
             out,
         }
     }
 
+    /// Follow each node's location with the first line of its source text.
+    pub fn with_snippets(mut self) -> Self {
+        self.snippets = true;
+        self
+    }
+
     pub fn write(&mut self, src_file: &ParsedSourceFile) -> Result<()> {
         let root = Node::new(src_file.tree.root_node(), src_file);
         self.write_node(&root, None)
@@ -428,6 +717,7 @@ impl<'w, W: Write> NodePrinter<'w, W> {
         }
         write!(self.out, ")")?;
         self.write_location(&Location::of(node))?;
+        self.write_snippet(node)?;
         Ok(())
     }
 
@@ -448,6 +738,25 @@ impl<'w, W: Write> NodePrinter<'w, W> {
         write!(self.out, " ; {loc}")?;
         Ok(())
     }
+
+    fn write_snippet(&mut self, node: &Node<'_>) -> Result<()> {
+        if !self.snippets || self.whitespace_style.is_compact() {
+            return Ok(());
+        }
+
+        let text = node
+            .source_file
+            .content
+            .get(node.byte_range())
+            .unwrap_or_default();
+        let line = text.lines().next().unwrap_or_default();
+        let mut snippet: String = line.chars().take(Self::MAX_SNIPPET_LEN).collect();
+        if snippet.len() < text.len() {
+            snippet.push_str(theme::current().ellipsis());
+        }
+        write!(self.out, " {snippet:?}")?;
+        Ok(())
+    }
 }
 
 #[derive(EnumIs)]
@@ -475,6 +784,7 @@ impl WhitespaceStyle {
     Ord,
     Hash,
     Allocative,
+    Deserialize,
     Serialize,
     ProvidesStaticType,
     StarlarkAttrs,
@@ -497,6 +807,15 @@ impl Location {
         }
     }
 
+    pub fn of_line(row: usize, len: usize) -> Self {
+        Self {
+            start_row: row,
+            start_column: 0,
+            end_row: row,
+            end_column: len,
+        }
+    }
+
     pub fn of(node: &TSNode<'_>) -> Self {
         let Point {
             row: start_row,
@@ -679,6 +998,7 @@ mod test {
                         def on_match(event):
                             expected_attrs = [
                                 'children',
+                                'end_byte',
                                 'is_extra',
                                 'is_named',
                                 'kind',
@@ -690,7 +1010,12 @@ mod test {
                                 'parents',
                                 'previous_sibling',
                                 'previous_siblings',
+                                'start_byte',
                                 'expr',
+                                'sexp',
+                                'structurally_equal',
+                                'text_matches',
+                                'walk',
                             ]
                             check['attrs'](event.captures['bin_expr'], expected_attrs)
                     "#,
@@ -752,6 +1077,194 @@ mod test {
             .assert_irritation_free();
     }
 
+    #[test]
+    fn byte_offsets() {
+        VexTest::new("byte-offsets")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search(
+                                'rust',
+                                '(binary_expression left: (integer_literal) @l_int right: (parenthesized_expression)) @bin_expr',
+                                on_match,
+                            )
+
+                        def on_match(event):
+                            bin_expr = event.captures['bin_expr']
+                            check['eq'](bin_expr.start_byte, 24)
+                            check['eq'](bin_expr.end_byte, 35)
+                            check['eq'](bin_expr.end_byte - bin_expr.start_byte, len(str(bin_expr)))
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + (2 + 3);
+                        println!("{x}");
+                    }
+                "#},
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn text_matches() {
+        VexTest::new("text-matches")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search(
+                                'rust',
+                                '(binary_expression left: (integer_literal) @l_int right: (parenthesized_expression)) @bin_expr',
+                                on_match,
+                            )
+
+                        def on_match(event):
+                            bin_expr = event.captures['bin_expr']
+                            check['true'](bin_expr.text_matches(r'\(2 \+ \d\)'))
+                            check['true'](bin_expr.text_matches('^1'))
+                            check['false'](bin_expr.text_matches('^2'))
+                            check['false'](event.captures['l_int'].text_matches('[a-z]'))
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + (2 + 3);
+                        println!("{x}");
+                    }
+                "#},
+            )
+            .assert_irritation_free();
+        VexTest::new("invalid-regex")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @l_int', on_match)
+
+                    def on_match(event):
+                        event.captures['l_int'].text_matches('(')
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { let x = 1; }")
+            .returns_error("regex parse error");
+    }
+
+    #[test]
+    fn walk() {
+        VexTest::new("walk")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(let_declaration) @let', on_match)
+
+                    def on_match(event):
+                        let = event.captures['let']
+                        kinds = ['%d:%s' % (depth, node.kind) for node, depth in let.walk() if node.is_named()]
+                        check['eq'](kinds, ['0:let_declaration', '1:identifier', '1:binary_expression', '2:integer_literal', '2:integer_literal'])
+
+                        walker = let.walk()
+                        kinds = []
+                        for node, depth in walker:
+                            kinds.append(node.kind)
+                            if node.kind == 'binary_expression':
+                                walker.skip_subtree()
+                        check['eq'](kinds, ['let_declaration', 'let', 'identifier', '=', 'binary_expression', ';'])
+
+                        walker = let.walk()
+                        kinds = []
+                        for node, depth in walker:
+                            kinds.append(node.kind)
+                            if node.kind == 'identifier':
+                                walker.stop()
+                        check['eq'](kinds, ['let_declaration', 'let', 'identifier'])
+
+                        walker = let.walk()
+                        check['eq'](walker.node, let)
+                        check['false'](walker.goto_parent())
+                        check['false'](walker.goto_next_sibling())
+                        check['true'](walker.goto_first_child())
+                        check['eq'](walker.node.kind, 'let')
+                        check['eq'](walker.depth, 1)
+                        check['true'](walker.goto_next_sibling())
+                        check['eq'](walker.node.kind, 'identifier')
+                        check['true'](walker.goto_parent())
+                        check['eq']((walker.node, walker.depth), (let, 0))
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file("src/main.rs", "fn main() {\n    let x = 1 + 2;\n}\n")
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn structure() {
+        VexTest::new("structure")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(source_file) @file', on_match)
+
+                        def on_match(event):
+                            a, b, c, d = [fn['body'] for fn in event.captures['file'].children()]
+
+                            check['eq'](a.sexp(), b.sexp())
+                            check['true'](a.structurally_equal(b))
+                            check['false'](a.structurally_equal(b, ignore_text=False))
+                            check['true'](a.structurally_equal(d, ignore_text=False))
+                            check['false'](a.structurally_equal(c))
+                            check['false'](a.structurally_equal(c[1]))
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn a() { f(x + 1); }
+                    fn b() { g(y + 2); }
+                    fn c() { f(x - 1); }
+                    fn d() { f(x /* comment */ + 1); }
+                "#},
+            )
+            .assert_irritation_free();
+    }
+
     #[test]
     fn is_extra() {
         VexTest::new("is_extra")
@@ -1,19 +1,37 @@
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
 use allocative::Allocative;
+use camino::Utf8Path;
 use derive_new::new;
 use dupe::Dupe;
 use starlark::{
     eval::Evaluator,
-    values::{Freeze, Freezer, FrozenValue, StarlarkValue, Value},
+    values::{Freeze, Freezer, FrozenValue, StarlarkValue, Value, ValueLike},
 };
 use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType, Trace};
 
 use crate::{
+    check_metadata::CheckMetadata,
+    error::Error,
     ignore_markers::IgnoreMarkers,
+    irritation::Irritation,
     result::Result,
     scriptlets::{
-        action::Action, event::EventKind, extra_data::TempData, handler_module::HandlerModule,
-        print_handler::PrintHandler, query_cache::QueryCache,
+        action::Action,
+        artifact_cache::ArtifactCache,
+        event::EventKind,
+        extra_data::{TempData, UnfrozenRetainedData},
+        handler_module::HandlerModule,
+        intent_log::IntentLog,
+        intents::UnfrozenIntent,
+        limits::HandlerLimits,
+        print_handler::PrintHandler,
+        shared_store::SharedStore,
     },
+    source_path::PrettyPath,
+    timings::Timer,
+    trigger::FilePattern,
+    vex_id::VexId,
 };
 
 #[derive(Debug, derive_more::Display, NoSerialize, ProvidesStaticType, Allocative)]
@@ -21,17 +39,26 @@ use crate::{
 pub struct ObserverData {
     on_open_project: Vec<Observer>,
     on_open_file: Vec<Observer>,
+    on_close_file: Vec<Observer>,
+    on_close_project: Vec<Observer>,
     on_pre_test_run: Vec<Observer>,
     on_post_test_run: Vec<Observer>,
+
+    /// Paths whose events are not sent to the observers of each scriptlet, keyed by scriptlet.
+    #[allocative(skip)]
+    ignored_paths: BTreeMap<Arc<str>, Vec<FilePattern>>,
 }
 
 impl ObserverData {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            on_open_project: Vec::with_capacity(capacity / 4),
-            on_open_file: Vec::with_capacity(capacity / 4),
-            on_pre_test_run: Vec::with_capacity(capacity / 4),
-            on_post_test_run: Vec::with_capacity(capacity / 4),
+            on_open_project: Vec::with_capacity(capacity / 6),
+            on_open_file: Vec::with_capacity(capacity / 6),
+            on_close_file: Vec::with_capacity(capacity / 6),
+            on_close_project: Vec::with_capacity(capacity / 6),
+            on_pre_test_run: Vec::with_capacity(capacity / 6),
+            on_post_test_run: Vec::with_capacity(capacity / 6),
+            ignored_paths: BTreeMap::new(),
         }
     }
 
@@ -39,8 +66,11 @@ impl ObserverData {
         Self {
             on_open_project: Vec::with_capacity(0),
             on_open_file: Vec::with_capacity(0),
+            on_close_file: Vec::with_capacity(0),
+            on_close_project: Vec::with_capacity(0),
             on_pre_test_run: Vec::with_capacity(0),
             on_post_test_run: Vec::with_capacity(0),
+            ignored_paths: BTreeMap::new(),
         }
     }
 
@@ -48,10 +78,18 @@ impl ObserverData {
         let Self {
             on_open_project,
             on_open_file,
+            on_close_file,
+            on_close_project,
             on_pre_test_run,
             on_post_test_run,
+            ignored_paths: _,
         } = self;
-        on_open_project.len() + on_open_file.len() + on_pre_test_run.len() + on_post_test_run.len()
+        on_open_project.len()
+            + on_open_file.len()
+            + on_close_file.len()
+            + on_close_project.len()
+            + on_pre_test_run.len()
+            + on_post_test_run.len()
     }
 
     pub fn add_open_project_observer(&mut self, observer: Observer) {
@@ -62,6 +100,14 @@ impl ObserverData {
         self.on_open_file.push(observer)
     }
 
+    pub fn add_close_file_observer(&mut self, observer: Observer) {
+        self.on_close_file.push(observer)
+    }
+
+    pub fn add_close_project_observer(&mut self, observer: Observer) {
+        self.on_close_project.push(observer)
+    }
+
     pub fn add_pre_test_run_observer(&mut self, observer: Observer) {
         self.on_pre_test_run.push(observer)
     }
@@ -70,17 +116,37 @@ impl ObserverData {
         self.on_post_test_run.push(observer)
     }
 
+    /// Stop sending the events of files which match any of the given patterns to the observers of
+    /// the given scriptlet.
+    pub fn ignore_paths(&mut self, scriptlet: Arc<str>, patterns: Vec<FilePattern>) {
+        self.ignored_paths
+            .entry(scriptlet)
+            .or_default()
+            .extend(patterns)
+    }
+
     pub fn extend(&mut self, other: Self) {
         let Self {
             on_open_project,
             on_open_file,
+            on_close_file,
+            on_close_project,
             on_pre_test_run,
             on_post_test_run,
+            ignored_paths,
         } = self;
         on_open_project.extend(other.on_open_project);
         on_open_file.extend(other.on_open_file);
+        on_close_file.extend(other.on_close_file);
+        on_close_project.extend(other.on_close_project);
         on_pre_test_run.extend(other.on_pre_test_run);
         on_post_test_run.extend(other.on_post_test_run);
+        other
+            .ignored_paths
+            .into_iter()
+            .for_each(|(scriptlet, patterns)| {
+                ignored_paths.entry(scriptlet).or_default().extend(patterns)
+            });
     }
 
     pub fn observers_for(&self, event_kind: EventKind) -> &[Observer] {
@@ -88,10 +154,40 @@ impl ObserverData {
             EventKind::OpenProject => &self.on_open_project,
             EventKind::OpenFile => &self.on_open_file,
             EventKind::Match => panic!("internal error: query_match not observable"),
+            EventKind::CloseFile => &self.on_close_file,
+            EventKind::CloseProject => &self.on_close_project,
             EventKind::PreTestRun => &self.on_pre_test_run,
             EventKind::PostTestRun => &self.on_post_test_run,
         }
     }
+
+    /// The observers of the given event whose scriptlets do not ignore the given path.
+    pub fn observers_for_path(
+        &self,
+        event_kind: EventKind,
+        path: &PrettyPath,
+    ) -> Cow<'_, [Observer]> {
+        let observers = self.observers_for(event_kind);
+        if self.ignored_paths.is_empty() {
+            return Cow::Borrowed(observers);
+        }
+        Cow::Owned(
+            observers
+                .iter()
+                .filter(|observer| !self.ignores(&observer.scriptlet, path))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Whether the given scriptlet ignores the given path.
+    pub fn ignores(&self, scriptlet: &str, path: &PrettyPath) -> bool {
+        let Some(patterns) = self.ignored_paths.get(scriptlet) else {
+            return false;
+        };
+        let path = Utf8Path::new("/").join(path.as_str());
+        patterns.iter().any(|pattern| pattern.matches(&path))
+    }
 }
 
 #[starlark_value(type = "ObserverData")]
@@ -100,21 +196,43 @@ impl<'v> StarlarkValue<'v> for ObserverData {}
 #[derive(new, Debug, Trace, Allocative)]
 pub struct UnfrozenObserver<'v> {
     callback: Value<'v>,
+
+    /// The scriptlet which declared this observer.
+    #[allocative(skip)]
+    scriptlet: Arc<str>,
+
+    /// The rule pack of the scriptlet which declared this observer, if any.
+    #[allocative(skip)]
+    namespace: Option<Arc<str>>,
 }
 
 impl<'v> Freeze for UnfrozenObserver<'v> {
     type Frozen = Observer;
 
     fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
-        let Self { callback } = self;
+        let Self {
+            callback,
+            scriptlet,
+            namespace,
+        } = self;
         let callback = callback.freeze(freezer)?;
-        Ok(Observer { callback })
+        Ok(Observer {
+            callback,
+            scriptlet,
+            namespace,
+        })
     }
 }
 
 #[derive(new, Debug, Clone, Dupe, Allocative)]
 pub struct Observer {
     callback: FrozenValue,
+
+    #[allocative(skip)]
+    scriptlet: Arc<str>,
+
+    #[allocative(skip)]
+    namespace: Option<Arc<str>>,
 }
 
 pub trait Observable {
@@ -129,9 +247,20 @@ pub trait Observable {
 #[derive(Clone, Debug, Dupe)]
 pub struct ObserveOptions<'v> {
     pub action: Action,
-    pub query_cache: Option<&'v QueryCache>,
+    pub artifact_cache: Option<&'v ArtifactCache>,
     pub ignore_markers: Option<&'v IgnoreMarkers>,
+    pub shared_store: Option<&'v SharedStore>,
+
+    /// The checks declared by all scriptlets, whose codes label the problems they find.
+    pub check_metadata: Option<&'v CheckMetadata>,
     pub print_handler: &'v PrintHandler<'v>,
+    pub limits: HandlerLimits,
+
+    /// Whether to report errors raised by the observer as problems rather than returning them.
+    pub keep_going: bool,
+
+    /// Where to record the intents declared by the observer, if anywhere.
+    pub intent_log: Option<&'v IntentLog>,
 }
 
 impl Observable for Observer {
@@ -143,14 +272,25 @@ impl Observable for Observer {
     ) -> Result<()> {
         let ObserveOptions {
             action,
-            query_cache,
+            artifact_cache,
             ignore_markers,
+            shared_store,
+            check_metadata,
             print_handler,
+            limits,
+            keep_going,
+            intent_log,
         } = opts;
+        let first_intent = handler_module.intent_count();
         let temp_data = TempData {
             action,
-            query_cache,
+            config: None,
+            artifact_cache,
             ignore_markers,
+            scriptlet: Some(&self.scriptlet),
+            namespace: self.namespace.as_deref(),
+            shared_store,
+            check_metadata,
         };
         let mut eval = Evaluator::new(handler_module);
         eval.extra = Some(&temp_data);
@@ -158,12 +298,80 @@ impl Observable for Observer {
 
         let func = self.callback.dupe().to_value(); // TODO(kcza): check thread safety! Can this unfrozen
                                                     // function mutate upvalues if it is a closure?
-        eval.eval_function(func, &[event], &[])?;
+        let handler_timer = Timer::start_unlogged("handler").about(&self.scriptlet);
+        let result = limits
+            .enforce(&mut eval, |eval| eval.eval_function(func, &[event], &[]))
+            .map_err(|exceeded| Error::HandlerLimitExceeded {
+                scriptlet: self.scriptlet.dupe(),
+                event: action.name(),
+                exceeded,
+            })
+            .and_then(|result| Ok(result?));
+        handler_timer.stop();
+        match result {
+            Ok(_) => {}
+            Err(err) if keep_going => {
+                self.report_error(handler_module, event, action, check_metadata, &err)
+            }
+            Err(err) => return Err(err),
+        }
 
+        if let Some(intent_log) = intent_log {
+            let path = event_path(handler_module, event);
+            UnfrozenRetainedData::get_from(handler_module)
+                .intents_since(first_intent)
+                .iter()
+                .try_for_each(|intent| {
+                    intent_log.record(action.name(), &self.scriptlet, path.as_ref(), intent)
+                })?;
+        }
         Ok(())
     }
 }
 
+impl Observer {
+    /// The name of the scriptlet which declared this observer.
+    pub fn scriptlet(&self) -> &Arc<str> {
+        &self.scriptlet
+    }
+
+    /// The id under which errors are reported if the scriptlet's name is not a valid id.
+    const FALLBACK_ERROR_ID: &'static str = "scriptlet-error";
+
+    /// Report an error raised by this observer as a problem found by its scriptlet.
+    fn report_error<'v>(
+        &self,
+        handler_module: &'v HandlerModule,
+        event: Value<'v>,
+        action: Action,
+        check_metadata: Option<&CheckMetadata>,
+        err: &Error,
+    ) {
+        let vex_id = VexId::try_from(self.scriptlet.to_string()).unwrap_or_else(|_| {
+            VexId::try_from(Self::FALLBACK_ERROR_ID.to_owned())
+                .expect("internal error: fallback error id invalid")
+        });
+        let code = check_metadata.and_then(|check_metadata| check_metadata.code_of(&vex_id));
+        let irritation = Irritation::scriptlet_error(
+            vex_id,
+            code,
+            &format!("error handling {}: {err}", action.name()),
+            event_path(handler_module, event),
+        );
+        UnfrozenRetainedData::get_from(handler_module)
+            .declare_intent(UnfrozenIntent::Warn(irritation));
+    }
+}
+
+/// The path of the file the given event concerns, if any.
+fn event_path<'v>(handler_module: &'v HandlerModule, event: Value<'v>) -> Option<PrettyPath> {
+    event
+        .get_attr("path", handler_module.heap())
+        .ok()
+        .flatten()
+        .and_then(|path| path.downcast_ref::<PrettyPath>().map(PrettyPath::dupe))
+}
+
 impl Observable for &[Observer] {
     fn observe<'v>(
         &self,
@@ -175,3 +383,65 @@ impl Observable for &[Observer] {
             .try_for_each(|observer| observer.observe(handler_module, event.dupe(), opts.dupe()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::{severity::Severity, vextest::VexTest};
+
+    fn vex_test(keep_going: bool) -> VexTest<'static> {
+        VexTest::new("keep-going")
+            .with_keep_going(keep_going)
+            .with_scriptlet(
+                "vexes/broken.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        fail('oh no')
+                "#},
+            )
+            .with_scriptlet(
+                "vexes/working.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        vex.warn('working', 'found literal', at=event.captures['lit'])
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {\n    let x = 1;\n}\n")
+    }
+
+    #[test]
+    fn keep_going() {
+        let irritations = vex_test(true).try_run().unwrap().irritations;
+        assert_eq!(irritations.len(), 2, "{irritations:?}");
+        let broken = irritations
+            .iter()
+            .find(|irritation| irritation.vex_id().as_ref() == "broken")
+            .unwrap();
+        assert_eq!(broken.severity(), Severity::Error);
+        assert_eq!(broken.path().unwrap().as_str(), "src/main.rs");
+        assert!(
+            broken.message().starts_with("error handling match: "),
+            "{}",
+            broken.message()
+        );
+        assert!(broken.message().contains("oh no"), "{}", broken.message());
+        assert!(irritations
+            .iter()
+            .any(|irritation| irritation.vex_id().as_ref() == "working"));
+
+        vex_test(false).returns_error("oh no");
+    }
+}
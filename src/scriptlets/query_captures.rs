@@ -24,21 +24,61 @@ pub struct QueryCaptures<'v> {
 impl<'v> QueryCaptures<'v> {
     pub fn new(
         query: &Query,
-        qmatch: QueryMatch<'v, '_>,
+        qmatch: &QueryMatch<'_, 'v>,
+        source_file: &'v ParsedSourceFile,
+        heap: &'v Heap,
+    ) -> Self {
+        let nodes = qmatch
+            .captures
+            .iter()
+            .map(|capture| (capture.index as usize, capture.node));
+        Self::from_nodes(query, qmatch.pattern_index, nodes, source_file, heap)
+    }
+
+    /// The captures of a match of a query combined with others, whose patterns start at the given
+    /// offset. Only the names captured by the original query are present.
+    pub fn new_combined(
+        query: &Query,
+        combined: &Query,
+        pattern_offset: usize,
+        qmatch: &QueryMatch<'_, 'v>,
+        source_file: &'v ParsedSourceFile,
+        heap: &'v Heap,
+    ) -> Self {
+        let combined_names = combined.capture_names();
+        let nodes = qmatch.captures.iter().map(|capture| {
+            let index = query
+                .capture_index_for_name(combined_names[capture.index as usize])
+                .expect("internal error: capture not in original query");
+            (index as usize, capture.node)
+        });
+        Self::from_nodes(
+            query,
+            qmatch.pattern_index - pattern_offset,
+            nodes,
+            source_file,
+            heap,
+        )
+    }
+
+    fn from_nodes(
+        query: &Query,
+        pattern_index: usize,
+        nodes: impl Iterator<Item = (usize, tree_sitter::Node<'v>)>,
         source_file: &'v ParsedSourceFile,
         heap: &'v Heap,
     ) -> Self {
         let names = query.capture_names();
-        let quantifiers = query.capture_quantifiers(qmatch.pattern_index);
+        let quantifiers = query.capture_quantifiers(pattern_index);
 
         let mut captures: SmallVec<[_; 10]> = names
             .iter()
             .zip(quantifiers)
             .map(|(name, quantifier)| (*name, Capture::new(*quantifier)))
             .collect();
-        qmatch.captures.iter().for_each(|r#match| {
-            let (_, ref mut capture) = captures[r#match.index as usize];
-            capture.push(Node::new(r#match.node, source_file))
+        nodes.for_each(|(index, node)| {
+            let (_, ref mut capture) = captures[index];
+            capture.push(Node::new(node, source_file))
         });
         captures.sort_by(|cap1, cap2| cap1.0.cmp(cap2.0));
 
@@ -74,6 +114,28 @@ impl<'v> QueryCaptures<'v> {
                 .collect())
         }
 
+        /// Every node captured under the given name, as a list whatever the capture's quantifier.
+        fn nodes<'v>(
+            this: Value<'v>,
+            #[starlark(require = pos)] name: Value<'v>,
+            heap: &'v Heap,
+        ) -> starlark::Result<Vec<Value<'v>>> {
+            let this = this
+                .request_value::<&QueryCaptures<'_>>()
+                .expect("internal error: incorrect receiver");
+            let capture = this.captures.at(name, heap)?;
+            if capture.is_none() {
+                Ok(Vec::new())
+            } else if capture.request_value::<&Node<'_>>().is_some() {
+                Ok(vec![capture])
+            } else {
+                Ok(capture
+                    .iterate(heap)
+                    .expect("internal error: capture not a node or list")
+                    .collect())
+            }
+        }
+
         fn items<'v>(this: Value<'v>, heap: &'v Heap) -> starlark::Result<Vec<Value<'v>>> {
             let this = this
                 .request_value::<&QueryCaptures<'_>>()
@@ -200,7 +262,8 @@ impl<'v> Capture<'v> {
     }
 }
 
-#[cfg(test)]
+// Every test here searches Rust code.
+#[cfg(all(test, feature = "lang-rust"))]
 mod test {
     use super::*;
 
@@ -584,6 +647,72 @@ mod test {
             .assert_irritation_free();
     }
 
+    #[test]
+    fn nodes() {
+        VexTest::new("nodes")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search(
+                                'rust',
+                                '''
+                                    (
+                                        (line_comment)+ @comments
+                                        (block_comment)? @block_comment
+                                        (let_declaration) @let
+                                    )
+                                ''',
+                                on_match,
+                            )
+
+                        def on_match(event):
+                            captures = event.captures
+                            check['eq'](len(captures.nodes('comments')), 2)
+                            check['eq'](captures.nodes('block_comment'), [])
+                            check['eq'](captures.nodes('let'), [captures['let']])
+                            check['type'](captures.nodes('let')[0], 'Node')
+                            vex.warn('test', 'found', at=captures.nodes('comments')[-1])
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        // some
+                        // comment
+                        let x = 1;
+                    }
+                "#},
+            )
+            .try_run()
+            .map(|run_data| assert_eq!(run_data.irritations.len(), 1))
+            .unwrap();
+        VexTest::new("nodes-missing")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_match)
+
+                    def on_match(event):
+                        event.captures.nodes('missing')
+                "#},
+            )
+            .with_source_file("src/main.rs", "const X: u32 = 1;")
+            .returns_error("missing");
+    }
+
     #[test]
     fn quantifiers() {
         let src_path = SourcePath::new_in(Utf8Path::new("main.rs"), Utf8Path::new("./"));
@@ -624,7 +753,7 @@ mod test {
             .next()
             .unwrap();
         let heap = Heap::new();
-        let captures = heap.alloc(QueryCaptures::new(&query, qmatch, &src_file, &heap));
+        let captures = heap.alloc(QueryCaptures::new(&query, &qmatch, &src_file, &heap));
 
         enum Expectatation {
             AttrType(&'static str),
@@ -702,7 +831,7 @@ mod test {
         let heap = Heap::new();
         let mut matches: Vec<_> = cursor
             .matches(&query, tree.root_node(), content.as_bytes())
-            .map(|qmatch| QueryCaptures::new(&query, qmatch, &src_file, &heap))
+            .map(|qmatch| QueryCaptures::new(&query, &qmatch, &src_file, &heap))
             .map(|caps| heap.alloc(caps))
             .map(|caps| {
                 caps.at(heap.alloc("duplicated_pattern_name"), &heap)
@@ -772,7 +901,7 @@ mod test {
         let heap = Heap::new();
         let mut matches: Vec<_> = cursor
             .matches(&query, tree.root_node(), content.as_bytes())
-            .map(|qmatch| QueryCaptures::new(&query, qmatch, &src_file, &heap))
+            .map(|qmatch| QueryCaptures::new(&query, &qmatch, &src_file, &heap))
             .map(|caps| heap.alloc(caps))
             .map(|caps| {
                 caps.at(heap.alloc("duplicated_capture_name"), &heap)
@@ -0,0 +1,204 @@
+use std::fmt::Display;
+
+use allocative::Allocative;
+use regex::Regex;
+use starlark::{
+    environment::{GlobalsBuilder, Methods, MethodsBuilder, MethodsStatic},
+    eval::Evaluator,
+    starlark_module, starlark_simple_value,
+    values::{Heap, NoSerialize, ProvidesStaticType, StarlarkValue, Value},
+};
+use starlark_derive::starlark_value;
+
+use crate::{error::Error, result::Result, scriptlets::extra_data::TempData};
+
+/// Add the `re` namespace, which compiles regexes, to the given globals.
+pub fn register(builder: &mut GlobalsBuilder) {
+    builder.struct_(RegexObject::NAMESPACE, re_members);
+}
+
+#[starlark_module]
+fn re_members(builder: &mut GlobalsBuilder) {
+    /// Compile the given regex, in the syntax of Rust's `regex` crate.
+    fn compile(
+        #[starlark(require = pos)] pattern: &str,
+        eval: &mut Evaluator<'_, '_>,
+    ) -> anyhow::Result<RegexObject> {
+        Ok(RegexObject::new(pattern, eval)?)
+    }
+}
+
+/// A compiled regex.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct RegexObject {
+    #[allocative(skip)]
+    regex: Regex,
+}
+
+impl RegexObject {
+    const NAMESPACE: &'static str = "re";
+    const PATTERN_ATTR_NAME: &'static str = "pattern";
+
+    fn new(pattern: &str, eval: &Evaluator<'_, '_>) -> Result<Self> {
+        // Evaluators used to load scriptlets have no artifacts to reuse.
+        let artifact_cache = eval
+            .extra
+            .and_then(|extra| extra.downcast_ref::<TempData>())
+            .and_then(|temp_data| temp_data.artifact_cache);
+        let regex = match artifact_cache {
+            Some(artifact_cache) => artifact_cache.regex(pattern)?,
+            None => Regex::new(pattern).map_err(Error::from)?,
+        };
+        Ok(Self { regex })
+    }
+
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        /// Whether this regex matches anywhere in the given text.
+        fn matches<'v>(
+            this: &RegexObject,
+            #[starlark(require = pos)] text: &'v str,
+        ) -> anyhow::Result<bool> {
+            Ok(this.regex.is_match(text))
+        }
+
+        /// The first match in the given text, if any.
+        fn find<'v>(
+            this: &RegexObject,
+            #[starlark(require = pos)] text: &'v str,
+        ) -> anyhow::Result<Option<&'v str>> {
+            Ok(this.regex.find(text).map(|m| m.as_str()))
+        }
+
+        /// Every non-overlapping match in the given text.
+        fn find_all<'v>(
+            this: &RegexObject,
+            #[starlark(require = pos)] text: &'v str,
+        ) -> anyhow::Result<Vec<&'v str>> {
+            Ok(this.regex.find_iter(text).map(|m| m.as_str()).collect())
+        }
+
+        /// The groups of the first match in the given text, if any. The first is the whole match
+        /// and groups which did not take part in the match are `None`.
+        fn groups<'v>(
+            this: &RegexObject,
+            #[starlark(require = pos)] text: &'v str,
+        ) -> anyhow::Result<Option<Vec<Option<&'v str>>>> {
+            Ok(this.regex.captures(text).map(|captures| {
+                captures
+                    .iter()
+                    .map(|group| group.map(|group| group.as_str()))
+                    .collect()
+            }))
+        }
+
+        /// The given text with every match replaced. The replacement may refer to groups as `$1`
+        /// or `$name`.
+        fn replace<'v>(
+            this: &RegexObject,
+            #[starlark(require = pos)] text: &'v str,
+            #[starlark(require = pos)] replacement: &str,
+        ) -> anyhow::Result<String> {
+            Ok(this.regex.replace_all(text, replacement).into_owned())
+        }
+
+        /// The parts of the given text between matches.
+        fn split<'v>(
+            this: &RegexObject,
+            #[starlark(require = pos)] text: &'v str,
+        ) -> anyhow::Result<Vec<&'v str>> {
+            Ok(this.regex.split(text).collect())
+        }
+    }
+}
+
+starlark_simple_value!(RegexObject);
+#[starlark_value(type = "Regex")]
+impl<'v> StarlarkValue<'v> for RegexObject {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(Self::methods)
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        vec![Self::PATTERN_ATTR_NAME.into()]
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        match attr {
+            Self::PATTERN_ATTR_NAME => Some(heap.alloc(self.regex.as_str())),
+            _ => None,
+        }
+    }
+
+    fn has_attr(&self, attr: &str, _: &'v Heap) -> bool {
+        attr == Self::PATTERN_ATTR_NAME
+    }
+}
+
+impl Display for RegexObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.compile({:?})", Self::NAMESPACE, self.regex.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::{formatdoc, indoc};
+
+    use crate::vextest::VexTest;
+
+    #[test]
+    fn regex() {
+        VexTest::new("regex")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    VERSION = re.compile(r'v(\d+)\.(\d+)(-\w+)?')
+
+                    check['eq'](str(VERSION), 're.compile("v(\\\\d+)\\\\.(\\\\d+)(-\\\\w+)?")')
+                    check['eq'](VERSION.pattern, r'v(\d+)\.(\d+)(-\w+)?')
+                    check['true'](VERSION.matches('at v1.2'))
+                    check['false'](VERSION.matches('at 1.2'))
+                    check['eq'](VERSION.find('from v1.2 to v3.4'), 'v1.2')
+                    check['eq'](VERSION.find('none'), None)
+                    check['eq'](VERSION.find_all('from v1.2 to v3.4-rc'), ['v1.2', 'v3.4-rc'])
+                    check['eq'](VERSION.groups('at v1.2'), ['v1.2', '1', '2', None])
+                    check['eq'](VERSION.groups('none'), None)
+                    check['eq'](VERSION.replace('from v1.2 to v3.4', '$2.$1'), 'from 2.1 to 4.3')
+                    check['eq'](re.compile(r',\s*').split('a, b,c'), ['a', 'b', 'c'])
+
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(line_comment) @comment', on_match)
+
+                    def on_match(event):
+                        todo = re.compile(r'TODO\((\w+)\)')
+                        check['eq'](todo.groups(str(event.captures['comment']))[1], 'someone')
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file("src/main.rs", "// TODO(someone): finish\nfn main() {}")
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn invalid_regex() {
+        VexTest::new("invalid-regex")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    re.compile('(')
+
+                    def init():
+                        pass
+                "#},
+            )
+            .returns_error("regex parse error");
+    }
+}
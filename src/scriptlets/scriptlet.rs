@@ -1,4 +1,7 @@
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::Arc,
+};
 
 use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use const_format::formatcp;
@@ -15,6 +18,9 @@ use starlark::{
 };
 
 use crate::{
+    check_metadata::{CheckInfo, CheckMetadata},
+    check_selection::CheckSelection,
+    context::ProjectInfo,
     error::{Error, InvalidLoadReason},
     result::Result,
     scriptlets::{
@@ -24,21 +30,28 @@ use crate::{
         extra_data::{RetainedData, TempData, UnfrozenRetainedData},
         handler_module::HandlerModule,
         print_handler::PrintHandler,
+        re, source,
         store::{InitOptions, PreinitedModuleStore},
         Intent, ObserverData, PreinitOptions,
     },
+    severity::SeverityOverrides,
     source_path::PrettyPath,
+    tags::CheckTags,
+    trigger::CaseSensitivity,
 };
 
 #[derive(Debug)]
 pub struct PreinitingScriptlet {
     pub path: Utf8PathBuf,
+
+    /// The name by which the manifest refers to this scriptlet.
+    pub name: String,
     ast: AstModule,
     loads: BTreeMap<String, LoadPath>,
 }
 
 impl PreinitingScriptlet {
-    pub fn new(path: Utf8PathBuf, code: String) -> Result<Self> {
+    pub fn new(path: Utf8PathBuf, name: String, code: String) -> Result<Self> {
         let ast = AstModule::parse(path.as_str(), code, &Dialect::Standard)?;
         let loads = ast
             .loads()
@@ -49,7 +62,12 @@ impl PreinitingScriptlet {
                 Ok((raw_load, load_path))
             })
             .collect::<Result<_>>()?;
-        Ok(Self { path, ast, loads })
+        Ok(Self {
+            path,
+            name,
+            ast,
+            loads,
+        })
     }
 
     #[allow(unused)]
@@ -68,8 +86,19 @@ impl PreinitingScriptlet {
         partial_store: &PreinitedModuleStore,
         frozen_heap: &FrozenHeap,
     ) -> Result<InitingScriptlet> {
-        let Self { path, ast, loads } = self;
-        let PreinitOptions { lenient, verbosity } = opts;
+        let Self {
+            path,
+            name,
+            ast,
+            loads,
+        } = self;
+        let PreinitOptions {
+            lenient,
+            project,
+            severity_overrides,
+            selection,
+            verbosity,
+        } = opts;
 
         let preinited_module = {
             let preinited_module = Module::new();
@@ -78,8 +107,13 @@ impl PreinitingScriptlet {
             {
                 let temp_data = TempData {
                     action: Action::Preiniting,
-                    query_cache: None,
+                    config: None,
+                    artifact_cache: None,
                     ignore_markers: None,
+                    scriptlet: None,
+                    namespace: None,
+                    shared_store: None,
+                    check_metadata: None,
                 };
                 let print_handler = PrintHandler::new(*verbosity, path.as_str());
                 let loader = Loader::new(&loads, partial_store);
@@ -87,7 +121,10 @@ impl PreinitingScriptlet {
                 eval.set_loader(&loader);
                 eval.set_print_handler(&print_handler);
                 eval.extra = Some(&temp_data);
-                eval.eval_module(ast, &Self::globals(*lenient))?;
+                eval.eval_module(
+                    ast,
+                    &Self::globals(*lenient, project, severity_overrides, selection),
+                )?;
             };
             preinited_module.freeze()?
         };
@@ -95,19 +132,39 @@ impl PreinitingScriptlet {
 
         Ok(InitingScriptlet {
             path,
+            name,
             preinited_module,
         })
     }
 
-    fn globals(lenient: bool) -> Globals {
-        let mut builder = GlobalsBuilder::extended_by(&[LibraryExtension::Print]);
-        let app = AppObject::new(lenient);
+    fn globals(
+        lenient: bool,
+        project: &ProjectInfo,
+        severity_overrides: &SeverityOverrides,
+        selection: &CheckSelection,
+    ) -> Globals {
+        let mut builder = GlobalsBuilder::extended_by(&[
+            LibraryExtension::Print,
+            LibraryExtension::Json,
+            LibraryExtension::StructType,
+            LibraryExtension::RecordType,
+        ]);
+        re::register(&mut builder);
+        let app = AppObject::new(
+            lenient,
+            project.clone(),
+            severity_overrides.clone(),
+            selection.clone(),
+        );
         builder.set(AppObject::NAME, builder.alloc(app));
         builder.build()
     }
 
     fn global_names(&self) -> HashSet<String> {
-        HashSet::from_iter(["vex".to_string()])
+        ["vex", "print", "json", "re", "struct", "record", "field"]
+            .into_iter()
+            .map(Into::into)
+            .collect()
     }
 
     pub fn loads(&self) -> &BTreeMap<String, LoadPath> {
@@ -146,14 +203,52 @@ impl LoadPath {
         &self.0
     }
 
+    /// Resolve a load from the scriptlet at the given path. Loads may name a rule pack, as in
+    /// `@pack/lib.star`, and loads from within a pack resolve within that pack.
     fn new(from: &Utf8Path, load: &str) -> Result<Self> {
+        let invalid_load = |reason| Error::InvalidLoad {
+            load: load.to_owned(),
+            module: PrettyPath::new(from),
+            reason,
+        };
+
+        if let Some((pack, load_in_pack)) =
+            load.strip_prefix('@').and_then(|load| load.split_once('/'))
+        {
+            if let Some(forbidden_char) = pack
+                .chars()
+                .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '-'))
+            {
+                return Err(invalid_load(InvalidLoadReason::ForbiddenChar(
+                    forbidden_char,
+                )));
+            }
+            let load_in_pack = Utf8Path::new(load_in_pack);
+            Self::validate_raw(from, load_in_pack)?;
+            if !matches!(
+                load_in_pack.components().next(),
+                Some(Utf8Component::Normal(_))
+            ) {
+                return Err(invalid_load(InvalidLoadReason::MidwayPathOperator));
+            }
+            return Ok(Self(Utf8Path::new(&format!("@{pack}")).join(load_in_pack)));
+        }
+
         let load_path = Utf8Path::new(load);
         Self::validate_raw(from, load_path)?;
+        let pack = source::pack_of(from);
         let resolved_path = match load_path.components().next() {
             Some(Utf8Component::CurDir | Utf8Component::ParentDir) => {
-                Self::path_in_dir(from, load)?
+                let resolved_path = Self::path_in_dir(from, load)?;
+                if pack.is_some() && source::pack_of(&resolved_path) != pack {
+                    return Err(invalid_load(InvalidLoadReason::OutsidePack));
+                }
+                resolved_path
             }
-            _ => load_path.to_owned(),
+            _ => match pack {
+                Some(pack) => Utf8Path::new(&format!("@{pack}")).join(load_path),
+                None => load_path.to_owned(),
+            },
         };
         Ok(Self(resolved_path))
     }
@@ -334,28 +429,44 @@ impl LoadPath {
 #[derive(Debug)]
 pub struct InitingScriptlet {
     pub path: Utf8PathBuf,
+    pub name: String,
     pub preinited_module: FrozenModule,
 }
 
 impl InitingScriptlet {
-    pub fn init(self, opts: &InitOptions, frozen_heap: &FrozenHeap) -> Result<ObserverData> {
+    pub fn init(
+        self,
+        opts: &InitOptions,
+        frozen_heap: &FrozenHeap,
+    ) -> Result<(ObserverData, CheckTags, CheckMetadata)> {
         let Self {
             path,
+            name,
             preinited_module,
         } = self;
-        let InitOptions { verbosity } = opts;
+        let InitOptions { verbosity, config } = opts;
 
         let Some(init) = preinited_module.get_option("init")? else {
-            return Ok(ObserverData::empty());
+            return Ok((
+                ObserverData::empty(),
+                CheckTags::default(),
+                CheckMetadata::default(),
+            ));
         };
 
         let module = {
             let module = HandlerModule::new();
             {
+                let scriptlet = Arc::from(name.as_str());
                 let temp_data = TempData {
                     action: Action::Initing,
-                    query_cache: None,
+                    config: Some(config.scope(&name)),
+                    artifact_cache: None,
                     ignore_markers: None,
+                    scriptlet: Some(&scriptlet),
+                    namespace: source::pack_of(&path),
+                    shared_store: None,
+                    check_metadata: None,
                 };
                 let print_handler = PrintHandler::new(*verbosity, path.as_str());
                 let mut eval = Evaluator::new(&module);
@@ -367,34 +478,89 @@ impl InitingScriptlet {
         };
         frozen_heap.add_reference(module.frozen_heap());
 
-        let observer_data = {
+        let (observer_data, check_tags, check_metadata) = {
             let invocation_data = RetainedData::get_from(&module);
             let intents = invocation_data.intents();
             let mut observer_data = ObserverData::with_capacity(intents.len());
-            intents.iter().for_each(|intent| {
-                if let Intent::Observe {
+            let mut check_tags = CheckTags::default();
+            let mut declarations = Vec::new();
+            let mut events = BTreeSet::new();
+            let mut ignored_paths = Vec::new();
+            intents.iter().for_each(|intent| match intent {
+                Intent::Observe {
                     event_kind,
                     observer,
-                } = intent
-                {
+                } => {
+                    events.insert(event_kind.name());
                     let observer = observer.dupe();
                     match event_kind {
                         EventKind::OpenProject => observer_data.add_open_project_observer(observer),
                         EventKind::OpenFile => observer_data.add_open_file_observer(observer),
                         EventKind::Match => panic!("internal error: query_match not observable"),
+                        EventKind::CloseFile => observer_data.add_close_file_observer(observer),
+                        EventKind::CloseProject => {
+                            observer_data.add_close_project_observer(observer)
+                        }
                         EventKind::PreTestRun => observer_data.add_pre_test_run_observer(observer),
                         EventKind::PostTestRun => {
                             observer_data.add_post_test_run_observer(observer)
                         }
                     }
                 }
+                Intent::Declare {
+                    vex_id,
+                    tags,
+                    description,
+                    code,
+                    languages,
+                    examples,
+                    allow_duplicates,
+                } => {
+                    check_tags.declare(vex_id.clone(), tags.iter().cloned());
+                    declarations.push((
+                        vex_id,
+                        description,
+                        code,
+                        languages,
+                        examples,
+                        allow_duplicates,
+                    ));
+                }
+                Intent::IgnorePaths(patterns) => ignored_paths.extend(patterns.iter().cloned()),
+                _ => {}
             });
-            observer_data
+            if !ignored_paths.is_empty() {
+                let patterns = ignored_paths
+                    .into_iter()
+                    .map(|pattern| pattern.compile(CaseSensitivity::default()))
+                    .collect::<Result<_>>()?;
+                observer_data.ignore_paths(Arc::from(name.as_str()), patterns);
+            }
+
+            let mut check_metadata = CheckMetadata::default();
+            let pretty_path = PrettyPath::new(&path);
+            declarations.into_iter().for_each(
+                |(vex_id, description, code, languages, examples, allow_duplicates)| {
+                    check_metadata.declare(
+                        vex_id.clone(),
+                        CheckInfo {
+                            path: Some(pretty_path.dupe()),
+                            description: description.clone(),
+                            code: code.clone(),
+                            events: events.clone(),
+                            languages: languages.iter().copied().collect(),
+                            examples: examples.clone(),
+                            allow_duplicates: *allow_duplicates,
+                        },
+                    )
+                },
+            );
+            (observer_data, check_tags, check_metadata)
         };
         if observer_data.len() == 0 {
-            crate::warn!("{} observes no events", path);
+            log::warn!("{} observes no events", path);
         }
-        Ok(observer_data)
+        Ok((observer_data, check_tags, check_metadata))
     }
 }
 
@@ -441,6 +607,44 @@ mod test {
             .assert_irritation_free();
     }
 
+    #[test]
+    fn name_conflicts() {
+        const SCRIPTLET: &str = indoc! {r#"
+            def init():
+                vex.observe('open_project', on_open_project)
+
+            def on_open_project(event):
+                pass
+        "#};
+        VexTest::new("name-conflict")
+            .with_scriptlet("vexes/style/long-functions.star", SCRIPTLET)
+            .with_scriptlet("vexes/perf/long-functions.star", SCRIPTLET)
+            .returns_error(
+                r"scriptlets perf/long-functions.star and style/long-functions.star are both named 'long-functions'",
+            );
+        VexTest::new("name-conflict-renamed")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [scriptlet-names]
+                "style/long-functions.star" = "style-long-functions"
+            "#})
+            .with_scriptlet("vexes/style/long-functions.star", SCRIPTLET)
+            .with_scriptlet("vexes/perf/long-functions.star", SCRIPTLET)
+            .assert_irritation_free();
+        VexTest::new("name-conflict-library")
+            .with_scriptlet("vexes/lib/long_functions.star", "def helper():\n    pass")
+            .with_scriptlet(
+                "vexes/long_functions.star",
+                formatdoc! {r#"
+                    load('lib/long_functions.star', 'helper')
+                    {SCRIPTLET}
+                "#},
+            )
+            .assert_irritation_free();
+    }
+
     #[test]
     fn no_callbacks() {
         VexTest::new("no-callbacks")
@@ -551,6 +755,11 @@ mod test {
             "vex.observe('open_file', lambda x: x)",
         );
         test_preiniting_availability("vex.warn", Unavailable, "vex.warn('test', 'oh no!')");
+        test_preiniting_availability(
+            "vex.declare",
+            Unavailable,
+            "vex.declare('test', tags=['style'])",
+        );
 
         let assert_available_initing = |name, call| {
             VexTest::new(format!("initing-{name}"))
@@ -565,6 +774,7 @@ mod test {
                 .returns_error(format!("{name} unavailable while initing"));
         };
         assert_available_initing("vex.warn", "vex.warn('test', 'oh no!')");
+        assert_available_initing("vex.store", "vex.store()");
 
         let test_vexing_open_availability = |name, availability, call| {
             let result = VexTest::new(format!("vexing-{name}"))
@@ -605,6 +815,13 @@ mod test {
             "vex.observe('open_file', lambda x: x)",
         );
         test_vexing_open_availability("vex.warn", Available, "vex.warn('test', 'oh no!')");
+        test_vexing_open_availability(
+            "vex.declare",
+            Unavailable,
+            "vex.declare('test', tags=['style'])",
+        );
+        test_vexing_open_availability("vex.skip_file", Unavailable, "vex.skip_file()");
+        test_vexing_open_availability("vex.store", Available, "vex.store()");
 
         let test_vexing_match_availability = |name, availability, call| {
             let result = VexTest::new(format!("vexing-{name}"))
@@ -656,6 +873,8 @@ mod test {
             "vex.observe('open_file', lambda x: x)",
         );
         test_vexing_match_availability("vex.warn", Available, "vex.warn('test', 'oh no!')");
+        test_vexing_match_availability("vex.skip_file", Unavailable, "vex.skip_file()");
+        test_vexing_match_availability("vex.store", Available, "vex.store()");
     }
 
     #[test]
@@ -665,6 +884,45 @@ mod test {
             .returns_error("not found")
     }
 
+    #[test]
+    fn library_extensions() {
+        VexTest::new("library-extensions")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    config = json.decode('{{"name": "vex", "tags": ["a", "b"], "max": 3}}')
+                    check['eq'](config, {{'name': 'vex', 'tags': ['a', 'b'], 'max': 3}})
+                    check['eq'](json.encode({{'a': [1, None, True]}}), '{{"a":[1,null,true]}}')
+
+                    point = struct(x=1, y=2)
+                    check['eq']((point.x, point.y), (1, 2))
+
+                    Finding = record(id=str, count=field(int, 0))
+                    finding = Finding(id='some-id')
+                    check['eq']((finding.id, finding.count), ('some-id', 0))
+
+                    def init():
+                        pass
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+        VexTest::new("invalid-json")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    json.decode('{')
+
+                    def init():
+                        pass
+                "#},
+            )
+            .returns_error("EOF while parsing");
+    }
+
     #[test]
     fn loads() {
         VexTest::new("valid-absolute")
@@ -693,6 +951,18 @@ mod test {
             .with_scriptlet("vexes/dir/test.star", "load('../sibling.star', 'func')")
             .with_scriptlet("vexes/sibling.star", "fail('marker')")
             .returns_error("marker");
+        VexTest::new("valid-pack")
+            .with_scriptlet("vexes/test.star", "load('@acme/lib/helper.star', 'func')")
+            .with_pack_scriptlet("acme", "lib/helper.star", "fail('marker')")
+            .returns_error("marker");
+        VexTest::new("valid-within-pack")
+            .with_pack_scriptlet("acme", "test.star", "load('lib/helper.star', 'func')")
+            .with_pack_scriptlet("acme", "lib/helper.star", "fail('marker')")
+            .with_scriptlet("vexes/lib/helper.star", "fail('wrong helper')")
+            .returns_error("marker");
+        VexTest::new("outside-pack")
+            .with_pack_scriptlet("acme", "test.star", "load('../test.star', 'func')")
+            .returns_error("load path cannot leave its rule pack");
 
         VexTest::new("nonexistent-loads")
             .with_scriptlet("vexes/test.star", "load('i_do_not_exist.star', 'x')")
@@ -766,6 +1036,7 @@ mod test {
                 const DIR: &str = "/tmp/vex_project";
                 PreinitingScriptlet::new(
                     Utf8PathBuf::from(formatcp!("{DIR}/test.star")),
+                    "test".into(),
                     formatdoc! {
                         r#"
                             load({}, 'unused')
@@ -790,6 +1061,14 @@ mod test {
             .path("../../../aaa/bbb/ccc.star")
             .ok();
 
+        LoadTest::new("pack").path("@acme/aaa/bbb.star").ok();
+        LoadTest::new("pack-parent")
+            .path("@acme/../aaa.star")
+            .causes("load path can only have path operators at the start");
+        LoadTest::new("pack-uppercase")
+            .path("@Acme/aaa.star")
+            .causes("load path can only contain a-z, 0-9, `_`, `.` and `/`, found `A`");
+
         LoadTest::new("dash")
             .path("---.star")
             .causes("load path can only contain a-z, 0-9, `_`, `.` and `/`, found `-`");
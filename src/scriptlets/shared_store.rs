@@ -0,0 +1,344 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use allocative::Allocative;
+use dupe::Dupe;
+use starlark::{
+    environment::{Methods, MethodsBuilder, MethodsStatic},
+    starlark_module, starlark_simple_value,
+    values::{
+        none::{NoneOr, NoneType},
+        Heap, StarlarkValue, Value, ValueError,
+    },
+};
+use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType};
+
+type Entries = BTreeMap<String, serde_json::Value>;
+
+/// Values recorded by event handlers during a single run, scoped by scriptlet name. Files are
+/// scanned in parallel, so each access locks the store.
+#[derive(Clone, Debug, Default, Dupe)]
+pub struct SharedStore {
+    entries: Arc<Mutex<BTreeMap<Arc<str>, Entries>>>,
+    used: Arc<AtomicBool>,
+}
+
+impl SharedStore {
+    /// Returns a handle to the same values which records separately whether it has been used, so
+    /// that the handlers of one file can be told apart from those of others.
+    pub fn for_file(&self) -> Self {
+        Self {
+            entries: self.entries.dupe(),
+            used: Arc::default(),
+        }
+    }
+
+    /// Whether `vex.store()` has been called through this handle.
+    pub fn used(&self) -> bool {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn view(&self, scriptlet: Arc<str>) -> StoreView {
+        self.used.store(true, Ordering::Relaxed);
+        StoreView {
+            store: self.dupe(),
+            scriptlet,
+        }
+    }
+
+    fn with_entries<T>(&self, scriptlet: &Arc<str>, f: impl FnOnce(&mut Entries) -> T) -> T {
+        let mut store = self.entries.lock().expect("failed to lock shared store");
+        f(store.entry(scriptlet.dupe()).or_default())
+    }
+}
+
+/// The part of the shared store belonging to one scriptlet, as seen by Starlark.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct StoreView {
+    #[allocative(skip)]
+    store: SharedStore,
+
+    #[allocative(skip)]
+    scriptlet: Arc<str>,
+}
+starlark_simple_value!(StoreView);
+
+impl StoreView {
+    fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.store
+            .with_entries(&self.scriptlet, |entries| entries.get(key).cloned())
+    }
+
+    fn to_json(value: Value<'_>) -> anyhow::Result<serde_json::Value> {
+        value
+            .to_json_value()
+            .map_err(|err| anyhow::anyhow!("cannot store {}: {err}", value.get_type()))
+    }
+
+    fn unpack_key(key: Value<'_>) -> starlark::Result<&str> {
+        key.unpack_str().ok_or_else(|| {
+            ValueError::IncorrectParameterTypeWithExpected(
+                "str".to_owned(),
+                key.get_type().to_owned(),
+            )
+            .into()
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        fn get<'v>(
+            this: &StoreView,
+            #[starlark(require=pos)] key: &str,
+            #[starlark(require=pos, default=NoneOr::None)] default: NoneOr<Value<'v>>,
+            heap: &'v Heap,
+        ) -> anyhow::Result<Value<'v>> {
+            Ok(match this.get(key) {
+                Some(value) => heap.alloc(&value),
+                None => default.into_option().unwrap_or_else(Value::new_none),
+            })
+        }
+
+        fn append<'v>(
+            this: &StoreView,
+            #[starlark(require=pos)] key: &str,
+            #[starlark(require=pos)] value: Value<'v>,
+        ) -> anyhow::Result<NoneType> {
+            let value = StoreView::to_json(value)?;
+            this.store.with_entries(&this.scriptlet, |entries| {
+                match entries
+                    .entry(key.to_owned())
+                    .or_insert_with(|| serde_json::Value::Array(vec![]))
+                {
+                    serde_json::Value::Array(items) => {
+                        items.push(value);
+                        Ok(NoneType)
+                    }
+                    _ => Err(anyhow::anyhow!(
+                        "cannot append to '{key}' as it is not a list"
+                    )),
+                }
+            })
+        }
+
+        fn incr<'v>(
+            this: &StoreView,
+            #[starlark(require=pos)] key: &str,
+            #[starlark(require=pos, default=1)] by: i64,
+            heap: &'v Heap,
+        ) -> anyhow::Result<Value<'v>> {
+            this.store.with_entries(&this.scriptlet, |entries| {
+                let entry = entries
+                    .entry(key.to_owned())
+                    .or_insert_with(|| serde_json::Value::from(0));
+                let count = entry
+                    .as_i64()
+                    .ok_or_else(|| anyhow::anyhow!("cannot increment '{key}' as it is not an int"))?
+                    .checked_add(by)
+                    .ok_or_else(|| anyhow::anyhow!("cannot increment '{key}' without overflow"))?;
+                *entry = count.into();
+                Ok(heap.alloc(count))
+            })
+        }
+    }
+}
+
+#[starlark_value(type = "Store")]
+impl<'v> StarlarkValue<'v> for StoreView {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(Self::methods)
+    }
+
+    fn at(&self, index: Value<'v>, heap: &'v Heap) -> starlark::Result<Value<'v>> {
+        let key = Self::unpack_key(index)?;
+        match self.get(key) {
+            Some(value) => Ok(heap.alloc(&value)),
+            None => Err(ValueError::KeyNotFound(index.to_repr()).into()),
+        }
+    }
+
+    fn set_at(&self, index: Value<'v>, new_value: Value<'v>) -> starlark::Result<()> {
+        let key = Self::unpack_key(index)?;
+        let value = Self::to_json(new_value)?;
+        self.store.with_entries(&self.scriptlet, |entries| {
+            entries.insert(key.to_owned(), value);
+        });
+        Ok(())
+    }
+
+    fn is_in(&self, other: Value<'v>) -> starlark::Result<bool> {
+        let key = Self::unpack_key(other)?;
+        Ok(self
+            .store
+            .with_entries(&self.scriptlet, |entries| entries.contains_key(key)))
+    }
+
+    fn length(&self) -> starlark::Result<i32> {
+        Ok(self
+            .store
+            .with_entries(&self.scriptlet, |entries| entries.len()) as i32)
+    }
+}
+
+impl Display for StoreView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as StarlarkValue>::TYPE.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    #[test]
+    fn aggregation() {
+        let irritations = VexTest::new("aggregation")
+            .with_scriptlet(
+                "vexes/counter.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('close_file', on_close_file)
+                        vex.observe('close_project', on_close_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        store = vex.store()
+                        key = str(event.path)
+                        store[key] = store.get(key, 0) + 1
+
+                    def on_close_file(event):
+                        store = vex.store()
+                        count = store[str(event.path)]
+                        vex.store().append('counts', count)
+                        vex.warn('counter', 'found %d literals' % count, at=event.path)
+
+                    def on_close_project(event):
+                        store = vex.store()
+                        if 'missing' in store:
+                            fail('unexpected key')
+                        total = 0
+                        for count in store['counts']:
+                            total += count
+                        vex.warn('counter', 'found %d literals in total' % total)
+                "#},
+            )
+            .with_scriptlet(
+                "vexes/other.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('close_project', on_close_project)
+
+                    def on_close_project(event):
+                        if len(vex.store()) != 0:
+                            fail('store not scoped by scriptlet')
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { let x = 1 + 2; }")
+            .with_source_file("src/lib.rs", "const X: u32 = 3;")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let messages: Vec<_> = irritations.iter().map(|irr| irr.message()).collect();
+        assert_eq!(
+            messages,
+            [
+                "found 3 literals in total",
+                "found 1 literals",
+                "found 2 literals"
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_across_files() {
+        const NUM_FILES: usize = 64;
+        let irritations = (0..NUM_FILES)
+            .fold(
+                VexTest::new("counts-across-files").with_scriptlet(
+                    "vexes/counter.star",
+                    indoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+                            vex.observe('close_project', on_close_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @lit', on_match)
+
+                        def on_match(event):
+                            vex.store().incr('literals')
+                            vex.store().incr('digits', len(str(event.captures['lit'])))
+
+                        def on_close_project(event):
+                            store = vex.store()
+                            vex.warn('counter', 'found %d literals, %d digits' % (store['literals'], store['digits']))
+                    "#},
+                ),
+                |test, i| test.with_source_file(format!("src/file{i}.rs"), "const X: u32 = 10;"),
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(
+            irritations[0].message(),
+            format!("found {NUM_FILES} literals, {} digits", 2 * NUM_FILES)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        VexTest::new("unstorable")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.store()['callback'] = on_open_project
+                "#},
+            )
+            .returns_error("cannot store function");
+        VexTest::new("append-to-non-list")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        store = vex.store()
+                        store['count'] = 1
+                        store.append('count', 2)
+                "#},
+            )
+            .returns_error("cannot append to 'count' as it is not a list");
+        VexTest::new("increment-non-int")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        store = vex.store()
+                        store['count'] = 'one'
+                        store.incr('count')
+                "#},
+            )
+            .returns_error("cannot increment 'count' as it is not an int");
+    }
+}
@@ -8,9 +8,11 @@ use log::{info, log_enabled};
 use walkdir::WalkDir;
 
 use crate::{
-    error::{Error, IOAction},
+    context::Context,
+    error::{Error, IOAction, InvalidIDReason},
     result::Result,
     source_path::PrettyPath,
+    vex_id::VexId,
 };
 
 pub trait ScriptSource {
@@ -57,6 +59,42 @@ impl ScriptSource for FileSource {
     }
 }
 
+/// Find all scriptlets which run in the given project: those in its vexes directory and those in
+/// each rule pack it imports, whose load paths start with `@<pack>/`.
+pub fn sources_in_project(ctx: &Context) -> Result<Vec<FileSource>> {
+    let mut sources = sources_in_dir(&ctx.vex_dir())?;
+    for (pack, config) in &ctx.packs {
+        // Pack names prefix the ids of their checks.
+        let invalid_pack_name = |reason| Error::InvalidPackName {
+            name: pack.clone(),
+            reason,
+        };
+        if let Err(Error::InvalidID { reason, .. }) = VexId::try_from(pack.clone()) {
+            return Err(invalid_pack_name(reason));
+        }
+        if pack.contains(':') {
+            return Err(invalid_pack_name(InvalidIDReason::IllegalChar));
+        }
+        let pack_dir = ctx.project_root.join(&config.path);
+        sources.extend(sources_in_dir(&pack_dir)?.into_iter().map(|source| {
+            let load_path = Utf8Path::new(&format!("@{pack}")).join(&source.load_path);
+            FileSource::new(load_path, source.real_path)
+        }));
+    }
+    Ok(sources)
+}
+
+/// The rule pack which holds the scriptlet with the given load path, if any.
+pub fn pack_of(load_path: &Utf8Path) -> Option<&str> {
+    load_path
+        .components()
+        .next()?
+        .as_str()
+        .strip_prefix('@')
+        .filter(|pack| !pack.is_empty())
+}
+
+/// Find all scriptlets in the given vexes directory.
 pub fn sources_in_dir(dir_path: &Utf8Path) -> Result<Vec<FileSource>> {
     if !dir_path.is_dir() {
         return Err(Error::NoVexesDir(PrettyPath::new(dir_path)));
@@ -134,4 +172,49 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn packs() -> Result<()> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned())?;
+        let project_path = tempdir_path.join("project");
+        for path in [
+            "project/vexes/local.star",
+            "acme-vexes/style/long-functions.star",
+        ] {
+            let path = tempdir_path.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+
+        let find_sources = |pack: &str| {
+            let manifest =
+                format!("[vex]\nversion = '1'\n[packs.'{pack}']\npath = '../acme-vexes'");
+            fs::write(project_path.join("vex.toml"), manifest).unwrap();
+            sources_in_project(&Context::acquire(&project_path)?)
+        };
+
+        let sources = find_sources("acme")?;
+        let load_paths: Vec<_> = sources
+            .iter()
+            .map(|source| source.path().as_str())
+            .collect();
+        assert_eq!(
+            load_paths,
+            ["local.star", "@acme/style/long-functions.star"]
+        );
+        assert_eq!(pack_of(sources[0].path()), None);
+        assert_eq!(pack_of(sources[1].path()), Some("acme"));
+
+        for pack in ["a", "Acme", "acme:style"] {
+            let err = find_sources(pack).unwrap_err();
+            assert!(
+                err.to_string()
+                    .starts_with(&format!("invalid pack name '{pack}'")),
+                "unexpected error: {err}"
+            );
+        }
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     ops::Deref,
     sync::{Mutex, MutexGuard},
 };
@@ -9,6 +9,9 @@ use log::{info, log_enabled};
 use starlark::values::FrozenHeap;
 
 use crate::{
+    check_metadata::CheckMetadata,
+    check_selection::CheckSelection,
+    context::{ProjectInfo, ScriptletConfig, ScriptletNames},
     error::Error,
     result::Result,
     scriptlets::{
@@ -16,10 +19,13 @@ use crate::{
         source::ScriptSource,
         ObserverData,
     },
+    severity::SeverityOverrides,
     source_path::PrettyPath,
+    tags::CheckTags,
     verbosity::Verbosity,
 };
 
+/// Scriptlets which have been parsed but not yet run.
 #[derive(Debug)]
 pub struct PreinitingStore {
     store: Vec<PreinitingScriptlet>,
@@ -30,6 +36,12 @@ struct StoreIndex(usize);
 
 impl PreinitingStore {
     pub fn new<S: ScriptSource>(scripts: &[S]) -> Result<Self> {
+        Self::new_with_names(scripts, &ScriptletNames::default())
+    }
+
+    /// Parse the given scripts, naming them as configured in the manifest. Scriptlets other than
+    /// those loaded by others must have distinct names.
+    pub fn new_with_names<S: ScriptSource>(scripts: &[S], names: &ScriptletNames) -> Result<Self> {
         let store: Vec<_> = scripts
             .iter()
             .map(|source| Result::Ok((source.path(), source.content()?)))
@@ -41,12 +53,17 @@ impl PreinitingStore {
                 }
             })
             .flatten()
-            .map(|(path, content)| PreinitingScriptlet::new(path.to_owned(), content))
+            .map(|(path, content)| {
+                PreinitingScriptlet::new(path.to_owned(), names.name_of(path), content)
+            })
             .collect::<Result<_>>()?;
-        Ok(Self { store })
+        let store = Self { store };
+        store.check_name_conflicts()?;
+        Ok(store)
     }
 
     pub fn preinit(mut self, opts: PreinitOptions) -> Result<InitingStore> {
+        self.remove_disabled(&opts.severity_overrides, &opts.selection);
         self.store.sort_by(|sc1, sc2| sc1.path.cmp(&sc2.path));
         self.topographic_sort()?;
         let Self { store } = self;
@@ -59,7 +76,67 @@ impl PreinitingStore {
         }
 
         let store = partial_store.into_entry_modules().collect();
-        Ok(InitingStore { store, frozen_heap })
+        let PreinitOptions {
+            severity_overrides,
+            selection,
+            ..
+        } = opts;
+        Ok(InitingStore {
+            store,
+            severity_overrides,
+            selection,
+            frozen_heap,
+        })
+    }
+
+    fn check_name_conflicts(&self) -> Result<()> {
+        let loaded = self.loaded_paths();
+        let mut paths_by_name: BTreeMap<&str, &Utf8Path> = BTreeMap::new();
+        for script in &self.store {
+            if loaded.contains(&script.path) {
+                continue;
+            }
+            if let Some(other_path) = paths_by_name.insert(&script.name, &script.path) {
+                let (first, second) = if other_path < script.path {
+                    (other_path, script.path.as_path())
+                } else {
+                    (script.path.as_path(), other_path)
+                };
+                return Err(Error::ScriptletNameConflict {
+                    name: script.name.clone(),
+                    first: PrettyPath::new(first),
+                    second: PrettyPath::new(second),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths of the scriptlets which are loaded by others.
+    fn loaded_paths(&self) -> HashSet<Utf8PathBuf> {
+        self.store
+            .iter()
+            .flat_map(|script| script.loads().values().map(|load| load.path().to_owned()))
+            .collect()
+    }
+
+    /// Drop scriptlets named after a disabled or unselected check, unless another scriptlet loads
+    /// them.
+    fn remove_disabled(
+        &mut self,
+        severity_overrides: &SeverityOverrides,
+        selection: &CheckSelection,
+    ) {
+        let loaded = self.loaded_paths();
+        self.store.retain(|script| {
+            let disabled =
+                severity_overrides.is_disabled(&script.name) || !selection.selects(&script.name);
+            if disabled && !loaded.contains(&script.path) {
+                info!("skipping disabled scriptlet {}", script.path);
+                return false;
+            }
+            true
+        });
     }
 
     /// Topographically order the store
@@ -208,6 +285,9 @@ impl PreinitingStore {
 #[derive(Debug, Default)]
 pub struct PreinitOptions {
     pub lenient: bool,
+    pub project: ProjectInfo,
+    pub severity_overrides: SeverityOverrides,
+    pub selection: CheckSelection,
     pub verbosity: Verbosity,
 }
 
@@ -239,26 +319,46 @@ impl PreinitedModuleStore {
 #[derive(Debug)]
 pub struct InitingStore {
     store: Vec<InitingScriptlet>,
+    severity_overrides: SeverityOverrides,
+    selection: CheckSelection,
     frozen_heap: FrozenHeap,
 }
 
 impl InitingStore {
     pub fn init(self, opts: InitOptions) -> Result<VexingStore> {
-        let Self { store, frozen_heap } = self;
+        let Self {
+            store,
+            severity_overrides,
+            selection,
+            frozen_heap,
+        } = self;
         let num_scripts = store.len();
 
-        let observer_data = store.into_iter().try_fold(
-            ObserverData::with_capacity(4 * num_scripts),
-            |mut data, scriptlet| {
-                data.extend(scriptlet.init(&opts, &frozen_heap)?);
-                Result::Ok(data)
+        let (observer_data, check_tags, check_metadata) = store.into_iter().try_fold(
+            (
+                ObserverData::with_capacity(4 * num_scripts),
+                CheckTags::default(),
+                CheckMetadata::default(),
+            ),
+            |(mut observer_data, mut check_tags, mut check_metadata), scriptlet| {
+                let (scriptlet_observer_data, scriptlet_check_tags, scriptlet_check_metadata) =
+                    scriptlet.init(&opts, &frozen_heap)?;
+                observer_data.extend(scriptlet_observer_data);
+                check_tags.extend(scriptlet_check_tags);
+                check_metadata.extend(scriptlet_check_metadata);
+                Result::Ok((observer_data, check_tags, check_metadata))
             },
         )?;
+        check_metadata.verify_codes()?;
 
         let frozen_heap = Mutex::new(frozen_heap);
         Ok(VexingStore {
             num_scripts,
             observer_data,
+            check_tags,
+            check_metadata,
+            severity_overrides,
+            selection,
             frozen_heap,
         })
     }
@@ -267,12 +367,18 @@ impl InitingStore {
 #[derive(Debug, Default)]
 pub struct InitOptions {
     pub verbosity: Verbosity,
+    pub config: ScriptletConfig,
 }
 
+/// Initialised scriptlets, ready to observe the events of a scan.
 #[derive(Debug)]
 pub struct VexingStore {
     num_scripts: usize,
     observer_data: ObserverData,
+    check_tags: CheckTags,
+    check_metadata: CheckMetadata,
+    severity_overrides: SeverityOverrides,
+    selection: CheckSelection,
     frozen_heap: Mutex<FrozenHeap>,
 }
 
@@ -281,6 +387,24 @@ impl VexingStore {
         self.frozen_heap.lock().expect("frozen heap lock poisoned")
     }
 
+    pub fn check_tags(&self) -> &CheckTags {
+        &self.check_tags
+    }
+
+    pub fn check_metadata(&self) -> &CheckMetadata {
+        &self.check_metadata
+    }
+
+    /// The severities which override those declared by checks, as used for external checks.
+    pub fn severity_overrides(&self) -> &SeverityOverrides {
+        &self.severity_overrides
+    }
+
+    /// The checks which were selected to run, as used for external checks.
+    pub fn selection(&self) -> &CheckSelection {
+        &self.selection
+    }
+
     pub fn project_queries_hint(&self) -> usize {
         // Heuristic: expect scriptlets to declare on average at most this many queries during the
         // `open_project` event.
@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
+use allocative::Allocative;
+use annotate_snippets::AnnotationType;
+use clap::ValueEnum;
+use dupe::Dupe;
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use strum::EnumIs;
+
+use crate::vex_id::VexId;
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Dupe,
+    EnumIs,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Allocative,
+    Deserialise,
+    Serialise,
+    ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    #[value(alias = "info")]
+    Advice,
+
+    #[default]
+    Warning,
+
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Advice => "advice",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn annotation_type(&self) -> AnnotationType {
+        match self {
+            Self::Advice => AnnotationType::Help,
+            Self::Warning => AnnotationType::Warning,
+            Self::Error => AnnotationType::Error,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name().fmt(f)
+    }
+}
+
+/// How a check is configured in the `[checks]` table of the manifest.
+#[derive(Copy, Clone, Debug, Dupe, PartialEq, Eq, Deserialise, Serialise)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckLevel {
+    Off,
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl CheckLevel {
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            Self::Off => None,
+            Self::Allow => Some(Severity::Advice),
+            Self::Warn => Some(Severity::Warning),
+            Self::Deny => Some(Severity::Error),
+        }
+    }
+}
+
+/// Per-vex severity overrides, as requested on the command-line or in the manifest.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Allocative)]
+pub struct SeverityOverrides {
+    severities: HashMap<String, Severity>,
+    disabled: HashSet<String>,
+}
+
+impl SeverityOverrides {
+    /// Build overrides from the ids passed to `--allow`, `--warn` and `--deny`. Where an id is
+    /// given more than once, the strictest severity wins.
+    pub fn new<'a>(
+        allow: impl IntoIterator<Item = &'a String>,
+        warn: impl IntoIterator<Item = &'a String>,
+        deny: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        let mut overrides = HashMap::new();
+        for id in allow {
+            overrides.insert(id.clone(), Severity::Advice);
+        }
+        for id in warn {
+            overrides.insert(id.clone(), Severity::Warning);
+        }
+        for id in deny {
+            overrides.insert(id.clone(), Severity::Error);
+        }
+        Self {
+            severities: overrides,
+            disabled: HashSet::new(),
+        }
+    }
+
+    /// Add the levels configured in the manifest. Ids given on the command-line take precedence.
+    pub fn with_configured<'a>(
+        mut self,
+        levels: impl IntoIterator<Item = (&'a str, CheckLevel)>,
+    ) -> Self {
+        for (id, level) in levels {
+            if self.severities.contains_key(id) {
+                continue;
+            }
+            match level.severity() {
+                Some(severity) => {
+                    self.severities.insert(id.to_owned(), severity);
+                }
+                None => {
+                    self.disabled.insert(id.to_owned());
+                }
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, vex_id: &VexId) -> Option<Severity> {
+        self.severities.get(vex_id.as_ref()).copied()
+    }
+
+    /// Whether problems with the given id should be discarded.
+    pub fn is_disabled(&self, vex_id: &str) -> bool {
+        self.disabled.contains(vex_id)
+    }
+}
+
+impl Hash for SeverityOverrides {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Sorted so that equal overrides hash equally.
+        self.severities
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .hash(state);
+        self.disabled.iter().collect::<BTreeSet<_>>().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn order() {
+        assert!(Severity::Advice < Severity::Warning);
+        assert!(Severity::Warning < Severity::Error);
+    }
+
+    #[test]
+    fn overrides() {
+        let ids = |ids: &[&str]| ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let overrides = SeverityOverrides::new(
+            &ids(&["allowed", "allowed-then-denied"]),
+            &ids(&["warned"]),
+            &ids(&["denied", "allowed-then-denied"]),
+        );
+        let get = |id: &str| overrides.get(&VexId::try_from(id.to_string()).unwrap());
+        assert_eq!(get("allowed"), Some(Severity::Advice));
+        assert_eq!(get("warned"), Some(Severity::Warning));
+        assert_eq!(get("denied"), Some(Severity::Error));
+        assert_eq!(get("allowed-then-denied"), Some(Severity::Error));
+        assert_eq!(get("unmentioned"), None);
+    }
+
+    #[test]
+    fn configured() {
+        let overrides = SeverityOverrides::new(&["loud".to_string()], [], []).with_configured([
+            ("loud", CheckLevel::Off),
+            ("quiet", CheckLevel::Off),
+            ("strict", CheckLevel::Deny),
+        ]);
+        let get = |id: &str| overrides.get(&VexId::try_from(id.to_string()).unwrap());
+        assert_eq!(get("loud"), Some(Severity::Advice));
+        assert!(!overrides.is_disabled("loud"));
+        assert_eq!(get("quiet"), None);
+        assert!(overrides.is_disabled("quiet"));
+        assert_eq!(get("strict"), Some(Severity::Error));
+        assert!(!overrides.is_disabled("strict"));
+    }
+}
@@ -1,15 +1,21 @@
-use std::{fs, ops::Range};
+use std::{
+    collections::HashSet,
+    fs::{self, Metadata},
+    io,
+    ops::Range,
+    time::SystemTime,
+};
 
 use allocative::Allocative;
 use camino::{Utf8Path, Utf8PathBuf};
 use dupe::Dupe;
 use log::{info, log_enabled};
-use tree_sitter::{Node as TSNode, Parser, QueryCursor, Tree};
-use walkdir::WalkDir;
+use tree_sitter::{Node as TSNode, Parser, QueryCursor, Range as TSRange, Tree};
+use walkdir::{DirEntry, WalkDir};
 
 use crate::{
     cli::MaxConcurrentFileLimit,
-    context::{Context, Manifest},
+    context::{Context, HiddenPolicy, Manifest, NonUtf8Policy, ScanMode},
     error::{Error, IOAction},
     ignore_markers::{IgnoreMarkers, VexIdFilter},
     result::{RecoverableResult, Result},
@@ -22,28 +28,21 @@ use crate::{
 pub fn sources_in_dir(
     ctx: &Context,
     max_concurrent_files: MaxConcurrentFileLimit,
+    follow_symlinks: bool,
 ) -> Result<Vec<SourceFile>> {
-    let ignores: Vec<_> = ctx
-        .files
-        .ignores
-        .clone()
-        .into_inner()
-        .into_iter()
-        .map(|ignore| ignore.compile())
-        .collect::<Result<_>>()?;
-    let allows: Vec<_> = ctx
-        .files
-        .allows
-        .clone()
-        .into_iter()
-        .map(|allow| allow.compile())
-        .collect::<Result<_>>()?;
+    let (ignores, allows) = ignores_and_allows(ctx)?;
     let associations = ctx.associations()?;
 
+    let follow_symlinks = follow_symlinks || ctx.files.follow_symlinks;
+    let decode_lossily = ctx.files.non_utf8 == NonUtf8Policy::Lossy;
+    let skip_hidden = ctx.files.hidden == HiddenPolicy::Skip;
+    let allows_hidden = ctx.files.allows_hidden();
+    let mut visited_dirs = HashSet::new();
+
     let root = ctx.project_root.as_str();
 
     WalkDir::new(root)
-        .follow_links(false)
+        .follow_links(follow_symlinks)
         .follow_root_links(false)
         .max_open(max_concurrent_files.into())
         .into_iter()
@@ -58,7 +57,7 @@ pub fn sources_in_dir(
             let is_hidden = entry_path
                 .file_name()
                 .is_some_and(|file_name| file_name.starts_with('.'));
-            if is_hidden && !is_root {
+            if is_hidden && !is_root && skip_hidden && !allows_hidden {
                 if log_enabled!(log::Level::Info) {
                     let dir_marker = if entry.file_type().is_dir() { "/" } else { "" };
                     info!("ignoring {entry_path}{dir_marker}: hidden");
@@ -66,9 +65,6 @@ pub fn sources_in_dir(
                 return false;
             }
 
-            let matches_any = |path, patterns: &[FilePattern]| {
-                patterns.iter().any(|pattern| pattern.matches(path))
-            };
             if matches_any(entry_path, &ignores) && !matches_any(entry_path, &allows) {
                 if log_enabled!(log::Level::Info) {
                     let dir_marker = if entry.file_type().is_dir() { "/" } else { "" };
@@ -93,29 +89,172 @@ pub fn sources_in_dir(
                 }
                 return false;
             }
+
+            if follow_symlinks && entry.file_type().is_dir() {
+                if let Some(dir_id) = dir_id(entry) {
+                    if !visited_dirs.insert(dir_id) {
+                        if log_enabled!(log::Level::Info) {
+                            info!(
+                                "ignoring {}/: already visited through another link",
+                                entry_path.strip_prefix(root).unwrap_or(entry_path),
+                            );
+                        }
+                        return false;
+                    }
+                }
+            }
             true
         })
         .flatten()
         .filter(|entry| entry.file_type().is_file())
         .flat_map(|entry| Utf8PathBuf::from_path_buf(entry.path().to_owned()))
+        .filter(|entry_path| {
+            if skip_hidden
+                && allows_hidden
+                && is_hidden(entry_path.strip_prefix(root).unwrap_or(entry_path))
+                && !matches_any(entry_path, &allows)
+            {
+                if log_enabled!(log::Level::Info) {
+                    info!(
+                        "ignoring {}: hidden",
+                        entry_path.strip_prefix(root).unwrap_or(entry_path),
+                    );
+                }
+                return false;
+            }
+            if ctx.files.scan == ScanMode::All || matches_any(entry_path, &allows) {
+                return true;
+            }
+            if log_enabled!(log::Level::Info) {
+                info!(
+                    "ignoring {}: matches no allow pattern",
+                    entry_path.strip_prefix(root).unwrap_or(entry_path),
+                );
+            }
+            false
+        })
         .map(|entry_path| SourcePath::new(&entry_path, &ctx.project_root))
         .map(|source_path| {
-            let language = associations.get_language(&source_path)?;
-            Ok(SourceFile::new(source_path, language))
+            let language = associations.detect_language(&source_path)?;
+            Ok(SourceFile::new(source_path, language).with_lossy_decoding(decode_lossily))
         })
         .collect()
 }
 
+/// Whether a walk of the project would reach the file at the given path, which need not exist.
+pub fn is_scanned(ctx: &Context, path: &Utf8Path) -> Result<bool> {
+    let Ok(rel_path) = path.strip_prefix(ctx.project_root.as_str()) else {
+        return Ok(false);
+    };
+    let (ignores, allows) = ignores_and_allows(ctx)?;
+    let skip_hidden = ctx.files.hidden == HiddenPolicy::Skip
+        && !(ctx.files.allows_hidden() && matches_any(path, &allows));
+
+    let num_components = rel_path.components().count();
+    let mut entry_path = ctx.project_root.to_path_buf();
+    for (index, component) in rel_path.components().enumerate() {
+        entry_path.push(component);
+        let is_file = index + 1 == num_components;
+        let reason = if skip_hidden && component.as_str().starts_with('.') {
+            "hidden"
+        } else if matches_any(&entry_path, &ignores) && !matches_any(&entry_path, &allows) {
+            "matches ignore pattern"
+        } else if !is_file && entry_path.join(Manifest::FILE_NAME).exists() {
+            "contains vex project"
+        } else {
+            continue;
+        };
+        if log_enabled!(log::Level::Info) {
+            let dir_marker = if is_file { "" } else { "/" };
+            info!(
+                "ignoring {}{dir_marker}: {reason}",
+                entry_path
+                    .strip_prefix(ctx.project_root.as_str())
+                    .unwrap_or(&entry_path),
+            );
+        }
+        return Ok(false);
+    }
+
+    if ctx.files.scan == ScanMode::All || matches_any(path, &allows) {
+        return Ok(true);
+    }
+    if log_enabled!(log::Level::Info) {
+        info!("ignoring {rel_path}: matches no allow pattern");
+    }
+    Ok(false)
+}
+
+fn ignores_and_allows(ctx: &Context) -> Result<(Vec<FilePattern>, Vec<FilePattern>)> {
+    let case_sensitivity = ctx.files.case_sensitivity();
+    let ignores = ctx
+        .files
+        .ignores
+        .clone()
+        .into_inner()
+        .into_iter()
+        .map(|ignore| ignore.compile(case_sensitivity))
+        .collect::<Result<_>>()?;
+    let allows = ctx
+        .files
+        .allows
+        .clone()
+        .into_iter()
+        .map(|allow| allow.compile(case_sensitivity))
+        .collect::<Result<_>>()?;
+    Ok((ignores, allows))
+}
+
+/// Whether any component of the given relative path is hidden.
+fn is_hidden(rel_path: &Utf8Path) -> bool {
+    rel_path
+        .components()
+        .any(|component| component.as_str().starts_with('.'))
+}
+
+fn matches_any(path: &Utf8Path, patterns: &[FilePattern]) -> bool {
+    patterns.iter().any(|pattern| pattern.matches(path))
+}
+
+/// Identifies a directory however it is reached, so that following symlinks never walks the same
+/// directory twice.
+#[cfg(unix)]
+fn dir_id(entry: &DirEntry) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = entry.metadata().ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(entry: &DirEntry) -> Option<std::path::PathBuf> {
+    fs::canonicalize(entry.path()).ok()
+}
+
 #[derive(Debug)]
 pub struct SourceFile {
     path: SourcePath,
     language: Option<SupportedLanguage>,
+    decode_lossily: bool,
 }
 
 impl SourceFile {
+    /// How much of a file is searched for null bytes to tell whether it is binary.
+    const BINARY_SNIFF_LEN: usize = 8000;
+
     pub fn new(path: SourcePath, language: Option<SupportedLanguage>) -> Self {
         let path = path.dupe();
-        Self { path, language }
+        Self {
+            path,
+            language,
+            decode_lossily: false,
+        }
+    }
+
+    /// Replace invalid UTF-8 in this file's content rather than failing to read it.
+    pub fn with_lossy_decoding(mut self, decode_lossily: bool) -> Self {
+        self.decode_lossily = decode_lossily;
+        self
     }
 
     pub fn path(&self) -> &SourcePath {
@@ -126,16 +265,67 @@ impl SourceFile {
         self.language
     }
 
+    /// Read this file, ensuring that it was not modified while being read so that locations in
+    /// problems match the content which was scanned.
+    pub fn read(&self) -> Result<String> {
+        let abs_path = self.path.abs_path.as_str();
+        let content = self.read_unchanging(
+            || fs::metadata(abs_path).map(|metadata| FileStamp::of(&metadata)),
+            || fs::read(abs_path),
+        )?;
+        self.decode(content)
+    }
+
+    /// Interpret raw file content as text, rejecting binary files.
+    pub fn decode(&self, content: Vec<u8>) -> Result<String> {
+        let sniff_len = content.len().min(Self::BINARY_SNIFF_LEN);
+        if content[..sniff_len].contains(&0) {
+            return Err(Error::BinaryFile(self.path.pretty_path.dupe()));
+        }
+        match String::from_utf8(content) {
+            Ok(content) => Ok(content),
+            Err(err) if self.decode_lossily => {
+                Ok(String::from_utf8_lossy(err.as_bytes()).into_owned())
+            }
+            Err(_) => Err(Error::NotUtf8(self.path.pretty_path.dupe())),
+        }
+    }
+
+    /// Read using the given functions, retrying once if the file changes during the read.
+    fn read_unchanging(
+        &self,
+        mut stamp: impl FnMut() -> io::Result<FileStamp>,
+        mut read: impl FnMut() -> io::Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let io_error = |cause| Error::IO {
+            path: self.path.pretty_path.dupe(),
+            action: IOAction::Read,
+            cause,
+        };
+        const MAX_ATTEMPTS: usize = 2;
+        for _ in 0..MAX_ATTEMPTS {
+            let before = stamp().map_err(io_error)?;
+            let content = read().map_err(io_error)?;
+            let after = stamp().map_err(io_error)?;
+            if before == after && after.len == content.len() as u64 {
+                return Ok(content);
+            }
+            if log_enabled!(log::Level::Info) {
+                info!("{} changed while being read", self.path);
+            }
+        }
+        Err(Error::FileChanging(self.path.pretty_path.dupe()))
+    }
+
     pub fn parse(&self) -> Result<ParsedSourceFile> {
+        let content = self.read()?;
+        self.parse_content(content)
+    }
+
+    pub fn parse_content(&self, content: String) -> Result<ParsedSourceFile> {
         if log_enabled!(log::Level::Info) {
             info!("parsing {}", self.path);
         }
-        let content =
-            fs::read_to_string(self.path.abs_path.as_str()).map_err(|cause| Error::IO {
-                path: self.path.pretty_path.dupe(),
-                action: IOAction::Read,
-                cause,
-            })?;
         let Some(language) = self.language else {
             return Err(Error::NoKnownLanguage(self.path.pretty_path.dupe()));
         };
@@ -143,6 +333,22 @@ impl SourceFile {
     }
 }
 
+/// The properties of a file which change when it is written to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn of(metadata: &Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Allocative)]
 pub struct ParsedSourceFile {
     pub path: SourcePath,
@@ -150,6 +356,9 @@ pub struct ParsedSourceFile {
     pub language: SupportedLanguage,
     #[allocative(skip)]
     pub tree: Tree,
+
+    /// Whether this was parsed from text given by a scriptlet, rather than read from a file.
+    pub from_text: bool,
 }
 
 impl ParsedSourceFile {
@@ -158,11 +367,32 @@ impl ParsedSourceFile {
         content: impl Into<String>,
         language: SupportedLanguage,
     ) -> Result<Self> {
-        let content = content.into();
+        Self::new_in_ranges(path, content.into(), language, &[])
+    }
+
+    /// Parse the given range of a file as a language embedded in it. Nodes keep their positions
+    /// in the whole file.
+    pub fn new_injected(
+        host: &ParsedSourceFile,
+        range: TSRange,
+        language: SupportedLanguage,
+    ) -> Result<Self> {
+        Self::new_in_ranges(host.path.dupe(), host.content.clone(), language, &[range])
+    }
 
+    /// Parse only the given ranges of the content, or all of it if none are given.
+    fn new_in_ranges(
+        path: SourcePath,
+        content: String,
+        language: SupportedLanguage,
+        ranges: &[TSRange],
+    ) -> Result<Self> {
         let tree = {
             let mut parser = Parser::new();
-            parser.set_language(language.ts_language())?;
+            parser.set_language(language.ts_language()?)?;
+            parser
+                .set_included_ranges(ranges)
+                .expect("internal error: injected ranges overlap");
             let tree = parser
                 .parse(&content, None)
                 .expect("unexpected parser failure");
@@ -179,10 +409,14 @@ impl ParsedSourceFile {
                         break Some(curr_node);
                     }
 
-                    if curr_node.has_error() {
-                        assert!(cursor.goto_first_child());
+                    let moved = if curr_node.has_error() {
+                        cursor.goto_first_child()
                     } else {
-                        assert!(cursor.goto_next_sibling());
+                        cursor.goto_next_sibling()
+                    };
+                    if !moved {
+                        // Some grammars mark a node as erroneous without an error child.
+                        break Some(curr_node);
                     }
                 }
             }
@@ -200,13 +434,25 @@ impl ParsedSourceFile {
             content,
             tree,
             language,
+            from_text: false,
+        })
+    }
+
+    /// Parse text which did not come from a file, such as a string embedded in another language.
+    pub fn new_from_text(content: impl Into<String>, language: SupportedLanguage) -> Result<Self> {
+        let path = SourcePath::new_in(Utf8Path::new("<text>"), Utf8Path::new(""));
+        Ok(Self {
+            from_text: true,
+            ..Self::new_with_content(path, content, language)?
         })
     }
 
     pub fn ignore_markers(&self) -> Result<IgnoreMarkers> {
         let mut builder = IgnoreMarkers::builder();
 
-        let ignore_query = self.language.ignore_query();
+        let Some(ignore_query) = self.language.ignore_query() else {
+            return Ok(builder.build());
+        };
         let marker_index = ignore_query
             .capture_index_for_name("marker")
             .expect("internal error: ignore query contains no 'marker' capture")
@@ -218,7 +464,7 @@ impl ParsedSourceFile {
                 debug_assert!(!qcaps.is_empty());
                 if qcaps.len() == 1 {
                     let marker_node = qcaps[0].node;
-                    crate::warn!(
+                    log::warn!(
                         "{}:{} ignore marker not associated with any block",
                         self.path.pretty_path,
                         Location::of(&Node::new(marker_node, self)),
@@ -255,7 +501,7 @@ impl ParsedSourceFile {
                         RecoverableResult::Ok(filter) => filter,
                         RecoverableResult::Recovered(filter, errs) => {
                             for err in errs {
-                                crate::warn!(
+                                log::warn!(
                                     "{}:{}: {}",
                                     self.path,
                                     Location::of(&Node::new(node, self)),
@@ -267,7 +513,7 @@ impl ParsedSourceFile {
                         RecoverableResult::Err(err) => return Err(err),
                     };
                     if filter.is_empty() {
-                        crate::warn!(
+                        log::warn!(
                             "{}:{}: no vex ids specified",
                             self.path,
                             Location::of(&Node::new(node, self)),
@@ -319,6 +565,75 @@ mod test {
 
     use super::{sources_in_dir, *};
 
+    #[test]
+    fn read_unchanging() {
+        let file = SourceFile::new(SourcePath::new_in("main.rs".into(), "".into()), None);
+        let stamp = |len| FileStamp {
+            len,
+            modified: None,
+        };
+
+        let content = file
+            .read_unchanging(|| Ok(stamp(7)), || Ok("content".into()))
+            .unwrap();
+        assert_eq!(content, b"content");
+
+        // A file which changes once is read again.
+        let mut stamps = [stamp(3), stamp(7), stamp(7), stamp(7)].into_iter();
+        let mut contents = ["old", "content"].into_iter();
+        let content = file
+            .read_unchanging(
+                || Ok(stamps.next().unwrap()),
+                || Ok(contents.next().unwrap().into()),
+            )
+            .unwrap();
+        assert_eq!(content, b"content");
+
+        // A file which keeps changing is given up on.
+        let mut len = 0;
+        let err = file
+            .read_unchanging(
+                || {
+                    len += 1;
+                    Ok(stamp(len))
+                },
+                || Ok("content".into()),
+            )
+            .unwrap_err();
+        assert_eq!(err.to_string(), "main.rs kept changing while being read");
+    }
+
+    #[test]
+    fn decode() {
+        let file = SourceFile::new(SourcePath::new_in("main.rs".into(), "".into()), None);
+        assert_eq!(
+            file.decode(b"fn main() {}".to_vec()).unwrap(),
+            "fn main() {}"
+        );
+        assert_eq!(
+            file.decode(b"// caf\xe9".to_vec()).unwrap_err().to_string(),
+            "main.rs is not valid UTF-8"
+        );
+        assert_eq!(
+            file.decode(b"\x7fELF\x00\x01".to_vec())
+                .unwrap_err()
+                .to_string(),
+            "main.rs appears to be binary"
+        );
+
+        let file = file.with_lossy_decoding(true);
+        assert_eq!(
+            file.decode(b"// caf\xe9".to_vec()).unwrap(),
+            "// caf\u{fffd}"
+        );
+        assert_eq!(
+            file.decode(b"\x7fELF\x00\x01".to_vec())
+                .unwrap_err()
+                .to_string(),
+            "main.rs appears to be binary"
+        );
+    }
+
     #[test]
     fn directory_walking() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -348,8 +663,8 @@ mod test {
                 .unwrap();
         }
 
-        let ctx = Context::acquire_in(&tempdir_path).unwrap();
-        let sources = sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1)).unwrap();
+        let ctx = Context::acquire(&tempdir_path).unwrap();
+        let sources = sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1), false).unwrap();
         let returned_paths = {
             let mut returned_paths: Vec<_> = sources
                 .iter()
@@ -363,6 +678,158 @@ mod test {
         assert_eq!(returned_paths, expected_paths);
     }
 
+    #[test]
+    fn allowlist() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+
+        let files = [
+            (
+                "vex.toml",
+                indoc! {r#"
+                    [vex]
+                    version = "1"
+
+                    [files]
+                    scan = "allowlist"
+                    allow = [ "services/billing/", "libs/*.rs" ]
+                "#},
+            ),
+            ("services/billing/main.rs", ""),
+            ("libs/README.md", ""),
+            ("services/shipping/main.rs", ""),
+            ("libs/util.rs", ""),
+            ("libs/nested/util.rs", ""),
+            ("main.rs", ""),
+        ];
+        for (path, content) in files {
+            let abs_path = tempdir_path.join(path);
+            fs::create_dir_all(abs_path.parent().unwrap()).unwrap();
+            fs::write(abs_path, content).unwrap();
+        }
+
+        let ctx = Context::acquire(&tempdir_path).unwrap();
+        let sources = sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1), false).unwrap();
+        let mut returned_paths: Vec<_> = sources
+            .iter()
+            .map(|source_file| source_file.path().pretty_path.as_str())
+            .collect();
+        returned_paths.sort();
+        assert_eq!(
+            returned_paths,
+            [
+                "libs/nested/util.rs",
+                "libs/util.rs",
+                "services/billing/main.rs",
+            ]
+        );
+    }
+
+    #[test]
+    fn hidden_paths() {
+        let walk = |files: &str| {
+            let tempdir = tempfile::tempdir().unwrap();
+            let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+            let manifest = format!("[vex]\nversion = '1'\n[files]\n{files}");
+            let files = [
+                ("vex.toml", manifest.as_str()),
+                ("main.rs", ""),
+                (".env", ""),
+                (".github/CODEOWNERS", ""),
+                (".github/workflows/ci.yml", ""),
+                (".venv/lib/site.py", ""),
+            ];
+            for (path, content) in files {
+                let abs_path = tempdir_path.join(path);
+                fs::create_dir_all(abs_path.parent().unwrap()).unwrap();
+                fs::write(abs_path, content).unwrap();
+            }
+
+            let ctx = Context::acquire(&tempdir_path).unwrap();
+            let sources = sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1), false).unwrap();
+            let mut returned_paths: Vec<_> = sources
+                .iter()
+                .map(|source_file| source_file.path().pretty_path.to_string())
+                .collect();
+            returned_paths.sort();
+            for path in [".github/workflows/ci.yml", ".venv/lib/site.py", "main.rs"] {
+                let is_scanned = is_scanned(&ctx, &tempdir_path.join(path)).unwrap();
+                assert_eq!(is_scanned, returned_paths.iter().any(|p| p == path));
+            }
+            returned_paths
+        };
+
+        assert_eq!(walk(""), ["main.rs"]);
+        assert_eq!(
+            walk("allow = ['.github/workflows/']"),
+            [".github/workflows/ci.yml", "main.rs"]
+        );
+        assert_eq!(walk("allow = ['*.yml']"), ["main.rs"]);
+        assert_eq!(
+            walk("hidden = 'scan'"),
+            [
+                ".env",
+                ".github/CODEOWNERS",
+                ".github/workflows/ci.yml",
+                ".venv/lib/site.py",
+                "main.rs",
+            ]
+        );
+        assert_eq!(
+            walk("hidden = 'scan'\nignore = ['vex.toml', '.venv/']"),
+            [
+                ".env",
+                ".github/CODEOWNERS",
+                ".github/workflows/ci.yml",
+                "main.rs",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlinks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        let vendor_dir = tempfile::tempdir().unwrap();
+        let vendor_path = Utf8PathBuf::try_from(vendor_dir.path().to_owned()).unwrap();
+
+        fs::write(
+            tempdir_path.join("vex.toml"),
+            indoc! {r#"
+                [vex]
+                version = "1"
+            "#},
+        )
+        .unwrap();
+        fs::create_dir_all(tempdir_path.join("src")).unwrap();
+        fs::write(tempdir_path.join("src/main.rs"), "").unwrap();
+        fs::write(vendor_path.join("lib.rs"), "").unwrap();
+        std::os::unix::fs::symlink(&vendor_path, tempdir_path.join("vendored")).unwrap();
+        std::os::unix::fs::symlink(&vendor_path, tempdir_path.join("vendored-again")).unwrap();
+        std::os::unix::fs::symlink(&tempdir_path, tempdir_path.join("src/cycle")).unwrap();
+
+        let ctx = Context::acquire(&tempdir_path).unwrap();
+        let paths = |follow_symlinks| {
+            let mut paths: Vec<_> =
+                sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1), follow_symlinks)
+                    .unwrap()
+                    .iter()
+                    .map(|source_file| source_file.path().pretty_path.to_string())
+                    .collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths(false), ["src/main.rs"]);
+        let followed = paths(true);
+        assert_eq!(followed.len(), 2, "{followed:?}");
+        assert_eq!(followed[0], "src/main.rs");
+        assert!(
+            ["vendored/lib.rs", "vendored-again/lib.rs"].contains(&followed[1].as_str()),
+            "{followed:?}"
+        );
+    }
+
     #[test]
     fn general_ignore_markers() {
         let source_file = ParsedSourceFile::new_with_content(
@@ -1,12 +1,18 @@
-use std::{fmt::Display, ops::Deref, sync::Arc};
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::Arc,
+};
 
 #[cfg(target_os = "windows")]
 use std::path;
 
 use allocative::Allocative;
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use dupe::{Dupe, OptionDupedExt};
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use starlark::{
     environment::{Methods, MethodsBuilder, MethodsStatic},
     starlark_module, starlark_simple_value,
@@ -14,6 +20,8 @@ use starlark::{
 };
 use starlark_derive::{starlark_value, ProvidesStaticType};
 
+use crate::trigger::{CaseSensitivity, RawFilePattern};
+
 #[derive(Clone, Debug, PartialEq, Eq, Dupe, Allocative)]
 pub struct SourcePath {
     #[allocative(skip)]
@@ -51,19 +59,7 @@ impl Display for SourcePath {
     }
 }
 
-#[derive(
-    Clone,
-    Debug,
-    Dupe,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
-    Allocative,
-    Serialize,
-    ProvidesStaticType,
-)]
+#[derive(Clone, Debug, Dupe, Allocative, ProvidesStaticType)]
 pub struct PrettyPath {
     #[allocative(skip)]
     path: Arc<Utf8Path>,
@@ -94,16 +90,53 @@ impl PrettyPath {
         self.path.components().count()
     }
 
+    const NAME_ATTR_NAME: &'static str = "name";
+    const EXTENSION_ATTR_NAME: &'static str = "extension";
+    const PARTS_ATTR_NAME: &'static str = "parts";
+    const ATTR_NAMES: [&'static str; 3] = [
+        Self::NAME_ATTR_NAME,
+        Self::EXTENSION_ATTR_NAME,
+        Self::PARTS_ATTR_NAME,
+    ];
+
     #[starlark_module]
     fn methods(builder: &mut MethodsBuilder) {
-        fn matches<'v>(this: &PrettyPath, other: Value<'v>) -> starlark::Result<bool> {
+        /// Whether this path is the given path, or matches the given glob as written in the
+        /// manifest.
+        fn matches<'v>(this: &PrettyPath, other: Value<'v>) -> anyhow::Result<bool> {
             if let Some(other) = other.request_value::<&PrettyPath>() {
                 return Ok(this == other);
             }
 
-            Ok(other
-                .unpack_str()
-                .is_some_and(|other| this.as_str() == other))
+            let Some(other) = other.unpack_str().filter(|other| !other.is_empty()) else {
+                return Ok(false);
+            };
+            // Globs which start with `/` are relative to the project root.
+            let pattern = RawFilePattern::new(other).compile(CaseSensitivity::default())?;
+            Ok(pattern.matches(&Utf8Path::new("/").join(this.as_str())))
+        }
+
+        /// Whether this path is inside the given directory.
+        fn is_under<'v>(
+            this: &PrettyPath,
+            #[starlark(require = pos)] dir: Value<'v>,
+        ) -> starlark::Result<bool> {
+            let dir = match dir.request_value::<&PrettyPath>() {
+                Some(dir) => dir.as_str(),
+                None => match dir.unpack_str() {
+                    Some(dir) => dir,
+                    None => {
+                        return Err(ValueError::IncorrectParameterTypeWithExpected(
+                            "str or Path".to_owned(),
+                            dir.get_type().to_owned(),
+                        )
+                        .into())
+                    }
+                },
+            };
+            let dir = Utf8Path::new(dir.trim_end_matches('/'));
+            let path = Utf8Path::new(this.as_str());
+            Ok(path != dir && path.starts_with(dir))
         }
 
         fn components<'v>(this: &'v PrettyPath) -> starlark::Result<Vec<&'v str>> {
@@ -112,6 +145,47 @@ impl PrettyPath {
     }
 }
 
+// Paths are compared, hashed and serialised in their sanitised form so that results are identical
+// across platforms.
+impl PartialEq for PrettyPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for PrettyPath {}
+
+impl Hash for PrettyPath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Ord for PrettyPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().split('/').cmp(other.as_str().split('/'))
+    }
+}
+
+impl PartialOrd for PrettyPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Serialize for PrettyPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for PrettyPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = Utf8PathBuf::deserialize(deserializer)?;
+        Ok(Self::new(&path))
+    }
+}
+
 impl From<&str> for PrettyPath {
     fn from(value: &str) -> Self {
         Self::new(Utf8Path::new(value))
@@ -152,6 +226,30 @@ impl<'v> StarlarkValue<'v> for PrettyPath {
         RES.methods(Self::methods)
     }
 
+    fn dir_attr(&self) -> Vec<String> {
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        let path = Utf8Path::new(self.as_str());
+        match attr {
+            Self::NAME_ATTR_NAME => Some(heap.alloc(path.file_name())),
+            Self::EXTENSION_ATTR_NAME => Some(heap.alloc(path.extension())),
+            Self::PARTS_ATTR_NAME => Some(
+                heap.alloc(
+                    path.components()
+                        .map(|component| component.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+            ),
+            _ => None,
+        }
+    }
+
+    fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
+        Self::ATTR_NAMES.contains(&attr)
+    }
+
     fn equals(&self, other: Value<'v>) -> starlark::Result<bool> {
         Ok(other
             .request_value::<&Self>()
@@ -360,6 +458,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn ordering() {
+        let mut paths: Vec<_> = ["src/a-b.rs", "src/a/b.rs", "src/a.rs", "build.rs"]
+            .into_iter()
+            .map(PrettyPath::from)
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths.iter().map(PrettyPath::as_str).collect::<Vec<_>>(),
+            ["build.rs", "src/a/b.rs", "src/a-b.rs", "src/a.rs"]
+        );
+    }
+
+    #[test]
+    fn serialisation() {
+        let path = PrettyPath::from("src/main.rs");
+        let serialised = serde_json::to_string(&path).unwrap();
+        assert_eq!(serialised, r#""src/main.rs""#);
+        assert_eq!(
+            serde_json::from_str::<PrettyPath>(&serialised).unwrap(),
+            path
+        );
+    }
+
     #[test]
     fn equals() {
         let path = "src/main.rs";
@@ -380,6 +502,63 @@ mod test {
         "#});
     }
 
+    #[test]
+    fn globs() {
+        PathTest::new("globs")
+            .path("src/vex/main_test.rs")
+            .run(indoc! {r#"
+            check['true'](path.matches('**/*_test.rs'))
+            check['true'](path.matches('*_test.rs'))
+            check['true'](path.matches('vex/'))
+            check['true'](path.matches('/src/**'))
+            check['false'](path.matches('/vex/**'))
+            check['false'](path.matches('*.go'))
+        "#});
+        let err = PathTest::new("invalid-glob")
+            .path("src/main.rs")
+            .try_run("path.matches('[src')")
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(r#"cannot compile "[src""#),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn is_under() {
+        PathTest::new("is-under")
+            .path("src/vex/main.rs")
+            .run(indoc! {r#"
+            check['true'](path.is_under('src'))
+            check['true'](path.is_under('src/'))
+            check['true'](path.is_under('src/vex'))
+            check['false'](path.is_under('sr'))
+            check['false'](path.is_under('vex'))
+            check['false'](path.is_under('src/vex/main.rs'))
+        "#});
+    }
+
+    #[test]
+    fn attrs() {
+        PathTest::new("attrs")
+            .path("src/vex/main.rs")
+            .run(indoc! {r#"
+            check['eq'](path.name, 'main.rs')
+            check['eq'](path.extension, 'rs')
+            check['eq'](path.parts, ['src', 'vex', 'main.rs'])
+            check['eq'](
+                dir(path),
+                ['components', 'extension', 'is_under', 'matches', 'name', 'parts'],
+            )
+        "#});
+        PathTest::new("no-extension")
+            .path("src/Makefile")
+            .run(indoc! {r#"
+            check['eq'](path.name, 'Makefile')
+            check['eq'](path.extension, None)
+        "#});
+    }
+
     #[test]
     fn components() {
         if !cfg!(target_os = "windows") {
@@ -442,8 +621,8 @@ mod test {
         {
             const PATH: &str = "src/foo/bar/baz/main.rs";
             let n = 1 + PATH.chars().filter(|c| *c == '/').count() as i64;
+            let expected = Regex::new("Index `-?[0-9]+` is out of bound").unwrap();
             for index in (-2 * n..-n).chain(n..2 * n) {
-                let expected = Regex::new("Index `-?[0-9]+` is out of bound").unwrap();
                 let err = PathTest::new("out-of-bounds")
                     .path(PATH)
                     .try_run(format!("path[{index}]"))
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use crate::{cli::GroupBy, irritation::Irritation, plural::Plural, source_path::PrettyPath};
+
+impl GroupBy {
+    pub fn group_of(self, irritation: &Irritation) -> &str {
+        match self {
+            Self::File => irritation.path().map_or("(project)", PrettyPath::as_str),
+            Self::Check => irritation.vex_id().as_ref(),
+        }
+    }
+}
+
+/// Split problems into groups, ordered by name. Problems keep their order within each group.
+pub fn groups(irritations: &[Irritation], group_by: GroupBy) -> Vec<(&str, Vec<&Irritation>)> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for irritation in irritations {
+        groups
+            .entry(group_by.group_of(irritation))
+            .or_default()
+            .push(irritation);
+    }
+    groups.into_iter().collect()
+}
+
+/// The header printed above each group of problems.
+pub fn group_header(group: &str, size: usize) -> String {
+    format!(
+        "== {group} ({}) ==",
+        Plural::new(size, "problem", "problems")
+    )
+}
+
+/// Tabulate how many problems each check found, most first.
+pub fn count_table(irritations: &[Irritation]) -> String {
+    let mut counts = BTreeMap::<_, usize>::new();
+    for irritation in irritations {
+        *counts.entry(irritation.vex_id().as_ref()).or_default() += 1;
+    }
+    let mut rows: Vec<_> = counts.into_iter().collect();
+    rows.sort_by(|(id, count), (other_id, other_count)| {
+        other_count.cmp(count).then_with(|| id.cmp(other_id))
+    });
+    let width = rows
+        .iter()
+        .map(|(id, _)| id.len())
+        .max()
+        .unwrap_or_default();
+    let mut table = format!(
+        "found {}:",
+        Plural::new(irritations.len(), "problem", "problems")
+    );
+    for (id, count) in rows {
+        table.push_str(&format!("\n  {id:width$}  {count}"));
+    }
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    fn irritations() -> Vec<Irritation> {
+        VexTest::new("summary")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @num', on_num)
+                        vex.search('rust', '(string_literal) @str', on_str)
+
+                    def on_num(event):
+                        vex.warn('numbers', 'found number', at=event.captures['num'])
+
+                    def on_str(event):
+                        vex.warn('strings', 'found string', at=event.captures['str'])
+                "#},
+            )
+            .with_source_file("src/a.rs", "fn a() {\n    f(1, 2);\n}\n")
+            .with_source_file("src/b.rs", "fn b() {\n    f(3, \"x\");\n}\n")
+            .try_run()
+            .unwrap()
+            .irritations
+    }
+
+    #[test]
+    fn grouping() {
+        let irritations = irritations();
+        let summarise = |group_by| {
+            groups(&irritations, group_by)
+                .into_iter()
+                .map(|(group, members)| {
+                    let ids: Vec<_> = members
+                        .iter()
+                        .map(|irritation| irritation.vex_id().as_ref())
+                        .collect();
+                    (group, ids)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summarise(GroupBy::File),
+            [
+                ("src/a.rs", vec!["numbers", "numbers"]),
+                ("src/b.rs", vec!["numbers", "strings"]),
+            ]
+        );
+        assert_eq!(
+            summarise(GroupBy::Check)
+                .into_iter()
+                .map(|(group, ids)| (group, ids.len()))
+                .collect::<Vec<_>>(),
+            [("numbers", 3), ("strings", 1)]
+        );
+        assert_eq!(group_header("src/a.rs", 1), "== src/a.rs (1 problem) ==");
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(
+            count_table(&irritations()),
+            indoc! {"
+                found 4 problems:
+                  numbers  3
+                  strings  1"
+            }
+        );
+        assert_eq!(count_table(&[]), "found 0 problems:");
+    }
+}
@@ -1,109 +1,300 @@
-use std::{fmt::Display, iter, str::FromStr, sync::OnceLock};
+use std::{fmt::Display, str::FromStr, sync::OnceLock};
 
 use allocative::Allocative;
-use clap::Subcommand;
 use dupe::Dupe;
-use enum_map::{Enum, EnumMap};
 use indoc::indoc;
-use lazy_static::lazy_static;
-use serde::{Deserialize as Deserialise, Serialize as Serialise};
-use strum::{EnumIter, IntoEnumIterator};
+use serde::{Deserialize as Deserialise, Deserializer, Serialize as Serialise, Serializer};
 use tree_sitter::{Language, Query};
 
-use crate::{error::Error, result::Result};
-
-#[derive(
-    Copy,
-    Clone,
-    Debug,
-    Dupe,
-    EnumIter,
-    Subcommand,
-    Enum,
-    Allocative,
-    PartialEq,
-    Eq,
-    Hash,
-    Deserialise,
-    Serialise,
-)]
-#[serde(rename_all = "kebab-case")]
+use crate::{error::Error, grammar::GrammarId, result::Result};
+
+#[derive(Copy, Clone, Debug, Dupe, Allocative, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum SupportedLanguage {
     Go,
+    Java,
+    Json,
+    Kotlin,
     Python,
+    Ruby,
     Rust,
+    Toml,
+    Yaml,
+
+    /// A grammar loaded at runtime.
+    Dynamic(GrammarId),
 }
 
 impl SupportedLanguage {
+    pub const BUILTIN: [Self; 9] = [
+        Self::Go,
+        Self::Java,
+        Self::Json,
+        Self::Kotlin,
+        Self::Python,
+        Self::Ruby,
+        Self::Rust,
+        Self::Toml,
+        Self::Yaml,
+    ];
+
+    /// All built-in languages, followed by all grammars loaded so far.
+    pub fn iter() -> impl Iterator<Item = Self> {
+        Self::BUILTIN
+            .into_iter()
+            .chain(GrammarId::iter().map(Self::Dynamic))
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             Self::Go => "go",
+            Self::Java => "java",
+            Self::Json => "json",
+            Self::Kotlin => "kotlin",
             Self::Python => "python",
+            Self::Ruby => "ruby",
             Self::Rust => "rust",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+            Self::Dynamic(id) => id.grammar().name,
         }
     }
 
-    pub fn ts_language(&self) -> &Language {
-        lazy_static! {
-            static ref LANGUAGES: EnumMap<SupportedLanguage, OnceLock<Language>> =
-                SupportedLanguage::iter()
-                    .zip(iter::repeat_with(OnceLock::new))
-                    .collect();
-        };
+    /// The prefix which starts a single-line comment, if known.
+    pub fn line_comment(&self) -> Option<&'static str> {
+        match self {
+            Self::Go | Self::Java | Self::Kotlin | Self::Rust => Some("//"),
+            Self::Python | Self::Ruby | Self::Toml | Self::Yaml => Some("#"),
+            Self::Json => None,
+            Self::Dynamic(id) => id.grammar().line_comment,
+        }
+    }
+
+    /// Built-in languages whose grammars were left out of this build.
+    pub fn missing() -> impl Iterator<Item = Self> + Clone {
+        Self::BUILTIN
+            .into_iter()
+            .filter(|language| !language.is_available())
+    }
 
-        LANGUAGES[*self].get_or_init(|| match self {
-            Self::Go => tree_sitter_go::language(),
-            Self::Python => tree_sitter_python::language(),
-            Self::Rust => tree_sitter_rust::language(),
-        })
+    pub fn is_available(&self) -> bool {
+        matches!(self, Self::Dynamic(_)) || self.builtin_grammar().is_some()
     }
 
-    pub fn ignore_query(&self) -> &Query {
-        lazy_static! {
-            static ref IGNORE_QUERIES: EnumMap<SupportedLanguage, OnceLock<Query>> =
-                SupportedLanguage::iter()
-                    .zip(iter::repeat_with(OnceLock::new))
-                    .collect();
+    fn builtin_grammar(&self) -> Option<fn() -> Language> {
+        match self {
+            #[cfg(feature = "lang-go")]
+            Self::Go => Some(tree_sitter_go::language),
+            #[cfg(feature = "lang-java")]
+            Self::Java => Some(tree_sitter_java::language),
+            #[cfg(feature = "lang-json")]
+            Self::Json => Some(tree_sitter_json::language),
+            #[cfg(feature = "lang-kotlin")]
+            Self::Kotlin => Some(tree_sitter_kotlin::language),
+            #[cfg(feature = "lang-python")]
+            Self::Python => Some(tree_sitter_python::language),
+            #[cfg(feature = "lang-ruby")]
+            Self::Ruby => Some(tree_sitter_ruby::language),
+            #[cfg(feature = "lang-rust")]
+            Self::Rust => Some(tree_sitter_rust::language),
+            #[cfg(feature = "lang-toml")]
+            Self::Toml => Some(tree_sitter_toml),
+            #[cfg(feature = "lang-yaml")]
+            Self::Yaml => Some(tree_sitter_yaml::language),
+            _ => None,
         }
+    }
+
+    pub fn ts_language(&self) -> Result<&'static Language> {
+        static GO: OnceLock<Language> = OnceLock::new();
+        static JAVA: OnceLock<Language> = OnceLock::new();
+        static JSON: OnceLock<Language> = OnceLock::new();
+        static KOTLIN: OnceLock<Language> = OnceLock::new();
+        static PYTHON: OnceLock<Language> = OnceLock::new();
+        static RUBY: OnceLock<Language> = OnceLock::new();
+        static RUST: OnceLock<Language> = OnceLock::new();
+        static TOML: OnceLock<Language> = OnceLock::new();
+        static YAML: OnceLock<Language> = OnceLock::new();
+
+        let cell = match self {
+            Self::Go => &GO,
+            Self::Java => &JAVA,
+            Self::Json => &JSON,
+            Self::Kotlin => &KOTLIN,
+            Self::Python => &PYTHON,
+            Self::Ruby => &RUBY,
+            Self::Rust => &RUST,
+            Self::Toml => &TOML,
+            Self::Yaml => &YAML,
+            Self::Dynamic(id) => return Ok(&id.grammar().language),
+        };
+        let grammar = self
+            .builtin_grammar()
+            .ok_or(Error::LanguageUnavailable(*self))?;
+        Ok(cell.get_or_init(grammar))
+    }
+
+    /// A query for `vex:ignore` markers, if this language has comments vex understands.
+    pub fn ignore_query(&self) -> Option<&'static Query> {
+        static GO: OnceLock<Query> = OnceLock::new();
+        static JAVA: OnceLock<Query> = OnceLock::new();
+        static JSON: OnceLock<Query> = OnceLock::new();
+        static KOTLIN: OnceLock<Query> = OnceLock::new();
+        static PYTHON: OnceLock<Query> = OnceLock::new();
+        static RUBY: OnceLock<Query> = OnceLock::new();
+        static RUST: OnceLock<Query> = OnceLock::new();
+        static TOML: OnceLock<Query> = OnceLock::new();
+        static YAML: OnceLock<Query> = OnceLock::new();
 
-        IGNORE_QUERIES[*self].get_or_init(|| {
-            let raw = match self {
-                Self::Go => indoc! {r#"
+        let (cell, raw) = match self {
+            Self::Go => (
+                &GO,
+                indoc! {r#"
+                    (
+                        (comment) @marker (#match? @marker "^/[/*] *vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#},
+            ),
+            Self::Java => (
+                &JAVA,
+                indoc! {r#"
+                    (
+                        [(line_comment) (block_comment)] @marker (#match? @marker "^/[/*] *vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#},
+            ),
+            Self::Json => (
+                &JSON,
+                indoc! {r#"
                     (
                         (comment) @marker (#match? @marker "^/[/*] *vex:ignore")
                         .
                         (_)? @ignore
                     )
                 "#},
-                Self::Python => indoc! {r#"
+            ),
+            Self::Kotlin => (
+                &KOTLIN,
+                // A comment at the start of a block precedes the block's statements rather than
+                // being among them, so only the first statement is ignored.
+                indoc! {r#"
+                    (
+                        [(line_comment) (multiline_comment)] @marker (#match? @marker "^/[/*] *vex:ignore")
+                        .
+                        (statements . (_) @ignore)
+                    )
+                    ([(source_file) (statements) (class_body) (enum_class_body)]
+                        [(line_comment) (multiline_comment)] @marker (#match? @marker "^/[/*] *vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#},
+            ),
+            Self::Python => (
+                &PYTHON,
+                indoc! {r#"
+                    (
+                        (comment) @marker (#match? @marker "^# *vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#},
+            ),
+            Self::Ruby => (
+                &RUBY,
+                // As in Kotlin, a comment at the start of a body precedes the body's statements.
+                indoc! {r#"
                     (
+                        (comment) @marker (#match? @marker "^# *vex:ignore")
+                        .
+                        (body_statement . (_) @ignore)
+                    )
+                    ([(program) (body_statement) (begin) (begin_block) (block_body) (do) (else) (end_block) (ensure) (parenthesized_statements) (then)]
                         (comment) @marker (#match? @marker "^# *vex:ignore")
                         .
                         (_)? @ignore
                     )
                 "#},
-                Self::Rust => indoc! {r#"
+            ),
+            Self::Rust => (
+                &RUST,
+                indoc! {r#"
                     (
                         (line_comment) @marker (#match? @marker "^// *vex:ignore")
                         .
                         (_)? @ignore
                     )
                 "#},
-            };
-            Query::new(self.ts_language(), raw).expect("internal error: ignore query invalid")
-        })
+            ),
+            Self::Toml => (
+                &TOML,
+                indoc! {r#"
+                    (
+                        (comment) @marker (#match? @marker "^# *vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#},
+            ),
+            Self::Yaml => (
+                &YAML,
+                // As in Kotlin, a comment at the start of a block or document precedes the block or
+                // document itself.
+                indoc! {r#"
+                    (
+                        (comment) @marker (#match? @marker "^# *vex:ignore")
+                        .
+                        [
+                            (block_node [(block_mapping . (_) @ignore) (block_sequence . (_) @ignore)])
+                            (document . (block_node [(block_mapping . (_) @ignore) (block_sequence . (_) @ignore)]))
+                        ]
+                    )
+                    ([(document) (block_mapping) (block_sequence)]
+                        (comment) @marker (#match? @marker "^# *vex:ignore")
+                        .
+                        (_)? @ignore
+                    )
+                "#},
+            ),
+            Self::Dynamic(id) => return id.grammar().ignore_query(),
+        };
+        let language = self.ts_language().ok()?;
+        Some(cell.get_or_init(|| {
+            Query::new(language, raw).expect("internal error: ignore query invalid")
+        }))
     }
 }
 
+/// The vendored TOML grammar, compiled by the build script.
+#[cfg(feature = "lang-toml")]
+fn tree_sitter_toml() -> Language {
+    extern "C" {
+        #[link_name = "tree_sitter_toml"]
+        fn language() -> Language;
+    }
+    unsafe { language() }
+}
+
 impl FromStr for SupportedLanguage {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
         match s {
             "go" => Ok(Self::Go),
+            "java" => Ok(Self::Java),
+            "json" => Ok(Self::Json),
+            "kotlin" => Ok(Self::Kotlin),
             "python" => Ok(Self::Python),
+            "ruby" => Ok(Self::Ruby),
             "rust" => Ok(Self::Rust),
-            _ => Err(Error::UnsupportedLanguage(s.to_string())),
+            "toml" => Ok(Self::Toml),
+            "yaml" => Ok(Self::Yaml),
+            _ => GrammarId::lookup(s)
+                .map(Self::Dynamic)
+                .ok_or_else(|| Error::UnsupportedLanguage(s.to_string())),
         }
     }
 }
@@ -114,12 +305,23 @@ impl Display for SupportedLanguage {
     }
 }
 
+impl Serialise for SupportedLanguage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialise<'de> for SupportedLanguage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::ops::Range;
 
-    use strum::IntoEnumIterator;
-
     use crate::{source_file::ParsedSourceFile, source_path::SourcePath};
 
     use super::*;
@@ -132,9 +334,112 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn availability() {
+        for language in SupportedLanguage::BUILTIN {
+            let compiled_in = match language {
+                SupportedLanguage::Go => cfg!(feature = "lang-go"),
+                SupportedLanguage::Java => cfg!(feature = "lang-java"),
+                SupportedLanguage::Json => cfg!(feature = "lang-json"),
+                SupportedLanguage::Kotlin => cfg!(feature = "lang-kotlin"),
+                SupportedLanguage::Python => cfg!(feature = "lang-python"),
+                SupportedLanguage::Ruby => cfg!(feature = "lang-ruby"),
+                SupportedLanguage::Rust => cfg!(feature = "lang-rust"),
+                SupportedLanguage::Toml => cfg!(feature = "lang-toml"),
+                SupportedLanguage::Yaml => cfg!(feature = "lang-yaml"),
+                SupportedLanguage::Dynamic(_) => unreachable!(),
+            };
+            assert_eq!(language.is_available(), compiled_in);
+            assert_eq!(language.ts_language().is_ok(), compiled_in);
+            assert_eq!(
+                SupportedLanguage::missing().any(|l| l == language),
+                !compiled_in
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "lang-go", feature = "lang-rust"))]
+    fn searchable() {
+        use crate::vextest::VexTest;
+
+        let irritations = VexTest::new("go")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('go', '(function_declaration name: (identifier) @name)', on_match)
+
+                    def on_match(event):
+                        vex.warn('test', 'found %s' % event.captures['name'], at=event.captures['name'])
+                "#},
+            )
+            .with_source_file(
+                "src/main.go",
+                indoc! {r#"
+                    package main
+
+                    func main() {}
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {}")
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].message(), "found main");
+        assert_eq!(irritations[0].path().unwrap().as_str(), "src/main.go");
+    }
+
+    #[test]
+    #[cfg(all(feature = "lang-json", feature = "lang-toml", feature = "lang-yaml"))]
+    fn config_files() {
+        use crate::vextest::VexTest;
+
+        let irritations = VexTest::new("config-files")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    QUERIES = {
+                        'json': '(pair key: (string (string_content) @key))',
+                        'toml': '(pair (bare_key) @key)',
+                        'yaml': '(block_mapping_pair key: (flow_node) @key)',
+                    }
+
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        for language, query in QUERIES.items():
+                            vex.search(language, query, on_match)
+
+                    def on_match(event):
+                        key = event.captures['key']
+                        if str(key) == 'version':
+                            vex.warn('test', 'found version', at=key)
+                "#},
+            )
+            .with_source_file("package.json", r#"{"name": "foo", "version": "1.0.0"}"#)
+            .with_source_file("Cargo.toml", "[package]\nname = 'foo'\nversion = '1.0.0'\n")
+            .with_source_file("ci/pipeline.yml", "version: 2\njobs: {}\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let mut paths: Vec<_> = irritations
+            .iter()
+            .map(|irritation| irritation.path().unwrap().as_str())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["Cargo.toml", "ci/pipeline.yml", "package.json"]);
+    }
+
     #[test]
     #[allow(clippy::single_range_in_vec_init)]
     fn ignore_queries() {
+        #[cfg(feature = "lang-go")]
         Test::language(SupportedLanguage::Go)
             .with_source(indoc! {r#"
                 package main
@@ -151,6 +456,53 @@ mod test {
                 }
             "#})
             .ignores_ranges(&[32..102]);
+        #[cfg(feature = "lang-java")]
+        Test::language(SupportedLanguage::Java)
+            .with_source(indoc! {r#"
+                class Main {
+                    void main() {
+                        // vex:ignore *
+                        int[] x = {
+                            1,
+                            2,
+                            3,
+                        };
+                        // unrelated
+                        int z = 1;
+                    }
+                }
+            "#})
+            .ignores_ranges(&[39..130]);
+        #[cfg(feature = "lang-json")]
+        Test::language(SupportedLanguage::Json)
+            .with_source(indoc! {r#"
+                {
+                    // vex:ignore *
+                    "x": [
+                        1,
+                        2
+                    ],
+                    // unrelated
+                    "z": 1
+                }
+            "#})
+            .ignores_ranges(&[6..59]);
+        #[cfg(feature = "lang-kotlin")]
+        Test::language(SupportedLanguage::Kotlin)
+            .with_source(indoc! {r#"
+                fun main() {
+                    // vex:ignore *
+                    val x = listOf(
+                        1,
+                        2,
+                        3,
+                    )
+                    // unrelated
+                    val z = 1
+                }
+            "#})
+            .ignores_ranges(&[17..91]);
+        #[cfg(feature = "lang-python")]
         Test::language(SupportedLanguage::Python)
             .with_source(indoc! {r#"
                 def main():
@@ -165,6 +517,21 @@ mod test {
                     z = 1;
             "#})
             .ignores_ranges(&[127..190]);
+        #[cfg(feature = "lang-ruby")]
+        Test::language(SupportedLanguage::Ruby)
+            .with_source(indoc! {r#"
+                def main
+                    # vex:ignore *
+                    x = [
+                        1,
+                        2,
+                        3,
+                    ]
+                    # unrelated
+                    z = 1
+                end
+            "#})
+            .ignores_ranges(&[13..76]);
         Test::language(SupportedLanguage::Rust)
             .with_source(indoc! {r#"
                 fn main() {
@@ -179,6 +546,33 @@ mod test {
                 }
             "#})
             .ignores_ranges(&[16..85]);
+        #[cfg(feature = "lang-toml")]
+        Test::language(SupportedLanguage::Toml)
+            .with_source(indoc! {r#"
+                [package]
+                # vex:ignore *
+                x = [
+                    1,
+                    2,
+                ]
+                # unrelated
+                z = 1
+            "#})
+            .ignores_ranges(&[10..46]);
+        #[cfg(feature = "lang-yaml")]
+        Test::language(SupportedLanguage::Yaml)
+            .with_source(indoc! {r#"
+                # vex:ignore *
+                version: 1
+                main:
+                  # vex:ignore *
+                  x:
+                    - 1
+                    - 2
+                  # unrelated
+                  z: 1
+            "#})
+            .ignores_ranges(&[0..25, 34..83]);
 
         // Test structs
         #[must_use]
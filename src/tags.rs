@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    context::Context, error::Error, irritation::Irritation, result::Result,
+    scriptlets::VexingStore, vex_id::VexId,
+};
+
+/// The tags which classify each declared check.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckTags(BTreeMap<VexId, BTreeSet<String>>);
+
+impl CheckTags {
+    /// Collect the tags declared by scriptlets and by external checks in the manifest.
+    pub fn new(ctx: &Context, store: &VexingStore) -> Result<Self> {
+        let mut check_tags = store.check_tags().clone();
+        for check in &ctx.external {
+            check.tags.iter().try_for_each(|tag| validate_tag(tag))?;
+            check_tags.declare(VexId::try_from(check.id.clone())?, check.tags.clone());
+        }
+        Ok(check_tags)
+    }
+
+    pub fn declare(&mut self, vex_id: VexId, tags: impl IntoIterator<Item = String>) {
+        self.0.entry(vex_id).or_default().extend(tags)
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        other
+            .0
+            .into_iter()
+            .for_each(|(vex_id, tags)| self.declare(vex_id, tags))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&VexId, &BTreeSet<String>)> {
+        self.0.iter()
+    }
+
+    pub fn of(&self, vex_id: &VexId) -> impl Iterator<Item = &str> {
+        self.0.get(vex_id).into_iter().flatten().map(String::as_str)
+    }
+
+    /// Returns whether the given check has any of the given tags.
+    pub fn has_any(&self, vex_id: &VexId, tags: &[String]) -> bool {
+        self.of(vex_id).any(|tag| tags.iter().any(|t| t == tag))
+    }
+
+    /// Count irritations by the tags of the checks which raised them. Irritations from checks
+    /// with multiple tags are counted once under each.
+    pub fn count<'a>(
+        &self,
+        irritations: impl IntoIterator<Item = &'a Irritation>,
+    ) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for irritation in irritations {
+            for tag in self.of(irritation.vex_id()) {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        counts
+    }
+}
+
+pub fn validate_tag(tag: &str) -> Result<()> {
+    lazy_static! {
+        static ref VALID_TAG: Regex = Regex::new("^[a-z0-9]+(-[a-z0-9]+)*$").unwrap();
+    }
+    if !VALID_TAG.is_match(tag) {
+        return Err(Error::InvalidTag(tag.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn validation() {
+        ["security", "style", "a", "perf-2"]
+            .into_iter()
+            .for_each(|tag| validate_tag(tag).unwrap());
+        ["", "Style", "-style", "style-", "big--style", "style!"]
+            .into_iter()
+            .for_each(|tag| {
+                assert_eq!(
+                    validate_tag(tag).unwrap_err().to_string(),
+                    format!("invalid tag '{tag}': can only contain a-z, 0-9 and single '-'")
+                )
+            });
+    }
+
+    #[test]
+    fn counts() {
+        let id = |id: &str| VexId::try_from(id.to_string()).unwrap();
+        let mut check_tags = CheckTags::default();
+        check_tags.declare(id("check-1"), ["security".into(), "style".into()]);
+        let mut other_check_tags = CheckTags::default();
+        other_check_tags.declare(id("check-1"), ["security".into()]);
+        other_check_tags.declare(id("check-2"), ["perf".into()]);
+        check_tags.extend(other_check_tags);
+
+        assert_eq!(
+            check_tags.of(&id("check-1")).collect::<Vec<_>>(),
+            ["security", "style"]
+        );
+        assert!(check_tags.has_any(&id("check-2"), &["perf".into(), "style".into()]));
+        assert!(!check_tags.has_any(&id("check-2"), &["style".into()]));
+        assert!(!check_tags.has_any(&id("check-3"), &["style".into()]));
+    }
+
+    #[test]
+    fn only_tags() {
+        let irritations = VexTest::new("only-tags")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('secure-check', tags=['security'])
+                        vex.declare('stylish-check', tags=['style'])
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('secure-check', 'insecure')
+                        vex.warn('stylish-check', 'unstylish')
+                        vex.warn('untagged-check', 'untagged')
+                "#},
+            )
+            .with_only_tags(["security"])
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+        assert_eq!(irritations[0].vex_id().as_ref(), "secure-check");
+    }
+
+    #[test]
+    fn invalid_declaration() {
+        VexTest::new("invalid-tag")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('some-check', tags=['Security'])
+                        vex.observe('open_project', lambda x: x)
+                "#},
+            )
+            .returns_error("invalid tag 'Security'");
+        VexTest::new("invalid-id")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.declare('CHECK', tags=['security'])
+                        vex.observe('open_project', lambda x: x)
+                "#},
+            )
+            .returns_error("invalid vex ID 'CHECK'");
+    }
+}
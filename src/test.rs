@@ -13,27 +13,29 @@ use starlark::values::FrozenHeap;
 use crate::{
     associations::Associations,
     cli::{MaxConcurrentFileLimit, MaxProblems},
-    context::{Context, Manifest},
+    context::{self, Context, Manifest},
     error::{Error, IOAction},
     logger,
     result::Result,
-    scan,
+    scan::{self, ScanOptions},
     scriptlets::{
         action::Action,
+        artifact_cache::ArtifactCache,
         event::{PostTestRunEvent, PreTestRunEvent},
         handler_module::HandlerModule,
-        query_cache::QueryCache,
+        limits::HandlerLimits,
         source::{self, ScriptSource},
         InitOptions, Intent, Observable, ObserveOptions, PreinitOptions, PreinitingStore,
         PrintHandler,
     },
     source_path::{PrettyPath, SourcePath},
+    trigger::CaseSensitivity,
     verbosity::Verbosity,
 };
 
 pub fn test() -> Result<()> {
-    let ctx = Context::acquire()?;
-    run_tests(&source::sources_in_dir(&ctx.vex_dir())?)
+    let ctx = Context::acquire(&context::current_dir()?)?;
+    run_tests(&source::sources_in_project(&ctx)?)
 }
 
 pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
@@ -44,22 +46,29 @@ pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
         };
         let init_opts = InitOptions {
             verbosity: Verbosity::Quiet,
+            ..InitOptions::default()
         };
         PreinitingStore::new(script_sources)?
             .preinit(preinit_opts)?
             .init(init_opts)?
     };
 
-    let query_cache = QueryCache::new();
+    // Each test file is scanned twice, first strictly then leniently, so parses are retained.
+    let artifact_cache = ArtifactCache::retaining_parsed_files();
     let files_to_scan = {
         let frozen_heap = FrozenHeap::new();
         let event = PreTestRunEvent;
         let handler_module = HandlerModule::new();
         let observe_opts = ObserveOptions {
             action: Action::Vexing(event.kind()),
-            query_cache: Some(&query_cache),
+            artifact_cache: Some(&artifact_cache),
             ignore_markers: None,
+            shared_store: None,
+            check_metadata: Some(store.check_metadata()),
             print_handler: &PrintHandler::new(logger::verbosity(), event.kind().name()),
+            limits: HandlerLimits::default(),
+            keep_going: false,
+            intent_log: None,
         };
         store.observers_for(event.kind()).observe(
             &handler_module,
@@ -72,20 +81,25 @@ pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
         handler_module
             .into_intents_on(&frozen_heap)?
             .into_iter()
-            .for_each(|intent| match intent {
-                Intent::ScanFile {
-                    file_name,
-                    language,
-                    content,
-                } => {
-                    seen_file_names
-                        .entry(file_name.dupe())
-                        .and_modify(|count| *count += 1)
-                        .or_insert(1);
-                    files_to_scan.push((file_name, language, content));
+            .try_for_each(|intent| {
+                match intent {
+                    Intent::ScanFile {
+                        file_name,
+                        language,
+                        content,
+                    } => {
+                        seen_file_names
+                            .entry(file_name.dupe())
+                            .and_modify(|count| *count += 1)
+                            .or_insert(1);
+                        files_to_scan.push((file_name, language, content));
+                    }
+                    _ => {
+                        return Err(intent.unavailable_while(Action::Vexing(PreTestRunEvent.kind())))
+                    }
                 }
-                _ => panic!("internal error: unexpected intent: {intent:?}"),
-            });
+                Ok(())
+            })?;
         let mut test_run_invalid = false;
         seen_file_names
             .into_iter()
@@ -103,7 +117,7 @@ pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
     };
 
     // TODO(kzca): Remove this constraint once language can be specified.
-    let base_associations = Associations::base();
+    let base_associations = Associations::base(CaseSensitivity::default());
     files_to_scan.iter().try_for_each(|(path, language, _)| {
         let src_path = SourcePath::new_in(Utf8Path::new(path.as_str()), Utf8Path::new(""));
         let Some(associated_language) = base_associations.get_language(&src_path)? else {
@@ -162,8 +176,15 @@ pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
         let sub_ctx = Context::new_with_manifest(&temp_dir_path, Manifest::default());
         let sub_store = {
             let verbosity = Verbosity::Quiet;
-            let preinit_opts = PreinitOptions { lenient, verbosity };
-            let init_opts = InitOptions { verbosity };
+            let preinit_opts = PreinitOptions {
+                lenient,
+                verbosity,
+                ..PreinitOptions::default()
+            };
+            let init_opts = InitOptions {
+                verbosity,
+                ..InitOptions::default()
+            };
             PreinitingStore::new(script_sources)?
                 .preinit(preinit_opts)?
                 .init(init_opts)?
@@ -171,9 +192,14 @@ pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
         scan::scan_project(
             &sub_ctx,
             &sub_store,
-            MaxProblems::Unlimited,
-            MaxConcurrentFileLimit::new(1),
-            Verbosity::Quiet,
+            ScanOptions {
+                max_problems: MaxProblems::Unlimited,
+                max_concurrent_files: MaxConcurrentFileLimit::new(1),
+                verbosity: Verbosity::Quiet,
+                artifact_cache: Some(&artifact_cache),
+                fail_fast: true,
+                ..ScanOptions::default()
+            },
         )
     };
     let nonlenient_run = collect_run_data(false)?;
@@ -189,9 +215,14 @@ pub(crate) fn run_tests(script_sources: &[impl ScriptSource]) -> Result<()> {
         let event = PostTestRunEvent::new(irritations, handler_module.heap());
         let observer_opts = ObserveOptions {
             action: Action::Vexing(event.kind()),
-            query_cache: Some(&query_cache),
+            artifact_cache: Some(&artifact_cache),
             ignore_markers: None,
+            shared_store: None,
+            check_metadata: Some(store.check_metadata()),
             print_handler: &PrintHandler::new(logger::verbosity(), event.kind().name()),
+            limits: HandlerLimits::default(),
+            keep_going: false,
+            intent_log: None,
         };
         store.observers_for(event.kind()).observe(
             &handler_module,
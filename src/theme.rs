@@ -0,0 +1,176 @@
+use std::sync::RwLock;
+
+use annotate_snippets::{
+    renderer::{Color, Effects, RgbColor, Style},
+    Renderer,
+};
+use clap::ValueEnum;
+use dupe::Dupe;
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+
+/// How problems and other output are drawn, as configured in the `[theme]` section of the
+/// manifest. Options passed on the command-line take precedence.
+#[derive(Copy, Clone, Debug, Default, Dupe, PartialEq, Eq, Hash, Deserialise, Serialise)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    #[serde(default)]
+    pub palette: Palette,
+
+    #[serde(default)]
+    pub glyphs: Glyphs,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Dupe, PartialEq, Eq, Hash, Deserialise, Serialise, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    #[default]
+    Default,
+
+    /// Colours which remain distinct under common colour-vision deficiencies.
+    Colorblind,
+
+    /// No colour at all.
+    None,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Dupe, PartialEq, Eq, Hash, Deserialise, Serialise, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Glyphs {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+static THEME: RwLock<Theme> = RwLock::new(Theme {
+    palette: Palette::Default,
+    glyphs: Glyphs::Unicode,
+});
+static OVERRIDES: RwLock<(Option<Palette>, Option<Glyphs>)> = RwLock::new((None, None));
+
+/// Set the parts of the theme given on the command-line, which the manifest cannot change.
+pub fn init(palette: Option<Palette>, glyphs: Option<Glyphs>) {
+    *OVERRIDES
+        .write()
+        .expect("internal error: theme overrides poisoned") = (palette, glyphs);
+    configure(Theme::default());
+}
+
+/// Use the given theme, except where overridden on the command-line.
+pub fn configure(theme: Theme) {
+    let (palette, glyphs) = *OVERRIDES
+        .read()
+        .expect("internal error: theme overrides poisoned");
+    *THEME.write().expect("internal error: theme poisoned") = Theme {
+        palette: palette.unwrap_or(theme.palette),
+        glyphs: glyphs.unwrap_or(theme.glyphs),
+    };
+}
+
+pub fn current() -> Theme {
+    *THEME.read().expect("internal error: theme poisoned")
+}
+
+impl Theme {
+    pub fn renderer(&self) -> Renderer {
+        match self.palette {
+            Palette::Default => Renderer::styled(),
+            Palette::Colorblind => {
+                // Colours from the Okabe-Ito palette.
+                const fn rgb(r: u8, g: u8, b: u8) -> Style {
+                    Style::new()
+                        .fg_color(Some(Color::Rgb(RgbColor(r, g, b))))
+                        .effects(Effects::BOLD)
+                }
+                Renderer::styled()
+                    .error(rgb(213, 94, 0))
+                    .warning(rgb(230, 159, 0))
+                    .info(rgb(86, 180, 233))
+                    .note(rgb(0, 158, 115))
+                    .help(rgb(0, 114, 178))
+                    .line_no(rgb(86, 180, 233))
+            }
+            Palette::None => Renderer::plain(),
+        }
+    }
+
+    /// The style of the `success` label.
+    pub fn success_style(&self) -> owo_colors::Style {
+        match self.palette {
+            Palette::Default => owo_colors::Style::new().green().bold(),
+            Palette::Colorblind => owo_colors::Style::new().truecolor(0, 114, 178).bold(),
+            Palette::None => owo_colors::Style::new(),
+        }
+    }
+
+    /// The characters which fill a progress bar, from full to empty.
+    pub fn progress_chars(&self) -> &'static str {
+        match self.glyphs {
+            Glyphs::Unicode => "█▉▊▋▌▍▎▏ ",
+            Glyphs::Ascii => "=> ",
+        }
+    }
+
+    /// Marks where text was cut short.
+    pub fn ellipsis(&self) -> &'static str {
+        match self.glyphs {
+            Glyphs::Unicode => "…",
+            Glyphs::Ascii => "...",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn palettes_differ() {
+        let rendered = |palette| {
+            let theme = Theme {
+                palette,
+                ..Theme::default()
+            };
+            let snippet = annotate_snippets::Snippet {
+                title: Some(annotate_snippets::Annotation {
+                    id: Some("some-id"),
+                    label: Some("some message"),
+                    annotation_type: annotate_snippets::AnnotationType::Error,
+                }),
+                footer: vec![],
+                slices: vec![],
+            };
+            theme.renderer().render(snippet).to_string()
+        };
+        let default = rendered(Palette::Default);
+        let colorblind = rendered(Palette::Colorblind);
+        let none = rendered(Palette::None);
+        assert_ne!(default, colorblind);
+        assert_eq!(none, "error[some-id]: some message");
+        assert!(default.contains("some message"));
+        assert!(colorblind.contains("213;94;0"));
+    }
+
+    #[test]
+    fn ellipsis() {
+        let theme = |glyphs| Theme {
+            glyphs,
+            ..Theme::default()
+        };
+        assert_eq!(theme(Glyphs::Unicode).ellipsis(), "…");
+        assert_eq!(theme(Glyphs::Ascii).ellipsis(), "...");
+    }
+
+    #[test]
+    fn progress_chars() {
+        let theme = |glyphs| Theme {
+            glyphs,
+            ..Theme::default()
+        };
+        assert!(theme(Glyphs::Ascii).progress_chars().is_ascii());
+        assert!(!theme(Glyphs::Unicode).progress_chars().is_ascii());
+    }
+}
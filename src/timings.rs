@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use dupe::Dupe;
+use log::info;
+
+use crate::source_path::PrettyPath;
+
+/// The log target of timing lines, which are shown with `--timings` whatever the verbosity.
+pub const TARGET: &str = "vex::timings";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The total time spent in each phase so far, by phase and subject.
+static TOTALS: Mutex<BTreeMap<(&str, String), Total>> = Mutex::new(BTreeMap::new());
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Total {
+    count: usize,
+    elapsed: Duration,
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Measures one phase of a run, logging how long it took once stopped or dropped. Each
+/// measurement is also added to the breakdown logged at the end of the run.
+#[must_use]
+pub struct Timer {
+    phase: &'static str,
+    path: Option<PrettyPath>,
+    subject: Option<String>,
+    logged: bool,
+    start: Option<Instant>,
+}
+
+impl Timer {
+    pub fn start(phase: &'static str) -> Self {
+        Self {
+            phase,
+            path: None,
+            subject: None,
+            logged: true,
+            start: enabled().then(Instant::now),
+        }
+    }
+
+    /// Start timing a phase which concerns a single file.
+    pub fn start_for(phase: &'static str, path: &PrettyPath) -> Self {
+        let start = enabled().then(Instant::now);
+        Self {
+            phase,
+            path: start.map(|_| path.dupe()),
+            subject: None,
+            logged: true,
+            start,
+        }
+    }
+
+    /// Start timing a phase which happens too often to log each time, such as running a
+    /// handler, so is only added to the breakdown.
+    pub fn start_unlogged(phase: &'static str) -> Self {
+        Self {
+            phase,
+            path: None,
+            subject: None,
+            logged: false,
+            start: enabled().then(Instant::now),
+        }
+    }
+
+    /// Break down the time spent in this phase by the given subject, such as a language or
+    /// check.
+    pub fn about(mut self, subject: impl Display) -> Self {
+        if self.start.is_some() {
+            self.subject = Some(subject.to_string());
+        }
+        self
+    }
+
+    pub fn stop(self) {}
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        let phase = self.phase;
+        {
+            let mut totals = TOTALS.lock().expect("internal error: timings poisoned");
+            let total = totals
+                .entry((phase, self.subject.take().unwrap_or_default()))
+                .or_default();
+            total.count += 1;
+            total.elapsed += elapsed;
+        }
+        if !self.logged {
+            return;
+        }
+
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        match &self.path {
+            Some(path) => info!(
+                target: TARGET,
+                custom = true, phase = phase, path = path.as_str(), elapsed_ms = elapsed_ms;
+                "timing: phase={phase} path={path} elapsed_ms={elapsed_ms:.3}",
+            ),
+            None => info!(
+                target: TARGET,
+                custom = true, phase = phase, elapsed_ms = elapsed_ms;
+                "timing: phase={phase} elapsed_ms={elapsed_ms:.3}",
+            ),
+        }
+    }
+}
+
+/// Log the total time spent in each phase, broken down by subject, slowest first.
+pub fn log_breakdown() {
+    if !enabled() {
+        return;
+    }
+    let totals = std::mem::take(&mut *TOTALS.lock().expect("internal error: timings poisoned"));
+    for line in breakdown(&totals) {
+        info!(target: TARGET, custom = true; "{line}");
+    }
+}
+
+fn breakdown(totals: &BTreeMap<(&str, String), Total>) -> Vec<String> {
+    let mut rows: Vec<_> = totals.iter().collect();
+    rows.sort_by(
+        |((phase, subject), total), ((other_phase, other_subject), other_total)| {
+            phase
+                .cmp(other_phase)
+                .then_with(|| other_total.elapsed.cmp(&total.elapsed))
+                .then_with(|| subject.cmp(other_subject))
+        },
+    );
+    let label = |phase: &str, subject: &str| {
+        if subject.is_empty() {
+            phase.to_owned()
+        } else {
+            format!("{phase} {subject}")
+        }
+    };
+    let width = rows
+        .iter()
+        .map(|((phase, subject), _)| label(phase, subject).len())
+        .max()
+        .unwrap_or_default();
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    lines.push(format!(
+        "timing breakdown: {:width$}  {:>10}  {:>7}",
+        "phase", "total ms", "count"
+    ));
+    for ((phase, subject), total) in rows {
+        lines.push(format!(
+            "timing breakdown: {:width$}  {:>10.3}  {:>7}",
+            label(phase, subject),
+            total.elapsed.as_secs_f64() * 1000.0,
+            total.count,
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn breakdown_order() {
+        let total = |ms, count| Total {
+            count,
+            elapsed: Duration::from_millis(ms),
+        };
+        let totals = BTreeMap::from([
+            (("walk", String::new()), total(5, 1)),
+            (("query", "fast-check".to_owned()), total(1, 10)),
+            (("query", "slow-check".to_owned()), total(20, 10)),
+            (("parse", "rust".to_owned()), total(3, 2)),
+        ]);
+        assert_eq!(
+            breakdown(&totals),
+            [
+                "timing breakdown: phase               total ms    count",
+                "timing breakdown: parse rust             3.000        2",
+                "timing breakdown: query slow-check      20.000       10",
+                "timing breakdown: query fast-check       1.000       10",
+                "timing breakdown: walk                   5.000        1",
+            ]
+        );
+    }
+}
@@ -2,17 +2,45 @@ use std::{fmt::Display, ops::Deref};
 
 use allocative::Allocative;
 use camino::{Utf8Path, Utf8PathBuf};
-use glob::Pattern;
+use glob::{MatchOptions, Pattern};
 use serde::{Deserialize, Serialize};
 
 use crate::{error::Error, result::Result};
 
 #[derive(Clone, Debug, Allocative)]
-pub struct FilePattern(#[allocative(skip)] pub Pattern);
+pub struct FilePattern {
+    #[allocative(skip)]
+    pattern: Pattern,
+
+    case_sensitivity: CaseSensitivity,
+}
 
 impl FilePattern {
     pub fn matches(&self, path: &Utf8Path) -> bool {
-        self.0.matches(path.as_str())
+        let options = MatchOptions {
+            case_sensitive: self.case_sensitivity == CaseSensitivity::Sensitive,
+            ..MatchOptions::new()
+        };
+        self.pattern.matches_with(path.as_str(), options)
+    }
+}
+
+/// Whether file patterns distinguish between upper- and lower-case letters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Allocative)]
+pub enum CaseSensitivity {
+    Sensitive,
+    Insensitive,
+}
+
+impl Default for CaseSensitivity {
+    /// Patterns follow the file systems usual on each platform, which ignore case on Windows and
+    /// macOS.
+    fn default() -> Self {
+        if cfg!(any(windows, target_os = "macos")) {
+            Self::Insensitive
+        } else {
+            Self::Sensitive
+        }
     }
 }
 
@@ -24,7 +52,7 @@ impl<S: AsRef<str>> RawFilePattern<S> {
         Self(raw)
     }
 
-    pub fn compile(self) -> Result<FilePattern> {
+    pub fn compile(self, case_sensitivity: CaseSensitivity) -> Result<FilePattern> {
         let pattern = {
             let mut pattern_buf = Utf8PathBuf::with_capacity("**/".len() + self.len() + "*".len());
             let original_start_index = if !self.starts_with('/') {
@@ -43,7 +71,10 @@ impl<S: AsRef<str>> RawFilePattern<S> {
                 cause,
             })?
         };
-        Ok(FilePattern(pattern))
+        Ok(FilePattern {
+            pattern,
+            case_sensitivity,
+        })
     }
 }
 
@@ -65,12 +96,17 @@ impl<S: AsRef<str>> Display for RawFilePattern<S> {
 mod test {
     use super::*;
 
-    use indoc::{formatdoc, indoc};
+    use indoc::indoc;
 
-    use crate::{irritation::Irritation, vextest::VexTest};
+    use crate::vextest::VexTest;
 
     #[test]
+    #[cfg(all(feature = "lang-go", feature = "lang-python", feature = "lang-rust"))]
     fn supported_language() {
+        use indoc::formatdoc;
+
+        use crate::irritation::Irritation;
+
         struct LanguageTest {
             language: &'static str,
             query: &'static str,
@@ -302,7 +338,9 @@ mod test {
                 eprintln!("running test {}...", self.name);
 
                 let path_pattern = self.path_pattern.unwrap();
-                let pattern = RawFilePattern::new(path_pattern).compile().unwrap();
+                let pattern = RawFilePattern::new(path_pattern)
+                    .compile(CaseSensitivity::Sensitive)
+                    .unwrap();
                 let matches = self
                     .test_paths
                     .iter()
@@ -407,10 +445,44 @@ mod test {
     #[test]
     fn malformed_glob() {
         let pattern = "[".to_string();
-        let err = RawFilePattern::new(&pattern).compile().unwrap_err();
+        let err = RawFilePattern::new(&pattern)
+            .compile(CaseSensitivity::Sensitive)
+            .unwrap_err();
         assert_eq!(
             r#"cannot compile "[": invalid range pattern at position 1"#,
             err.to_string()
         );
     }
+
+    #[test]
+    fn case_sensitivity() {
+        let matches = |case_sensitivity, pattern, path| {
+            RawFilePattern::new(pattern)
+                .compile(case_sensitivity)
+                .unwrap()
+                .matches(Utf8Path::new(path))
+        };
+        assert!(matches(CaseSensitivity::Sensitive, "*.rs", "/src/main.rs"));
+        assert!(!matches(CaseSensitivity::Sensitive, "*.rs", "/src/main.RS"));
+        assert!(!matches(
+            CaseSensitivity::Sensitive,
+            "/Src/",
+            "/src/main.rs"
+        ));
+        assert!(matches(
+            CaseSensitivity::Insensitive,
+            "*.RS",
+            "/src/main.rs"
+        ));
+        assert!(matches(
+            CaseSensitivity::Insensitive,
+            "*.rs",
+            "/src/main.RS"
+        ));
+        assert!(matches(
+            CaseSensitivity::Insensitive,
+            "/Src/",
+            "/src/main.rs"
+        ));
+    }
 }
@@ -0,0 +1,584 @@
+use std::{collections::BTreeMap, fmt::Write as _, fs};
+
+use camino::Utf8Path;
+use console::{Key, Term};
+
+use crate::{
+    associations::Associations,
+    cli::GroupBy,
+    error::{Error, IOAction},
+    fix::Fix,
+    irritation::Irritation,
+    plural::Plural,
+    result::Result,
+    scriptlets::Location,
+    source_path::{PrettyPath, SourcePath},
+    summary,
+};
+
+/// A terminal on which problems are browsed.
+pub trait Screen {
+    /// The number of rows and columns which can be drawn.
+    fn size(&self) -> (usize, usize);
+
+    /// Replace everything shown with the given frame.
+    fn draw(&mut self, frame: &str) -> Result<()>;
+
+    fn read_key(&mut self) -> Result<Key>;
+
+    /// Hand the terminal back, such as before running an editor. The next draw takes it again.
+    fn release(&mut self) -> Result<()>;
+}
+
+/// The terminal on stdout, drawn on in its alternate screen so that the shell's output is kept.
+pub struct Terminal {
+    term: Term,
+    active: bool,
+}
+
+impl Terminal {
+    /// Returns `None` if stdout is not a terminal.
+    pub fn stdout() -> Option<Self> {
+        let term = Term::stdout();
+        term.is_term().then_some(Self {
+            term,
+            active: false,
+        })
+    }
+
+    fn io_error(cause: std::io::Error) -> Error {
+        Error::IO {
+            path: PrettyPath::from("stdout"),
+            action: IOAction::Write,
+            cause,
+        }
+    }
+}
+
+impl Screen for Terminal {
+    fn size(&self) -> (usize, usize) {
+        let (rows, columns) = self.term.size();
+        (rows as usize, columns as usize)
+    }
+
+    fn draw(&mut self, frame: &str) -> Result<()> {
+        if !self.active {
+            self.term
+                .write_str("\x1b[?1049h")
+                .and_then(|_| self.term.hide_cursor())
+                .map_err(Self::io_error)?;
+            self.active = true;
+        }
+        self.term
+            .clear_screen()
+            .and_then(|_| self.term.write_str(frame))
+            .and_then(|_| self.term.flush())
+            .map_err(Self::io_error)
+    }
+
+    fn read_key(&mut self) -> Result<Key> {
+        self.term.read_key().map_err(|cause| Error::IO {
+            path: PrettyPath::from("stdin"),
+            action: IOAction::Read,
+            cause,
+        })
+    }
+
+    fn release(&mut self) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        self.term
+            .show_cursor()
+            .and_then(|_| self.term.write_str("\x1b[?1049l"))
+            .and_then(|_| self.term.flush())
+            .map_err(Self::io_error)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.release();
+    }
+}
+
+/// Browse problems in a list grouped by file or check, showing the selected problem in full
+/// beneath. Problems may be opened in an editor or suppressed. Returns the edits which insert the
+/// requested suppressions. If there are no problems, nothing is drawn.
+pub fn browse(
+    project_root: &Utf8Path,
+    associations: &Associations,
+    irritations: &[Irritation],
+    group_by: GroupBy,
+    screen: &mut impl Screen,
+    mut open: impl FnMut(&Utf8Path, &Location) -> Result<()>,
+) -> Result<Vec<Fix>> {
+    if irritations.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut browser = Browser::new(irritations, group_by);
+    loop {
+        let frame = browser.frame(screen.size())?;
+        screen.draw(&frame)?;
+        browser.status = None;
+        let key = screen.read_key()?;
+        let irritation = browser.selected();
+        match key {
+            Key::ArrowDown | Key::Char('j' | 'n') => browser.select(browser.index + 1),
+            Key::ArrowUp | Key::Char('k' | 'p') => browser.select(browser.index.saturating_sub(1)),
+            Key::PageDown => browser.select(browser.index + browser.list_height),
+            Key::PageUp => browser.select(browser.index.saturating_sub(browser.list_height)),
+            Key::Home => browser.select(0),
+            Key::End => browser.select(usize::MAX),
+            Key::Tab | Key::Char('g') => browser.select_next_group(),
+            Key::Enter | Key::Char('e') => match (irritation.path(), irritation.location()) {
+                (Some(path), Some(location)) => {
+                    screen.release()?;
+                    if let Err(err) = open(&project_root.join(path.as_str()), location) {
+                        browser.status = Some(err.to_string());
+                    }
+                }
+                _ => browser.status = Some("cannot open a problem without a location".into()),
+            },
+            Key::Char('s') => {
+                if browser.suppressions.remove(&browser.index).is_some() {
+                    browser.status = Some("suppression undone".into());
+                } else {
+                    match suppression(project_root, associations, irritation)? {
+                        Some(suppression) => {
+                            browser.suppressions.insert(browser.index, suppression);
+                            browser.select(browser.index + 1);
+                        }
+                        None => {
+                            browser.status =
+                                Some("cannot suppress a problem without a location".into())
+                        }
+                    }
+                }
+            }
+            Key::Char('q') | Key::Escape | Key::CtrlC => break,
+            _ => {}
+        }
+    }
+    screen.release()?;
+    Ok(browser.suppressions.into_values().collect())
+}
+
+struct Browser<'a> {
+    irritations: Vec<&'a Irritation>,
+    rows: Vec<Row<'a>>,
+    group_by: GroupBy,
+    index: usize,
+    suppressions: BTreeMap<usize, Fix>,
+    status: Option<String>,
+
+    /// The first row shown in the list.
+    scroll: usize,
+
+    /// The number of rows in the list when last drawn.
+    list_height: usize,
+}
+
+enum Row<'a> {
+    Header(&'a str, usize),
+    Problem(usize),
+}
+
+impl<'a> Browser<'a> {
+    const HELP: &'static str = "↑↓ move  g next group  e edit  s suppress  q quit";
+
+    fn new(irritations: &'a [Irritation], group_by: GroupBy) -> Self {
+        let mut irritations: Vec<_> = irritations.iter().collect();
+        irritations.sort_by_key(|irritation| group_by.group_of(irritation));
+        let mut rows = Vec::with_capacity(irritations.len());
+        for (index, irritation) in irritations.iter().enumerate() {
+            let group = group_by.group_of(irritation);
+            if index == 0 || group_by.group_of(irritations[index - 1]) != group {
+                let size = irritations[index..]
+                    .iter()
+                    .take_while(|irritation| group_by.group_of(irritation) == group)
+                    .count();
+                rows.push(Row::Header(group, size));
+            }
+            rows.push(Row::Problem(index));
+        }
+        Self {
+            irritations,
+            rows,
+            group_by,
+            index: 0,
+            suppressions: BTreeMap::new(),
+            status: None,
+            scroll: 0,
+            list_height: 1,
+        }
+    }
+
+    fn selected(&self) -> &'a Irritation {
+        self.irritations[self.index]
+    }
+
+    fn select(&mut self, index: usize) {
+        self.index = index.min(self.irritations.len().saturating_sub(1));
+    }
+
+    fn select_next_group(&mut self) {
+        let group = self.group_by.group_of(self.selected());
+        if let Some(offset) = self.irritations[self.index..]
+            .iter()
+            .position(|irritation| self.group_by.group_of(irritation) != group)
+        {
+            self.select(self.index + offset);
+        }
+    }
+
+    /// Render the whole screen: a title, the list of problems, the selected problem in full and
+    /// a status line.
+    fn frame(&mut self, (height, width): (usize, usize)) -> Result<String> {
+        let height = height.max(6);
+        let width = width.max(20);
+        let truncate = |line: &str| console::truncate_str(line, width, "…").into_owned();
+
+        self.list_height = (height / 3).clamp(1, self.rows.len());
+        let selected_row = self
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Problem(index) if *index == self.index))
+            .expect("internal error: selected problem has no row");
+        // The header of the selected problem's group is kept in view where possible.
+        let first_wanted = match selected_row.checked_sub(1).map(|row| &self.rows[row]) {
+            Some(Row::Header(..)) => selected_row - 1,
+            _ => selected_row,
+        };
+        if first_wanted < self.scroll {
+            self.scroll = first_wanted;
+        } else if selected_row >= self.scroll + self.list_height {
+            self.scroll = selected_row + 1 - self.list_height;
+        }
+
+        let mut frame = String::new();
+        let title = format!(
+            "vex: {} ({} suppressed)    {}",
+            Plural::new(self.irritations.len(), "problem", "problems"),
+            self.suppressions.len(),
+            Self::HELP
+        );
+        writeln!(frame, "{}", truncate(&title))?;
+        for row in self.rows.iter().skip(self.scroll).take(self.list_height) {
+            let line = match row {
+                Row::Header(group, size) => summary::group_header(group, *size),
+                Row::Problem(index) => {
+                    let irritation = self.irritations[*index];
+                    let marker = if *index == self.index { '>' } else { ' ' };
+                    let location = match (irritation.path(), irritation.location()) {
+                        (Some(path), Some(location)) => format!("{path}:{}", location.start_row),
+                        (Some(path), None) => path.to_string(),
+                        _ => "(project)".into(),
+                    };
+                    let suppressed = if self.suppressions.contains_key(index) {
+                        " (suppressed)"
+                    } else {
+                        ""
+                    };
+                    format!(
+                        "{marker} {}[{}] {location}: {}{suppressed}",
+                        irritation.severity(),
+                        irritation.vex_id(),
+                        irritation.message(),
+                    )
+                }
+            };
+            let line = truncate(&line);
+            if matches!(row, Row::Problem(index) if *index == self.index) {
+                writeln!(frame, "{}", console::style(line).reverse())?;
+            } else {
+                writeln!(frame, "{line}")?;
+            }
+        }
+        writeln!(frame, "{}", "─".repeat(width))?;
+
+        let detail_height = height.saturating_sub(self.list_height + 3);
+        let detail = self.selected().to_string();
+        for line in detail.lines().take(detail_height) {
+            writeln!(frame, "{}", truncate(line))?;
+        }
+        let shown = 2 + self.list_height + detail.lines().take(detail_height).count();
+        for _ in shown..height - 1 {
+            frame.push('\n');
+        }
+        write!(
+            frame,
+            "{}",
+            truncate(self.status.as_deref().unwrap_or_default())
+        )?;
+        Ok(frame)
+    }
+}
+
+/// Compute the edit which inserts a `vex:ignore` comment above the given problem.
+fn suppression(
+    project_root: &Utf8Path,
+    associations: &Associations,
+    irritation: &Irritation,
+) -> Result<Option<Fix>> {
+    let (Some(path), Some(location)) = (irritation.path(), irritation.location()) else {
+        return Ok(None);
+    };
+    let Some(line_comment) = associations
+        .get_language(&SourcePath::new_in(path, project_root))?
+        .and_then(|language| language.line_comment())
+    else {
+        return Ok(None);
+    };
+    let content =
+        fs::read_to_string(project_root.join(path.as_str())).map_err(|cause| Error::IO {
+            path: path.clone(),
+            action: IOAction::Read,
+            cause,
+        })?;
+
+    let line_start = content
+        .split_inclusive('\n')
+        .take(location.start_row.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let line = &content[line_start..];
+    let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+    Ok(Some(Fix {
+        path: path.clone(),
+        byte_range: line_start..line_start,
+        replaced: String::new(),
+        replacement: format!(
+            "{indent}{line_comment} vex:ignore {}\n",
+            irritation.vex_id()
+        ),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+
+    use crate::{fix, trigger::CaseSensitivity, vextest::VexTest};
+
+    use super::*;
+
+    const SOURCE: &str = indoc! {r#"
+        fn main() {
+            let x = 1;
+            let y = 2;
+        }
+    "#};
+
+    fn irritations() -> Vec<Irritation> {
+        VexTest::new("tui")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+                        vex.warn('project-check', 'project-wide problem')
+
+                    def on_match(event):
+                        vex.warn('literal-check', 'literal found', at=event.captures['lit'])
+                "#},
+            )
+            .with_source_file("src/main.rs", SOURCE)
+            .try_run()
+            .unwrap()
+            .irritations
+    }
+
+    /// A screen which reads scripted keys and records each frame drawn.
+    struct FakeScreen {
+        size: (usize, usize),
+        keys: std::vec::IntoIter<Key>,
+        frames: Vec<String>,
+        releases: usize,
+    }
+
+    impl Screen for FakeScreen {
+        fn size(&self) -> (usize, usize) {
+            self.size
+        }
+
+        fn draw(&mut self, frame: &str) -> Result<()> {
+            assert_eq!(frame.split('\n').count(), self.size.0, "frame:\n{frame}");
+            self.frames
+                .push(console::strip_ansi_codes(frame).into_owned());
+            Ok(())
+        }
+
+        fn read_key(&mut self) -> Result<Key> {
+            Ok(self.keys.next().expect("ran out of keys"))
+        }
+
+        fn release(&mut self) -> Result<()> {
+            self.releases += 1;
+            Ok(())
+        }
+    }
+
+    struct Session {
+        frames: Vec<String>,
+        releases: usize,
+        opened: Vec<(Utf8PathBuf, usize)>,
+        fixed: Option<String>,
+    }
+
+    impl Session {
+        fn last_frame(&self) -> &str {
+            self.frames.last().unwrap()
+        }
+    }
+
+    fn run(group_by: GroupBy, rows: usize, keys: &str) -> Session {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_owned()).unwrap();
+        fs::create_dir(root_path.join("src")).unwrap();
+        fs::write(root_path.join("src/main.rs"), SOURCE).unwrap();
+
+        let mut screen = FakeScreen {
+            size: (rows, 80),
+            keys: keys
+                .chars()
+                .map(|key| match key {
+                    '$' => Key::End,
+                    '\n' => Key::Enter,
+                    key => Key::Char(key),
+                })
+                .collect::<Vec<_>>()
+                .into_iter(),
+            frames: vec![],
+            releases: 0,
+        };
+        let mut opened = vec![];
+        let suppressions = browse(
+            &root_path,
+            &Associations::base(CaseSensitivity::Sensitive),
+            &irritations(),
+            group_by,
+            &mut screen,
+            |path, location| {
+                opened.push((
+                    path.strip_prefix(&root_path).unwrap().to_owned(),
+                    location.start_row,
+                ));
+                Ok(())
+            },
+        )
+        .unwrap();
+        let fixed = fix::fix_files(&root_path, &suppressions)
+            .unwrap()
+            .into_iter()
+            .next()
+            .map(|fixed_file| fixed_file.fixed);
+        Session {
+            frames: screen.frames,
+            releases: screen.releases,
+            opened,
+            fixed,
+        }
+    }
+
+    #[test]
+    fn groups() {
+        let session = run(GroupBy::File, 24, "q");
+        let frame = &session.frames[0];
+        assert!(frame.starts_with("vex: 3 problems (0 suppressed)"));
+        let list: Vec<_> = frame.lines().skip(1).take(5).collect();
+        assert_eq!(
+            list,
+            [
+                "== (project) (1 problem) ==",
+                "> warning[project-check] (project): project-wide problem",
+                "== src/main.rs (2 problems) ==",
+                "  warning[literal-check] src/main.rs:2: literal found",
+                "  warning[literal-check] src/main.rs:3: literal found",
+            ]
+        );
+        assert!(frame.contains("project-wide problem\n"));
+
+        let session = run(GroupBy::Check, 24, "gq");
+        assert!(session.frames[0].contains("== literal-check (2 problems) =="));
+        assert!(session.last_frame().contains("> warning[project-check]"));
+        assert_eq!(session.releases, 1);
+    }
+
+    #[test]
+    fn no_problems() {
+        let mut screen = FakeScreen {
+            size: (24, 80),
+            keys: vec![].into_iter(),
+            frames: vec![],
+            releases: 0,
+        };
+        let suppressions = browse(
+            Utf8Path::new("."),
+            &Associations::base(CaseSensitivity::Sensitive),
+            &[],
+            GroupBy::File,
+            &mut screen,
+            |_, _| panic!("nothing to open"),
+        )
+        .unwrap();
+        assert!(suppressions.is_empty());
+        assert!(screen.frames.is_empty());
+    }
+
+    #[test]
+    fn scroll() {
+        // A short screen leaves room for only three rows of the list.
+        let session = run(GroupBy::Check, 9, "$q");
+        assert!(session.frames[0].contains("== literal-check (2 problems) =="));
+        assert!(!session.frames[0].contains("project-check"));
+        let frame = session.last_frame();
+        assert!(!frame.contains("literal-check (2 problems)"));
+        assert!(frame.contains("== project-check (1 problem) =="));
+        assert!(frame.contains("> warning[project-check]"));
+    }
+
+    #[test]
+    fn edit() {
+        let session = run(GroupBy::Check, 24, "ej\ngeq");
+        assert_eq!(
+            session.opened,
+            [
+                (Utf8PathBuf::from("src/main.rs"), 2),
+                (Utf8PathBuf::from("src/main.rs"), 3)
+            ]
+        );
+        // The terminal is handed back for each edit and once more on quitting.
+        assert_eq!(session.releases, 3);
+        assert!(session.frames[5].ends_with("cannot open a problem without a location"));
+        assert!(!session.frames[4].contains("cannot open"));
+    }
+
+    #[test]
+    fn suppress() {
+        let session = run(GroupBy::Check, 24, "skss$sq");
+        assert_eq!(
+            session.fixed.as_deref(),
+            Some(indoc! {r#"
+                fn main() {
+                    // vex:ignore literal-check
+                    let x = 1;
+                    let y = 2;
+                }
+            "#})
+        );
+        assert!(session.frames[2]
+            .contains("> warning[literal-check] src/main.rs:2: literal found (suppressed)"));
+        assert!(session.frames[3].ends_with("suppression undone"));
+        assert!(session.frames[4].starts_with("vex: 3 problems (1 suppressed)"));
+        assert!(session
+            .last_frame()
+            .ends_with("cannot suppress a problem without a location"));
+        assert_eq!(run(GroupBy::Check, 24, "q").fixed, None);
+    }
+}
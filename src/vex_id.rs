@@ -8,11 +8,11 @@ use std::{
 use allocative::Allocative;
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, InvalidIDReason};
 
-#[derive(Debug, Clone, Allocative, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Allocative, Eq, PartialEq, Deserialize, Serialize)]
 pub struct VexId {
     hash: u64,
 
@@ -1,23 +1,27 @@
 use std::{
     borrow::Cow,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs::{self, File},
     io::Write,
 };
 
-use camino::{Utf8Component, Utf8PathBuf};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use indoc::indoc;
 use regex::Regex;
 
 use crate::{
-    cli::{MaxConcurrentFileLimit, MaxProblems},
+    check_selection::CheckSelection,
+    cli::{MaxConcurrentFileLimit, MaxProblems, SortBy},
     context::Context,
     result::Result,
-    scan,
+    scan::{self, ScanOptions},
     scriptlets::{
+        intent_log::IntentLog,
         source::{ScriptSource, TestSource},
         InitOptions, PreinitOptions, PreinitingStore,
     },
+    severity::{Severity, SeverityOverrides},
+    source_path::SourcePath,
     verbosity::Verbosity,
     ProjectRunData,
 };
@@ -29,10 +33,20 @@ pub struct VexTest<'s> {
     bare: bool,
     manifest_content: Option<Cow<'s, str>>,
     max_problems: MaxProblems,
+    max_problems_per_check: Option<u32>,
+    report_level: Option<Severity>,
+    sort_by: SortBy,
     lenient: bool,
+    severity_overrides: SeverityOverrides,
+    selection: CheckSelection,
+    only_tags: Vec<String>,
+    changed_files: Option<BTreeSet<Utf8PathBuf>>,
     fire_test_events: bool,
+    keep_going: bool,
+    intent_log: Option<&'s IntentLog>,
     scriptlets: Vec<TestSource<Utf8PathBuf, Cow<'s, str>>>,
     source_files: BTreeMap<Utf8PathBuf, Cow<'s, str>>,
+    buffer: Option<(Utf8PathBuf, Cow<'s, str>)>,
 }
 
 impl<'s> VexTest<'s> {
@@ -60,16 +74,61 @@ impl<'s> VexTest<'s> {
         self
     }
 
+    pub fn with_max_problems_per_check(mut self, max_problems_per_check: u32) -> Self {
+        self.max_problems_per_check = Some(max_problems_per_check);
+        self
+    }
+
+    pub fn with_report_level(mut self, report_level: Severity) -> Self {
+        self.report_level = Some(report_level);
+        self
+    }
+
+    pub fn with_sort_by(mut self, sort_by: SortBy) -> Self {
+        self.sort_by = sort_by;
+        self
+    }
+
     pub fn with_lenient(mut self, lenient: bool) -> Self {
         self.lenient = lenient;
         self
     }
 
+    pub fn with_severity_overrides(mut self, severity_overrides: SeverityOverrides) -> Self {
+        self.severity_overrides = severity_overrides;
+        self
+    }
+
+    pub fn with_selection(mut self, selection: CheckSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn with_only_tags(mut self, only_tags: impl IntoIterator<Item = &'s str>) -> Self {
+        self.only_tags = only_tags.into_iter().map(ToString::to_string).collect();
+        self
+    }
+
+    pub fn with_changed_files(mut self, changed_files: impl IntoIterator<Item = &'s str>) -> Self {
+        self.changed_files = Some(changed_files.into_iter().map(Utf8PathBuf::from).collect());
+        self
+    }
+
     pub fn with_test_events(mut self, fire_test_events: bool) -> Self {
         self.fire_test_events = fire_test_events;
         self
     }
 
+    pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    pub fn with_intent_log(mut self, intent_log: &'s IntentLog) -> Self {
+        self.intent_log = Some(intent_log);
+        self
+    }
+
     pub fn with_scriptlet(
         mut self,
         path: impl Into<Utf8PathBuf>,
@@ -79,6 +138,28 @@ impl<'s> VexTest<'s> {
         self
     }
 
+    /// Add a scriptlet to the rule pack with the given name.
+    pub fn with_pack_scriptlet(
+        mut self,
+        pack: &str,
+        path: impl AsRef<Utf8Path>,
+        content: impl Into<Cow<'s, str>>,
+    ) -> Self {
+        let path = Utf8Path::new("packs")
+            .join(format!("@{pack}"))
+            .join(path.as_ref());
+        assert!(
+            !self.scriptlets.iter().any(|s| s.path == path),
+            "duplicate scriptlet declaration"
+        );
+        self.scriptlets.push(TestSource {
+            vex_dir: "packs".into(),
+            path,
+            content: content.into(),
+        });
+        self
+    }
+
     fn add_scriptlet(&mut self, path: impl Into<Utf8PathBuf>, content: impl Into<Cow<'s, str>>) {
         let path = path.into();
         let content = content.into();
@@ -123,6 +204,16 @@ impl<'s> VexTest<'s> {
         self
     }
 
+    /// Scan only the given unsaved content, as if it were saved at the given path.
+    pub fn with_buffer(
+        mut self,
+        path: impl Into<Utf8PathBuf>,
+        content: impl Into<Cow<'s, str>>,
+    ) -> Self {
+        self.buffer = Some((path.into(), content.into()));
+        self
+    }
+
     pub fn assert_irritation_free(self) {
         assert_eq!(
             self.try_run().unwrap().irritations,
@@ -158,7 +249,7 @@ impl<'s> VexTest<'s> {
                 .unwrap();
         }
 
-        let ctx = Context::acquire_in(&root_path).unwrap();
+        let ctx = Context::acquire(&root_path).unwrap();
         if !self.bare {
             fs::create_dir(ctx.vex_dir()).ok();
         }
@@ -178,19 +269,44 @@ impl<'s> VexTest<'s> {
             let verbosity = Verbosity::default();
             let preinit_opts = PreinitOptions {
                 lenient: self.lenient,
+                project: ctx.project.clone(),
+                severity_overrides: self
+                    .severity_overrides
+                    .clone()
+                    .with_configured(ctx.checks.levels()),
+                selection: self.selection.clone(),
+                verbosity,
+            };
+            let init_opts = InitOptions {
                 verbosity,
+                config: ctx.config.clone(),
             };
-            let init_opts = InitOptions { verbosity };
-            let store = PreinitingStore::new(&self.scriptlets)?
+            let store = PreinitingStore::new_with_names(&self.scriptlets, &ctx.scriptlet_names)?
                 .preinit(preinit_opts)?
                 .init(init_opts)?;
-            scan::scan_project(
-                &ctx,
-                &store,
-                self.max_problems,
-                MaxConcurrentFileLimit::new(1),
+            let scan_opts = ScanOptions {
+                only_tags: &self.only_tags,
+                changed_files: self.changed_files.as_ref(),
+                sort_by: self.sort_by,
+                max_problems: self.max_problems,
+                max_problems_per_check: self.max_problems_per_check,
+                report_level: self.report_level,
+                max_concurrent_files: MaxConcurrentFileLimit::new(1),
                 verbosity,
-            )
+                fail_fast: !self.keep_going,
+                intent_log: self.intent_log,
+                ..ScanOptions::default()
+            };
+            match &self.buffer {
+                Some((path, content)) => scan::scan_buffer(
+                    &ctx,
+                    &store,
+                    &SourcePath::new_in(path, &root_path),
+                    content.to_string(),
+                    scan_opts,
+                ),
+                None => scan::scan_project(&ctx, &store, scan_opts),
+            }
         }
     }
 